@@ -0,0 +1,19 @@
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=proto");
+    // commit reported by `dob_server_info`, left empty if built out of a git checkout
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    let git_commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=GIT_COMMIT={git_commit}");
+    // generate gRPC service from proto definition, which requires `protoc` installed
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/decoder.proto").expect("compile proto");
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use ckb_types::H256;
+use hyper::header::{HeaderName, UPGRADE};
+use hyper::{Body, Request, Response, StatusCode};
+use tower::{Layer, Service};
+
+use crate::middleware::RejectionBody;
+use crate::types::{ApiKeySettings, Error};
+
+pub const API_KEY_HEADER: HeaderName = HeaderName::from_static("x-api-key");
+
+tokio::task_local! {
+    // clusters allowed to the key of the request being served, only set for restricted keys
+    static ALLOWED_CLUSTERS: Arc<Vec<H256>>;
+}
+
+// spores outside the clusters allowed to the key can't be decoded, including those without
+// cluster, decoding outside of requests is never restricted
+pub fn check_cluster_access(cluster_id: Option<&H256>) -> Result<(), Error> {
+    match ALLOWED_CLUSTERS.try_with(|allowed| cluster_id.is_some_and(|id| allowed.contains(id))) {
+        Ok(false) => Err(Error::ClusterAccessDenied),
+        _ => Ok(()),
+    }
+}
+
+// require one of configured keys in `X-Api-Key` header, and restrict decoding to the clusters
+// allowed to the key if any, all requests are passed through if no key is configured
+#[derive(Clone)]
+pub struct ApiKeyLayer {
+    // allowed clusters by key, empty for unrestricted keys
+    keys: Arc<HashMap<String, Arc<Vec<H256>>>>,
+}
+
+impl ApiKeyLayer {
+    pub fn new(api_keys: &[ApiKeySettings]) -> Self {
+        let keys = api_keys
+            .iter()
+            .map(|api_key| {
+                (
+                    api_key.key.clone(),
+                    Arc::new(api_key.allowed_clusters.clone()),
+                )
+            })
+            .collect();
+        Self {
+            keys: Arc::new(keys),
+        }
+    }
+}
+
+impl<S> Layer<S> for ApiKeyLayer {
+    type Service = ApiKeyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ApiKeyService {
+            inner,
+            keys: self.keys.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ApiKeyService<S> {
+    inner: S,
+    keys: Arc<HashMap<String, Arc<Vec<H256>>>>,
+}
+
+impl<S, ResBody> Service<Request<Body>> for ApiKeyService<S>
+where
+    S: Service<Request<Body>, Response = Response<ResBody>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+    ResBody: RejectionBody + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if self.keys.is_empty() {
+            return Box::pin(self.inner.call(request));
+        }
+        let allowed_clusters = request
+            .headers()
+            .get(&API_KEY_HEADER)
+            .and_then(|key| key.to_str().ok())
+            .and_then(|key| self.keys.get(key))
+            .cloned();
+        let Some(allowed_clusters) = allowed_clusters else {
+            return Box::pin(async move {
+                Ok(ResBody::rejection(
+                    StatusCode::UNAUTHORIZED,
+                    Error::ApiKeyInvalid,
+                ))
+            });
+        };
+        if allowed_clusters.is_empty() {
+            return Box::pin(self.inner.call(request));
+        }
+        // calls over WebSocket are served out of this request, where restrictions can't follow
+        if request.headers().contains_key(UPGRADE) {
+            return Box::pin(async move {
+                Ok(ResBody::rejection(
+                    StatusCode::FORBIDDEN,
+                    Error::ClusterAccessDenied,
+                ))
+            });
+        }
+        Box::pin(ALLOWED_CLUSTERS.scope(allowed_clusters, self.inner.call(request)))
+    }
+}
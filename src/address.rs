@@ -0,0 +1,94 @@
+use bech32::{FromBase32, ToBase32, Variant};
+use ckb_types::{
+    core::ScriptHashType,
+    h256,
+    packed::Script,
+    prelude::{Builder, Entity, Pack},
+    H256,
+};
+
+use crate::types::{Error, Network};
+
+// code hashes of the short address format, indexed by the code hash index in payload
+const SECP256K1_BLAKE160_CODE_HASH: H256 =
+    h256!("0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8");
+const SECP256K1_MULTISIG_CODE_HASH: H256 =
+    h256!("0x5c5069eb0857efc65e1bca0c07df34c31663b3622fd3876c876320fc9634e2a8");
+
+// payload formats defined in RFC 0021, the short and deprecated full formats are still seen
+const FULL_FORMAT: u8 = 0x00;
+const SHORT_FORMAT: u8 = 0x01;
+const DEPRECATED_FULL_DATA_FORMAT: u8 = 0x02;
+const DEPRECATED_FULL_TYPE_FORMAT: u8 = 0x04;
+
+// parse lock script out of mainnet or testnet address, the short format of anyone-can-pay is
+// rejected since its code hash differs between networks
+pub fn parse_address(address: &str) -> Result<Script, Error> {
+    let (hrp, data, variant) = bech32::decode(address).map_err(|_| Error::AddressParseError)?;
+    if hrp != "ckb" && hrp != "ckt" {
+        return Err(Error::AddressParseError);
+    }
+    let payload = Vec::<u8>::from_base32(&data).map_err(|_| Error::AddressParseError)?;
+    let (format, payload) = payload.split_first().ok_or(Error::AddressParseError)?;
+    let (code_hash, hash_type, args) = match (*format, variant) {
+        (FULL_FORMAT, Variant::Bech32m) if payload.len() >= 33 => {
+            let hash_type =
+                ScriptHashType::try_from(payload[32]).map_err(|_| Error::AddressParseError)?;
+            (&payload[..32], hash_type, &payload[33..])
+        }
+        (SHORT_FORMAT, Variant::Bech32) if payload.len() == 21 => {
+            let code_hash = match payload[0] {
+                0x00 => SECP256K1_BLAKE160_CODE_HASH.as_bytes(),
+                0x01 => SECP256K1_MULTISIG_CODE_HASH.as_bytes(),
+                _ => return Err(Error::AddressParseError),
+            };
+            (code_hash, ScriptHashType::Type, &payload[1..])
+        }
+        (DEPRECATED_FULL_DATA_FORMAT, Variant::Bech32) if payload.len() >= 32 => {
+            (&payload[..32], ScriptHashType::Data, &payload[32..])
+        }
+        (DEPRECATED_FULL_TYPE_FORMAT, Variant::Bech32) if payload.len() >= 32 => {
+            (&payload[..32], ScriptHashType::Type, &payload[32..])
+        }
+        _ => return Err(Error::AddressParseError),
+    };
+    let code_hash: [u8; 32] = code_hash.try_into().expect("32 bytes code hash");
+    Ok(Script::new_builder()
+        .code_hash(code_hash.pack())
+        .hash_type(hash_type.into())
+        .args(args.to_vec().pack())
+        .build())
+}
+
+// locks of wallets which users know their addresses of, only these are rendered into addresses
+// since others, like those of contracts, hardly mean anything to users
+const OMNILOCK_CODE_HASHES: [H256; 2] = [
+    h256!("0x9b819793a64463aed77c615d6cb226eea5487ccfc0783043a587254cda2b6f26"),
+    h256!("0xf329effd1c475a2978453c8600e1eaf0bc2087ee093c3ee64cc96ec6847752cb"),
+];
+const JOYID_CODE_HASHES: [H256; 2] = [
+    h256!("0xd00c84f0ec8fd441c38bc3f87a371f547190f2fcff88e642bc5bf54b9e318323"),
+    h256!("0xd23761b364210735c19c60561d213fb3beae2fd6172743719eff6920e020baac"),
+];
+
+// render lock script of a recognized wallet into address in the full format, like `ckb1qz...`
+pub fn encode_address(lock_script: &Script, network: Network) -> Option<String> {
+    let code_hash = H256::from_slice(lock_script.code_hash().as_slice()).ok()?;
+    let hash_type = lock_script.hash_type().as_slice()[0];
+    let recognized = code_hash == SECP256K1_BLAKE160_CODE_HASH
+        || OMNILOCK_CODE_HASHES.contains(&code_hash)
+        || JOYID_CODE_HASHES.contains(&code_hash);
+    if !recognized || hash_type != ScriptHashType::Type as u8 {
+        return None;
+    }
+    let mut payload = vec![FULL_FORMAT];
+    payload.extend_from_slice(code_hash.as_bytes());
+    payload.push(hash_type);
+    payload.extend_from_slice(&lock_script.args().raw_data());
+    bech32::encode(
+        network.address_prefix(),
+        payload.to_base32(),
+        Variant::Bech32m,
+    )
+    .ok()
+}
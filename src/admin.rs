@@ -0,0 +1,219 @@
+use std::str::FromStr;
+
+use ckb_types::H256;
+use jsonrpsee::core::async_trait;
+use jsonrpsee::{proc_macros::rpc, tracing, types::ErrorObjectOwned};
+use serde::Serialize;
+
+use crate::config::SettingsSource;
+use crate::crawler::CrawlReport;
+use crate::invalidation::{apply_invalidation_blocking, InvalidationEvent};
+use crate::logging;
+use crate::rate_limit;
+use crate::server::{
+    decode_dob, DecodeOptions, DecoderStandaloneServer, ServerDecodeResult, SporeIdParam,
+};
+use crate::types::{CachedDecoder, Error, RenderDebugSettings, Settings};
+
+// outcome of reloading settings file, changes of settings not reloadable are only listed
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SettingsReload {
+    pub reloaded: Vec<String>,
+    pub restart_required: Vec<String>,
+}
+
+// operational controls applied without restarting, served on `admin_rpc_server_address` only,
+// so that they can be kept away from public networks, and not served at all if it's not configured
+#[rpc(server, namespace = "admin")]
+trait AdminRpc {
+    #[method(name = "reload_settings")]
+    async fn reload_settings(&self) -> Result<SettingsReload, ErrorObjectOwned>;
+
+    #[method(name = "invalidate_cache")]
+    async fn invalidate_cache(&self, event: InvalidationEvent) -> Result<usize, ErrorObjectOwned>;
+
+    #[method(name = "refresh")]
+    async fn refresh(
+        &self,
+        hexed_spore_id: SporeIdParam,
+        redecode: Option<bool>,
+        network: Option<String>,
+    ) -> Result<Option<ServerDecodeResult>, ErrorObjectOwned>;
+
+    #[method(name = "purge_cache")]
+    async fn purge_cache(&self) -> Result<usize, ErrorObjectOwned>;
+
+    #[method(name = "invalidate_cluster")]
+    async fn invalidate_cluster(&self, cluster_id: String) -> Result<usize, ErrorObjectOwned>;
+
+    #[method(name = "crawl_cluster")]
+    async fn crawl_cluster(&self, cluster_id: String) -> Result<CrawlReport, ErrorObjectOwned>;
+
+    #[method(name = "list_decoders")]
+    async fn list_decoders(&self) -> Vec<CachedDecoder>;
+
+    #[method(name = "set_render_debug")]
+    async fn set_render_debug(&self, render_debug: RenderDebugSettings) -> RenderDebugSettings;
+
+    #[method(name = "set_log_filter")]
+    async fn set_log_filter(&self, directives: String) -> Result<String, ErrorObjectOwned>;
+}
+
+#[async_trait]
+impl AdminRpcServer for DecoderStandaloneServer {
+    async fn reload_settings(&self) -> Result<SettingsReload, ErrorObjectOwned> {
+        Ok(reload_settings_file(self)?)
+    }
+
+    // drop stale cache entries locally, then broadcast to other replicas if possible, returns the
+    // number of render results dropped locally
+    async fn invalidate_cache(&self, event: InvalidationEvent) -> Result<usize, ErrorObjectOwned> {
+        Ok(self.invalidate(event).await?)
+    }
+
+    // drop render result of the spore cached by a bugged decoder, on every replica if possible,
+    // then decode it again right away if requested, returning the new result, render cache of
+    // other networks is dropped on this replica only since the bus carries events of the primary
+    async fn refresh(
+        &self,
+        hexed_spore_id: SporeIdParam,
+        redecode: Option<bool>,
+        network: Option<String>,
+    ) -> Result<Option<ServerDecodeResult>, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let hexed_spore_id = hexed_spore_id.to_hex();
+        let hexed_spore_id = hexed_spore_id.strip_prefix("0x").unwrap_or(&hexed_spore_id);
+        let spore_id: [u8; 32] = hex::decode(hexed_spore_id)
+            .map_err(|_| Error::HexedSporeIdParseError)?
+            .try_into()
+            .map_err(|_| Error::SporeIdLengthInvalid)?;
+        let event = InvalidationEvent::Spore {
+            spore_id: spore_id.into(),
+        };
+        match &network {
+            // invalidation bus is only shared by replicas of the primary network
+            Some(name) => {
+                apply_invalidation_blocking(self.networks()[name.as_str()].clone(), event).await;
+            }
+            None => {
+                self.invalidate(event).await?;
+            }
+        }
+        if !redecode.unwrap_or_default() {
+            return Ok(None);
+        }
+        let result = decode_dob(decoder, hex::encode(spore_id), &DecodeOptions::default()).await?;
+        Ok(Some(result))
+    }
+
+    // drop all render results and cluster metadata, on every replica if possible, returns the
+    // number of render results dropped locally
+    async fn purge_cache(&self) -> Result<usize, ErrorObjectOwned> {
+        Ok(self.invalidate(InvalidationEvent::Purge).await?)
+    }
+
+    // purge render results of all spores in the cluster along with its metadata, on every replica
+    // if possible, after its decoder is redeployed, returns the number of render results dropped
+    // locally
+    async fn invalidate_cluster(&self, cluster_id: String) -> Result<usize, ErrorObjectOwned> {
+        let hexed_cluster_id = cluster_id.strip_prefix("0x").unwrap_or(&cluster_id);
+        let cluster_id =
+            H256::from_str(hexed_cluster_id).map_err(|_| Error::HexedClusterIdParseError)?;
+        Ok(self
+            .invalidate(InvalidationEvent::Cluster { cluster_id })
+            .await?)
+    }
+
+    // decode all live spores of the cluster into render cache in background ahead of its launch,
+    // only for clusters whose spores are indexed, returns progress of the crawl
+    async fn crawl_cluster(&self, cluster_id: String) -> Result<CrawlReport, ErrorObjectOwned> {
+        let hexed_cluster_id = cluster_id.strip_prefix("0x").unwrap_or(&cluster_id);
+        let cluster_id =
+            H256::from_str(hexed_cluster_id).map_err(|_| Error::HexedClusterIdParseError)?;
+        if !self.decoder().is_cluster_indexed(&cluster_id.0) {
+            return Err(Error::ClusterNotIndexed.into());
+        }
+        Ok(self.crawl_in_background(cluster_id.0))
+    }
+
+    // decoder binaries cached locally, for checking which decoders are loaded after redeployment
+    async fn list_decoders(&self) -> Vec<CachedDecoder> {
+        self.decoder().cached_decoders()
+    }
+
+    // replace targets of render debug without restarting, returns the previous targets
+    async fn set_render_debug(&self, render_debug: RenderDebugSettings) -> RenderDebugSettings {
+        tracing::info!("render debug targets set to {render_debug:?}");
+        self.decoder().set_render_debug(render_debug)
+    }
+
+    // turn log levels up for investigation without restarting, returns the previous directives,
+    // which can be set back once done
+    async fn set_log_filter(&self, directives: String) -> Result<String, ErrorObjectOwned> {
+        let previous = logging::set_log_filter(&directives)?;
+        tracing::info!("log filter set to {directives}, previously {previous}");
+        Ok(previous)
+    }
+}
+
+// read settings file again and apply what takes effect without restarting, on `admin_reload_settings`
+// or SIGHUP, the file is rejected as a whole if it doesn't pass validation
+pub fn reload_settings_file(server: &DecoderStandaloneServer) -> Result<SettingsReload, Error> {
+    let settings_source = server.settings_source().ok_or(Error::SettingsReloadError)?;
+    let settings = load_settings(settings_source)?;
+    if let Err(error) = logging::set_log_filter(&settings.logging.directives()) {
+        tracing::warn!("log filter not reloaded: {error}");
+    }
+    rate_limit::set_rate_limit(settings.rate_limit.clone());
+    let networks = settings.networks.clone();
+    let mut restart_required = server.decoder().reload_settings(settings.clone());
+    // decoders of other networks are reloaded along with the primary one, whose changes are
+    // reported by network, while adding or removing networks still requires restarting
+    restart_required.retain(|name| name != "networks");
+    let mut network_names = networks
+        .iter()
+        .map(|network| network.name.as_str())
+        .collect::<Vec<_>>();
+    let mut served_names = server
+        .networks()
+        .keys()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    network_names.sort();
+    served_names.sort();
+    if network_names != served_names {
+        restart_required.push("networks".to_string());
+    }
+    for network in &networks {
+        let Some(decoder) = server.networks().get(&network.name) else {
+            continue;
+        };
+        let network_restart_required = decoder
+            .reload_settings(settings.for_network(network))
+            .into_iter()
+            .filter(|name| !restart_required.contains(name))
+            .map(|name| format!("networks.{}.{name}", network.name))
+            .collect::<Vec<_>>();
+        restart_required.extend(network_restart_required);
+    }
+    tracing::info!("settings reloaded, restart required by {restart_required:?}");
+    Ok(SettingsReload {
+        reloaded: Settings::RELOADABLE.map(str::to_string).to_vec(),
+        restart_required,
+    })
+}
+
+// read and validate settings file, problems are logged since error code alone tells nothing
+pub fn load_settings(settings_source: &SettingsSource) -> Result<Settings, Error> {
+    let settings = settings_source.read().map_err(|error| {
+        tracing::error!("failed to load settings from {settings_source:?}: {error}");
+        Error::SettingsReloadError
+    })?;
+    if let Err(errors) = settings.validate() {
+        errors
+            .iter()
+            .for_each(|error| tracing::error!("invalid settings: {error}"));
+        return Err(Error::SettingsReloadError);
+    }
+    Ok(settings)
+}
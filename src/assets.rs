@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::time::Duration;
+#[cfg(not(feature = "shuttle"))]
+use std::{fs, path::PathBuf};
+
+use futures::future::join_all;
+use jsonrpsee::tracing;
+use serde_json::{json, Value};
+
+use crate::types::{AssetsSettings, BtcfsSource, Error};
+
+type AssetResult<T> = Result<T, Error>;
+
+// content of an asset referenced in render output, along with its MIME type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Asset {
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+// inscription referenced like `btcfs://{txid}i{index}`, where index counts inscriptions revealed
+// in the transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InscriptionId {
+    pub txid: String,
+    pub index: usize,
+}
+
+impl InscriptionId {
+    pub fn parse(id: &str) -> AssetResult<Self> {
+        let (txid, index) = id.rsplit_once('i').ok_or(Error::AssetUriInvalid)?;
+        if txid.len() != 64 || hex::decode(txid).is_err() {
+            return Err(Error::AssetUriInvalid);
+        }
+        let index = index.parse().map_err(|_| Error::AssetUriInvalid)?;
+        Ok(Self {
+            txid: txid.to_lowercase(),
+            index,
+        })
+    }
+}
+
+impl std::fmt::Display for InscriptionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}i{}", self.txid, self.index)
+    }
+}
+
+// fetch assets referenced by decode output, which decoders can't reach in VM, and cache them
+// since inscriptions are immutable
+pub struct AssetResolver {
+    settings: AssetsSettings,
+    client: reqwest::Client,
+}
+
+impl AssetResolver {
+    pub fn new(settings: AssetsSettings) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(settings.timeout_ms))
+            .build()
+            .expect("build assets http client");
+        Self { settings, client }
+    }
+
+    // resolve `btcfs://` uri into its inscription content, `ipfs://` uri through gateways, or
+    // `content://{hash}` into prefetched asset
+    pub async fn resolve(&self, uri: &str) -> AssetResult<Asset> {
+        match uri.split_once("://") {
+            Some(("btcfs", id)) => self.resolve_btcfs(&InscriptionId::parse(id)?).await,
+            Some(("ipfs", path)) => self.resolve_ipfs(path).await,
+            Some(("content", hash)) => self.resolve_content(hash),
+            _ => Err(Error::AssetUriInvalid),
+        }
+    }
+
+    // prefetched assets are only found in cache, they are never fetched by hash
+    pub fn resolve_content(&self, hash: &str) -> AssetResult<Asset> {
+        if hash.len() != 64 || hex::decode(hash).is_err() {
+            return Err(Error::AssetUriInvalid);
+        }
+        self.read_cache(&format!("content/{}", hash.to_lowercase()))
+            .ok_or(Error::AssetNotFound)
+    }
+
+    // fetch asset of the uri, which may be a HTTP link as well unlike `resolve`, into cache keyed
+    // by hash of its content, returning the hash
+    pub async fn prefetch(&self, uri: &str) -> AssetResult<String> {
+        let asset = match uri.split_once("://") {
+            Some(("http" | "https", _)) => self.fetch_content(uri).await?,
+            _ => self.resolve(uri).await?,
+        };
+        let hash = hex::encode(ckb_hash::blake2b_256(&asset.content));
+        self.write_cache(&format!("content/{hash}"), &asset);
+        Ok(hash)
+    }
+
+    // replace asset uris in render output with urls of their prefetched copies served at
+    // `/assets/content/{hash}`, uris failed to fetch are left as they are so that clients may still
+    // try them, prefetched assets can't be kept under shuttle though
+    pub async fn prefetch_render_output(&self, render_output: &str) -> String {
+        if !self.settings.prefetch || cfg!(feature = "shuttle") {
+            return render_output.to_string();
+        }
+        let Ok(mut parsed_output) = serde_json::from_str::<Value>(render_output) else {
+            return render_output.to_string();
+        };
+        let uris = collect_asset_uris(&parsed_output);
+        let hashes = join_all(uris.iter().map(|uri| self.prefetch(uri))).await;
+        let public_url = self.settings.public_url.trim_end_matches('/');
+        let local_urls = uris
+            .into_iter()
+            .zip(hashes)
+            .filter_map(|(uri, hash)| match hash {
+                Ok(hash) => Some((uri, format!("{public_url}/assets/content/{hash}"))),
+                Err(error) => {
+                    tracing::warn!("failed to prefetch asset {uri}: {error}");
+                    None
+                }
+            })
+            .collect::<HashMap<_, _>>();
+        if local_urls.is_empty() {
+            return render_output.to_string();
+        }
+        rewrite_asset_uris(&mut parsed_output, &local_urls);
+        parsed_output.to_string()
+    }
+
+    // path is like `{cid}` or `{cid}/image.png`, which is tried on each gateway in order until
+    // one of them responds, and cached by its hash since content under a cid is immutable
+    pub async fn resolve_ipfs(&self, path: &str) -> AssetResult<Asset> {
+        let path_valid = !path.is_empty()
+            && path
+                .split('/')
+                .all(|segment| !segment.is_empty() && segment != "." && segment != "..");
+        if !path_valid || self.settings.ipfs_gateways.is_empty() {
+            return Err(Error::AssetUriInvalid);
+        }
+        let cache_key = format!("ipfs/{}", hex::encode(ckb_hash::blake2b_256(path)));
+        if let Some(asset) = self.read_cache(&cache_key) {
+            return Ok(asset);
+        }
+        let mut error = Error::AssetFetchError;
+        for gateway in &self.settings.ipfs_gateways {
+            let url = format!("{}/{path}", gateway.trim_end_matches('/'));
+            match self.fetch_content(&url).await {
+                Ok(asset) => {
+                    self.write_cache(&cache_key, &asset);
+                    return Ok(asset);
+                }
+                Err(fetch_error) => {
+                    tracing::debug!("failed to fetch ipfs://{path} from gateway {gateway}");
+                    error = fetch_error;
+                }
+            }
+        }
+        Err(error)
+    }
+
+    async fn fetch_content(&self, url: &str) -> AssetResult<Asset> {
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(|_| Error::AssetFetchError)?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(Error::AssetNotFound);
+        }
+        let response = response
+            .error_for_status()
+            .map_err(|_| Error::AssetFetchError)?;
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        let content = response.bytes().await.map_err(|_| Error::AssetFetchError)?;
+        Ok(Asset {
+            content_type,
+            content: content.to_vec(),
+        })
+    }
+
+    pub async fn resolve_btcfs(&self, id: &InscriptionId) -> AssetResult<Asset> {
+        let cache_key = format!("btcfs/{id}");
+        if let Some(asset) = self.read_cache(&cache_key) {
+            return Ok(asset);
+        }
+        let witnesses = self.fetch_witnesses(&id.txid).await?;
+        let asset = witnesses
+            .iter()
+            .flat_map(|witness| witness.iter())
+            .flat_map(|element| parse_inscriptions(element))
+            .nth(id.index)
+            .ok_or(Error::AssetNotFound)?;
+        self.write_cache(&cache_key, &asset);
+        Ok(asset)
+    }
+
+    // witness stacks of all inputs of the transaction, elements decoded from hex
+    async fn fetch_witnesses(&self, txid: &str) -> AssetResult<Vec<Vec<Vec<u8>>>> {
+        let btcfs = self.settings.btcfs.as_ref().ok_or(Error::AssetUriInvalid)?;
+        let (transaction, witness_field) = match btcfs.source {
+            BtcfsSource::Esplora => {
+                let url = format!("{}/tx/{txid}", btcfs.url.trim_end_matches('/'));
+                (self.fetch_json(self.client.get(url)).await?, "witness")
+            }
+            BtcfsSource::BitcoinRpc => {
+                let request = json!({
+                    "id": 0,
+                    "jsonrpc": "1.0",
+                    "method": "getrawtransaction",
+                    "params": [txid, true],
+                });
+                let response = self
+                    .fetch_json(self.client.post(&btcfs.url).json(&request))
+                    .await?;
+                (response["result"].clone(), "txinwitness")
+            }
+        };
+        let inputs = transaction["vin"].as_array().ok_or(Error::AssetNotFound)?;
+        Ok(inputs
+            .iter()
+            .map(|input| {
+                input[witness_field]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|element| hex::decode(element.as_str()?).ok())
+                    .collect()
+            })
+            .collect())
+    }
+
+    async fn fetch_json(&self, request: reqwest::RequestBuilder) -> AssetResult<Value> {
+        request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|_| Error::AssetFetchError)?
+            .json()
+            .await
+            .map_err(|_| Error::AssetFetchError)
+    }
+
+    // cache entry holds content type in the first line and then raw content
+    #[cfg(not(feature = "shuttle"))]
+    fn read_cache(&self, key: &str) -> Option<Asset> {
+        let file_content = fs::read(self.cache_path(key)).ok()?;
+        let separator = file_content.iter().position(|byte| *byte == b'\n')?;
+        Some(Asset {
+            content_type: String::from_utf8(file_content[..separator].to_vec()).ok()?,
+            content: file_content[separator + 1..].to_vec(),
+        })
+    }
+
+    #[cfg(not(feature = "shuttle"))]
+    fn write_cache(&self, key: &str, asset: &Asset) {
+        let cache_path = self.cache_path(key);
+        let mut file_content = format!("{}\n", asset.content_type).into_bytes();
+        file_content.extend_from_slice(&asset.content);
+        let written = cache_path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&cache_path, file_content));
+        if let Err(error) = written {
+            tracing::warn!("failed to cache asset {cache_path:?}: {error}");
+        }
+    }
+
+    #[cfg(not(feature = "shuttle"))]
+    fn cache_path(&self, key: &str) -> PathBuf {
+        self.settings.cache_directory.join(key)
+    }
+
+    // assets are fetched every time under shuttle, whose persist is reserved for render results
+    #[cfg(feature = "shuttle")]
+    fn read_cache(&self, _key: &str) -> Option<Asset> {
+        None
+    }
+
+    #[cfg(feature = "shuttle")]
+    fn write_cache(&self, _key: &str, _asset: &Asset) {}
+}
+
+// schemes of assets worth prefetching, only whole string values in render output are taken as uris
+const PREFETCH_SCHEMES: [&str; 4] = ["btcfs", "ipfs", "http", "https"];
+
+// distinct asset uris referenced in render output, in order of their appearance
+pub fn collect_asset_uris(render_output: &Value) -> Vec<String> {
+    let mut uris = Vec::new();
+    let mut pending = vec![render_output];
+    while let Some(value) = pending.pop() {
+        match value {
+            Value::String(text) => {
+                let is_asset_uri = text.split_once("://").is_some_and(|(scheme, path)| {
+                    PREFETCH_SCHEMES.contains(&scheme) && !path.is_empty()
+                });
+                if is_asset_uri && !uris.contains(text) {
+                    uris.push(text.clone());
+                }
+            }
+            Value::Array(values) => pending.extend(values.iter().rev()),
+            Value::Object(fields) => pending.extend(fields.values().rev()),
+            _ => {}
+        }
+    }
+    uris
+}
+
+pub fn rewrite_asset_uris(render_output: &mut Value, local_urls: &HashMap<String, String>) {
+    match render_output {
+        Value::String(text) => {
+            if let Some(local_url) = local_urls.get(text.as_str()) {
+                *text = local_url.clone();
+            }
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| rewrite_asset_uris(value, local_urls)),
+        Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|value| rewrite_asset_uris(value, local_urls)),
+        _ => {}
+    }
+}
+
+// opcodes of inscription envelope, like `OP_FALSE OP_IF "ord" OP_1 <content type> OP_0 <body>
+// OP_ENDIF` in tapscript
+const OP_FALSE: u8 = 0x00;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+const OP_1: u8 = 0x51;
+const OP_16: u8 = 0x60;
+const OP_IF: u8 = 0x63;
+const OP_ENDIF: u8 = 0x68;
+const CONTENT_TYPE_TAG: u8 = 1;
+
+#[derive(PartialEq)]
+enum Instruction<'a> {
+    Push(&'a [u8]),
+    Op(u8),
+}
+
+fn parse_instructions(script: &[u8]) -> Vec<Instruction<'_>> {
+    let mut instructions = Vec::new();
+    let mut cursor = 0;
+    while let Some(&opcode) = script.get(cursor) {
+        cursor += 1;
+        let length = match opcode {
+            OP_FALSE => Some(0),
+            0x01..=0x4b => Some(opcode as usize),
+            OP_PUSHDATA1 => script.get(cursor).map(|length| {
+                cursor += 1;
+                *length as usize
+            }),
+            OP_PUSHDATA2 => script.get(cursor..cursor + 2).map(|length| {
+                cursor += 2;
+                u16::from_le_bytes([length[0], length[1]]) as usize
+            }),
+            OP_PUSHDATA4 => script.get(cursor..cursor + 4).map(|length| {
+                cursor += 4;
+                u32::from_le_bytes([length[0], length[1], length[2], length[3]]) as usize
+            }),
+            _ => None,
+        };
+        match length {
+            Some(length) => {
+                let Some(data) = script.get(cursor..cursor + length) else {
+                    break;
+                };
+                cursor += length;
+                instructions.push(Instruction::Push(data));
+            }
+            None if matches!(opcode, OP_PUSHDATA1..=OP_PUSHDATA4) => break,
+            None => instructions.push(Instruction::Op(opcode)),
+        }
+    }
+    instructions
+}
+
+// extract inscriptions from envelopes in a witness element, in order of their appearance
+pub fn parse_inscriptions(script: &[u8]) -> Vec<Asset> {
+    let instructions = parse_instructions(script);
+    let mut inscriptions = Vec::new();
+    let mut cursor = 0;
+    while cursor + 2 < instructions.len() {
+        let envelope_start = instructions[cursor] == Instruction::Push(&[])
+            && instructions[cursor + 1] == Instruction::Op(OP_IF)
+            && instructions[cursor + 2] == Instruction::Push(b"ord");
+        cursor += 1;
+        if !envelope_start {
+            continue;
+        }
+        cursor += 2;
+        let mut content_type = String::new();
+        let mut content = Vec::new();
+        let mut in_body = false;
+        while let Some(instruction) = instructions.get(cursor) {
+            cursor += 1;
+            match instruction {
+                Instruction::Op(OP_ENDIF) => break,
+                Instruction::Push(data) if in_body => content.extend_from_slice(data),
+                // empty push separates fields from body
+                Instruction::Push([]) => in_body = true,
+                tag => {
+                    let tag = match tag {
+                        Instruction::Push([tag]) => *tag,
+                        Instruction::Op(opcode @ OP_1..=OP_16) => opcode - OP_1 + 1,
+                        _ => continue,
+                    };
+                    let Some(Instruction::Push(value)) = instructions.get(cursor) else {
+                        continue;
+                    };
+                    cursor += 1;
+                    if tag == CONTENT_TYPE_TAG {
+                        content_type = String::from_utf8_lossy(value).into_owned();
+                    }
+                }
+            }
+        }
+        inscriptions.push(Asset {
+            content_type,
+            content,
+        });
+    }
+    inscriptions
+}
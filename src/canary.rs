@@ -0,0 +1,180 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use jsonrpsee::tracing;
+use serde::Serialize;
+use serde_json::Value;
+use tower::{Layer, Service};
+
+use crate::decoder::DOBDecoder;
+use crate::server::decode_dob_uncached;
+use crate::types::CanarySettings;
+
+pub const READINESS_PATH: &str = "/ready";
+
+// outcome of canary decodings, server is healthy once the latest check passed and turns
+// unhealthy after `failure_threshold` consecutive failures
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CanaryStatus {
+    pub healthy: bool,
+    pub checks: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_checked_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+// decode the canary spore end-to-end periodically, bypassing render cache, to catch indexer or
+// decoder regressions before users do
+pub struct CanaryMonitor {
+    settings: CanarySettings,
+    status: RwLock<CanaryStatus>,
+}
+
+impl CanaryMonitor {
+    pub fn new(settings: CanarySettings) -> Self {
+        Self {
+            settings,
+            status: RwLock::default(),
+        }
+    }
+
+    pub fn status(&self) -> CanaryStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    pub async fn run(&self, decoder: &DOBDecoder) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.settings.interval_secs));
+        loop {
+            interval.tick().await;
+            self.check(decoder).await;
+        }
+    }
+
+    pub async fn check(&self, decoder: &DOBDecoder) {
+        let start = Instant::now();
+        let result = decode_dob_uncached(decoder, self.settings.spore_id.0)
+            .await
+            .map_err(|failure| format!("{} at stage {:?}", failure.error, failure.stage))
+            .and_then(|render_output| self.verify_render_output(&render_output));
+        let latency = start.elapsed();
+        let mut status = self.status.write().unwrap();
+        status.checks += 1;
+        status.last_checked_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|elapsed| elapsed.as_secs());
+        status.last_latency_ms = Some(latency.as_millis() as u64);
+        match result {
+            Ok(()) => {
+                if !status.healthy {
+                    tracing::info!("canary decoding passed, server is healthy");
+                }
+                status.healthy = true;
+                status.consecutive_failures = 0;
+                status.last_error = None;
+            }
+            Err(error) => {
+                status.failures += 1;
+                status.consecutive_failures += 1;
+                tracing::warn!("canary decoding failed: {error}");
+                if status.healthy && status.consecutive_failures >= self.settings.failure_threshold
+                {
+                    tracing::error!(
+                        "canary decoding failed {} times in a row, server is unhealthy",
+                        status.consecutive_failures
+                    );
+                    status.healthy = false;
+                }
+                status.last_error = Some(error);
+            }
+        }
+    }
+
+    fn verify_render_output(&self, render_output: &Value) -> Result<(), String> {
+        let Some(expected) = &self.settings.expected_render_output else {
+            return Ok(());
+        };
+        let expected: Value = serde_json::from_str(expected)
+            .map_err(|error| format!("invalid expected render output: {error}"))?;
+        if &expected != render_output {
+            return Err(format!("unexpected render output: {render_output}"));
+        }
+        Ok(())
+    }
+}
+
+// serve readiness on `GET /ready` for load balancers and orchestrators, in 200 if canary is
+// healthy or not configured, otherwise in 503, along with canary status in body
+#[derive(Clone, Default)]
+pub struct ReadinessLayer {
+    canary: Option<Arc<CanaryMonitor>>,
+}
+
+impl ReadinessLayer {
+    pub fn new(canary: Option<Arc<CanaryMonitor>>) -> Self {
+        Self { canary }
+    }
+}
+
+impl<S> Layer<S> for ReadinessLayer {
+    type Service = ReadinessService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ReadinessService {
+            inner,
+            canary: self.canary.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ReadinessService<S> {
+    inner: S,
+    canary: Option<Arc<CanaryMonitor>>,
+}
+
+impl<S> Service<Request<Body>> for ReadinessService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if request.method() == Method::GET && request.uri().path() == READINESS_PATH {
+            let (healthy, body) = match &self.canary {
+                Some(canary) => {
+                    let status = canary.status();
+                    (status.healthy, serde_json::to_string(&status).unwrap())
+                }
+                None => (true, "{}".to_string()),
+            };
+            let mut response = Response::new(Body::from(body));
+            if !healthy {
+                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            }
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            return Box::pin(async move { Ok(response) });
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
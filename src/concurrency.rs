@@ -0,0 +1,72 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use jsonrpsee::tracing;
+
+use crate::types::{BatchConcurrencySettings, Error};
+
+// errors reflecting the pressure on CKB node, rather than something wrong with the spore itself
+const RPC_PRESSURE_ERRORS: [Error; 4] = [
+    Error::FetchLiveCellsError,
+    Error::FetchTransactionError,
+    Error::FetchTipBlockNumberError,
+    Error::JsonRpcRequestError,
+];
+
+// AIMD-style controller of batch decoding parallelism, which increases the limit by one on each
+// fast and healthy decoding, and halves it once decoding is slow or CKB node starts failing, at
+// most once in a window of `target_latency`, since decodings in flight when the limit was cut
+// were started under the old limit and tell nothing about the new one
+pub struct AdaptiveConcurrency {
+    min_limit: usize,
+    max_limit: usize,
+    target_latency: Duration,
+    limit: AtomicUsize,
+    decreased_at: Mutex<Option<Instant>>,
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(settings: &BatchConcurrencySettings) -> Self {
+        let min_limit = settings.min_parallelism.max(1);
+        let max_limit = settings.max_parallelism.max(min_limit);
+        Self {
+            min_limit,
+            max_limit,
+            target_latency: Duration::from_millis(settings.target_latency_ms),
+            // start halfway, so that batches after restart are neither serial nor overwhelming
+            limit: AtomicUsize::new((max_limit / 2).max(min_limit)),
+            decreased_at: Mutex::new(None),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    pub fn record(&self, latency: Duration, error: Option<Error>) {
+        self.record_at(latency, error, Instant::now());
+    }
+
+    pub fn record_at(&self, latency: Duration, error: Option<Error>, now: Instant) {
+        let under_pressure = error.is_some_and(|error| RPC_PRESSURE_ERRORS.contains(&error));
+        let mut decreased_at = self.decreased_at.lock().unwrap();
+        let limit = self.limit();
+        let new_limit = if under_pressure || latency > self.target_latency {
+            let in_window = decreased_at.is_some_and(|decreased_at| {
+                now.saturating_duration_since(decreased_at) < self.target_latency
+            });
+            if in_window {
+                return;
+            }
+            *decreased_at = Some(now);
+            (limit / 2).max(self.min_limit)
+        } else {
+            (limit + 1).min(self.max_limit)
+        };
+        if new_limit != limit {
+            self.limit.store(new_limit, Ordering::Relaxed);
+            tracing::debug!("batch decoding parallelism adjusted from {limit} to {new_limit}");
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::env_overrides::apply_env_overrides;
+use crate::types::{Network, Settings};
+
+// well-known spore and cluster scripts and decoder deployments of each network, along with defaults
+// to get a working server without a settings file
+const MAINNET_PROFILE: &str = include_str!("../profiles/mainnet.toml");
+const TESTNET_PROFILE: &str = include_str!("../profiles/testnet.toml");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    // told by file extension, files without a known one are taken as TOML
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase)
+            .as_deref()
+        {
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
+        }
+    }
+}
+
+// where settings are loaded from on start, and again on reloading, a settings file is layered over
+// the profile of network if both are given, then `DOB_*` environment variables over them
+#[derive(Debug, Clone, Default)]
+pub struct SettingsSource {
+    pub network: Option<Network>,
+    pub file: Option<PathBuf>,
+}
+
+impl SettingsSource {
+    pub fn read(&self) -> Result<Settings, String> {
+        let mut settings = match self.network {
+            Some(network) => parse_table(profile(network), ConfigFormat::Toml)?,
+            None => Map::new(),
+        };
+        if let Some(file) = &self.file {
+            let content = std::fs::read_to_string(file)
+                .map_err(|error| format!("failed to read {file:?}: {error}"))?;
+            merge_table(
+                &mut settings,
+                parse_table(&content, ConfigFormat::from_path(file))?,
+            );
+        }
+        into_settings(settings)
+    }
+}
+
+pub fn profile(network: Network) -> &'static str {
+    match network {
+        Network::Mainnet => MAINNET_PROFILE,
+        Network::Testnet => TESTNET_PROFILE,
+    }
+}
+
+pub fn parse_settings(content: &str, format: ConfigFormat) -> Result<Settings, String> {
+    into_settings(parse_table(content, format)?)
+}
+
+fn parse_table(content: &str, format: ConfigFormat) -> Result<Map<String, Value>, String> {
+    let settings = match format {
+        ConfigFormat::Toml => toml::from_str::<Value>(content).map_err(|error| error.to_string()),
+        ConfigFormat::Yaml => {
+            serde_yaml::from_str::<Value>(content).map_err(|error| error.to_string())
+        }
+        ConfigFormat::Json => {
+            serde_json::from_str::<Value>(content).map_err(|error| error.to_string())
+        }
+    }?;
+    match settings {
+        Value::Object(settings) => Ok(settings),
+        _ => Err("settings should be a table".to_string()),
+    }
+}
+
+fn into_settings(mut settings: Map<String, Value>) -> Result<Settings, String> {
+    // variables not in unicode can't be settings anyway
+    let vars = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
+    apply_env_overrides(&mut settings, vars);
+    serde_json::from_value(Value::Object(settings)).map_err(|error| error.to_string())
+}
+
+// tables are merged key by key, while other values, including arrays of tables like
+// `available_spores`, are replaced as a whole
+pub fn merge_table(base: &mut Map<String, Value>, overlay: Map<String, Value>) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Object(base)), Value::Object(overlay)) => merge_table(base, overlay),
+            (Some(base), value) => *base = value,
+            (None, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
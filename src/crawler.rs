@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use jsonrpsee::tracing;
+use serde::Serialize;
+
+use crate::concurrency::AdaptiveConcurrency;
+use crate::decoder::DOBDecoder;
+use crate::server::{batch_decode_dob_each, DecodeOptions};
+use crate::types::{BatchConcurrencySettings, ClusterCrawlerSettings, Settings};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CrawlState {
+    Running,
+    Finished,
+    Failed,
+}
+
+// progress of the latest crawl of a cluster, counters grow while it's running
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct CrawlReport {
+    pub state: CrawlState,
+    // live spores found in the cluster, known once enumeration is done
+    pub spores: usize,
+    pub decoded: usize,
+    pub failures: usize,
+    // unix timestamps in seconds
+    pub started_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// enumerate live spores of clusters and decode them all ahead, so that render cache is warm
+// before collection launches bring in a wave of cold requests, spores are decoded through the
+// regular path so results land in render cache as usual
+pub struct ClusterCrawler {
+    settings: ClusterCrawlerSettings,
+    concurrency: AdaptiveConcurrency,
+    reports: Mutex<HashMap<[u8; 32], CrawlReport>>,
+}
+
+impl ClusterCrawler {
+    // parallelism adapts to latency like batch decoding, bounded by its own limit to leave room
+    // for serving requests
+    pub fn new(settings: &Settings) -> Self {
+        let concurrency = AdaptiveConcurrency::new(&BatchConcurrencySettings {
+            min_parallelism: 1,
+            max_parallelism: settings.cluster_crawler.max_parallelism,
+            target_latency_ms: settings.batch_concurrency.target_latency_ms,
+        });
+        Self {
+            settings: settings.cluster_crawler.clone(),
+            concurrency,
+            reports: Mutex::default(),
+        }
+    }
+
+    pub fn report(&self, cluster_id: &[u8; 32]) -> Option<CrawlReport> {
+        self.reports.lock().unwrap().get(cluster_id).cloned()
+    }
+
+    // crawl configured clusters one after another on start and every `interval_secs` then
+    pub async fn run(&self, decoder: &DOBDecoder) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.settings.interval_secs));
+        loop {
+            interval.tick().await;
+            for cluster_id in &self.settings.cluster_ids {
+                self.crawl(decoder, cluster_id.0).await;
+            }
+        }
+    }
+
+    pub async fn crawl(&self, decoder: &DOBDecoder, cluster_id: [u8; 32]) {
+        if self.start(cluster_id).is_ok() {
+            self.proceed(decoder, cluster_id).await;
+        }
+    }
+
+    // mark the cluster as being crawled and returns the fresh report, or report of the crawl in
+    // progress as error, taken under the same lock so that it never races with the crawl
+    pub fn start(&self, cluster_id: [u8; 32]) -> Result<CrawlReport, CrawlReport> {
+        let mut reports = self.reports.lock().unwrap();
+        if let Some(report) = reports
+            .get(&cluster_id)
+            .filter(|report| report.state == CrawlState::Running)
+        {
+            return Err(report.clone());
+        }
+        let report = CrawlReport {
+            state: CrawlState::Running,
+            spores: 0,
+            decoded: 0,
+            failures: 0,
+            started_at: unix_timestamp(),
+            finished_at: None,
+            error: None,
+        };
+        reports.insert(cluster_id, report.clone());
+        Ok(report)
+    }
+
+    // crawl the cluster marked by `start`
+    pub async fn proceed(&self, decoder: &DOBDecoder, cluster_id: [u8; 32]) {
+        let hexed_cluster_id = hex::encode(cluster_id);
+        tracing::info!("crawling spores of cluster {hexed_cluster_id}");
+        let index = match decoder.cluster_serial_index(cluster_id).await {
+            Ok(index) => index,
+            Err(error) => {
+                tracing::warn!("failed to enumerate spores of cluster {hexed_cluster_id}: {error}");
+                self.update(&cluster_id, |report| {
+                    report.state = CrawlState::Failed;
+                    report.error = Some(error.to_string());
+                });
+                return;
+            }
+        };
+        self.update(&cluster_id, |report| report.spores = index.spore_ids.len());
+        batch_decode_dob_each(
+            decoder,
+            index.spore_ids.iter().map(hex::encode).collect(),
+            &DecodeOptions::default(),
+            &self.concurrency,
+            |_, result| {
+                self.update(&cluster_id, |report| match result {
+                    Ok(_) => report.decoded += 1,
+                    Err(_) => report.failures += 1,
+                });
+                true
+            },
+        )
+        .await;
+        self.update(&cluster_id, |report| {
+            report.state = CrawlState::Finished;
+            tracing::info!(
+                "crawled cluster {hexed_cluster_id}, {} decoded and {} failed",
+                report.decoded,
+                report.failures
+            );
+        });
+    }
+
+    fn update(&self, cluster_id: &[u8; 32], update: impl FnOnce(&mut CrawlReport)) {
+        if let Some(report) = self.reports.lock().unwrap().get_mut(cluster_id) {
+            update(report);
+            if report.state != CrawlState::Running {
+                report.finished_at = Some(unix_timestamp());
+            }
+        }
+    }
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,56 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Method, Request, Response};
+use tower::{Layer, Service};
+
+// single page calling json-rpc methods of this server from browser, embedded into binary
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+pub const DASHBOARD_PATH: &str = "/dashboard";
+
+// serve the dashboard page on `GET /dashboard`, other requests are passed to json-rpc server
+#[derive(Clone, Default)]
+pub struct DashboardLayer;
+
+impl<S> Layer<S> for DashboardLayer {
+    type Service = DashboardService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DashboardService { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct DashboardService<S> {
+    inner: S,
+}
+
+impl<S> Service<Request<Body>> for DashboardService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if request.method() == Method::GET && request.uri().path() == DASHBOARD_PATH {
+            let mut response = Response::new(Body::from(DASHBOARD_HTML));
+            response.headers_mut().insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("text/html; charset=utf-8"),
+            );
+            return Box::pin(async move { Ok(response) });
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
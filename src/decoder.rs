@@ -1,27 +1,138 @@
-use crate::types::{ClusterDescriptionField, DecoderLocationType, Error, ScriptId, Settings};
+use crate::assets::AssetResolver;
+use crate::metrics;
+use crate::protocol::ProtocolRegistry;
+use crate::registry::parse_decoder_registry;
+use crate::retention::ClusterActivity;
+use crate::slow_decode;
+use crate::stats::ClusterStats;
+#[cfg(feature = "trait_index")]
+use crate::trait_index::TraitIndex;
+use crate::types::{
+    CachedDecoder, ClusterDecoderOverride, ClusterDescriptionField, DOBDecoderFormat,
+    DecodeFailure, DecodePipeline, DecodeStage, DecoderLocationType, DecoderStatus, Error,
+    HashType, NormalizedClusterData, NormalizedSporeData, OnchainDecoderDeployment,
+    OwnedSporesCursor, ParsedClusterMetadata, ProtocolVersionInfo, ProtocolVersionMatching,
+    RawDecoderParam, RenderDebugSettings, ScriptId, Settings, SporeCellContent, SporeCheck,
+    UnconfirmedSporePolicy,
+};
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
 use ckb_client::rpc_client::RpcClient;
 use ckb_client::{
     constant::TYPE_ID_CODE_HASH,
-    types::{IndexerScriptSearchMode, Order, SearchKey},
+    types::{Cell, CellType, IndexerScriptSearchMode, Order, SearchKey, SearchKeyFilter, Tx},
 };
+use ckb_jsonrpc_types::Either;
 use ckb_types::{
     core::ScriptHashType,
-    packed::{OutPoint, Script},
+    packed::{self, OutPoint, Script},
     prelude::{Builder, Entity, Pack},
     H256,
 };
+use futures::{StreamExt, TryStreamExt};
+use jsonrpsee::tracing;
+use lru::LruCache;
 use serde_json::Value;
-use spore_types::generated::spore::{ClusterData, SporeData};
+use spore_types::generated::spore::{ClusterData, ClusterDataV2, SporeData};
 
 type DecodeResult<T> = Result<T, Error>;
 
+// decoder binary is located in file system, or keyed in persist instance when shuttle enabled
+#[cfg(not(feature = "shuttle"))]
+pub type DecoderPath = std::path::PathBuf;
+#[cfg(feature = "shuttle")]
+pub type DecoderPath = String;
+
+// max live cells returned in one search, more than one cell means duplicated type scripts
+const LIVE_CELLS_SEARCH_LIMIT: u32 = 8;
+
+// max distinct unknown protocol versions to be logged, in case of spam content types
+const UNKNOWN_VERSIONS_LIMIT: usize = 256;
+
 // import persistinstance when shuttle feature enabled
 #[cfg(feature = "shuttle")]
 use shuttle_persist::PersistInstance;
 
+// everything needed to run the decoder for spores under a same cluster, built once per cluster
+pub struct DecodeContext {
+    pub cluster_id: [u8; 32],
+    pub cluster: Arc<ParsedClusterMetadata>,
+    pub decoder_path: DecoderPath,
+    // on-chain decoder behind `decoder_path`, none if it's a local binary pinned by operator
+    pub decoder: Option<DOBDecoderFormat>,
+    // decoders of following DOB/1 stages, in order
+    pub stage_decoder_paths: Vec<DecoderPath>,
+    pub max_cycles: u64,
+}
+
+// decode contexts shared by spores in one batch, keyed by cluster id
+#[derive(Default)]
+pub struct DecodeContexts(Mutex<HashMap<[u8; 32], Arc<DecodeContext>>>);
+
+// parsed cluster metadata keyed by cluster id, cluster is immutable so entries never go stale
+type ClusterMetadataCache = Mutex<LruCache<[u8; 32], Arc<ParsedClusterMetadata>>>;
+
+// outputs of DOB/1 stages keyed by hash of decoder, pattern and input, decoders are deterministic so
+// entries never go stale either
+type StageOutputCache = Mutex<LruCache<[u8; 32], String>>;
+
+// live spores of a cluster in mint order, where serial `n` refers to the `n-1`th spore
+pub struct ClusterSerialIndex {
+    pub spore_ids: Vec<[u8; 32]>,
+    built_at: Instant,
+}
+
+// max clusters whose serial index is kept in memory
+const CLUSTER_SERIAL_INDEXES_CAPACITY: usize = 64;
+
+// page size of scanning live spore cells
+const SPORE_CELLS_PAGE_SIZE: u32 = 256;
+
+// max pages of live spore cells scanned for one page of spores in a cluster
+const CLUSTER_SPORES_SCAN_PAGES: usize = 16;
+
+// parallelism of looking up mint transactions while building serial index
+const MINT_LOOKUP_PARALLELISM: usize = 8;
+
+// decoders supplied by callers of `dob_decode_raw` are untrusted, so they are always bounded by
+// these whatever `decoder_max_cycles` says
+const RAW_DECODE_MAX_CYCLES: u64 = 1_000_000_000;
+const RAW_DECODER_MAX_BYTES: usize = 512 * 1024;
+
 pub struct DOBDecoder {
     rpc: RpcClient,
     settings: Settings,
+    cluster_cache: Option<ClusterMetadataCache>,
+    // served protocol versions and their decode pipelines, replaced on reloading settings
+    protocols: RwLock<ProtocolRegistry>,
+    // protocol versions seen in spores but not configured, for tracking new protocol adoption
+    unknown_versions: Mutex<HashSet<String>>,
+    // targets whose full decoder outputs are logged, toggleable at runtime
+    render_debug: RwLock<RenderDebugSettings>,
+    // spore serial indexes of clusters, rebuilt once expired
+    cluster_serial_indexes: Mutex<LruCache<[u8; 32], Arc<ClusterSerialIndex>>>,
+    // decoder deployments synced from remote registry, replaced on each sync
+    registry_deployments: RwLock<Vec<OnchainDecoderDeployment>>,
+    // configured decoder deployments and overrides, replaced on reloading settings
+    onchain_decoder_deployment: RwLock<Vec<OnchainDecoderDeployment>>,
+    cluster_decoder_overrides: RwLock<Vec<ClusterDecoderOverride>>,
+    // spore and cluster scripts searched for cells, replaced on reloading settings
+    available_spores: RwLock<Vec<ScriptId>>,
+    available_clusters: RwLock<Vec<ScriptId>>,
+    // request frequency of clusters, which decides retention of their render cache entries
+    cluster_activity: ClusterActivity,
+    // request counts, cache hits, execution time and the latest error of clusters
+    cluster_stats: ClusterStats,
+    // outputs of DOB/1 stages following the first one, shared by spores with the same traits
+    stage_outputs: Option<StageOutputCache>,
+    // resolver of assets referenced in render output, only present if `assets` is configured
+    assets: Option<AssetResolver>,
+    // trait values of decoded spores, only present if `trait_index` is configured
+    #[cfg(feature = "trait_index")]
+    trait_index: Option<TraitIndex>,
     // only enabled when shuttle feature enabled
     #[cfg(feature = "shuttle")]
     pub persist: PersistInstance,
@@ -35,179 +146,771 @@ impl DOBDecoder {
         let _ = std::fs::create_dir_all(&settings.decoders_cache_directory);
         let _ = std::fs::create_dir_all(&settings.dobs_cache_directory);
 
-        Self {
-            rpc: RpcClient::new(&settings.ckb_rpc),
-            settings,
-            #[cfg(feature = "shuttle")]
-            persist,
-        }
+        let rpc = RpcClient::new(&settings.ckb_rpc);
+        Self::build(settings, rpc)
     }
 
     #[allow(dead_code)]
     #[cfg(feature = "shuttle")]
     pub fn new(settings: Settings, persist: PersistInstance) -> Self {
-        Self {
-            rpc: RpcClient::new(&settings.ckb_rpc),
-            settings,
-            persist,
-        }
+        let rpc = RpcClient::new(&settings.ckb_rpc);
+        Self::build(settings, rpc, persist)
     }
 
     #[allow(dead_code)]
     #[cfg(not(feature = "shuttle"))]
     pub fn new_with_rpc(settings: Settings, rpc: RpcClient) -> Self {
-        Self { rpc, settings }
+        Self::build(settings, rpc)
     }
 
     #[allow(dead_code)]
     #[cfg(feature = "shuttle")]
     pub fn new_with_rpc(settings: Settings, rpc: RpcClient, persist: PersistInstance) -> Self {
+        Self::build(settings, rpc, persist)
+    }
+
+    // shared by constructors above, all caches start empty
+    fn build(
+        settings: Settings,
+        rpc: RpcClient,
+        #[cfg(feature = "shuttle")] persist: PersistInstance,
+    ) -> Self {
         Self {
             rpc,
+            cluster_cache: build_cluster_cache(&settings),
+            protocols: RwLock::new(ProtocolRegistry::new(&settings)),
+            unknown_versions: Mutex::default(),
+            render_debug: RwLock::new(settings.render_debug.clone()),
+            cluster_serial_indexes: Mutex::new(LruCache::new(
+                NonZeroUsize::new(CLUSTER_SERIAL_INDEXES_CAPACITY).unwrap(),
+            )),
+            registry_deployments: RwLock::default(),
+            onchain_decoder_deployment: RwLock::new(settings.onchain_decoder_deployment.clone()),
+            cluster_decoder_overrides: RwLock::new(settings.cluster_decoder_overrides.clone()),
+            available_spores: RwLock::new(settings.available_spores.clone()),
+            available_clusters: RwLock::new(settings.available_clusters.clone()),
+            cluster_stats: ClusterStats::default(),
+            stage_outputs: build_stage_cache(&settings),
+            assets: settings.assets.clone().map(AssetResolver::new),
+            #[cfg(feature = "trait_index")]
+            trait_index: None,
+            cluster_activity: ClusterActivity::new(
+                settings.dobs_cache_retention.activity_half_life_secs,
+            ),
             settings,
+            #[cfg(feature = "shuttle")]
             persist,
         }
     }
 
     pub fn protocol_versions(&self) -> Vec<String> {
-        self.settings.protocol_versions.clone()
+        self.protocols.read().unwrap().versions()
     }
 
+    pub fn protocol_version_infos(&self) -> Vec<ProtocolVersionInfo> {
+        self.protocols.read().unwrap().infos()
+    }
+
+    pub fn available_spores(&self) -> Vec<ScriptId> {
+        self.available_spores.read().unwrap().clone()
+    }
+
+    pub fn available_clusters(&self) -> Vec<ScriptId> {
+        self.available_clusters.read().unwrap().clone()
+    }
+
+    // settings loaded on start, those replaced by reloading are read through their own accessors
     pub fn setting(&self) -> &Settings {
         &self.settings
     }
 
+    // apply settings which take effect without restarting, which are render debug targets,
+    // protocol versions, decoder deployments and overrides, and spore and cluster scripts, returns
+    // names of other settings changed since start, which only take effect after restarting
+    pub fn reload_settings(&self, settings: Settings) -> Vec<String> {
+        let to_object = |settings: &Settings| match serde_json::to_value(settings) {
+            Ok(Value::Object(object)) => object,
+            _ => serde_json::Map::new(),
+        };
+        let (loaded, reloaded) = (to_object(&self.settings), to_object(&settings));
+        let mut restart_required = reloaded
+            .iter()
+            .filter(|(name, _)| !Settings::RELOADABLE.contains(&name.as_str()))
+            .filter(|(name, value)| loaded.get(name.as_str()) != Some(value))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        restart_required.sort();
+        let protocols = ProtocolRegistry::new(&settings);
+        self.set_render_debug(settings.render_debug);
+        // swapped together, so that no decoding sees deployments or scripts half reloaded
+        let mut guards = (
+            self.protocols.write().unwrap(),
+            self.onchain_decoder_deployment.write().unwrap(),
+            self.cluster_decoder_overrides.write().unwrap(),
+            self.available_spores.write().unwrap(),
+            self.available_clusters.write().unwrap(),
+        );
+        *guards.0 = protocols;
+        *guards.1 = settings.onchain_decoder_deployment;
+        *guards.2 = settings.cluster_decoder_overrides;
+        *guards.3 = settings.available_spores;
+        *guards.4 = settings.available_clusters;
+        restart_required
+    }
+
+    // decoder pinned by operator for the cluster
+    pub fn cluster_decoder_override(
+        &self,
+        cluster_id: &[u8; 32],
+    ) -> Option<ClusterDecoderOverride> {
+        self.cluster_decoder_overrides
+            .read()
+            .unwrap()
+            .iter()
+            .find(|decoder_override| decoder_override.cluster_id.0 == *cluster_id)
+            .cloned()
+    }
+
+    pub fn cluster_activity(&self) -> &ClusterActivity {
+        &self.cluster_activity
+    }
+
+    pub fn cluster_stats(&self) -> &ClusterStats {
+        &self.cluster_stats
+    }
+
+    pub fn assets(&self) -> Option<&AssetResolver> {
+        self.assets.as_ref()
+    }
+
+    #[cfg(feature = "trait_index")]
+    pub fn with_trait_index(mut self, trait_index: TraitIndex) -> Self {
+        self.trait_index = Some(trait_index);
+        self
+    }
+
+    #[cfg(feature = "trait_index")]
+    pub fn trait_index(&self) -> Option<&TraitIndex> {
+        self.trait_index.as_ref()
+    }
+
+    // replace render debug targets at runtime, returns the previous ones
+    pub fn set_render_debug(&self, render_debug: RenderDebugSettings) -> RenderDebugSettings {
+        std::mem::replace(&mut self.render_debug.write().unwrap(), render_debug)
+    }
+
+    // drop parsed metadata of one cluster, or all clusters if not specified
+    pub fn evict_cluster_cache(&self, cluster_id: Option<&[u8; 32]>) {
+        let Some(cluster_cache) = &self.cluster_cache else {
+            return;
+        };
+        let mut cluster_cache = cluster_cache.lock().unwrap();
+        match cluster_id {
+            Some(cluster_id) => {
+                cluster_cache.pop(cluster_id);
+            }
+            None => cluster_cache.clear(),
+        }
+    }
+
+    // entries number and estimated heap bytes of the cluster metadata cache
+    pub fn cluster_cache_usage(&self) -> (usize, usize) {
+        let Some(cluster_cache) = &self.cluster_cache else {
+            return (0, 0);
+        };
+        let cluster_cache = cluster_cache.lock().unwrap();
+        let bytes = cluster_cache
+            .iter()
+            .map(|(_, cluster)| {
+                // pattern is held both as parsed JSON and stringified, count its text twice
+                std::mem::size_of::<ParsedClusterMetadata>()
+                    + cluster.metadata.description.len()
+                    + cluster.pattern.len() * 2
+            })
+            .sum();
+        (cluster_cache.len(), bytes)
+    }
+
     pub async fn fetch_decode_ingredients(
         &self,
         spore_id: [u8; 32],
     ) -> DecodeResult<((Value, String), ClusterDescriptionField)> {
-        let (content, cluster_id) = self.fetch_dob_content(spore_id).await?;
-        let dob_metadata = self.fetch_dob_metadata(cluster_id).await?;
-        Ok((content, dob_metadata))
+        let spore = self.fetch_dob_content(spore_id, None).await?;
+        let cluster = self.fetch_dob_metadata(spore.cluster_id, None).await?;
+        Ok(((spore.dob_content, spore.dna), cluster.metadata.clone()))
     }
 
-    // decode DNA under target spore_id
-    pub async fn decode_dna(
+    // resolve local path of decoder binary, which will be downloaded and persisted if not cached
+    #[tracing::instrument(name = "fetch_decoder_binary", skip_all, fields(hash = %decoder.hash))]
+    pub async fn resolve_decoder_path(
         &self,
-        dna: &str,
-        dob_metadata: ClusterDescriptionField,
-    ) -> DecodeResult<String> {
-        let decoder_path = match dob_metadata.dob.decoder.location {
-            DecoderLocationType::CodeHash => {
-                #[cfg(not(feature = "shuttle"))]
-                {
-                    let mut decoder_path = self.settings.decoders_cache_directory.clone();
-                    decoder_path.push(format!(
-                        "code_hash_{}.bin",
-                        hex::encode(&dob_metadata.dob.decoder.hash)
-                    ));
-                    if !decoder_path.exists() {
-                        let onchain_decoder =
-                            self.settings.onchain_decoder_deployment.iter().find_map(
-                                |deployment| {
-                                    if deployment.code_hash == dob_metadata.dob.decoder.hash {
-                                        Some(self.fetch_decoder_binary_directly(
-                                            deployment.tx_hash.clone(),
-                                            deployment.out_index,
-                                        ))
-                                    } else {
-                                        None
-                                    }
-                                },
-                            );
-                        let Some(decoder_binary) = onchain_decoder else {
-                            return Err(Error::NativeDecoderNotFound);
-                        };
-                        let decoder_file_content = decoder_binary.await?;
-                        if ckb_hash::blake2b_256(&decoder_file_content)
-                            != dob_metadata.dob.decoder.hash.0
-                        {
-                            return Err(Error::DecoderBinaryHashInvalid);
-                        }
-                        println!("write decoder binary to {:?}", decoder_path);
-                        std::fs::write(decoder_path.clone(), decoder_file_content)
-                            .map_err(|_| Error::DecoderBinaryPathInvalid)?;
-                    }
-                    decoder_path
-                }
-                // do this when shuttle enabled
-                #[cfg(feature = "shuttle")]
-                {
-                    let decoder_path = format!(
-                        "code_hash_{}.bin",
-                        hex::encode(&dob_metadata.dob.decoder.hash)
-                    );
-                    if self.persist.load::<String>(decoder_path.as_str()).is_err() {
-                        let onchain_decoder =
-                            self.settings.onchain_decoder_deployment.iter().find_map(
-                                |deployment| {
-                                    if deployment.code_hash == dob_metadata.dob.decoder.hash {
-                                        Some(self.fetch_decoder_binary_directly(
-                                            deployment.tx_hash.clone(),
-                                            deployment.out_index,
-                                        ))
-                                    } else {
-                                        None
-                                    }
-                                },
-                            );
-                        let Some(decoder_binary) = onchain_decoder else {
-                            return Err(Error::NativeDecoderNotFound);
-                        };
-                        let decoder_file_content = decoder_binary.await?;
-                        if ckb_hash::blake2b_256(&decoder_file_content)
-                            != dob_metadata.dob.decoder.hash.0
-                        {
-                            return Err(Error::DecoderBinaryHashInvalid);
-                        }
-                        println!("write decoder binary to {:?}", decoder_path);
-                        self.persist
-                            .save::<Vec<u8>>(decoder_path.as_str(), decoder_file_content)
-                            .map_err(|_| Error::DecoderBinaryPathInvalid)?;
-                        println!("save to persist! cache_path: {:?}", decoder_path);
-                    }
-                    decoder_path
+        decoder: &DOBDecoderFormat,
+    ) -> DecodeResult<DecoderPath> {
+        let start = Instant::now();
+        let decoder_path = match decoder.location {
+            DecoderLocationType::CodeHash => self.resolve_code_hash_decoder(&decoder.hash).await,
+            DecoderLocationType::TypeId => self.resolve_type_id_decoder(&decoder.hash).await,
+        };
+        metrics::observe_decode_phase(DecodeStage::DownloadDecoder, start.elapsed());
+        decoder_path
+    }
+
+    // decoder located by `type_id` may have been upgraded since the anchor block, so anchored
+    // decoding loads the binary deployed by then, which is cached by its hash like those located
+    // by `code_hash`, while binaries located by `code_hash` are the same at any block
+    async fn resolve_anchored_decoder_path(
+        &self,
+        decoder: &DOBDecoderFormat,
+        anchor_block_number: Option<u64>,
+    ) -> DecodeResult<DecoderPath> {
+        let (DecoderLocationType::TypeId, Some(anchor_block_number)) =
+            (&decoder.location, anchor_block_number)
+        else {
+            return self.resolve_decoder_path(decoder).await;
+        };
+        let type_id_script = [ScriptId {
+            code_hash: TYPE_ID_CODE_HASH,
+            hash_type: HashType::Type,
+        }];
+        let decoder_binary = self
+            .fetch_cell_at_anchor(decoder.hash.0, &type_id_script, anchor_block_number)
+            .await?
+            .and_then(|decoder_cell| decoder_cell.output_data)
+            .ok_or(Error::DecoderIdNotFound)?
+            .as_bytes()
+            .to_vec();
+        let code_hash = hex::encode(ckb_hash::blake2b_256(&decoder_binary));
+        #[cfg(not(feature = "shuttle"))]
+        {
+            let mut decoder_path = self.settings.decoders_cache_directory.clone();
+            decoder_path.push(format!("code_hash_{code_hash}.bin"));
+            if !decoder_path.exists() {
+                std::fs::write(decoder_path.clone(), decoder_binary)
+                    .map_err(|_| Error::DecoderBinaryPathInvalid)?;
+            }
+            Ok(decoder_path)
+        }
+        #[cfg(feature = "shuttle")]
+        {
+            let decoder_path = format!("code_hash_{code_hash}.bin");
+            if self.persist.load::<String>(decoder_path.as_str()).is_err() {
+                self.persist
+                    .save::<Vec<u8>>(decoder_path.as_str(), decoder_binary)
+                    .map_err(|_| Error::DecoderBinaryPathInvalid)?;
+            }
+            Ok(decoder_path)
+        }
+    }
+
+    // decoder pinned by operator for a cluster, local binary is used as is without hash check
+    async fn resolve_override_decoder_path(
+        &self,
+        decoder_override: &ClusterDecoderOverride,
+    ) -> DecodeResult<DecoderPath> {
+        if let Some(code_hash) = &decoder_override.code_hash {
+            return self.resolve_code_hash_decoder(code_hash).await;
+        }
+        if let Some(type_id) = &decoder_override.type_id {
+            return self.resolve_type_id_decoder(type_id).await;
+        }
+        #[cfg(not(feature = "shuttle"))]
+        if let Some(binary_path) = &decoder_override.binary_path {
+            if !binary_path.is_file() {
+                return Err(Error::DecoderBinaryPathInvalid);
+            }
+            return Ok(binary_path.clone());
+        }
+        Err(Error::NativeDecoderNotFound)
+    }
+
+    // configured deployments take precedence over those synced from remote registry
+    fn find_decoder_deployment(&self, code_hash: &H256) -> Option<OnchainDecoderDeployment> {
+        self.onchain_decoder_deployment
+            .read()
+            .unwrap()
+            .iter()
+            .find(|deployment| deployment.code_hash == *code_hash)
+            .cloned()
+            .or_else(|| {
+                self.registry_deployments
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .find(|deployment| deployment.code_hash == *code_hash)
+                    .cloned()
+            })
+    }
+
+    // fetch and verify decoder deployments from the configured registry, then replace those
+    // synced before, returns the number of deployments synced
+    pub async fn sync_decoder_registry(&self) -> DecodeResult<usize> {
+        let Some(registry) = &self.settings.decoder_registry else {
+            return Ok(0);
+        };
+        let deployments = match (&registry.url, &registry.type_id) {
+            (Some(url), _) => {
+                let document = reqwest::get(url)
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                    .map_err(|_| Error::DecoderRegistryFetchError)?
+                    .bytes()
+                    .await
+                    .map_err(|_| Error::DecoderRegistryFetchError)?;
+                let public_key = registry
+                    .public_key
+                    .as_deref()
+                    .ok_or(Error::DecoderRegistryInvalid)?;
+                parse_decoder_registry(&document, Some(public_key))?
+            }
+            (None, Some(type_id)) => {
+                let registry_cell = self
+                    .fetch_newest_live_cell(build_type_id_search_option(type_id.0))
+                    .await?
+                    .ok_or(Error::DecoderRegistryFetchError)?;
+                let document = registry_cell.output_data.unwrap_or_default();
+                parse_decoder_registry(document.as_bytes(), None)?
+            }
+            (None, None) => return Err(Error::DecoderRegistryFetchError),
+        };
+        let synced = deployments.len();
+        *self.registry_deployments.write().unwrap() = deployments;
+        Ok(synced)
+    }
+
+    // decoder binary located by `code_hash` comes from either local cache or configured deployment
+    async fn resolve_code_hash_decoder(&self, code_hash: &H256) -> DecodeResult<DecoderPath> {
+        #[cfg(not(feature = "shuttle"))]
+        {
+            let mut decoder_path = self.settings.decoders_cache_directory.clone();
+            decoder_path.push(format!("code_hash_{}.bin", hex::encode(code_hash)));
+            if !decoder_path.exists() {
+                let Some(deployment) = self.find_decoder_deployment(code_hash) else {
+                    return Err(Error::NativeDecoderNotFound);
+                };
+                let decoder_file_content =
+                    self.fetch_decoder_binary_deployment(&deployment).await?;
+                if ckb_hash::blake2b_256(&decoder_file_content) != code_hash.0 {
+                    return Err(Error::DecoderBinaryHashInvalid);
                 }
+                tracing::info!("write decoder binary to {decoder_path:?}");
+                std::fs::write(decoder_path.clone(), decoder_file_content)
+                    .map_err(|_| Error::DecoderBinaryPathInvalid)?;
             }
-            DecoderLocationType::TypeId => {
-                #[cfg(not(feature = "shuttle"))]
-                {
-                    let mut decoder_path = self.settings.decoders_cache_directory.clone();
-                    decoder_path.push(format!(
-                        "type_id_{}.bin",
-                        hex::encode(&dob_metadata.dob.decoder.hash)
-                    ));
-                    if !decoder_path.exists() {
-                        let decoder_binary = self
-                            .fetch_decoder_binary(dob_metadata.dob.decoder.hash.into())
-                            .await?;
-                        std::fs::write(decoder_path.clone(), decoder_binary)
-                            .map_err(|_| Error::DecoderBinaryPathInvalid)?;
-                    }
-                    decoder_path
+            Ok(decoder_path)
+        }
+        // do this when shuttle enabled
+        #[cfg(feature = "shuttle")]
+        {
+            let decoder_path = format!("code_hash_{}.bin", hex::encode(code_hash));
+            if self.persist.load::<String>(decoder_path.as_str()).is_err() {
+                let Some(deployment) = self.find_decoder_deployment(code_hash) else {
+                    return Err(Error::NativeDecoderNotFound);
+                };
+                let decoder_file_content =
+                    self.fetch_decoder_binary_deployment(&deployment).await?;
+                if ckb_hash::blake2b_256(&decoder_file_content) != code_hash.0 {
+                    return Err(Error::DecoderBinaryHashInvalid);
                 }
-                #[cfg(feature = "shuttle")]
-                {
-                    let decoder_path = format!(
-                        "type_id_{}.bin",
-                        hex::encode(&dob_metadata.dob.decoder.hash)
-                    );
-                    if self.persist.load::<String>(decoder_path.as_str()).is_err() {
-                        let decoder_binary = self
-                            .fetch_decoder_binary(dob_metadata.dob.decoder.hash.into())
-                            .await?;
-                        self.persist
-                            .save::<Vec<u8>>(format!("{:?}", decoder_path).as_str(), decoder_binary)
-                            .map_err(|_| Error::DecoderBinaryPathInvalid)?;
-                    }
-                    decoder_path
+                tracing::info!("write decoder binary to {decoder_path:?}");
+                self.persist
+                    .save::<Vec<u8>>(decoder_path.as_str(), decoder_file_content)
+                    .map_err(|_| Error::DecoderBinaryPathInvalid)?;
+                tracing::debug!("save to persist! cache_path: {decoder_path:?}");
+            }
+            Ok(decoder_path)
+        }
+    }
+
+    // decoder binary located by `type_id` comes from either local cache or on-chain type_id cell
+    async fn resolve_type_id_decoder(&self, type_id: &H256) -> DecodeResult<DecoderPath> {
+        #[cfg(not(feature = "shuttle"))]
+        {
+            let mut decoder_path = self.settings.decoders_cache_directory.clone();
+            decoder_path.push(format!("type_id_{}.bin", hex::encode(type_id)));
+            if !decoder_path.exists() {
+                let decoder_binary = self.fetch_decoder_binary(type_id.0).await?;
+                std::fs::write(decoder_path.clone(), decoder_binary)
+                    .map_err(|_| Error::DecoderBinaryPathInvalid)?;
+            }
+            Ok(decoder_path)
+        }
+        #[cfg(feature = "shuttle")]
+        {
+            let decoder_path = format!("type_id_{}.bin", hex::encode(type_id));
+            if self.persist.load::<String>(decoder_path.as_str()).is_err() {
+                let decoder_binary = self.fetch_decoder_binary(type_id.0).await?;
+                self.persist
+                    .save::<Vec<u8>>(format!("{:?}", decoder_path).as_str(), decoder_binary)
+                    .map_err(|_| Error::DecoderBinaryPathInvalid)?;
+            }
+            Ok(decoder_path)
+        }
+    }
+
+    // outpoint of the cell where decoder binary is loaded from, which is the first cell for those
+    // deployed in chunks, none if decoder isn't deployed on-chain
+    pub async fn locate_decoder_cell(
+        &self,
+        decoder: &DOBDecoderFormat,
+    ) -> DecodeResult<Option<ckb_jsonrpc_types::OutPoint>> {
+        let out_point = match decoder.location {
+            DecoderLocationType::CodeHash => {
+                self.find_decoder_deployment(&decoder.hash)
+                    .map(|deployment| ckb_jsonrpc_types::OutPoint {
+                        tx_hash: deployment.tx_hash,
+                        index: deployment.out_index.into(),
+                    })
+            }
+            DecoderLocationType::TypeId => self
+                .fetch_newest_live_cell(build_type_id_search_option(decoder.hash.0))
+                .await?
+                .map(|decoder_cell| decoder_cell.out_point),
+        };
+        Ok(out_point)
+    }
+
+    // decoder binaries in decoders cache, whether they are still in use or not
+    pub fn cached_decoders(&self) -> Vec<CachedDecoder> {
+        #[cfg(not(feature = "shuttle"))]
+        let files = std::fs::read_dir(&self.settings.decoders_cache_directory)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|entry| {
+                        let size = entry.metadata().ok()?.len();
+                        Some((entry.file_name().to_string_lossy().into_owned(), Some(size)))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        // shuttle persist doesn't tell size of its entries without loading them
+        #[cfg(feature = "shuttle")]
+        let files = self
+            .persist
+            .list()
+            .map(|keys| keys.into_iter().map(|key| (key, None)).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let mut decoders = files
+            .into_iter()
+            .filter_map(|(file_name, size)| {
+                let decoder = parse_decoder_file_name(&file_name)?;
+                Some(CachedDecoder {
+                    deployment: self.decoder_status(&decoder).deployment,
+                    decoder,
+                    size,
+                })
+            })
+            .collect::<Vec<_>>();
+        decoders.sort_by(|a, b| a.decoder.hash.0.cmp(&b.decoder.hash.0));
+        decoders
+    }
+
+    // where the decoder comes from and whether its binary is cached, without downloading it
+    pub fn decoder_status(&self, decoder: &DOBDecoderFormat) -> DecoderStatus {
+        let deployment = match decoder.location {
+            DecoderLocationType::CodeHash => self.find_decoder_deployment(&decoder.hash),
+            DecoderLocationType::TypeId => None,
+        };
+        let file_name = decoder_file_name(decoder);
+        #[cfg(not(feature = "shuttle"))]
+        let cached = self
+            .settings
+            .decoders_cache_directory
+            .join(file_name)
+            .exists();
+        #[cfg(feature = "shuttle")]
+        let cached = self.persist.load::<String>(file_name.as_str()).is_ok();
+        DecoderStatus {
+            decoder: decoder.clone(),
+            deployment,
+            cached,
+        }
+    }
+
+    // download and verify all configured on-chain decoders in advance with bounded parallelism,
+    // failures are only reported since they will be retried on the first decoding request
+    pub async fn warm_decoders(&self, parallelism: usize) {
+        let deployments = self.onchain_decoder_deployment.read().unwrap().clone();
+        futures::stream::iter(&deployments)
+            .for_each_concurrent(parallelism, |deployment| async move {
+                match self.resolve_code_hash_decoder(&deployment.code_hash).await {
+                    Ok(_) => tracing::info!(
+                        "decoder {} is warmed up",
+                        hex::encode(&deployment.code_hash)
+                    ),
+                    Err(error) => tracing::warn!(
+                        "failed to warm decoder {}: {error}",
+                        hex::encode(&deployment.code_hash)
+                    ),
                 }
+            })
+            .await;
+    }
+
+    // decode DNA under target spore_id, running every stage declared by the cluster since protocol
+    // version of the spore is unknown here
+    pub async fn decode_dna(
+        &self,
+        dna: &str,
+        dob_metadata: ClusterDescriptionField,
+    ) -> DecodeResult<String> {
+        let pattern = stringify_pattern(&dob_metadata.dob.pattern);
+        let traits_output = self
+            .decode_dna_with_pattern(dna, &dob_metadata.dob.decoder, &pattern)
+            .await?;
+        let mut stages = Vec::new();
+        for stage in &dob_metadata.dob.stages {
+            let decoder_path = self.resolve_decoder_path(&stage.decoder).await?;
+            stages.push((decoder_path, stringify_pattern(&stage.pattern)));
+        }
+        let render_debug = self.render_debug.read().unwrap().all;
+        self.run_pipeline(
+            DecodePipeline::Compose,
+            traits_output,
+            stages
+                .iter()
+                .map(|(decoder_path, pattern)| (decoder_path, pattern.as_str())),
+            self.max_cycles(),
+            render_debug,
+            &mut 0,
+        )
+    }
+
+    // decode DNA with the pattern which has been stringified in advance
+    pub async fn decode_dna_with_pattern(
+        &self,
+        dna: &str,
+        decoder: &DOBDecoderFormat,
+        pattern: &str,
+    ) -> DecodeResult<String> {
+        let decoder_path = self.resolve_decoder_path(decoder).await?;
+        let render_debug = self.render_debug.read().unwrap().all;
+        self.execute_decoder(
+            &decoder_path,
+            dna,
+            pattern,
+            self.max_cycles(),
+            render_debug,
+            &mut 0,
+        )
+    }
+
+    // fetch decode context of the cluster from batch shared contexts, or build it if absent
+    pub async fn decode_context(
+        &self,
+        cluster_id: [u8; 32],
+        anchor_block_number: Option<u64>,
+        contexts: &DecodeContexts,
+    ) -> Result<Arc<DecodeContext>, DecodeFailure> {
+        if let Some(context) = contexts.0.lock().unwrap().get(&cluster_id) {
+            return Ok(context.clone());
+        }
+        let cluster = self
+            .fetch_dob_metadata(cluster_id, anchor_block_number)
+            .await
+            .map_err(|error| {
+                DecodeFailure::new(error, DecodeStage::FetchCluster).with_cluster_id(cluster_id)
+            })?;
+        let (decoder_path, decoder) = match self.cluster_decoder_override(&cluster_id) {
+            Some(decoder_override) => (
+                self.resolve_override_decoder_path(&decoder_override).await,
+                decoder_override.decoder(),
+            ),
+            None => {
+                let decoder = &cluster.metadata.dob.decoder;
+                (
+                    self.resolve_anchored_decoder_path(decoder, anchor_block_number)
+                        .await,
+                    Some(decoder.clone()),
+                )
             }
         };
-        let pattern = match &dob_metadata.dob.pattern {
-            Value::String(string) => string.to_owned(),
-            pattern => pattern.to_string(),
+        let decoder_path = decoder_path.map_err(|error| {
+            let failure =
+                DecodeFailure::new(error, DecodeStage::DownloadDecoder).with_cluster_id(cluster_id);
+            match &decoder {
+                Some(decoder) => failure.with_decoder_hash(decoder.hash.clone()),
+                None => failure,
+            }
+        })?;
+        let mut stage_decoder_paths = Vec::new();
+        for stage in &cluster.metadata.dob.stages {
+            let decoder_path = self
+                .resolve_anchored_decoder_path(&stage.decoder, anchor_block_number)
+                .await
+                .map_err(|error| {
+                    DecodeFailure::new(error, DecodeStage::DownloadDecoder)
+                        .with_cluster_id(cluster_id)
+                        .with_decoder_hash(stage.decoder.hash.clone())
+                })?;
+            stage_decoder_paths.push(decoder_path);
+        }
+        let context = Arc::new(DecodeContext {
+            cluster_id,
+            cluster,
+            decoder_path,
+            decoder,
+            stage_decoder_paths,
+            max_cycles: self.max_cycles(),
+        });
+        contexts
+            .0
+            .lock()
+            .unwrap()
+            .insert(cluster_id, context.clone());
+        Ok(context)
+    }
+
+    // decode DNA with decoder and pattern prepared in context
+    pub fn decode_dna_in_context(
+        &self,
+        spore_id: &[u8; 32],
+        dna: &str,
+        context: &DecodeContext,
+        pipeline: DecodePipeline,
+    ) -> DecodeResult<String> {
+        self.decode_dna_in_context_metered(spore_id, dna, context, pipeline, &mut 0)
+    }
+
+    // decode like `decode_dna_in_context`, adding up cycles consumed by decoders into `cycles`,
+    // stages served from stage cache consume none
+    pub fn decode_dna_in_context_metered(
+        &self,
+        spore_id: &[u8; 32],
+        dna: &str,
+        context: &DecodeContext,
+        pipeline: DecodePipeline,
+        cycles: &mut u64,
+    ) -> DecodeResult<String> {
+        let render_debug = self
+            .render_debug
+            .read()
+            .unwrap()
+            .matches(spore_id, &context.cluster_id);
+        let start = Instant::now();
+        let render_output = self
+            .execute_decoder(
+                &context.decoder_path,
+                dna,
+                &context.cluster.pattern,
+                context.max_cycles,
+                render_debug,
+                cycles,
+            )
+            .and_then(|traits_output| {
+                let stages = context
+                    .stage_decoder_paths
+                    .iter()
+                    .zip(context.cluster.stage_patterns.iter().map(AsRef::as_ref));
+                self.run_pipeline(
+                    pipeline,
+                    traits_output,
+                    stages,
+                    context.max_cycles,
+                    render_debug,
+                    cycles,
+                )
+            });
+        self.cluster_stats
+            .record_execution(&context.cluster_id, start.elapsed());
+        metrics::observe_decode_phase(DecodeStage::Execute, start.elapsed());
+        slow_decode::record_decoder(
+            context.cluster_id,
+            context.decoder.as_ref().map(|decoder| decoder.hash.clone()),
+        );
+        render_output
+    }
+
+    // traits pipeline stops at the first decoder, while in compose pipeline following DOB/1 stages
+    // run, each takes output of the previous stage in place of DNA, like images decoder composing
+    // `[{"name":"IMAGE","traits":[{"SVG":"<svg ...>"}]}]` from traits, and outputs of all stages
+    // are appended in order so that render output carries every of them
+    fn run_pipeline<'a>(
+        &self,
+        pipeline: DecodePipeline,
+        traits_output: String,
+        stages: impl Iterator<Item = (&'a DecoderPath, &'a str)>,
+        max_cycles: u64,
+        render_debug: bool,
+        cycles: &mut u64,
+    ) -> DecodeResult<String> {
+        if pipeline == DecodePipeline::Traits {
+            return Ok(traits_output);
+        }
+        let mut render_output = traits_output.clone();
+        let mut stage_input = traits_output;
+        for (decoder_path, pattern) in stages {
+            let stage_output = self.execute_stage(
+                decoder_path,
+                &stage_input,
+                pattern,
+                max_cycles,
+                render_debug,
+                cycles,
+            )?;
+            render_output = merge_render_outputs(&render_output, &stage_output)?;
+            stage_input = stage_output;
+        }
+        Ok(render_output)
+    }
+
+    // stage inputs are decoded traits, which are frequently shared among spores unlike DNA, so
+    // outputs are cached in memory, except for debugging renders which always execute
+    fn execute_stage(
+        &self,
+        decoder_path: &DecoderPath,
+        stage_input: &str,
+        pattern: &str,
+        max_cycles: u64,
+        render_debug: bool,
+        cycles: &mut u64,
+    ) -> DecodeResult<String> {
+        let Some(stage_outputs) = self.stage_outputs.as_ref().filter(|_| !render_debug) else {
+            return self.execute_decoder(
+                decoder_path,
+                stage_input,
+                pattern,
+                max_cycles,
+                render_debug,
+                cycles,
+            );
         };
+        let stage_key = stage_cache_key(decoder_path, pattern, stage_input);
+        if let Some(stage_output) = stage_outputs.lock().unwrap().get(&stage_key) {
+            return Ok(stage_output.clone());
+        }
+        let stage_output = self.execute_decoder(
+            decoder_path,
+            stage_input,
+            pattern,
+            max_cycles,
+            render_debug,
+            cycles,
+        )?;
+        stage_outputs
+            .lock()
+            .unwrap()
+            .put(stage_key, stage_output.clone());
+        Ok(stage_output)
+    }
+
+    fn max_cycles(&self) -> u64 {
+        match self.settings.decoder_max_cycles {
+            0 => u64::MAX,
+            max_cycles => max_cycles,
+        }
+    }
+
+    #[tracing::instrument(name = "vm_execution", skip_all, fields(decoder = ?decoder_path))]
+    fn execute_decoder(
+        &self,
+        decoder_path: &DecoderPath,
+        dna: &str,
+        pattern: &str,
+        max_cycles: u64,
+        render_debug: bool,
+        cycles: &mut u64,
+    ) -> DecodeResult<String> {
         let raw_render_result = {
             let binary_path = {
                 #[cfg(not(feature = "shuttle"))]
@@ -219,27 +922,78 @@ impl DOBDecoder {
                     decoder_path
                 }
             };
-            let (exit_code, outputs) = crate::vm::execute_riscv_binary(
+            let (exit_code, outputs, consumed_cycles) = crate::vm::execute_riscv_binary(
                 &binary_path,
-                vec![dna.to_owned().into(), pattern.into()],
+                vec![dna.to_owned().into(), pattern.to_owned().into()],
+                max_cycles,
                 #[cfg(feature = "shuttle")]
                 &self.persist,
             )
             .map_err(|_| Error::DecoderExecutionError)?;
-            #[cfg(feature = "render_debug")]
-            {
-                println!("-------- DECODE RESULT ({exit_code}) ---------");
-                outputs.iter().for_each(|output| println!("{output}"));
-                println!("-------- DECODE RESULT END ---------");
-            }
-            if exit_code != 0 {
-                return Err(Error::DecoderExecutionInternalError);
-            }
-            outputs.first().ok_or(Error::DecoderOutputInvalid)?.clone()
+            *cycles += consumed_cycles;
+            decoder_output(exit_code, outputs, render_debug)?
         };
         Ok(raw_render_result)
     }
 
+    // decode DNA with pattern and decoder supplied by caller, for testing them before deploying
+    // clusters, nothing is looked up on-chain except for downloading located decoders
+    pub async fn decode_dna_raw(
+        &self,
+        dna: &str,
+        pattern: &Value,
+        decoder: &RawDecoderParam,
+    ) -> Result<String, DecodeFailure> {
+        let pattern = stringify_pattern(pattern);
+        let render_debug = self.render_debug.read().unwrap().all;
+        let max_cycles = self.max_cycles().min(RAW_DECODE_MAX_CYCLES);
+        match decoder {
+            RawDecoderParam::Located(decoder) => {
+                let failure = |error, stage| {
+                    DecodeFailure::new(error, stage).with_decoder_hash(decoder.hash.clone())
+                };
+                let decoder_path = self
+                    .resolve_decoder_path(decoder)
+                    .await
+                    .map_err(|error| failure(error, DecodeStage::DownloadDecoder))?;
+                self.execute_decoder(
+                    &decoder_path,
+                    dna,
+                    &pattern,
+                    max_cycles,
+                    render_debug,
+                    &mut 0,
+                )
+                .map_err(|error| failure(error, DecodeStage::Execute))
+            }
+            RawDecoderParam::Binary { binary } => {
+                if binary.trim_start_matches("0x").len() > RAW_DECODER_MAX_BYTES * 2 {
+                    return Err(DecodeFailure::new(
+                        Error::DecoderBinaryTooLarge,
+                        DecodeStage::ParseRequest,
+                    ));
+                }
+                let binary =
+                    hex::decode(binary.strip_prefix("0x").unwrap_or(binary)).map_err(|_| {
+                        DecodeFailure::new(
+                            Error::HexedDecoderBinaryParseError,
+                            DecodeStage::ParseRequest,
+                        )
+                    })?;
+                crate::vm::execute_riscv_code(
+                    binary.into(),
+                    vec![dna.to_owned().into(), pattern.into()],
+                    max_cycles,
+                )
+                .map_err(|_| Error::DecoderExecutionError)
+                .and_then(|(exit_code, outputs, _)| {
+                    decoder_output(exit_code, outputs, render_debug)
+                })
+                .map_err(|error| DecodeFailure::new(error, DecodeStage::Execute))
+            }
+        }
+    }
+
     // // invoke `ckb-vm-runner` in native machine and collect console output as result
     // #[cfg(not(feature = "embeded_vm"))]
     // fn execute_externally(
@@ -276,115 +1030,664 @@ impl DOBDecoder {
     //     Ok(raw_render_result)
     // }
 
-    // search on-chain spore cell and return its content field, which represents dob content
-    async fn fetch_dob_content(
+    // search on-chain spore cell and return its content field, which represents dob content, the
+    // spore is taken as it was at `anchor_block_number` if it's provided
+    #[tracing::instrument(skip_all, fields(spore_id = %hex::encode(spore_id)))]
+    pub async fn fetch_dob_content(
         &self,
         spore_id: [u8; 32],
-    ) -> DecodeResult<((Value, String), [u8; 32])> {
-        let mut spore_cell = None;
-        for spore_search_option in
-            build_batch_search_options(spore_id, &self.settings.available_spores)
-        {
-            spore_cell = self
-                .rpc
-                .get_cells(
-                    spore_search_option.into(),
+        anchor_block_number: Option<u64>,
+    ) -> DecodeResult<SporeCellContent> {
+        let start = Instant::now();
+        let spore_cell = self.fetch_spore_cell(spore_id, anchor_block_number).await;
+        metrics::observe_decode_phase(DecodeStage::FetchSpore, start.elapsed());
+        let spore_cell = spore_cell?;
+        let mut spore = self.parse_dob_content(
+            spore_cell.output_data.unwrap_or_default().as_bytes(),
+            spore_cell.output.lock,
+            spore_cell.out_point,
+        )?;
+        spore.provisional = self
+            .check_spore_confirmations(spore_cell.block_number.value())
+            .await?;
+        Ok(spore)
+    }
+
+    // load spore cell directly by its outpoint rather than searching indexer, so that it works
+    // while indexer lags behind, spore id is taken from args of its type script
+    pub async fn fetch_dob_content_by_outpoint(
+        &self,
+        tx_hash: H256,
+        out_index: u32,
+    ) -> DecodeResult<([u8; 32], SporeCellContent)> {
+        let spore_cell = metrics::observe_ckb_rpc(
+            "get_live_cell",
+            self.rpc
+                .get_live_cell(OutPoint::new(tx_hash.pack(), out_index).into(), true)
+                .await,
+        )
+        .map_err(|_| Error::FetchLiveCellsError)?
+        .cell
+        .ok_or(Error::NoOutputCellInTransaction)?;
+        let type_script = spore_cell.output.type_.ok_or(Error::CellNotSpore)?;
+        let is_spore = self.available_spores().iter().any(
+            |ScriptId {
+                 code_hash,
+                 hash_type,
+             }| {
+                let hash_type: ScriptHashType = hash_type.into();
+                type_script.code_hash == *code_hash && type_script.hash_type == hash_type.into()
+            },
+        );
+        if !is_spore {
+            return Err(Error::CellNotSpore);
+        }
+        let spore_id: [u8; 32] = type_script
+            .args
+            .as_bytes()
+            .try_into()
+            .map_err(|_| Error::CellNotSpore)?;
+        let spore_data = spore_cell.data.ok_or(Error::SporeDataUncompatible)?.content;
+        let out_point = OutPoint::new(tx_hash.pack(), out_index).into();
+        let mut spore =
+            self.parse_dob_content(spore_data.as_bytes(), spore_cell.output.lock, out_point)?;
+        // live cell carries no block number, which is only looked up if confirmations matter
+        if self.settings.spore_confirmations > 0 {
+            let block_number = self.fetch_transaction_block_number(tx_hash).await?;
+            spore.provisional = self.check_spore_confirmations(block_number).await?;
+        }
+        Ok((spore_id, spore))
+    }
+
+    // extract DOB content from data of spore cell, which is taken as confirmed
+    fn parse_dob_content(
+        &self,
+        spore_data: &[u8],
+        lock_script: ckb_jsonrpc_types::Script,
+        out_point: ckb_jsonrpc_types::OutPoint,
+    ) -> DecodeResult<SporeCellContent> {
+        let spore_data = parse_spore_data(spore_data)?;
+        let content_type = String::from_utf8(spore_data.content_type)
+            .map_err(|_| Error::SporeDataContentTypeUncompatible)?;
+        let pipeline = self.check_protocol_version(&content_type)?;
+        let cluster_id = spore_data.cluster_id.ok_or(Error::ClusterIdNotSet)?;
+        let (dob_content, dna) = decode_spore_data(&spore_data.content)?;
+        Ok(SporeCellContent {
+            dob_content,
+            dna,
+            cluster_id: cluster_id.try_into().unwrap(),
+            provisional: false,
+            pipeline,
+            lock_script,
+            out_point,
+        })
+    }
+
+    async fn fetch_transaction_block_number(&self, tx_hash: H256) -> DecodeResult<u64> {
+        let block_number =
+            metrics::observe_ckb_rpc("get_transaction", self.rpc.get_transaction(tx_hash).await)
+                .map_err(|_| Error::FetchTransactionError)?
+                .and_then(|tx_with_status| tx_with_status.tx_status.block_number)
+                .ok_or(Error::FetchTransactionError)?;
+        Ok(block_number.value())
+    }
+
+    // classify the live spore by its content type and cluster id without running decoder, spores
+    // not decodable are reported along with the reason rather than as errors
+    pub async fn check_spore(&self, spore_id: [u8; 32]) -> DecodeResult<SporeCheck> {
+        let spore_cell = self.fetch_spore_cell(spore_id, None).await?;
+        Ok(self.check_spore_data(spore_cell.output_data.unwrap_or_default().as_bytes()))
+    }
+
+    pub fn check_spore_data(&self, spore_data: &[u8]) -> SporeCheck {
+        let mut check = SporeCheck::default();
+        let checked = parse_spore_data(spore_data).and_then(|spore_data| {
+            let content_type = String::from_utf8(spore_data.content_type)
+                .map_err(|_| Error::SporeDataContentTypeUncompatible)?;
+            let version = content_type.split(';').next().unwrap_or_default().trim();
+            check.version = ProtocolVersionInfo::new(version).version;
+            check.content_type = Some(content_type.clone());
+            self.check_protocol_version(&content_type)?;
+            let cluster_id = spore_data.cluster_id.ok_or(Error::ClusterIdNotSet)?;
+            check.cluster_id = <[u8; 32]>::try_from(cluster_id).ok().map(H256);
+            decode_spore_data(&spore_data.content)?;
+            Ok(())
+        });
+        check.decodable = checked.is_ok();
+        check.reason = checked.err().map(|error| error.to_string());
+        check
+    }
+
+    // lock script of the live spore cell, for results served from cache which don't carry it
+    pub async fn fetch_spore_owner(
+        &self,
+        spore_id: [u8; 32],
+    ) -> DecodeResult<ckb_jsonrpc_types::Script> {
+        Ok(self.fetch_spore_cell(spore_id, None).await?.output.lock)
+    }
+
+    async fn fetch_spore_cell(
+        &self,
+        spore_id: [u8; 32],
+        anchor_block_number: Option<u64>,
+    ) -> DecodeResult<Cell> {
+        if let Some(anchor_block_number) = anchor_block_number {
+            return self
+                .fetch_cell_at_anchor(spore_id, &self.available_spores(), anchor_block_number)
+                .await?
+                .ok_or(Error::SporeIdNotFound);
+        }
+        for spore_search_option in build_batch_search_options(spore_id, &self.available_spores()) {
+            if let Some(spore_cell) = self.fetch_newest_live_cell(spore_search_option).await? {
+                return Ok(spore_cell);
+            }
+        }
+        Err(Error::SporeIdNotFound)
+    }
+
+    // cell typed by `type_args` as it was at the anchor block, even if it has been transferred or
+    // consumed since, which is the output of the newest transaction touching the type script at or
+    // before the anchor, none if the cell was not yet created or already consumed by then
+    async fn fetch_cell_at_anchor(
+        &self,
+        type_args: [u8; 32],
+        script_ids: &[ScriptId],
+        anchor_block_number: u64,
+    ) -> DecodeResult<Option<Cell>> {
+        for search_key in build_batch_search_options(type_args, script_ids) {
+            let search_key = SearchKey {
+                filter: Some(SearchKeyFilter {
+                    block_range: Some([0.into(), (anchor_block_number + 1).into()]),
+                    ..Default::default()
+                }),
+                group_by_transaction: Some(true),
+                ..search_key
+            };
+            let transactions = metrics::observe_ckb_rpc(
+                "get_transactions",
+                self.rpc
+                    .get_transactions(
+                        search_key.into(),
+                        Order::Desc,
+                        ckb_jsonrpc_types::Uint32::from(1),
+                        None,
+                    )
+                    .await,
+            )
+            .map_err(|_| Error::FetchTransactionError)?
+            .objects;
+            let tx = match transactions.into_iter().next() {
+                Some(Tx::Grouped(tx)) => tx,
+                Some(Tx::Ungrouped(_)) => return Err(Error::FetchTransactionError),
+                None => continue,
+            };
+            let Some(out_index) = tx.cells.iter().find_map(|(cell_type, index)| {
+                matches!(cell_type, CellType::Output).then_some(index.value())
+            }) else {
+                return Ok(None);
+            };
+            let (output, output_data) = self
+                .fetch_transaction_output(tx.tx_hash.clone(), out_index)
+                .await?;
+            return Ok(Some(Cell {
+                output,
+                output_data: Some(ckb_jsonrpc_types::JsonBytes::from_vec(output_data)),
+                out_point: OutPoint::new(tx.tx_hash.pack(), out_index).into(),
+                block_number: tx.block_number,
+                tx_index: tx.tx_index,
+            }));
+        }
+        Ok(None)
+    }
+
+    // map serial of spore in cluster, starting from 1 in mint order, to its spore id
+    pub async fn resolve_spore_by_serial(
+        &self,
+        cluster_id: [u8; 32],
+        serial: u64,
+    ) -> DecodeResult<[u8; 32]> {
+        let index = self.cluster_serial_index(cluster_id).await?;
+        serial
+            .checked_sub(1)
+            .and_then(|position| index.spore_ids.get(position as usize))
+            .copied()
+            .ok_or(Error::SporeSerialNotFound)
+    }
+
+    // building the index scans every live spore cell, since indexer can't filter cells by cluster,
+    // so only clusters configured in `serial_index_clusters` or crawled are indexed
+    pub fn is_cluster_indexed(&self, cluster_id: &[u8; 32]) -> bool {
+        self.settings
+            .serial_index_clusters
+            .iter()
+            .chain(&self.settings.cluster_crawler.cluster_ids)
+            .any(|indexed_cluster_id| indexed_cluster_id.0 == *cluster_id)
+    }
+
+    pub async fn cluster_serial_index(
+        &self,
+        cluster_id: [u8; 32],
+    ) -> DecodeResult<Arc<ClusterSerialIndex>> {
+        if !self.is_cluster_indexed(&cluster_id) {
+            return Err(Error::ClusterNotIndexed);
+        }
+        let ttl = Duration::from_secs(self.settings.cluster_index_ttl_secs);
+        if let Some(index) = self.cluster_serial_indexes.lock().unwrap().get(&cluster_id) {
+            if index.built_at.elapsed() < ttl {
+                return Ok(index.clone());
+            }
+        }
+        let index = Arc::new(self.build_cluster_serial_index(cluster_id).await?);
+        self.cluster_serial_indexes
+            .lock()
+            .unwrap()
+            .put(cluster_id, index.clone());
+        Ok(index)
+    }
+
+    // scan live spore cells of all available spore scripts for those in the cluster, then order
+    // them by their mint transactions, melted spores are absent so serials exclude them
+    async fn build_cluster_serial_index(
+        &self,
+        cluster_id: [u8; 32],
+    ) -> DecodeResult<ClusterSerialIndex> {
+        let mut spores = Vec::new();
+        for spore_script in &self.available_spores() {
+            let search_key = build_script_prefix_search_option(spore_script);
+            let mut cursor = None;
+            loop {
+                let page = metrics::observe_ckb_rpc(
+                    "get_cells",
+                    self.rpc
+                        .get_cells(
+                            search_key.clone().into(),
+                            Order::Asc,
+                            ckb_jsonrpc_types::Uint32::from(SPORE_CELLS_PAGE_SIZE),
+                            cursor,
+                        )
+                        .await,
+                )
+                .map_err(|_| Error::FetchLiveCellsError)?;
+                let page_size = page.objects.len();
+                for cell in page.objects {
+                    let Some(type_script) = cell.output.type_ else {
+                        continue;
+                    };
+                    let Ok(spore_data) =
+                        parse_spore_data(cell.output_data.unwrap_or_default().as_bytes())
+                    else {
+                        continue;
+                    };
+                    if spore_data.cluster_id.as_deref() != Some(cluster_id.as_slice()) {
+                        continue;
+                    }
+                    if let Ok(spore_id) = type_script.args.as_bytes().try_into() {
+                        spores.push((spore_id, packed::Script::from(type_script)));
+                    }
+                }
+                if page_size < SPORE_CELLS_PAGE_SIZE as usize {
+                    break;
+                }
+                cursor = Some(page.last_cursor);
+            }
+        }
+        let mut minted_spores = futures::stream::iter(spores)
+            .map(|(spore_id, spore_script)| async move {
+                let mint_position = self.fetch_mint_position(spore_script).await?;
+                Ok::<_, Error>((mint_position, spore_id))
+            })
+            .buffer_unordered(MINT_LOOKUP_PARALLELISM)
+            .try_collect::<Vec<_>>()
+            .await?;
+        minted_spores.sort();
+        Ok(ClusterSerialIndex {
+            spore_ids: minted_spores
+                .into_iter()
+                .map(|(_, spore_id)| spore_id)
+                .collect(),
+            built_at: Instant::now(),
+        })
+    }
+
+    // page through live spore cells of available spore scripts for those in the cluster, indexer
+    // can't filter cells by cluster so they are scanned in pages of `limit` cells, and a page of
+    // less spores is returned with cursor as well once `CLUSTER_SPORES_SCAN_PAGES` are scanned
+    pub async fn fetch_cluster_spore_ids(
+        &self,
+        cluster_id: [u8; 32],
+        cursor: OwnedSporesCursor,
+        limit: u32,
+    ) -> DecodeResult<(Vec<[u8; 32]>, Option<OwnedSporesCursor>)> {
+        let mut spore_ids = Vec::new();
+        let mut scanned_pages = 0;
+        let mut indexer_cursor = cursor
+            .indexer_cursor
+            .map(ckb_jsonrpc_types::JsonBytes::from_vec);
+        let available_spores = self.available_spores();
+        let spore_scripts = available_spores.iter().enumerate();
+        for (script_index, spore_script) in spore_scripts.skip(cursor.script_index) {
+            let search_key = build_script_prefix_search_option(spore_script);
+            loop {
+                let remaining = limit - spore_ids.len() as u32;
+                let page = metrics::observe_ckb_rpc(
+                    "get_cells",
+                    self.rpc
+                        .get_cells(
+                            search_key.clone().into(),
+                            Order::Asc,
+                            ckb_jsonrpc_types::Uint32::from(remaining),
+                            indexer_cursor.take(),
+                        )
+                        .await,
+                )
+                .map_err(|_| Error::FetchLiveCellsError)?;
+                scanned_pages += 1;
+                let page_size = page.objects.len() as u32;
+                for cell in page.objects {
+                    let Some(type_script) = cell.output.type_ else {
+                        continue;
+                    };
+                    let Ok(spore_data) =
+                        parse_spore_data(cell.output_data.unwrap_or_default().as_bytes())
+                    else {
+                        continue;
+                    };
+                    if spore_data.cluster_id.as_deref() != Some(cluster_id.as_slice()) {
+                        continue;
+                    }
+                    let content_type = String::from_utf8_lossy(&spore_data.content_type);
+                    if self.check_protocol_version(&content_type).is_err() {
+                        continue;
+                    }
+                    if let Ok(spore_id) = type_script.args.as_bytes().try_into() {
+                        spore_ids.push(spore_id);
+                    }
+                }
+                if page_size < remaining {
+                    break;
+                }
+                if spore_ids.len() as u32 == limit || scanned_pages >= CLUSTER_SPORES_SCAN_PAGES {
+                    let next_cursor = OwnedSporesCursor {
+                        script_index,
+                        indexer_cursor: Some(page.last_cursor.as_bytes().to_vec()),
+                    };
+                    return Ok((spore_ids, Some(next_cursor)));
+                }
+                indexer_cursor = Some(page.last_cursor);
+            }
+        }
+        Ok((spore_ids, None))
+    }
+
+    // page through live spore cells locked by the owner across available spore scripts, spores
+    // in unsupported protocol versions are counted but skipped, so a page may carry less
+    pub async fn fetch_owned_spore_ids(
+        &self,
+        lock_script: packed::Script,
+        cursor: OwnedSporesCursor,
+        limit: u32,
+    ) -> DecodeResult<(Vec<[u8; 32]>, Option<OwnedSporesCursor>)> {
+        let mut spore_ids = Vec::new();
+        let mut remaining = limit;
+        let mut indexer_cursor = cursor
+            .indexer_cursor
+            .map(ckb_jsonrpc_types::JsonBytes::from_vec);
+        let available_spores = self.available_spores();
+        let spore_scripts = available_spores.iter().enumerate();
+        for (script_index, spore_script) in spore_scripts.skip(cursor.script_index) {
+            let search_key = build_owner_search_option(lock_script.clone(), spore_script);
+            let page = metrics::observe_ckb_rpc(
+                "get_cells",
+                self.rpc
+                    .get_cells(
+                        search_key.into(),
+                        Order::Asc,
+                        ckb_jsonrpc_types::Uint32::from(remaining),
+                        indexer_cursor.take(),
+                    )
+                    .await,
+            )
+            .map_err(|_| Error::FetchLiveCellsError)?;
+            let page_size = page.objects.len() as u32;
+            for cell in page.objects {
+                let Some(type_script) = cell.output.type_ else {
+                    continue;
+                };
+                let Ok(spore_data) =
+                    parse_spore_data(cell.output_data.unwrap_or_default().as_bytes())
+                else {
+                    continue;
+                };
+                let content_type = String::from_utf8_lossy(&spore_data.content_type);
+                if self.check_protocol_version(&content_type).is_err() {
+                    continue;
+                }
+                if let Ok(spore_id) = type_script.args.as_bytes().try_into() {
+                    spore_ids.push(spore_id);
+                }
+            }
+            if page_size == remaining {
+                let next_cursor = OwnedSporesCursor {
+                    script_index,
+                    indexer_cursor: Some(page.last_cursor.as_bytes().to_vec()),
+                };
+                return Ok((spore_ids, Some(next_cursor)));
+            }
+            remaining -= page_size;
+        }
+        Ok((spore_ids, None))
+    }
+
+    // position of the first transaction ever touching the spore type script, which is its mint
+    async fn fetch_mint_position(&self, spore_script: packed::Script) -> DecodeResult<(u64, u32)> {
+        let search_key = SearchKey {
+            script: spore_script.into(),
+            script_type: ckb_client::types::ScriptType::Type,
+            script_search_mode: Some(IndexerScriptSearchMode::Exact),
+            filter: None,
+            with_data: None,
+            group_by_transaction: Some(true),
+        };
+        let transactions = metrics::observe_ckb_rpc(
+            "get_transactions",
+            self.rpc
+                .get_transactions(
+                    search_key.into(),
                     Order::Asc,
                     ckb_jsonrpc_types::Uint32::from(1),
                     None,
                 )
-                .await
-                .map_err(|err| {
-                    println!("{:?}", err);
-                    Error::FetchLiveCellsError
-                })?
-                .objects
-                .first()
-                .cloned();
-            if spore_cell.is_some() {
-                break;
-            }
+                .await,
+        )
+        .map_err(|_| Error::FetchTransactionError)?
+        .objects;
+        match transactions.first() {
+            Some(Tx::Grouped(tx)) => Ok((tx.block_number.value(), tx.tx_index.value())),
+            Some(Tx::Ungrouped(tx)) => Ok((tx.block_number.value(), tx.tx_index.value())),
+            None => Err(Error::FetchTransactionError),
         }
-        let Some(spore_cell) = spore_cell else {
-            return Err(Error::SporeIdNotFound);
-        };
-        let molecule_spore_data =
-            SporeData::from_compatible_slice(spore_cell.output_data.unwrap_or_default().as_bytes())
-                .map_err(|_| Error::SporeDataUncompatible)?;
-        let content_type =
-            String::from_utf8(molecule_spore_data.content_type().raw_data().to_vec())
-                .map_err(|_| Error::SporeDataContentTypeUncompatible)?;
-        if !self
-            .settings
-            .protocol_versions
-            .iter()
-            .any(|version| content_type.starts_with(version))
+    }
+
+    // exact match of content type, regardless of its parameters, is always accepted, otherwise
+    // the version is logged once as unknown and accepted by prefix only in lenient mode, returning
+    // pipeline of the matched version
+    pub(crate) fn check_protocol_version(
+        &self,
+        content_type: &str,
+    ) -> DecodeResult<DecodePipeline> {
+        let version = content_type.split(';').next().unwrap_or_default().trim();
+        if let Some(pipeline) = self.protocols.read().unwrap().find(version) {
+            return Ok(pipeline);
+        }
+        let mut unknown_versions = self.unknown_versions.lock().unwrap();
+        if unknown_versions.len() < UNKNOWN_VERSIONS_LIMIT
+            && unknown_versions.insert(version.to_string())
         {
-            return Err(Error::DOBVersionUnexpected);
+            tracing::warn!("unknown protocol version seen: {version}");
         }
-        let cluster_id = molecule_spore_data
-            .cluster_id()
-            .to_opt()
-            .ok_or(Error::ClusterIdNotSet)?
-            .raw_data();
-        let dob_content = decode_spore_data(&molecule_spore_data.content().raw_data())?;
-        Ok((dob_content, cluster_id.to_vec().try_into().unwrap()))
+        let pipeline = match self.settings.protocol_version_matching {
+            ProtocolVersionMatching::Strict => None,
+            ProtocolVersionMatching::Lenient => {
+                self.protocols.read().unwrap().find_by_prefix(content_type)
+            }
+        };
+        pipeline.ok_or(Error::DOBVersionUnexpected)
+    }
+
+    // resolve anchor block hash into its block number, the block must be on the canonical chain
+    pub async fn resolve_anchor_block(&self, block_hash: H256) -> DecodeResult<u64> {
+        let block_number =
+            metrics::observe_ckb_rpc("get_header", self.rpc.get_header(block_hash.clone()).await)
+                .map_err(|_| Error::AnchorBlockNotFound)?
+                .ok_or(Error::AnchorBlockNotFound)?
+                .inner
+                .number;
+        let canonical_block_hash = metrics::observe_ckb_rpc(
+            "get_block_hash",
+            self.rpc.get_block_hash(block_number).await,
+        )
+        .map_err(|_| Error::AnchorBlockNotFound)?;
+        if canonical_block_hash != Some(block_hash) {
+            return Err(Error::AnchorBlockNotFound);
+        }
+        Ok(block_number.value())
+    }
+
+    // also tells whether CKB node is reachable
+    pub async fn tip_block_number(&self) -> DecodeResult<u64> {
+        let tip_block_number = metrics::observe_ckb_rpc(
+            "get_tip_block_number",
+            self.rpc.get_tip_block_number().await,
+        )
+        .map_err(|_| Error::FetchTipBlockNumberError)?;
+        Ok(tip_block_number.value())
+    }
+
+    // check whether the spore cell has been buried under enough blocks, return true if it's
+    // not confirmed yet but allowed to be decoded provisionally
+    async fn check_spore_confirmations(&self, spore_block_number: u64) -> DecodeResult<bool> {
+        if self.settings.spore_confirmations == 0 {
+            return Ok(false);
+        }
+        let tip_block_number = self.tip_block_number().await?;
+        let confirmations = tip_block_number.saturating_sub(spore_block_number);
+        if confirmations >= self.settings.spore_confirmations {
+            return Ok(false);
+        }
+        match self.settings.unconfirmed_spore_policy {
+            UnconfirmedSporePolicy::Refuse => Err(Error::SporeUnconfirmed),
+            UnconfirmedSporePolicy::Provisional => Ok(true),
+        }
+    }
+
+    // return parsed dob metadata of the cluster, from LRU cache if possible, anchored lookups
+    // always go on-chain since the cluster may not exist at the anchor block
+    #[tracing::instrument(skip_all, fields(cluster_id = %hex::encode(cluster_id)))]
+    pub async fn fetch_dob_metadata(
+        &self,
+        cluster_id: [u8; 32],
+        anchor_block_number: Option<u64>,
+    ) -> DecodeResult<Arc<ParsedClusterMetadata>> {
+        let cluster_cache = self
+            .cluster_cache
+            .as_ref()
+            .filter(|_| anchor_block_number.is_none());
+        if let Some(cluster_cache) = cluster_cache {
+            if let Some(cluster) = cluster_cache.lock().unwrap().get(&cluster_id) {
+                return Ok(cluster.clone());
+            }
+        }
+        let (metadata, cluster_data_hash) = self
+            .fetch_dob_metadata_onchain(cluster_id, anchor_block_number)
+            .await?;
+        let cluster = Arc::new(ParsedClusterMetadata {
+            pattern: stringify_pattern(&metadata.dob.pattern).into(),
+            stage_patterns: metadata
+                .dob
+                .stages
+                .iter()
+                .map(|stage| stringify_pattern(&stage.pattern).into())
+                .collect(),
+            metadata,
+            cluster_data_hash,
+        });
+        if let Some(cluster_cache) = cluster_cache {
+            cluster_cache
+                .lock()
+                .unwrap()
+                .put(cluster_id, cluster.clone());
+        }
+        Ok(cluster)
+    }
+
+    // search on-chain cluster cell and return its description field, which contains dob metadata,
+    // along with the blake2b hash of the whole cluster data
+    async fn fetch_dob_metadata_onchain(
+        &self,
+        cluster_id: [u8; 32],
+        anchor_block_number: Option<u64>,
+    ) -> DecodeResult<(ClusterDescriptionField, [u8; 32])> {
+        let cluster_cell = self
+            .fetch_cluster_cell(cluster_id, anchor_block_number)
+            .await?;
+        let raw_cluster_data = cluster_cell.output_data.unwrap_or_default();
+        let cluster_data_hash = ckb_hash::blake2b_256(raw_cluster_data.as_bytes());
+        let cluster_data = parse_cluster_data(raw_cluster_data.as_bytes())?;
+        let dob_metadata = serde_json::from_slice(&cluster_data.description)
+            .map_err(|_| Error::DOBMetadataUnexpected)?;
+        Ok((dob_metadata, cluster_data_hash))
     }
 
-    // search on-chain cluster cell and return its description field, which contains dob metadata
-    async fn fetch_dob_metadata(
+    // outpoint of the live cluster cell, which changes as the cluster is transferred
+    pub async fn fetch_cluster_out_point(
         &self,
         cluster_id: [u8; 32],
-    ) -> DecodeResult<ClusterDescriptionField> {
+    ) -> DecodeResult<ckb_jsonrpc_types::OutPoint> {
+        Ok(self.fetch_cluster_cell(cluster_id, None).await?.out_point)
+    }
+
+    async fn fetch_cluster_cell(
+        &self,
+        cluster_id: [u8; 32],
+        anchor_block_number: Option<u64>,
+    ) -> DecodeResult<Cell> {
         let mut cluster_cell = None;
-        for cluster_search_option in
-            build_batch_search_options(cluster_id, &self.settings.available_clusters)
-        {
+        if let Some(anchor_block_number) = anchor_block_number {
             cluster_cell = self
-                .rpc
-                .get_cells(
-                    cluster_search_option.into(),
-                    Order::Asc,
-                    ckb_jsonrpc_types::Uint32::from(1),
-                    None,
-                )
-                .await
-                .map_err(|_| Error::FetchLiveCellsError)?
-                .objects
-                .first()
-                .cloned();
-            if cluster_cell.is_some() {
-                break;
+                .fetch_cell_at_anchor(cluster_id, &self.available_clusters(), anchor_block_number)
+                .await?;
+        } else {
+            for cluster_search_option in
+                build_batch_search_options(cluster_id, &self.available_clusters())
+            {
+                cluster_cell = self.fetch_newest_live_cell(cluster_search_option).await?;
+                if cluster_cell.is_some() {
+                    break;
+                }
             }
         }
         let Some(cluster_cell) = cluster_cell else {
             return Err(Error::ClusterIdNotFound);
         };
-        let molecule_cluster_data = ClusterData::from_compatible_slice(
-            cluster_cell.output_data.unwrap_or_default().as_bytes(),
-        )
-        .map_err(|_| Error::ClusterDataUncompatible)?;
-        let dob_metadata = serde_json::from_slice(&molecule_cluster_data.description().raw_data())
-            .map_err(|_| Error::DOBMetadataUnexpected)?;
-        Ok(dob_metadata)
+        if self.settings.verify_cluster_cell {
+            let cluster_cell_id = cluster_cell
+                .output
+                .type_
+                .as_ref()
+                .map(|type_script| type_script.args.as_bytes());
+            if cluster_cell_id != Some(cluster_id.as_slice()) {
+                return Err(Error::ClusterCellMismatch);
+            }
+        }
+        Ok(cluster_cell)
     }
 
     // search on-chain decoder cell, deployed with type_id feature enabled
     async fn fetch_decoder_binary(&self, decoder_id: [u8; 32]) -> DecodeResult<Vec<u8>> {
         let decoder_search_option = build_type_id_search_option(decoder_id);
         let decoder_cell = self
-            .rpc
-            .get_cells(
-                decoder_search_option.into(),
-                Order::Asc,
-                ckb_jsonrpc_types::Uint32::from(1),
-                None,
-            )
-            .await
-            .map_err(|_| Error::FetchLiveCellsError)?
-            .objects
-            .first()
-            .cloned()
+            .fetch_newest_live_cell(decoder_search_option)
+            .await?
             .ok_or(Error::DecoderIdNotFound)?;
         Ok(decoder_cell
             .output_data
@@ -393,17 +1696,85 @@ impl DOBDecoder {
             .into())
     }
 
+    // search live cells under the search key, and pick the newest one deterministically
+    // if the indexer returns more than one cell for a same type script
+    async fn fetch_newest_live_cell(&self, search_key: SearchKey) -> DecodeResult<Option<Cell>> {
+        let cells = metrics::observe_ckb_rpc(
+            "get_cells",
+            self.rpc
+                .get_cells(
+                    search_key.into(),
+                    Order::Desc,
+                    ckb_jsonrpc_types::Uint32::from(LIVE_CELLS_SEARCH_LIMIT),
+                    None,
+                )
+                .await,
+        )
+        .map_err(|err| {
+            tracing::warn!("failed to search live cells: {err:?}");
+            Error::FetchLiveCellsError
+        })?
+        .objects;
+        if cells.len() > 1 {
+            tracing::warn!(
+                "found {} live cells with duplicate type script, pick the newest one",
+                cells.len()
+            );
+        }
+        Ok(pick_newest_cell(cells))
+    }
+
+    // fetch decoder binary from its deployment, concatenating all of chunks in order if the
+    // binary is split across several cells, the hash is verified on the whole binary by caller
+    async fn fetch_decoder_binary_deployment(
+        &self,
+        deployment: &OnchainDecoderDeployment,
+    ) -> DecodeResult<Vec<u8>> {
+        let mut decoder_binary = self
+            .fetch_decoder_binary_directly(deployment.tx_hash.clone(), deployment.out_index)
+            .await?;
+        for chunk in &deployment.chunks {
+            let chunk_binary = self
+                .fetch_decoder_binary_directly(chunk.tx_hash.clone(), chunk.out_index)
+                .await?;
+            decoder_binary.extend(chunk_binary);
+        }
+        Ok(decoder_binary)
+    }
+
     // search on-chain decoder cell, directly by its tx_hash and out_index
     async fn fetch_decoder_binary_directly(
         &self,
         tx_hash: H256,
         out_index: u32,
     ) -> DecodeResult<Vec<u8>> {
-        let decoder_cell = self
-            .rpc
-            .get_live_cell(OutPoint::new(tx_hash.pack(), out_index).into(), true)
+        match self
+            .fetch_decoder_binary_from_live_cell(tx_hash.clone(), out_index)
             .await
-            .map_err(|_| Error::FetchTransactionError)?;
+        {
+            Ok(decoder_binary) => Ok(decoder_binary),
+            Err(error) => {
+                tracing::warn!(
+                    "live decoder cell unavailable ({error}), fallback to get_transaction"
+                );
+                self.fetch_decoder_binary_from_transaction(tx_hash, out_index)
+                    .await
+            }
+        }
+    }
+
+    async fn fetch_decoder_binary_from_live_cell(
+        &self,
+        tx_hash: H256,
+        out_index: u32,
+    ) -> DecodeResult<Vec<u8>> {
+        let decoder_cell = metrics::observe_ckb_rpc(
+            "get_live_cell",
+            self.rpc
+                .get_live_cell(OutPoint::new(tx_hash.pack(), out_index).into(), true)
+                .await,
+        )
+        .map_err(|_| Error::FetchTransactionError)?;
         let decoder_binary = decoder_cell
             .cell
             .ok_or(Error::NoOutputCellInTransaction)?
@@ -412,6 +1783,170 @@ impl DOBDecoder {
             .content;
         Ok(decoder_binary.as_bytes().to_vec())
     }
+
+    // extract decoder binary from the deployment transaction body, which still works when the
+    // decoder cell has been consumed or its data pruned from the node
+    async fn fetch_decoder_binary_from_transaction(
+        &self,
+        tx_hash: H256,
+        out_index: u32,
+    ) -> DecodeResult<Vec<u8>> {
+        let (_, decoder_binary) = self.fetch_transaction_output(tx_hash, out_index).await?;
+        if decoder_binary.is_empty() {
+            return Err(Error::DecoderBinaryNotFoundInCell);
+        }
+        Ok(decoder_binary)
+    }
+
+    // output cell and its data in a committed transaction, available whether or not the cell is
+    // still live
+    async fn fetch_transaction_output(
+        &self,
+        tx_hash: H256,
+        out_index: u32,
+    ) -> DecodeResult<(ckb_jsonrpc_types::CellOutput, Vec<u8>)> {
+        let transaction =
+            metrics::observe_ckb_rpc("get_transaction", self.rpc.get_transaction(tx_hash).await)
+                .map_err(|_| Error::FetchTransactionError)?
+                .and_then(|tx_with_status| tx_with_status.transaction)
+                .ok_or(Error::FetchTransactionError)?;
+        let index = out_index as usize;
+        match transaction.inner {
+            Either::Left(transaction) => {
+                let transaction = transaction.inner;
+                let output = transaction
+                    .outputs
+                    .get(index)
+                    .cloned()
+                    .ok_or(Error::NoOutputCellInTransaction)?;
+                let output_data = transaction
+                    .outputs_data
+                    .get(index)
+                    .ok_or(Error::NoOutputCellInTransaction)?
+                    .as_bytes()
+                    .to_vec();
+                Ok((output, output_data))
+            }
+            Either::Right(raw_transaction) => {
+                let transaction = packed::Transaction::from_slice(raw_transaction.as_bytes())
+                    .map_err(|_| Error::FetchTransactionError)?
+                    .raw();
+                let output = transaction
+                    .outputs()
+                    .get(index)
+                    .ok_or(Error::NoOutputCellInTransaction)?;
+                let output_data = transaction
+                    .outputs_data()
+                    .get(index)
+                    .ok_or(Error::NoOutputCellInTransaction)?
+                    .raw_data()
+                    .to_vec();
+                Ok((output.into(), output_data))
+            }
+        }
+    }
+}
+
+fn build_cluster_cache(settings: &Settings) -> Option<ClusterMetadataCache> {
+    NonZeroUsize::new(settings.cluster_cache_capacity)
+        .map(|capacity| Mutex::new(LruCache::new(capacity)))
+}
+
+fn build_stage_cache(settings: &Settings) -> Option<StageOutputCache> {
+    NonZeroUsize::new(settings.stage_cache_capacity)
+        .map(|capacity| Mutex::new(LruCache::new(capacity)))
+}
+
+// fields are separated by zero byte, which appears in none of them
+fn stage_cache_key(decoder_path: &DecoderPath, pattern: &str, stage_input: &str) -> [u8; 32] {
+    let decoder_path = std::path::Path::new(decoder_path).to_string_lossy();
+    ckb_hash::blake2b_256(
+        [
+            decoder_path.as_bytes(),
+            pattern.as_bytes(),
+            stage_input.as_bytes(),
+        ]
+        .join(&0u8),
+    )
+}
+
+// name of decoder binary in decoders cache
+fn decoder_file_name(decoder: &DOBDecoderFormat) -> String {
+    match decoder.location {
+        DecoderLocationType::CodeHash => format!("code_hash_{}.bin", hex::encode(&decoder.hash)),
+        DecoderLocationType::TypeId => format!("type_id_{}.bin", hex::encode(&decoder.hash)),
+    }
+}
+
+// reverse of `decoder_file_name`, none for files not named by it
+pub(crate) fn parse_decoder_file_name(file_name: &str) -> Option<DOBDecoderFormat> {
+    let file_stem = file_name.strip_suffix(".bin")?;
+    let (location, hexed_hash) = if let Some(hexed_hash) = file_stem.strip_prefix("code_hash_") {
+        (DecoderLocationType::CodeHash, hexed_hash)
+    } else {
+        (
+            DecoderLocationType::TypeId,
+            file_stem.strip_prefix("type_id_")?,
+        )
+    };
+    let hash = hex::decode(hexed_hash).ok()?.try_into().ok()?;
+    Some(DOBDecoderFormat {
+        location,
+        hash: H256(hash),
+    })
+}
+
+// first line of decoder output is the result, the others are for debugging, which are logged
+// under target `render_debug` for spores and clusters matching render debug targets
+fn decoder_output(exit_code: i8, outputs: Vec<String>, render_debug: bool) -> DecodeResult<String> {
+    if render_debug {
+        tracing::info!(
+            target: "render_debug",
+            exit_code,
+            "decode result:\n{}",
+            outputs.join("\n")
+        );
+    }
+    if exit_code != 0 {
+        return Err(Error::DecoderExecutionInternalError);
+    }
+    outputs
+        .into_iter()
+        .next()
+        .ok_or(Error::DecoderOutputInvalid)
+}
+
+// decoder accepts pattern in string, non-string pattern is passed in JSON format
+fn stringify_pattern(pattern: &Value) -> String {
+    match pattern {
+        Value::String(string) => string.to_owned(),
+        pattern => pattern.to_string(),
+    }
+}
+
+// concatenate render outputs in array, outputs of earlier stages go first
+pub(crate) fn merge_render_outputs(
+    render_output: &str,
+    stage_output: &str,
+) -> DecodeResult<String> {
+    let parse = |output: &str| match serde_json::from_str(output) {
+        Ok(Value::Array(items)) => Ok(items),
+        _ => Err(Error::DecoderOutputInvalid),
+    };
+    let mut items = parse(render_output)?;
+    items.extend(parse(stage_output)?);
+    Ok(Value::Array(items).to_string())
+}
+
+// select the cell with the highest block number, and then the highest position in that block
+fn pick_newest_cell(cells: Vec<Cell>) -> Option<Cell> {
+    cells.into_iter().max_by_key(|cell| {
+        (
+            cell.block_number.value(),
+            cell.tx_index.value(),
+            cell.out_point.index.value(),
+        )
+    })
 }
 
 fn build_type_id_search_option(type_id_args: [u8; 32]) -> SearchKey {
@@ -430,6 +1965,43 @@ fn build_type_id_search_option(type_id_args: [u8; 32]) -> SearchKey {
     }
 }
 
+// search all cells of the script regardless of args, along with their data
+fn build_script_prefix_search_option(script_id: &ScriptId) -> SearchKey {
+    let hash_type: ScriptHashType = (&script_id.hash_type).into();
+    let type_script = Script::new_builder()
+        .code_hash(script_id.code_hash.0.pack())
+        .hash_type(hash_type.into())
+        .build();
+    SearchKey {
+        script: type_script.into(),
+        script_type: ckb_client::types::ScriptType::Type,
+        script_search_mode: Some(IndexerScriptSearchMode::Prefix),
+        filter: None,
+        with_data: Some(true),
+        group_by_transaction: None,
+    }
+}
+
+// search live cells locked by the owner, filtered to those typed by the spore script
+fn build_owner_search_option(lock_script: Script, spore_script: &ScriptId) -> SearchKey {
+    let hash_type: ScriptHashType = (&spore_script.hash_type).into();
+    let type_script = Script::new_builder()
+        .code_hash(spore_script.code_hash.0.pack())
+        .hash_type(hash_type.into())
+        .build();
+    SearchKey {
+        script: lock_script.into(),
+        script_type: ckb_client::types::ScriptType::Lock,
+        script_search_mode: Some(IndexerScriptSearchMode::Exact),
+        filter: Some(SearchKeyFilter {
+            script: Some(type_script.into()),
+            ..Default::default()
+        }),
+        with_data: Some(true),
+        group_by_transaction: None,
+    }
+}
+
 fn build_batch_search_options(
     type_args: [u8; 32],
     available_script_ids: &[ScriptId],
@@ -460,26 +2032,67 @@ fn build_batch_search_options(
         .collect()
 }
 
+// parse spore data in compatible mode, so that newer revisions of the schema appending extra
+// optional fields are accepted as well, and normalize the result into internal type
+pub(crate) fn parse_spore_data(data: &[u8]) -> Result<NormalizedSporeData, Error> {
+    let spore_data =
+        SporeData::from_compatible_slice(data).map_err(|_| Error::SporeDataUncompatible)?;
+    Ok(NormalizedSporeData {
+        content_type: spore_data.content_type().raw_data().to_vec(),
+        content: spore_data.content().raw_data().to_vec(),
+        cluster_id: spore_data
+            .cluster_id()
+            .to_opt()
+            .map(|cluster_id| cluster_id.raw_data().to_vec()),
+    })
+}
+
+// try cluster data schemas in order, v1 first, then v2 which comes with `mutant_id` along with
+// its newer revisions in compatible mode, normalize the result into internal type
+pub(crate) fn parse_cluster_data(data: &[u8]) -> Result<NormalizedClusterData, Error> {
+    if let Ok(cluster_data) = ClusterData::from_slice(data) {
+        return Ok(NormalizedClusterData {
+            name: cluster_data.name().raw_data().to_vec(),
+            description: cluster_data.description().raw_data().to_vec(),
+            mutant_id: None,
+        });
+    }
+    let cluster_data =
+        ClusterDataV2::from_compatible_slice(data).map_err(|_| Error::ClusterDataUncompatible)?;
+    Ok(NormalizedClusterData {
+        name: cluster_data.name().raw_data().to_vec(),
+        description: cluster_data.description().raw_data().to_vec(),
+        mutant_id: cluster_data
+            .mutant_id()
+            .to_opt()
+            .map(|mutant_id| mutant_id.raw_data().to_vec()),
+    })
+}
+
 pub(crate) fn decode_spore_data(spore_data: &[u8]) -> Result<(Value, String), Error> {
-    if spore_data[0] == 0u8 {
+    if spore_data.first() == Some(&0u8) {
         let dna = hex::encode(&spore_data[1..]);
         return Ok((serde_json::Value::String(dna.clone()), dna));
     }
 
     let value: Value =
         serde_json::from_slice(spore_data).map_err(|_| Error::DOBContentUnexpected)?;
-    let dna = match &value {
-        serde_json::Value::String(_) => &value,
+    let dna = extract_dna(&value)?;
+    Ok((value, dna))
+}
+
+// DNA is the string itself, the first element of array, or `dna` field of object in DOB content
+pub(crate) fn extract_dna(value: &Value) -> Result<String, Error> {
+    let dna = match value {
+        serde_json::Value::String(_) => value,
         serde_json::Value::Array(array) => array.first().ok_or(Error::DOBContentUnexpected)?,
         serde_json::Value::Object(object) => {
             object.get("dna").ok_or(Error::DOBContentUnexpected)?
         }
         _ => return Err(Error::DOBContentUnexpected),
     };
-    let dna = match dna {
-        serde_json::Value::String(string) => string.to_owned(),
-        _ => return Err(Error::DOBContentUnexpected),
-    };
-
-    Ok((value, dna))
+    match dna {
+        serde_json::Value::String(string) => Ok(string.to_owned()),
+        _ => Err(Error::DOBContentUnexpected),
+    }
 }
@@ -0,0 +1,52 @@
+use serde_json::{Map, Value};
+
+pub const ENV_PREFIX: &str = "DOB_";
+
+// nested settings are reached by double underscores, like `DOB_BATCH_CONCURRENCY__MAX_PARALLELISM`
+const NESTING_SEPARATOR: &str = "__";
+
+// layer `DOB_*` environment variables over settings parsed from file, so that containers can be
+// configured without baking a file, each variable names a setting by its path in upper case, like
+// `DOB_CKB_RPC`, whose value is taken as TOML, like `true`, `8` or `["dob/0", "dob/1"]`, or as
+// string if it's not a valid TOML value, variables not prefixed are ignored
+pub fn apply_env_overrides(
+    settings: &mut Map<String, Value>,
+    vars: impl IntoIterator<Item = (String, String)>,
+) {
+    for (name, value) in vars {
+        let Some(path) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let keys = path
+            .split(NESTING_SEPARATOR)
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        if keys.iter().any(String::is_empty) {
+            continue;
+        }
+        set_value(settings, &keys, parse_value(&value));
+    }
+}
+
+fn parse_value(value: &str) -> Value {
+    toml::from_str::<Map<String, Value>>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(value.to_string()))
+}
+
+// tables on the way are created if missing, or replaced if they are not tables
+fn set_value(settings: &mut Map<String, Value>, keys: &[String], value: Value) {
+    let (key, parents) = keys.split_last().expect("non-empty keys");
+    let mut table = settings;
+    for parent in parents {
+        let entry = table
+            .entry(parent.clone())
+            .or_insert(Value::Object(Map::new()));
+        if !entry.is_object() {
+            *entry = Value::Object(Map::new());
+        }
+        table = entry.as_object_mut().expect("table");
+    }
+    table.insert(key.clone(), value);
+}
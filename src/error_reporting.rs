@@ -0,0 +1,49 @@
+use crate::types::DecodeFailure;
+#[cfg(feature = "sentry")]
+use crate::types::SentrySettings;
+
+// panics are reported by the default integrations of Sentry, keep the guard until exiting so that
+// pending events are flushed
+#[cfg(feature = "sentry")]
+pub fn init(settings: &SentrySettings) -> sentry::ClientInitGuard {
+    sentry::init((
+        settings.dsn.as_str(),
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            environment: settings.environment.clone().map(Into::into),
+            sample_rate: settings.sample_rate,
+            ..Default::default()
+        },
+    ))
+}
+
+// report severe decoding failures along with the spore, cluster and decoder they happened on,
+// failures caused by requests or chain data are left out
+#[cfg(feature = "sentry")]
+pub fn report_failure(failure: &DecodeFailure) {
+    if !failure.error.is_severe() {
+        return;
+    }
+    sentry::with_scope(
+        |scope| {
+            scope.set_tag("error_code", failure.error as i32);
+            scope.set_tag("stage", format!("{:?}", failure.stage));
+            if let Some(spore_id) = &failure.spore_id {
+                scope.set_tag("spore_id", format!("0x{spore_id}"));
+            }
+            if let Some(cluster_id) = &failure.cluster_id {
+                scope.set_tag("cluster_id", format!("0x{cluster_id}"));
+            }
+            if let Some(decoder_hash) = &failure.decoder_hash {
+                scope.set_tag("decoder_hash", format!("0x{decoder_hash}"));
+            }
+            if let Some(request_id) = crate::request_id::current() {
+                scope.set_tag("request_id", request_id);
+            }
+        },
+        || sentry::capture_message(&failure.error.to_string(), sentry::Level::Error),
+    );
+}
+
+#[cfg(not(feature = "sentry"))]
+pub fn report_failure(_failure: &DecodeFailure) {}
@@ -0,0 +1,184 @@
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use hyper::header::HeaderName;
+use hyper::{Body, Request};
+use tower::{Layer, Service};
+
+pub const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+// address of the peer connected over TCP, attached to each request before http middlewares, none
+// for Unix domain socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerAddr(pub SocketAddr);
+
+#[derive(Clone)]
+pub struct PeerAddrService<S> {
+    inner: S,
+    peer_addr: SocketAddr,
+}
+
+impl<S> PeerAddrService<S> {
+    pub fn new(inner: S, peer_addr: SocketAddr) -> Self {
+        Self { inner, peer_addr }
+    }
+}
+
+impl<S> Service<Request<Body>> for PeerAddrService<S>
+where
+    S: Service<Request<Body>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<Body>) -> Self::Future {
+        request.extensions_mut().insert(PeerAddr(self.peer_addr));
+        self.inner.call(request)
+    }
+}
+
+// network in CIDR notation like `10.0.0.0/8` or `2400:cb00::/32`, a bare address is taken as a
+// network of itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = String;
+
+    fn from_str(net: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid network {net}");
+        let (addr, prefix_len) = match net.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (net, None),
+        };
+        let addr = IpAddr::from_str(addr)
+            .map_err(|_| invalid())?
+            .to_canonical();
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .ok()
+                .filter(|prefix_len| *prefix_len <= max_prefix_len)
+                .ok_or_else(invalid)?,
+            None => max_prefix_len,
+        };
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+// real client of the request, attached by `ClientIpLayer` for rate limiting and logging
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+// `X-Forwarded-For` is only taken from trusted proxies, walking from the nearest hop until an
+// address not trusted, which is the client, so that clients can't spoof it by sending their own,
+// peers over Unix domain socket are co-located proxies and always trusted
+pub fn client_ip<B>(request: &Request<B>, trusted_proxies: &[IpNet]) -> Option<IpAddr> {
+    let trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+    let mut client_ip = request
+        .extensions()
+        .get::<PeerAddr>()
+        .map(|peer_addr| peer_addr.0.ip().to_canonical());
+    if client_ip.as_ref().is_some_and(|ip| !trusted(ip)) {
+        return client_ip;
+    }
+    let forwarded = request
+        .headers()
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .rev()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.rsplit(','));
+    for ip in forwarded {
+        let Ok(ip) = IpAddr::from_str(ip.trim()) else {
+            break;
+        };
+        client_ip = Some(ip.to_canonical());
+        if !trusted(&ip) {
+            break;
+        }
+    }
+    client_ip
+}
+
+// resolve client IP once for middlewares after, requests of unknown clients are left as is
+#[derive(Clone)]
+pub struct ClientIpLayer {
+    trusted_proxies: Arc<Vec<IpNet>>,
+}
+
+impl ClientIpLayer {
+    pub fn new(trusted_proxies: Vec<IpNet>) -> Self {
+        Self {
+            trusted_proxies: Arc::new(trusted_proxies),
+        }
+    }
+}
+
+impl<S> Layer<S> for ClientIpLayer {
+    type Service = ClientIpService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ClientIpService {
+            inner,
+            trusted_proxies: self.trusted_proxies.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ClientIpService<S> {
+    inner: S,
+    trusted_proxies: Arc<Vec<IpNet>>,
+}
+
+impl<S> Service<Request<Body>> for ClientIpService<S>
+where
+    S: Service<Request<Body>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<Body>) -> Self::Future {
+        if let Some(ip) = client_ip(&request, &self.trusted_proxies) {
+            request.extensions_mut().insert(ClientIp(ip));
+        }
+        self.inner.call(request)
+    }
+}
@@ -0,0 +1,257 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, ErrorExtensions, Json, Object, Schema, SimpleObject,
+};
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde_json::Value;
+use tower::{Layer, Service};
+
+use crate::rest::read_body;
+use crate::server::{
+    batch_decode_dob, decode_dob, DecodeMetadata, DecodeOptions, DecoderStandaloneServer,
+    ServerDecodeResult,
+};
+use crate::types::DecodeFailure;
+
+pub const GRAPHQL_PATH: &str = "/graphql";
+
+pub type DobSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub fn build_schema(server: DecoderStandaloneServer) -> DobSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(server)
+        .finish()
+}
+
+// decoded DOB, `cluster` is only fetched if selected
+#[derive(SimpleObject)]
+pub struct Dob {
+    spore_id: String,
+    render_output: Json<Value>,
+    dob_content: Json<Value>,
+    provisional: bool,
+    cluster: Option<ClusterMetadata>,
+}
+
+#[derive(SimpleObject)]
+pub struct ClusterMetadata {
+    dna: String,
+    cluster_id: String,
+    description: String,
+    pattern: Json<Value>,
+}
+
+// error code and message are the same as those of json-rpc errors
+#[derive(SimpleObject)]
+pub struct DobError {
+    code: i32,
+    message: String,
+    stage: String,
+}
+
+// entry of `dobs` query, in either `dob` or `error`
+#[derive(SimpleObject)]
+pub struct DobEntry {
+    spore_id: String,
+    dob: Option<Dob>,
+    error: Option<DobError>,
+}
+
+impl Dob {
+    fn new(spore_id: String, result: ServerDecodeResult) -> Self {
+        Self {
+            spore_id,
+            render_output: Json(result.render_output),
+            dob_content: Json(result.dob_content),
+            provisional: result.provisional,
+            cluster: result.metadata.map(Into::into),
+        }
+    }
+}
+
+impl From<DecodeMetadata> for ClusterMetadata {
+    fn from(value: DecodeMetadata) -> Self {
+        Self {
+            dna: value.dna,
+            cluster_id: format!("{:#x}", value.cluster_id),
+            description: value.cluster_description,
+            pattern: Json(value.pattern),
+        }
+    }
+}
+
+// failure of the whole field, with error code and stage in extensions
+fn graphql_error(failure: DecodeFailure) -> async_graphql::Error {
+    let DobError {
+        code,
+        message,
+        stage,
+    } = failure.into();
+    async_graphql::Error::new(message).extend_with(|_, extensions| {
+        extensions.set("code", code);
+        extensions.set("stage", stage);
+    })
+}
+
+impl From<DecodeFailure> for DobError {
+    fn from(value: DecodeFailure) -> Self {
+        Self {
+            code: value.error as i32,
+            message: value.error.to_string(),
+            stage: serde_json::to_value(value.stage)
+                .ok()
+                .and_then(|stage| stage.as_str().map(str::to_string))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn dob(
+        &self,
+        ctx: &Context<'_>,
+        spore_id: String,
+        anchor_block_hash: Option<String>,
+    ) -> async_graphql::Result<Dob> {
+        let server = ctx.data::<DecoderStandaloneServer>()?;
+        let options = DecodeOptions {
+            anchor_block_hash,
+            include_metadata: ctx.look_ahead().field("cluster").exists(),
+            traits: None,
+            ..Default::default()
+        };
+        match decode_dob(server.decoder(), spore_id.clone(), &options).await {
+            Ok(result) => Ok(Dob::new(spore_id, result)),
+            Err(failure) => Err(graphql_error(failure)),
+        }
+    }
+
+    async fn dobs(
+        &self,
+        ctx: &Context<'_>,
+        spore_ids: Vec<String>,
+        anchor_block_hash: Option<String>,
+    ) -> async_graphql::Result<Vec<DobEntry>> {
+        let server = ctx.data::<DecoderStandaloneServer>()?;
+        server
+            .check_batch_size(spore_ids.len())
+            .map_err(graphql_error)?;
+        let options = DecodeOptions {
+            anchor_block_hash,
+            include_metadata: ctx.look_ahead().field("dob").field("cluster").exists(),
+            traits: None,
+            ..Default::default()
+        };
+        let entries = batch_decode_dob(
+            server.decoder(),
+            spore_ids,
+            &options,
+            server.batch_concurrency(),
+        )
+        .await
+        .into_iter()
+        .map(|(spore_id, result)| match result {
+            Ok(result) => DobEntry {
+                dob: Some(Dob::new(spore_id.clone(), result)),
+                spore_id,
+                error: None,
+            },
+            Err(failure) => DobEntry {
+                spore_id,
+                dob: None,
+                error: Some(failure.into()),
+            },
+        })
+        .collect();
+        Ok(entries)
+    }
+}
+
+// serve GraphQL queries on `POST /graphql`, and GraphiQL explorer on `GET /graphql`
+#[derive(Clone)]
+pub struct GraphqlLayer {
+    schema: DobSchema,
+}
+
+impl GraphqlLayer {
+    pub fn new(server: DecoderStandaloneServer) -> Self {
+        Self {
+            schema: build_schema(server),
+        }
+    }
+}
+
+impl<S> Layer<S> for GraphqlLayer {
+    type Service = GraphqlService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GraphqlService {
+            inner,
+            schema: self.schema.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GraphqlService<S> {
+    inner: S,
+    schema: DobSchema,
+}
+
+impl<S> Service<Request<Body>> for GraphqlService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if request.uri().path() != GRAPHQL_PATH {
+            return Box::pin(self.inner.call(request));
+        }
+        if request.method() == Method::GET {
+            let explorer = GraphiQLSource::build().endpoint(GRAPHQL_PATH).finish();
+            let mut response = Response::new(Body::from(explorer));
+            response.headers_mut().insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("text/html; charset=utf-8"),
+            );
+            return Box::pin(async move { Ok(response) });
+        }
+        if request.method() != Method::POST {
+            return Box::pin(self.inner.call(request));
+        }
+        let schema = self.schema.clone();
+        Box::pin(async move {
+            let query = read_body(request.into_body())
+                .await
+                .and_then(|body| serde_json::from_slice::<async_graphql::Request>(&body).ok());
+            let Some(query) = query else {
+                let mut response = Response::new(Body::from("invalid GraphQL request"));
+                *response.status_mut() = StatusCode::BAD_REQUEST;
+                return Ok(response);
+            };
+            let result = schema.execute(query).await;
+            let mut response = Response::new(Body::from(serde_json::to_vec(&result).unwrap()));
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            Ok(response)
+        })
+    }
+}
@@ -0,0 +1,310 @@
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use hyper::StatusCode;
+use jsonrpsee::tracing;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tonic::transport::server::Connected;
+use tonic::{Code, Request, Response, Status};
+
+use crate::access::ApiKeyLayer;
+use crate::forwarded::{ClientIpLayer, PeerAddr};
+use crate::middleware::RejectionBody;
+use crate::rate_limit::RateLimitLayer;
+use crate::server::{
+    batch_decode_dob, decode_dob, DecodeMetadata, DecodeOptions, DecoderStandaloneServer,
+    ServerDecodeResult,
+};
+#[cfg(feature = "tls")]
+use crate::tls;
+use crate::types::{DecodeFailure, DecodeStage, Error};
+
+pub mod pb {
+    tonic::include_proto!("dob_decoder");
+}
+
+use pb::dob_decoder_server::{DobDecoder, DobDecoderServer};
+
+// gRPC mirror of json-rpc methods for backend-to-backend integrations, sharing decoder and
+// cache with the json-rpc server
+pub struct GrpcDecoder {
+    server: DecoderStandaloneServer,
+}
+
+impl GrpcDecoder {
+    // messages are capped by `request_limits.max_request_body_bytes` the same as json-rpc requests
+    pub fn into_service(server: DecoderStandaloneServer) -> DobDecoderServer<Self> {
+        let max_message_bytes = server
+            .decoder()
+            .setting()
+            .request_limits
+            .max_request_body_bytes as usize;
+        DobDecoderServer::new(Self { server }).max_decoding_message_size(max_message_bytes)
+    }
+}
+
+// serve on the listener bound on start, behind the same client IP resolution, rate limit and api
+// keys as json-rpc server, and over TLS with the same certificate if `[tls]` is configured
+pub fn serve(server: DecoderStandaloneServer, listener: std::net::TcpListener) -> io::Result<()> {
+    let settings = server.decoder().setting();
+    // networks are validated on start
+    let trusted_proxies = settings
+        .trusted_proxies
+        .iter()
+        .filter_map(|net| net.parse().ok())
+        .collect();
+    let middleware = tower::ServiceBuilder::new()
+        .layer(ClientIpLayer::new(trusted_proxies))
+        .layer(RateLimitLayer::new(settings.rate_limit.clone()))
+        .layer(ApiKeyLayer::new(&settings.api_keys))
+        .into_inner();
+    #[cfg(feature = "tls")]
+    let tls_handshaker = settings
+        .tls
+        .as_ref()
+        .map(|tls| tls::TlsHandshaker::new(tls, b"h2"))
+        .transpose()?;
+    #[cfg(not(feature = "tls"))]
+    if settings.tls.is_some() {
+        tracing::warn!("[tls] configured but feature `tls` not enabled, serving plaintext gRPC");
+    }
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    let (connections, mut accepted) = tokio::sync::mpsc::channel(64);
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer_addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(error) => {
+                    tracing::warn!("failed to accept gRPC connection: {error}");
+                    continue;
+                }
+            };
+            let _ = stream.set_nodelay(true);
+            // handshake is done apart from accepting, so that slow clients don't hold others
+            #[cfg(feature = "tls")]
+            if let Some(tls_handshaker) = tls_handshaker.clone() {
+                let connections = connections.clone();
+                tokio::spawn(async move {
+                    match tls_handshaker.accept(stream).await {
+                        Ok(stream) => {
+                            let _ = connections
+                                .send(GrpcConnection::new(stream, peer_addr))
+                                .await;
+                        }
+                        Err(error) => {
+                            tracing::debug!("TLS handshake with {peer_addr} failed: {error}")
+                        }
+                    }
+                });
+                continue;
+            }
+            if connections
+                .send(GrpcConnection::new(stream, peer_addr))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+    let incoming = Box::pin(futures::stream::poll_fn(move |cx| {
+        accepted
+            .poll_recv(cx)
+            .map(|connection| connection.map(Ok::<_, io::Error>))
+    }));
+    let serving = tonic::transport::Server::builder()
+        .layer(middleware)
+        .add_service(GrpcDecoder::into_service(server))
+        .serve_with_incoming(incoming);
+    tokio::spawn(async move {
+        if let Err(error) = serving.await {
+            tracing::error!("gRPC server stopped: {error}");
+        }
+    });
+    Ok(())
+}
+
+trait GrpcIo: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> GrpcIo for T {}
+
+// connection over plain TCP or TLS, whose peer address is attached to requests as `PeerAddr` the
+// same as json-rpc server does, so that middlewares resolve client IP in the same way
+struct GrpcConnection {
+    io: Box<dyn GrpcIo>,
+    peer_addr: SocketAddr,
+}
+
+impl GrpcConnection {
+    fn new(io: impl GrpcIo, peer_addr: SocketAddr) -> Self {
+        Self {
+            io: Box::new(io),
+            peer_addr,
+        }
+    }
+}
+
+impl Connected for GrpcConnection {
+    type ConnectInfo = PeerAddr;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        PeerAddr(self.peer_addr)
+    }
+}
+
+impl AsyncRead for GrpcConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for GrpcConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+#[tonic::async_trait]
+impl DobDecoder for GrpcDecoder {
+    async fn protocol_versions(
+        &self,
+        _request: Request<pb::ProtocolVersionsRequest>,
+    ) -> Result<Response<pb::ProtocolVersionsResponse>, Status> {
+        Ok(Response::new(pb::ProtocolVersionsResponse {
+            protocol_versions: self.server.decoder().protocol_versions(),
+        }))
+    }
+
+    async fn decode(
+        &self,
+        request: Request<pb::DecodeRequest>,
+    ) -> Result<Response<pb::DecodeResult>, Status> {
+        let request = request.into_inner();
+        let options = DecodeOptions {
+            anchor_block_hash: request.anchor_block_hash,
+            include_metadata: request.include_metadata,
+            traits: (!request.traits.is_empty()).then_some(request.traits),
+            ..Default::default()
+        };
+        let result = decode_dob(self.server.decoder(), request.spore_id, &options)
+            .await
+            .map_err(failure_status)?;
+        Ok(Response::new(result.into()))
+    }
+
+    async fn batch_decode(
+        &self,
+        request: Request<pb::BatchDecodeRequest>,
+    ) -> Result<Response<pb::BatchDecodeResponse>, Status> {
+        let request = request.into_inner();
+        self.server
+            .check_batch_size(request.spore_ids.len())
+            .map_err(failure_status)?;
+        let options = DecodeOptions {
+            anchor_block_hash: request.anchor_block_hash,
+            ..Default::default()
+        };
+        let entries = batch_decode_dob(
+            self.server.decoder(),
+            request.spore_ids,
+            &options,
+            self.server.batch_concurrency(),
+        )
+        .await
+        .into_iter()
+        .map(|(spore_id, result)| pb::BatchDecodeEntry {
+            spore_id,
+            result: Some(match result {
+                Ok(result) => pb::batch_decode_entry::Result::Ok(result.into()),
+                Err(failure) => pb::batch_decode_entry::Result::Err(failure.into()),
+            }),
+        })
+        .collect();
+        Ok(Response::new(pb::BatchDecodeResponse { entries }))
+    }
+}
+
+impl From<ServerDecodeResult> for pb::DecodeResult {
+    fn from(value: ServerDecodeResult) -> Self {
+        Self {
+            render_output: value.render_output.to_string(),
+            dob_content: value.dob_content.to_string(),
+            provisional: value.provisional,
+            metadata: value.metadata.map(Into::into),
+        }
+    }
+}
+
+impl From<DecodeMetadata> for pb::DecodeMetadata {
+    fn from(value: DecodeMetadata) -> Self {
+        Self {
+            dna: value.dna,
+            cluster_id: format!("{:#x}", value.cluster_id),
+            cluster_description: value.cluster_description,
+            pattern: value.pattern.to_string(),
+        }
+    }
+}
+
+impl From<DecodeFailure> for pb::DecodeError {
+    fn from(value: DecodeFailure) -> Self {
+        Self {
+            code: value.error as i32,
+            message: value.error.to_string(),
+            stage: serde_json::to_value(value.stage)
+                .ok()
+                .and_then(|stage| stage.as_str().map(str::to_string))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+// requests rejected by api keys or rate limit are responded in gRPC status, with json-rpc error
+// code attached in metadata the same as decode failures
+impl RejectionBody for tonic::body::BoxBody {
+    fn rejection(status: StatusCode, error: Error) -> hyper::Response<Self> {
+        let code = match status {
+            StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+            StatusCode::FORBIDDEN => Code::PermissionDenied,
+            StatusCode::TOO_MANY_REQUESTS => Code::ResourceExhausted,
+            _ => Code::Internal,
+        };
+        let mut status = Status::new(code, error.to_string());
+        status
+            .metadata_mut()
+            .insert("dob-error-code", (error as i32).into());
+        status.to_http()
+    }
+}
+
+// status code follows that of REST api, with json-rpc error code attached in metadata
+fn failure_status(failure: DecodeFailure) -> Status {
+    let code = match (failure.stage, failure.error) {
+        (DecodeStage::ParseRequest, _) => Code::InvalidArgument,
+        (_, Error::SporeIdNotFound) => Code::NotFound,
+        _ => Code::Internal,
+    };
+    let mut status = Status::new(code, failure.error.to_string());
+    status
+        .metadata_mut()
+        .insert("dob-error-code", (failure.error as i32).into());
+    status
+}
@@ -0,0 +1,205 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::Serialize;
+use tower::{Layer, Service};
+
+use crate::canary::CanaryMonitor;
+use crate::decoder::DOBDecoder;
+use crate::vm;
+
+pub const LIVENESS_PATH: &str = "/healthz";
+pub const READINESS_CHECKS_PATH: &str = "/readyz";
+
+// probes are expected to answer quickly, an unresponsive CKB node counts as unreachable
+const CKB_RPC_TIMEOUT: Duration = Duration::from_secs(5);
+
+// file written and removed right away to tell whether a cache directory is writable
+#[cfg(not(feature = "shuttle"))]
+const PROBE_FILE_NAME: &str = ".readyz";
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReadinessCheck {
+    pub name: String,
+    pub passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ReadinessCheck {
+    fn new(name: &str, result: Result<(), String>) -> Self {
+        let (passed, error) = match result {
+            Ok(()) => (true, None),
+            Err(error) => (false, Some(error)),
+        };
+        Self {
+            name: name.to_string(),
+            passed,
+            error,
+        }
+    }
+}
+
+// server is ready only if all checks passed
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheck>,
+}
+
+pub async fn check_ckb_rpc(decoder: &DOBDecoder) -> Result<(), String> {
+    match tokio::time::timeout(CKB_RPC_TIMEOUT, decoder.tip_block_number()).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(error)) => Err(error.to_string()),
+        Err(_) => Err(format!(
+            "no response in {} seconds",
+            CKB_RPC_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+// check what decoding depends on, which are CKB node, cache directories and the VM, along with
+// canary status if configured
+pub async fn check_readiness(
+    decoder: Arc<DOBDecoder>,
+    canary: Option<&CanaryMonitor>,
+) -> Readiness {
+    let mut checks = Vec::new();
+    checks.push(ReadinessCheck::new(
+        "ckb_rpc",
+        check_ckb_rpc(&decoder).await,
+    ));
+    // file system and VM are blocking, keep them away from the async runtime
+    let local_checks = tokio::task::spawn_blocking(move || {
+        let mut checks = cache_directory_checks(&decoder);
+        checks.push(ReadinessCheck::new("vm", vm::smoke_test()));
+        checks
+    })
+    .await
+    .unwrap_or_else(|error| vec![ReadinessCheck::new("vm", Err(error.to_string()))]);
+    checks.extend(local_checks);
+    if let Some(canary) = canary {
+        let status = canary.status();
+        let result = match status.healthy {
+            true => Ok(()),
+            false => Err(status
+                .last_error
+                .unwrap_or_else(|| "no canary check passed yet".to_string())),
+        };
+        checks.push(ReadinessCheck::new("canary", result));
+    }
+    Readiness {
+        ready: checks.iter().all(|check| check.passed),
+        checks,
+    }
+}
+
+#[cfg(not(feature = "shuttle"))]
+fn cache_directory_checks(decoder: &DOBDecoder) -> Vec<ReadinessCheck> {
+    let settings = decoder.setting();
+    [
+        ("dobs_cache_directory", &settings.dobs_cache_directory),
+        (
+            "decoders_cache_directory",
+            &settings.decoders_cache_directory,
+        ),
+    ]
+    .into_iter()
+    .map(|(name, directory)| {
+        let probe_file = directory.join(PROBE_FILE_NAME);
+        let result = std::fs::write(&probe_file, [])
+            .and_then(|_| std::fs::remove_file(&probe_file))
+            .map_err(|error| format!("{directory:?} is not writable: {error}"));
+        ReadinessCheck::new(name, result)
+    })
+    .collect()
+}
+
+// shuttle persist is managed by the platform
+#[cfg(feature = "shuttle")]
+fn cache_directory_checks(_decoder: &DOBDecoder) -> Vec<ReadinessCheck> {
+    Vec::new()
+}
+
+// serve liveness on `GET /healthz`, which is always 200 as long as the process answers, and
+// readiness on `GET /readyz`, in 200 if all checks passed, otherwise in 503, along with results
+// of checks in body
+#[derive(Clone)]
+pub struct HealthLayer {
+    decoder: Arc<DOBDecoder>,
+    canary: Option<Arc<CanaryMonitor>>,
+}
+
+impl HealthLayer {
+    pub fn new(decoder: Arc<DOBDecoder>, canary: Option<Arc<CanaryMonitor>>) -> Self {
+        Self { decoder, canary }
+    }
+}
+
+impl<S> Layer<S> for HealthLayer {
+    type Service = HealthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HealthService {
+            inner,
+            decoder: self.decoder.clone(),
+            canary: self.canary.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct HealthService<S> {
+    inner: S,
+    decoder: Arc<DOBDecoder>,
+    canary: Option<Arc<CanaryMonitor>>,
+}
+
+impl<S> Service<Request<Body>> for HealthService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if request.method() != Method::GET {
+            return Box::pin(self.inner.call(request));
+        }
+        match request.uri().path() {
+            LIVENESS_PATH => Box::pin(async move { Ok(json_response(true, "{}".to_string())) }),
+            READINESS_CHECKS_PATH => {
+                let (decoder, canary) = (self.decoder.clone(), self.canary.clone());
+                Box::pin(async move {
+                    let readiness = check_readiness(decoder, canary.as_deref()).await;
+                    let body = serde_json::to_string(&readiness).unwrap();
+                    Ok(json_response(readiness.ready, body))
+                })
+            }
+            _ => Box::pin(self.inner.call(request)),
+        }
+    }
+}
+
+fn json_response(ok: bool, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    if !ok {
+        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    }
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
@@ -0,0 +1,138 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::decoder::DOBDecoder;
+use crate::types::Network;
+
+// walking cache directories takes a while on large caches, so their usage is reused for a while
+// in case the method is scraped frequently
+const CACHE_USAGE_TTL: Duration = Duration::from_secs(60);
+
+// files of a cache, bytes are unknown under shuttle persist
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheUsage {
+    pub entries: usize,
+    pub bytes: Option<u64>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    pub version: String,
+    // empty if built out of a git checkout
+    pub git_commit: String,
+    pub network: Network,
+    pub protocol_versions: Vec<String>,
+    pub uptime_secs: u64,
+    // render results, bytes include composed images going along with them
+    pub dobs_cache: CacheUsage,
+    pub decoders_cache: CacheUsage,
+}
+
+// facts of the running server for monitoring dashboards
+pub struct ServerInfoCollector {
+    started_at: Instant,
+    cache_usage: Mutex<Option<(Instant, CacheUsage, CacheUsage)>>,
+}
+
+impl Default for ServerInfoCollector {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            cache_usage: Mutex::default(),
+        }
+    }
+}
+
+impl ServerInfoCollector {
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    pub fn server_info(&self, decoder: &DOBDecoder) -> ServerInfo {
+        let (dobs_cache, decoders_cache) = self.cache_usage(decoder);
+        ServerInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: env!("GIT_COMMIT").to_string(),
+            network: decoder.setting().network,
+            protocol_versions: decoder.protocol_versions(),
+            uptime_secs: self.uptime().as_secs(),
+            dobs_cache,
+            decoders_cache,
+        }
+    }
+
+    fn cache_usage(&self, decoder: &DOBDecoder) -> (CacheUsage, CacheUsage) {
+        let mut cache_usage = self.cache_usage.lock().unwrap();
+        match *cache_usage {
+            Some((measured_at, dobs_cache, decoders_cache))
+                if measured_at.elapsed() < CACHE_USAGE_TTL =>
+            {
+                (dobs_cache, decoders_cache)
+            }
+            _ => {
+                let (dobs_cache, decoders_cache) = measure_cache_usage(decoder);
+                *cache_usage = Some((Instant::now(), dobs_cache, decoders_cache));
+                (dobs_cache, decoders_cache)
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "shuttle"))]
+fn measure_cache_usage(decoder: &DOBDecoder) -> (CacheUsage, CacheUsage) {
+    use std::path::Path;
+
+    // count files with the extension, while bytes of all files are summed up
+    fn walk(directory: &Path, extension: &str, usage: &mut CacheUsage) {
+        let Ok(directory_entries) = std::fs::read_dir(directory) else {
+            return;
+        };
+        for directory_entry in directory_entries.flatten() {
+            let path = directory_entry.path();
+            if path.is_dir() {
+                walk(&path, extension, usage);
+                continue;
+            }
+            if path.extension().is_some_and(|value| value == extension) {
+                usage.entries += 1;
+            }
+            if let Ok(metadata) = directory_entry.metadata() {
+                *usage.bytes.get_or_insert(0) += metadata.len();
+            }
+        }
+    }
+
+    let mut dobs_cache = CacheUsage::default();
+    walk(
+        &decoder.setting().dobs_cache_directory,
+        "dob",
+        &mut dobs_cache,
+    );
+    let decoders = decoder.cached_decoders();
+    let decoders_cache = CacheUsage {
+        entries: decoders.len(),
+        bytes: Some(decoders.iter().filter_map(|decoder| decoder.size).sum()),
+    };
+    (dobs_cache, decoders_cache)
+}
+
+// shuttle persist doesn't tell size of its entries without loading them
+#[cfg(feature = "shuttle")]
+fn measure_cache_usage(decoder: &DOBDecoder) -> (CacheUsage, CacheUsage) {
+    let dobs = decoder
+        .persist
+        .list()
+        .map(|keys| keys.iter().filter(|key| key.ends_with(".dob")).count())
+        .unwrap_or_default();
+    let dobs_cache = CacheUsage {
+        entries: dobs,
+        bytes: None,
+    };
+    let decoders_cache = CacheUsage {
+        entries: decoder.cached_decoders().len(),
+        bytes: None,
+    };
+    (dobs_cache, decoders_cache)
+}
@@ -0,0 +1,298 @@
+use std::sync::Arc;
+#[cfg(not(feature = "shuttle"))]
+use std::{fs, path::Path};
+
+use ckb_types::H256;
+use jsonrpsee::tracing;
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::DOBDecoder;
+use crate::render::RenderTarget;
+#[cfg(feature = "shuttle")]
+use crate::server::read_dob_from_cache;
+#[cfg(not(feature = "shuttle"))]
+use crate::server::{dob_cache_path, read_dob_from_cache};
+#[cfg(feature = "cache_invalidation")]
+use crate::types::{CacheInvalidationSettings, Error};
+
+// events which make cached render results or decoders stale, broadcasted between replicas
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum InvalidationEvent {
+    // spore has been re-decoded, drop its render result
+    Spore { spore_id: H256 },
+    // cluster or its decoder has changed, drop its metadata and render results of its spores
+    Cluster { cluster_id: H256 },
+    // decoder binary has been upgraded, drop its cached binary
+    Decoder { hash: H256 },
+    // admin purge, drop all render results and cluster metadata
+    Purge,
+}
+
+// drop local cache entries invalidated by the event, failures are logged and ignored since
+// a stale entry left behind is no worse than before, returns the number of render results dropped
+pub fn apply_invalidation(decoder: &DOBDecoder, event: &InvalidationEvent) -> usize {
+    tracing::info!("applying cache invalidation: {event:?}");
+    let removed = match event {
+        InvalidationEvent::Spore { spore_id } => remove_dob_cache(decoder, &spore_id.0),
+        InvalidationEvent::Cluster { cluster_id } => {
+            decoder.evict_cluster_cache(Some(&cluster_id.0));
+            remove_dob_caches_where(decoder, |cache_cluster_id| {
+                cache_cluster_id == Some(cluster_id)
+            })
+        }
+        InvalidationEvent::Decoder { hash } => {
+            remove_decoder_cache(decoder, hash);
+            0
+        }
+        InvalidationEvent::Purge => {
+            decoder.evict_cluster_cache(None);
+            remove_dob_caches_where(decoder, |_| true)
+        }
+    };
+    // traits are indexed again once spores are decoded next time
+    #[cfg(feature = "trait_index")]
+    if let Some(trait_index) = decoder.trait_index() {
+        let _ = match event {
+            InvalidationEvent::Spore { spore_id } => trait_index.remove(&spore_id.0),
+            InvalidationEvent::Cluster { cluster_id } => trait_index.clear(Some(&cluster_id.0)),
+            InvalidationEvent::Decoder { .. } => Ok(()),
+            InvalidationEvent::Purge => trait_index.clear(None),
+        };
+    }
+    removed
+}
+
+// the same as `apply_invalidation`, but on the blocking pool, since walking cache directories
+// would otherwise stall the async runtime
+pub async fn apply_invalidation_blocking(
+    decoder: Arc<DOBDecoder>,
+    event: InvalidationEvent,
+) -> usize {
+    tokio::task::spawn_blocking(move || apply_invalidation(&decoder, &event))
+        .await
+        .expect("apply cache invalidation")
+}
+
+// composed images are dropped along with the render cache entry they come from
+#[cfg(not(feature = "shuttle"))]
+fn remove_dob_cache(decoder: &DOBDecoder, spore_id: &[u8; 32]) -> usize {
+    let cache_path = dob_cache_path(&decoder.setting().dobs_cache_directory, spore_id);
+    remove_image_caches(&cache_path);
+    if !cache_path.exists() {
+        return 0;
+    }
+    match fs::remove_file(&cache_path) {
+        Ok(()) => 1,
+        Err(error) => {
+            tracing::warn!("failed to remove cache {cache_path:?}: {error}");
+            0
+        }
+    }
+}
+
+#[cfg(feature = "shuttle")]
+fn remove_dob_cache(decoder: &DOBDecoder, spore_id: &[u8; 32]) -> usize {
+    let hexed_spore_id = hex::encode(spore_id);
+    for target in RenderTarget::all() {
+        let _ = decoder
+            .persist
+            .remove(&format!("{hexed_spore_id}.{}", target.cache_extension()));
+    }
+    decoder
+        .persist
+        .remove(&format!("{hexed_spore_id}.dob"))
+        .map_or(0, |_| 1)
+}
+
+#[cfg(not(feature = "shuttle"))]
+pub(crate) fn remove_image_caches(cache_path: &Path) {
+    for target in RenderTarget::all() {
+        let _ = fs::remove_file(cache_path.with_extension(target.cache_extension()));
+    }
+}
+
+// walk through all cache entries, including legacy flat ones, and remove those matched by
+// the cluster id recorded in their cache info
+#[cfg(not(feature = "shuttle"))]
+fn remove_dob_caches_where(decoder: &DOBDecoder, matches: impl Fn(Option<&H256>) -> bool) -> usize {
+    fn walk(directory: &Path, matches: &dyn Fn(Option<&H256>) -> bool) -> usize {
+        let Ok(entries) = fs::read_dir(directory) else {
+            return 0;
+        };
+        let mut removed = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                removed += walk(&path, matches);
+                continue;
+            }
+            if path.extension().is_some_and(|extension| extension == "dob") {
+                let cluster_id = read_dob_from_cache(path.clone())
+                    .ok()
+                    .and_then(|(_, _, cache_info)| cache_info.cluster_id);
+                if matches(cluster_id.as_ref()) {
+                    remove_image_caches(&path);
+                    match fs::remove_file(&path) {
+                        Ok(()) => removed += 1,
+                        Err(error) => tracing::warn!("failed to remove cache {path:?}: {error}"),
+                    }
+                }
+            }
+        }
+        removed
+    }
+    walk(&decoder.setting().dobs_cache_directory, &matches)
+}
+
+#[cfg(feature = "shuttle")]
+fn remove_dob_caches_where(decoder: &DOBDecoder, matches: impl Fn(Option<&H256>) -> bool) -> usize {
+    let Ok(keys) = decoder.persist.list() else {
+        return 0;
+    };
+    let mut removed = 0;
+    for key in keys.into_iter().filter(|key| key.ends_with(".dob")) {
+        let cluster_id = read_dob_from_cache(key.clone(), &decoder.persist)
+            .ok()
+            .and_then(|(_, _, cache_info)| cache_info.cluster_id);
+        if matches(cluster_id.as_ref()) {
+            for target in RenderTarget::all() {
+                let _ = decoder
+                    .persist
+                    .remove(&key.replace(".dob", &format!(".{}", target.cache_extension())));
+            }
+            if decoder.persist.remove(&key).is_ok() {
+                removed += 1;
+            }
+        }
+    }
+    removed
+}
+
+// decoder may be cached under either code_hash or type_id, drop both
+#[cfg(not(feature = "shuttle"))]
+fn remove_decoder_cache(decoder: &DOBDecoder, hash: &H256) {
+    for prefix in ["code_hash", "type_id"] {
+        let mut decoder_path = decoder.setting().decoders_cache_directory.clone();
+        decoder_path.push(format!("{prefix}_{}.bin", hex::encode(hash)));
+        if decoder_path.exists() {
+            if let Err(error) = fs::remove_file(&decoder_path) {
+                tracing::warn!("failed to remove decoder {decoder_path:?}: {error}");
+            }
+        }
+    }
+}
+
+#[cfg(feature = "shuttle")]
+fn remove_decoder_cache(decoder: &DOBDecoder, hash: &H256) {
+    for prefix in ["code_hash", "type_id"] {
+        let _ = decoder
+            .persist
+            .remove(&format!("{prefix}_{}.bin", hex::encode(hash)));
+    }
+}
+
+// message on the channel, tagged with its origin to skip events published by this replica
+#[cfg(feature = "cache_invalidation")]
+#[derive(Serialize, Deserialize)]
+struct InvalidationMessage {
+    origin: String,
+    #[serde(flatten)]
+    event: InvalidationEvent,
+}
+
+// Redis pub/sub channel shared by all replicas
+#[cfg(feature = "cache_invalidation")]
+pub struct InvalidationBus {
+    client: redis::Client,
+    channel: String,
+    origin: String,
+}
+
+#[cfg(feature = "cache_invalidation")]
+impl InvalidationBus {
+    pub fn new(settings: &CacheInvalidationSettings) -> Result<Self, redis::RedisError> {
+        Ok(Self {
+            client: redis::Client::open(settings.redis_url.as_str())?,
+            channel: settings.channel.clone(),
+            origin: replica_origin(),
+        })
+    }
+
+    pub async fn publish(&self, event: InvalidationEvent) -> Result<(), Error> {
+        use redis::AsyncCommands;
+
+        let message = InvalidationMessage {
+            origin: self.origin.clone(),
+            event,
+        };
+        let payload = serde_json::to_string(&message).unwrap();
+        let mut connection = self
+            .client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|_| Error::InvalidationPublishError)?;
+        connection
+            .publish::<_, _, ()>(&self.channel, payload)
+            .await
+            .map_err(|_| Error::InvalidationPublishError)
+    }
+
+    // keep applying events from other replicas, subscribing again with exponential backoff whenever
+    // the subscription breaks or Redis is unreachable, so that replicas never drift apart silently
+    pub async fn run(&self, decoder: &Arc<DOBDecoder>) {
+        let mut backoff = SUBSCRIBE_BACKOFF_INITIAL;
+        loop {
+            match self.subscribe(decoder).await {
+                // stream ended after subscribed, so Redis was reachable until just now
+                Ok(()) => {
+                    backoff = SUBSCRIBE_BACKOFF_INITIAL;
+                    tracing::warn!("cache invalidation subscription ended, subscribe again in {backoff:?}");
+                }
+                Err(error) => tracing::error!(
+                    "cache invalidation subscription broken: {error}, subscribe again in {backoff:?}"
+                ),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(SUBSCRIBE_BACKOFF_MAX);
+        }
+    }
+
+    // apply events from other replicas until the subscription breaks
+    async fn subscribe(&self, decoder: &Arc<DOBDecoder>) -> Result<(), redis::RedisError> {
+        use futures::StreamExt;
+
+        let mut pubsub = self.client.get_async_pubsub().await?;
+        pubsub.subscribe(&self.channel).await?;
+        tracing::info!("subscribed cache invalidation channel {}", self.channel);
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            let payload: String = message.get_payload()?;
+            match serde_json::from_str::<InvalidationMessage>(&payload) {
+                Ok(message) if message.origin != self.origin => {
+                    apply_invalidation_blocking(decoder.clone(), message.event).await;
+                }
+                Ok(_) => {}
+                Err(error) => tracing::warn!("unknown invalidation message {payload}: {error}"),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "cache_invalidation")]
+const SUBSCRIBE_BACKOFF_INITIAL: std::time::Duration = std::time::Duration::from_secs(1);
+#[cfg(feature = "cache_invalidation")]
+const SUBSCRIBE_BACKOFF_MAX: std::time::Duration = std::time::Duration::from_secs(60);
+
+// unique enough identifier for replicas sharing one channel
+#[cfg(feature = "cache_invalidation")]
+fn replica_origin() -> String {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("{}-{started_at}", std::process::id())
+}
@@ -1,7 +1,58 @@
+pub mod access;
+pub mod address;
+pub mod admin;
+pub mod assets;
+pub mod canary;
+pub mod concurrency;
+pub mod config;
+pub mod crawler;
+#[cfg(feature = "dashboard")]
+pub mod dashboard;
 pub mod decoder;
+pub mod env_overrides;
+pub mod error_reporting;
+pub mod forwarded;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod health;
+pub mod info;
+pub mod invalidation;
+pub mod logging;
+pub mod memory;
+pub mod metrics;
+pub mod middleware;
+pub mod openrpc;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+pub mod protocol;
+pub mod rarity;
+#[cfg(feature = "raster")]
+pub mod raster;
+pub mod rate_limit;
+pub mod registry;
+pub mod render;
+pub mod request_id;
+pub mod rest;
+pub mod retention;
+pub mod rgbpp;
 pub mod server;
+pub mod server_v2;
+pub mod slow_decode;
+pub mod stats;
+pub mod statsd;
+#[cfg(unix)]
+pub mod systemd;
+#[cfg(feature = "opentelemetry")]
+pub mod telemetry;
 #[cfg(test)]
 mod tests;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "trait_index")]
+pub mod trait_index;
 pub mod types;
+pub mod validation;
 mod vm;
 pub use server::ServerDecodeResult;
@@ -0,0 +1,32 @@
+use std::sync::OnceLock;
+
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::types::{Error, LoggingSettings};
+
+// filter of logs written to stdout, which is replaceable while running
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+// filter made of `RUST_LOG` if set, otherwise of `[logging]`, invalid directives are skipped here
+// since settings are validated later on
+pub fn reloadable_log_filter(settings: &LoggingSettings) -> reload::Layer<EnvFilter, Registry> {
+    let directives = std::env::var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .filter(|directives| !directives.is_empty())
+        .unwrap_or_else(|| settings.directives());
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(directives));
+    let _ = LOG_FILTER.set(handle);
+    filter
+}
+
+// replace log filter with directives in the form of `RUST_LOG`, like
+// `info,dob_decoder_server::decoder=debug`, returns the previous ones
+pub fn set_log_filter(directives: &str) -> Result<String, Error> {
+    let handle = LOG_FILTER.get().ok_or(Error::LogFilterInvalid)?;
+    let filter = EnvFilter::try_new(directives).map_err(|_| Error::LogFilterInvalid)?;
+    let previous = handle
+        .with_current(ToString::to_string)
+        .map_err(|_| Error::LogFilterInvalid)?;
+    handle.reload(filter).map_err(|_| Error::LogFilterInvalid)?;
+    Ok(previous)
+}
@@ -1,43 +1,692 @@
-use std::fs;
+use std::{fs, net::ToSocketAddrs, path::PathBuf, sync::Arc, time::Duration};
 
-use jsonrpsee::{server::ServerBuilder, tracing};
+use admin::AdminRpcServer;
+use jsonrpsee::server::{
+    stop_channel, Methods, ServerHandle, StopHandle, TowerService, TowerServiceBuilder,
+};
+use jsonrpsee::{
+    server::{middleware::rpc::RpcServiceBuilder, PingConfig, ServerBuilder},
+    tracing,
+};
 use server::DecoderRpcServer;
-use tracing_subscriber::EnvFilter;
+use server_v2::DecoderRpcV2Server;
+use socket2::{Domain, Socket, Type};
+use tower_http::map_response_body::MapResponseBodyLayer;
 
+mod access;
+mod address;
+mod admin;
+mod assets;
+mod canary;
+mod concurrency;
+mod config;
+mod crawler;
+#[cfg(feature = "dashboard")]
+mod dashboard;
 mod decoder;
+mod env_overrides;
+mod error_reporting;
+mod forwarded;
+#[cfg(feature = "graphql")]
+mod graphql;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod health;
+mod info;
+mod invalidation;
+mod logging;
+mod memory;
+mod metrics;
+mod middleware;
+mod openrpc;
+#[cfg(feature = "profiling")]
+mod profiling;
+mod protocol;
+mod rarity;
+#[cfg(feature = "raster")]
+mod raster;
+mod rate_limit;
+mod registry;
+mod render;
+mod request_id;
+mod rest;
+mod retention;
+mod rgbpp;
 mod server;
+mod server_v2;
+mod slow_decode;
+mod stats;
+mod statsd;
+#[cfg(unix)]
+mod systemd;
+#[cfg(feature = "opentelemetry")]
+mod telemetry;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(feature = "trait_index")]
+mod trait_index;
 mod types;
+mod validation;
 mod vm;
 
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
 const SETTINGS_FILE: &str = "./settings.toml";
 
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .init();
+// settings file in TOML, YAML or JSON told by its extension, instead of `SETTINGS_FILE`
+const CONFIG_FLAG: &str = "--config";
+
+// embedded profile of `mainnet` or `testnet`, over which only the file of `--config` is layered
+const NETWORK_FLAG: &str = "--network";
+
+// validate settings along with CKB node reachability, then exit without serving
+const CHECK_CONFIG_FLAG: &str = "--check-config";
+
+fn main() {
+    // subscriber depends on settings, so settings are loaded before anything can be logged
+    let settings_source = settings_source_from_args();
+    let settings = settings_source
+        .read()
+        .unwrap_or_else(|error| panic!("load settings from {settings_source:?}: {error}"));
+
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    runtime.enable_all();
+    if let Some(worker_threads) = settings.runtime_worker_threads {
+        runtime.worker_threads(worker_threads);
+    }
+    if let Some(max_blocking_threads) = settings.runtime_max_blocking_threads {
+        runtime.max_blocking_threads(max_blocking_threads);
+    }
+    let runtime = runtime.build().expect("build tokio runtime");
+    let _runtime_guard = runtime.enter();
+    init_tracing(&settings);
+
+    #[cfg(feature = "sentry")]
+    let _sentry_guard = settings.sentry.as_ref().map(error_reporting::init);
+    #[cfg(not(feature = "sentry"))]
+    if settings.sentry.is_some() {
+        tracing::warn!("[sentry] configured but feature `sentry` not enabled");
+    }
 
-    tracing::info!("loading settings file from {SETTINGS_FILE}");
-    let settings_file = fs::read_to_string(SETTINGS_FILE).expect("read settings.toml");
-    let settings: types::Settings = toml::from_str(&settings_file).expect("parse settings.toml");
+    tracing::info!("loaded settings from {settings_source:?}");
+    if let Err(errors) = settings.validate() {
+        errors
+            .iter()
+            .for_each(|error| tracing::error!("invalid settings: {error}"));
+        std::process::exit(1);
+    }
     tracing::debug!(
         "server settings: {}",
         serde_json::to_string_pretty(&settings).unwrap()
     );
+    let check_config = std::env::args().skip(1).any(|arg| arg == CHECK_CONFIG_FLAG);
+    runtime.block_on(run_server(settings, settings_source, check_config));
+    #[cfg(feature = "opentelemetry")]
+    telemetry::shutdown();
+}
+
+// `SETTINGS_FILE` is only read if neither `--config` nor `--network` is given
+fn settings_source_from_args() -> config::SettingsSource {
+    let network = flag_value(NETWORK_FLAG).map(|network| match network.as_str() {
+        "mainnet" => types::Network::Mainnet,
+        "testnet" => types::Network::Testnet,
+        _ => panic!("{NETWORK_FLAG} should be either mainnet or testnet"),
+    });
+    let file = flag_value(CONFIG_FLAG)
+        .map(PathBuf::from)
+        .or_else(|| network.is_none().then(|| PathBuf::from(SETTINGS_FILE)));
+    config::SettingsSource { network, file }
+}
+
+// value following the flag, either as the next argument or after `=`
+fn flag_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+        if let Some(value) = arg.strip_prefix(flag).and_then(|arg| arg.strip_prefix('=')) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+// log to stdout filtered by `RUST_LOG` or `[logging]`, along with exporting spans if `[opentelemetry]` is
+// configured, whose exporter requires tokio runtime entered
+fn init_tracing(settings: &types::Settings) {
+    use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
+
+    let subscriber = tracing_subscriber::registry().with(
+        tracing_subscriber::fmt::layer()
+            .with_filter(logging::reloadable_log_filter(&settings.logging)),
+    );
+    #[cfg(feature = "opentelemetry")]
+    let subscriber = subscriber.with(
+        settings
+            .opentelemetry
+            .as_ref()
+            .map(telemetry::opentelemetry_layer),
+    );
+    subscriber.init();
+    #[cfg(not(feature = "opentelemetry"))]
+    if settings.opentelemetry.is_some() {
+        tracing::warn!("[opentelemetry] configured but feature `opentelemetry` not enabled");
+    }
+}
+
+async fn run_server(
+    settings: types::Settings,
+    settings_source: config::SettingsSource,
+    check_config: bool,
+) {
     let rpc_server_address = settings.rpc_server_address.clone();
+    let warm_up_parallelism = settings.decoders_warm_up_parallelism;
+    let listen_reuse_port = settings.listen_reuse_port;
+    if let Some(statsd) = &settings.statsd {
+        let client = statsd::StatsdClient::new(statsd).expect("connect statsd");
+        tracing::info!("pushing metrics to statsd at {}", statsd.address);
+        metrics::set_statsd_client(client);
+    }
     let decoder = decoder::DOBDecoder::new(settings);
+    #[cfg(feature = "trait_index")]
+    let decoder = match decoder.setting().trait_index.clone() {
+        Some(settings) => {
+            let trait_index = trait_index::TraitIndex::open(&settings).expect("open trait index");
+            decoder.with_trait_index(trait_index)
+        }
+        None => decoder,
+    };
+    #[cfg(not(feature = "trait_index"))]
+    if decoder.setting().trait_index.is_some() {
+        tracing::warn!("trait_index configured but feature `trait_index` not enabled");
+    }
+    let decoder = Arc::new(decoder);
 
-    tracing::info!("running decoder server at {}", rpc_server_address);
-    let http_server = ServerBuilder::new()
-        .http_only()
-        .build(rpc_server_address)
-        .await
-        .expect("build http_server");
+    // every request fails without CKB node, which is better told on start
+    if let Err(error) = health::check_ckb_rpc(&decoder).await {
+        tracing::error!(
+            "invalid settings: `ckb_rpc` {} is not reachable: {error}, check that the node is \
+             running with RPC enabled",
+            decoder.setting().ckb_rpc
+        );
+        std::process::exit(1);
+    }
+    if check_config {
+        tracing::info!("settings are valid");
+        return;
+    }
 
-    let rpc_methods = server::DecoderStandaloneServer::new(decoder);
-    let handler = http_server.start(rpc_methods.into_rpc());
+    if warm_up_parallelism > 0 {
+        tracing::info!("warming up configured on-chain decoders");
+        if listen_reuse_port {
+            // upgrading process takes over listening only after it's warmed up
+            decoder.warm_decoders(warm_up_parallelism).await;
+        } else {
+            let decoder = decoder.clone();
+            tokio::spawn(async move {
+                decoder.warm_decoders(warm_up_parallelism).await;
+            });
+        }
+    }
 
-    tokio::signal::ctrl_c().await.unwrap();
-    tracing::info!("stopping decoder server");
+    if let Some(registry) = &decoder.setting().decoder_registry {
+        let sync_interval = Duration::from_secs(registry.sync_interval_secs);
+        let decoder = decoder.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(sync_interval);
+            loop {
+                interval.tick().await;
+                match decoder.sync_decoder_registry().await {
+                    Ok(synced) => {
+                        tracing::info!("synced {synced} decoder deployments from registry")
+                    }
+                    Err(error) => tracing::warn!("failed to sync decoder registry: {error}"),
+                }
+            }
+        });
+    }
+
+    let retention = &decoder.setting().dobs_cache_retention;
+    if retention.max_bytes > 0 {
+        let sweep_interval = Duration::from_secs(retention.sweep_interval_secs);
+        let decoder = decoder.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(sweep_interval);
+            loop {
+                interval.tick().await;
+                let sweeper = decoder.clone();
+                match tokio::task::spawn_blocking(move || retention::sweep_dob_cache(&sweeper))
+                    .await
+                {
+                    Ok(report) if report.removed > 0 => {
+                        tracing::info!("render cache swept: {report:?}")
+                    }
+                    Ok(_) => {}
+                    Err(error) => tracing::error!("render cache sweep panicked: {error}"),
+                }
+            }
+        });
+    }
+
+    let canary = decoder.setting().canary.clone().map(|settings| {
+        let canary = Arc::new(canary::CanaryMonitor::new(settings));
+        let (canary_monitor, decoder) = (canary.clone(), decoder.clone());
+        tokio::spawn(async move { canary_monitor.run(&decoder).await });
+        canary
+    });
+
+    let crawler = Arc::new(crawler::ClusterCrawler::new(decoder.setting()));
+    if !decoder.setting().cluster_crawler.cluster_ids.is_empty() {
+        let (crawler, decoder) = (crawler.clone(), decoder.clone());
+        tokio::spawn(async move { crawler.run(&decoder).await });
+    }
+
+    let networks = decoder
+        .setting()
+        .networks
+        .iter()
+        .map(|network| {
+            tracing::info!("serving network {} at {}", network.name, network.ckb_rpc);
+            let settings = decoder.setting().for_network(network);
+            (
+                network.name.clone(),
+                Arc::new(decoder::DOBDecoder::new(settings)),
+            )
+        })
+        .collect();
+
+    let mut rpc_methods = server::DecoderStandaloneServer::new(decoder.clone())
+        .with_crawler(crawler)
+        .with_networks(networks)
+        .with_settings_source(settings_source);
+    if let Some(canary) = &canary {
+        rpc_methods = rpc_methods.with_canary(canary.clone());
+    }
+    #[cfg(feature = "cache_invalidation")]
+    if let Some(settings) = &decoder.setting().cache_invalidation {
+        let invalidation_bus =
+            Arc::new(invalidation::InvalidationBus::new(settings).expect("connect redis"));
+        rpc_methods = rpc_methods.with_invalidation_bus(invalidation_bus.clone());
+        let decoder = decoder.clone();
+        tokio::spawn(async move { invalidation_bus.run(&decoder).await });
+    }
+    #[cfg(not(feature = "cache_invalidation"))]
+    if decoder.setting().cache_invalidation.is_some() {
+        tracing::warn!(
+            "cache_invalidation configured but feature `cache_invalidation` not enabled"
+        );
+    }
+
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_server_address) = &decoder.setting().grpc_server_address {
+        tracing::info!("running gRPC server at {grpc_server_address}");
+        let listener = std::net::TcpListener::bind(grpc_server_address)
+            .and_then(|listener| {
+                listener.set_nonblocking(true)?;
+                Ok(listener)
+            })
+            .expect("bind grpc_server_address");
+        grpc::serve(rpc_methods.clone(), listener).expect("serve gRPC");
+    }
+    #[cfg(not(feature = "grpc"))]
+    if decoder.setting().grpc_server_address.is_some() {
+        tracing::warn!("grpc_server_address configured but feature `grpc` not enabled");
+    }
+
+    let rest_api_server = decoder.setting().rest_api.then(|| {
+        tracing::info!("serving REST api at {}", rest::DOB_PATH_PREFIX);
+        rpc_methods.clone()
+    });
+    #[cfg(feature = "dashboard")]
+    let dashboard_layer = {
+        tracing::info!("serving dashboard at {}", dashboard::DASHBOARD_PATH);
+        dashboard::DashboardLayer
+    };
+    #[cfg(not(feature = "dashboard"))]
+    let dashboard_layer = tower::layer::util::Identity::new();
+    #[cfg(feature = "metrics")]
+    let metrics_layer = {
+        tracing::info!("serving metrics at {}", metrics::METRICS_PATH);
+        metrics::MetricsLayer
+    };
+    #[cfg(not(feature = "metrics"))]
+    let metrics_layer = tower::layer::util::Identity::new();
+    #[cfg(feature = "graphql")]
+    let graphql_layer = {
+        tracing::info!("serving GraphQL at {}", graphql::GRAPHQL_PATH);
+        graphql::GraphqlLayer::new(rpc_methods.clone())
+    };
+    #[cfg(not(feature = "graphql"))]
+    let graphql_layer = tower::layer::util::Identity::new();
+    // networks are validated on start
+    let trusted_proxies = decoder
+        .setting()
+        .trusted_proxies
+        .iter()
+        .filter_map(|net| net.parse().ok())
+        .collect();
+    // preflight requests are answered before anything else
+    let cors_layer = decoder.setting().cors.as_ref().map(middleware::cors_layer);
+    if let Some(cors) = &decoder.setting().cors {
+        tracing::info!(
+            "allowing cross-origin requests from {:?}",
+            cors.allowed_origins
+        );
+    }
+    let http_middleware = tower::ServiceBuilder::new()
+        .layer(tower::util::option_layer(cors_layer))
+        .layer(forwarded::ClientIpLayer::new(trusted_proxies))
+        .layer(request_id::RequestIdLayer)
+        .layer(dashboard_layer)
+        .layer(metrics_layer)
+        .layer(canary::ReadinessLayer::new(canary.clone()))
+        .layer(health::HealthLayer::new(decoder.clone(), canary.clone()))
+        .layer(rate_limit::RateLimitLayer::new(
+            decoder.setting().rate_limit.clone(),
+        ))
+        .layer(access::ApiKeyLayer::new(&decoder.setting().api_keys))
+        .layer(MapResponseBodyLayer::new(
+            middleware::compressed_into_hyper_body,
+        ))
+        .layer(middleware::compression_layer(
+            &decoder.setting().response_compression,
+        ))
+        .layer(middleware::ResponseEncodingLayer)
+        .layer(rest::RestApiLayer::new(rest_api_server))
+        .layer(graphql_layer);
+    let server_builder = match &decoder.setting().websocket {
+        Some(websocket) => {
+            tracing::info!("serving json-rpc over WebSocket along with HTTP");
+            ServerBuilder::new().enable_ws_ping(
+                PingConfig::new()
+                    .ping_interval(Duration::from_secs(websocket.ping_interval_secs))
+                    .inactive_limit(Duration::from_secs(websocket.inactive_limit_secs)),
+            )
+        }
+        None => ServerBuilder::new().http_only(),
+    }
+    .max_request_body_size(decoder.setting().request_limits.max_request_body_bytes)
+    .set_http_middleware(http_middleware)
+    .set_rpc_middleware(
+        RpcServiceBuilder::new()
+            .layer_fn(metrics::RpcLatency::new)
+            .layer_fn(rate_limit::RpcRateLimit::new),
+    );
+
+    #[cfg(unix)]
+    {
+        let rpc_methods = rpc_methods.clone();
+        tokio::spawn(async move { reload_on_sighup(&rpc_methods).await });
+    }
+
+    let mut rpc_module = DecoderRpcServer::into_rpc(rpc_methods.clone());
+    let admin_module = AdminRpcServer::into_rpc(rpc_methods.clone());
+    rpc_module
+        .merge(DecoderRpcV2Server::into_rpc(rpc_methods))
+        .expect("merge dob2 methods");
+    // admin methods are never served on the public listener, which has no way to tell operators
+    // from anyone else
+    let admin_handler = match &decoder.setting().admin_rpc_server_address {
+        Some(admin_rpc_server_address) => {
+            tracing::info!("running admin server at {admin_rpc_server_address}");
+            #[cfg(feature = "profiling")]
+            let profiling_layer = {
+                tracing::info!("serving CPU profile at {}", profiling::PROFILE_PATH);
+                profiling::ProfilingLayer
+            };
+            #[cfg(not(feature = "profiling"))]
+            let profiling_layer = tower::layer::util::Identity::new();
+            let admin_server = ServerBuilder::new()
+                .http_only()
+                .set_http_middleware(tower::ServiceBuilder::new().layer(profiling_layer))
+                .build(admin_rpc_server_address)
+                .await
+                .expect("build admin_server");
+            Some(admin_server.start(admin_module))
+        }
+        None => {
+            tracing::info!("admin methods not served without `admin_rpc_server_address`");
+            #[cfg(feature = "profiling")]
+            tracing::warn!("CPU profile is only served on `admin_rpc_server_address`");
+            None
+        }
+    };
+    let handler = match &decoder.setting().unix_socket {
+        #[cfg(unix)]
+        Some(unix_socket) => {
+            tracing::info!("running decoder server at {:?}", unix_socket.path);
+            serve_unix_socket(unix_socket, server_builder.to_service_builder(), rpc_module)
+                .expect("serve unix socket")
+        }
+        _ => {
+            tracing::info!("running decoder server at {}", rpc_server_address);
+            let listener = if listen_reuse_port {
+                bind_reuse_port_listener(&rpc_server_address)
+            } else {
+                std::net::TcpListener::bind(&rpc_server_address).and_then(|listener| {
+                    listener.set_nonblocking(true)?;
+                    Ok(listener)
+                })
+            }
+            .expect("bind listener");
+            serve_tcp(
+                listener,
+                decoder.setting().tls.as_ref(),
+                server_builder.to_service_builder(),
+                rpc_module,
+            )
+            .expect("serve tcp")
+        }
+    };
+
+    #[cfg(unix)]
+    tokio::spawn(systemd::run(decoder.clone()));
+
+    shutdown_signal().await;
+    tracing::info!("stopping decoder server, draining in-flight requests");
+    #[cfg(unix)]
+    if let Err(error) = systemd::notify("STOPPING=1") {
+        tracing::error!("failed to notify systemd: {error}");
+    }
     handler.stop().unwrap();
+    if let Some(admin_handler) = admin_handler {
+        admin_handler.stop().unwrap();
+        admin_handler.stopped().await;
+    }
+    handler.stopped().await;
+}
+
+// allow a new server process to listen on the same address, so that the old one can drain after it
+fn bind_reuse_port_listener(address: &str) -> std::io::Result<std::net::TcpListener> {
+    let address = address.to_socket_addrs()?.next().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "no address resolved")
+    })?;
+    let socket = Socket::new(Domain::for_address(address), Type::STREAM, None)?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(1024)?;
+    Ok(socket.into())
+}
+
+// serve on a Unix domain socket in the same way as jsonrpsee serves on TCP, connections are
+// closed gracefully once the returned handle is stopped
+#[cfg(unix)]
+fn serve_unix_socket<RpcMiddleware, HttpMiddleware>(
+    settings: &types::UnixSocketSettings,
+    service_builder: TowerServiceBuilder<RpcMiddleware, HttpMiddleware>,
+    methods: impl Into<Methods>,
+) -> std::io::Result<ServerHandle>
+where
+    TowerServiceBuilder<RpcMiddleware, HttpMiddleware>: Clone + Send + 'static,
+    TowerService<RpcMiddleware, HttpMiddleware>: hyper::service::Service<
+            hyper::Request<hyper::Body>,
+            Response = hyper::Response<hyper::Body>,
+            Error = Box<dyn std::error::Error + Send + Sync>,
+        > + Send
+        + 'static,
+    <TowerService<RpcMiddleware, HttpMiddleware> as hyper::service::Service<
+        hyper::Request<hyper::Body>,
+    >>::Future: Send + 'static,
+{
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    // socket file left by a previous process fails binding, while other files are kept intact
+    if let Ok(metadata) = fs::symlink_metadata(&settings.path) {
+        if metadata.file_type().is_socket() {
+            fs::remove_file(&settings.path)?;
+        }
+    }
+    let listener = tokio::net::UnixListener::bind(&settings.path)?;
+    fs::set_permissions(&settings.path, fs::Permissions::from_mode(settings.mode))?;
+    let methods: Methods = methods.into();
+    let (stop_handle, server_handle) = stop_channel();
+    tokio::spawn(async move {
+        loop {
+            let stream = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(error) => {
+                        tracing::warn!("failed to accept unix socket connection: {error}");
+                        continue;
+                    }
+                },
+                _ = stop_handle.clone().shutdown() => break,
+            };
+            let service = service_builder
+                .clone()
+                .build(methods.clone(), stop_handle.clone());
+            serve_connection(stream, service, stop_handle.clone());
+        }
+    });
+    Ok(server_handle)
+}
+
+// serve on TCP in the same way as jsonrpsee does, along with peer address attached to requests,
+// which jsonrpsee doesn't pass to http middlewares
+fn serve_tcp<RpcMiddleware, HttpMiddleware>(
+    listener: std::net::TcpListener,
+    tls: Option<&types::TlsSettings>,
+    service_builder: TowerServiceBuilder<RpcMiddleware, HttpMiddleware>,
+    methods: impl Into<Methods>,
+) -> std::io::Result<ServerHandle>
+where
+    TowerServiceBuilder<RpcMiddleware, HttpMiddleware>: Clone + Send + 'static,
+    TowerService<RpcMiddleware, HttpMiddleware>: hyper::service::Service<
+            hyper::Request<hyper::Body>,
+            Response = hyper::Response<hyper::Body>,
+            Error = Box<dyn std::error::Error + Send + Sync>,
+        > + Send
+        + 'static,
+    <TowerService<RpcMiddleware, HttpMiddleware> as hyper::service::Service<
+        hyper::Request<hyper::Body>,
+    >>::Future: Send + 'static,
+{
+    #[cfg(feature = "tls")]
+    let tls_handshaker = tls
+        .map(|tls| tls::TlsHandshaker::new(tls, b"http/1.1"))
+        .transpose()?;
+    #[cfg(not(feature = "tls"))]
+    if tls.is_some() {
+        tracing::warn!("[tls] configured but feature `tls` not enabled, serving plain HTTP");
+    }
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    let methods: Methods = methods.into();
+    let (stop_handle, server_handle) = stop_channel();
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer_addr) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(error) => {
+                        tracing::warn!("failed to accept tcp connection: {error}");
+                        continue;
+                    }
+                },
+                _ = stop_handle.clone().shutdown() => break,
+            };
+            let _ = stream.set_nodelay(true);
+            let service = service_builder
+                .clone()
+                .build(methods.clone(), stop_handle.clone());
+            let service = forwarded::PeerAddrService::new(service, peer_addr);
+            // handshake is done apart from accepting, so that slow clients don't hold others
+            #[cfg(feature = "tls")]
+            if let Some(tls_handshaker) = tls_handshaker.clone() {
+                let stop_handle = stop_handle.clone();
+                tokio::spawn(async move {
+                    match tls_handshaker.accept(stream).await {
+                        Ok(stream) => serve_connection(stream, service, stop_handle),
+                        Err(error) => {
+                            tracing::debug!("TLS handshake with {peer_addr} failed: {error}")
+                        }
+                    }
+                });
+                continue;
+            }
+            serve_connection(stream, service, stop_handle.clone());
+        }
+    });
+    Ok(server_handle)
+}
+
+// connections are closed gracefully once server is stopped
+fn serve_connection<I, S>(stream: I, service: S, stop_handle: StopHandle)
+where
+    I: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+    S: hyper::service::Service<
+            hyper::Request<hyper::Body>,
+            Response = hyper::Response<hyper::Body>,
+            Error = Box<dyn std::error::Error + Send + Sync>,
+        > + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    tokio::spawn(async move {
+        let connection = hyper::server::conn::Http::new()
+            .serve_connection(stream, service)
+            .with_upgrades();
+        tokio::pin!(connection);
+        tokio::select! {
+            _ = connection.as_mut() => {}
+            _ = stop_handle.shutdown() => {
+                connection.as_mut().graceful_shutdown();
+                let _ = connection.await;
+            }
+        }
+    });
+}
+
+// `kill -HUP` reloads settings file the same as `admin_reload_settings`, failures are logged and
+// the server keeps running with settings loaded before
+#[cfg(unix)]
+async fn reload_on_sighup(rpc_methods: &server::DecoderStandaloneServer) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut hangup = signal(SignalKind::hangup()).expect("listen SIGHUP");
+    while hangup.recv().await.is_some() {
+        tracing::info!("reloading settings on SIGHUP");
+        if admin::reload_settings_file(rpc_methods).is_err() {
+            tracing::error!("settings not reloaded, keep running with the previous ones");
+        }
+    }
+}
+
+// process managers usually send SIGTERM to the old process once the new one is up
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("listen SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {},
+            _ = terminate.recv() => {},
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.unwrap();
 }
@@ -0,0 +1,50 @@
+use crate::decoder::DOBDecoder;
+use crate::types::{JemallocStats, MemoryUsage};
+
+pub fn memory_usage(decoder: &DOBDecoder) -> MemoryUsage {
+    let (cluster_cache_entries, cluster_cache_bytes) = decoder.cluster_cache_usage();
+    MemoryUsage {
+        process_rss_bytes: process_rss_bytes(),
+        cluster_cache_entries,
+        cluster_cache_bytes,
+        jemalloc: jemalloc_stats(),
+    }
+}
+
+// read `VmRSS` line from procfs, which is reported in kB
+#[cfg(target_os = "linux")]
+fn process_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let rss_kb = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(rss_kb * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_rss_bytes() -> Option<u64> {
+    None
+}
+
+#[cfg(feature = "jemalloc")]
+fn jemalloc_stats() -> Option<JemallocStats> {
+    use tikv_jemalloc_ctl::{epoch, stats};
+
+    // statistics are cached in jemalloc until epoch advanced
+    epoch::advance().ok()?;
+    Some(JemallocStats {
+        allocated_bytes: stats::allocated::read().ok()?,
+        active_bytes: stats::active::read().ok()?,
+        resident_bytes: stats::resident::read().ok()?,
+    })
+}
+
+#[cfg(not(feature = "jemalloc"))]
+fn jemalloc_stats() -> Option<JemallocStats> {
+    None
+}
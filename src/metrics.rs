@@ -0,0 +1,265 @@
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use ckb_types::H256;
+use futures::future::BoxFuture;
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::server::MethodResponse;
+use jsonrpsee::types::{ErrorCode, Request};
+
+use crate::slow_decode;
+use crate::statsd::StatsdClient;
+use crate::types::DecodeStage;
+
+#[cfg(feature = "metrics")]
+pub use exporter::{MetricsLayer, MetricsService, METRICS_PATH};
+
+// metrics are collected into the default registry of prometheus and exported on `GET /metrics`
+// if server is built under feature `metrics`, and pushed to StatsD if configured
+#[cfg(feature = "metrics")]
+mod exporter {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use hyper::header::{HeaderValue, CONTENT_TYPE};
+    use hyper::{Body, Method, Request, Response};
+    use lazy_static::lazy_static;
+    use prometheus::{
+        register_histogram, register_histogram_vec, register_int_counter_vec, Encoder, Histogram,
+        HistogramVec, IntCounterVec, TextEncoder,
+    };
+    use tower::{Layer, Service};
+
+    pub const METRICS_PATH: &str = "/metrics";
+
+    lazy_static! {
+        pub(super) static ref DECODE_REQUESTS: IntCounterVec = register_int_counter_vec!(
+            "dob_decode_requests_total",
+            "decode requests by outcome",
+            &["outcome"]
+        )
+        .unwrap();
+        pub(super) static ref DECODE_DURATION: Histogram = register_histogram!(
+            "dob_decode_duration_seconds",
+            "latency of decode requests, including those served from render cache"
+        )
+        .unwrap();
+        pub(super) static ref DECODE_PHASE_DURATION: HistogramVec = register_histogram_vec!(
+            "dob_decode_phase_duration_seconds",
+            "latency of decoding phases, which are fetching spore from indexer, fetching decoder and VM execution",
+            &["phase"]
+        )
+        .unwrap();
+        pub(super) static ref CLUSTER_DECODE_DURATION: HistogramVec = register_histogram_vec!(
+            "dob_cluster_decode_duration_seconds",
+            "latency of decode requests by cluster, spores without cluster are left out",
+            &["cluster_id"]
+        )
+        .unwrap();
+        pub(super) static ref RPC_DURATION: HistogramVec = register_histogram_vec!(
+            "dob_rpc_duration_seconds",
+            "latency of json-rpc calls by method, calls to unknown methods are labeled `unknown`",
+            &["method"]
+        )
+        .unwrap();
+        pub(super) static ref RENDER_CACHE: IntCounterVec = register_int_counter_vec!(
+            "dob_render_cache_total",
+            "render cache lookups by result",
+            &["result"]
+        )
+        .unwrap();
+        pub(super) static ref CKB_RPC_REQUESTS: IntCounterVec = register_int_counter_vec!(
+            "dob_ckb_rpc_requests_total",
+            "requests sent to CKB node by method",
+            &["method"]
+        )
+        .unwrap();
+        pub(super) static ref CKB_RPC_ERRORS: IntCounterVec = register_int_counter_vec!(
+            "dob_ckb_rpc_errors_total",
+            "failed requests sent to CKB node by method",
+            &["method"]
+        )
+        .unwrap();
+    }
+
+    // serve metrics in prometheus text format on `GET /metrics`
+    #[derive(Clone, Default)]
+    pub struct MetricsLayer;
+
+    impl<S> Layer<S> for MetricsLayer {
+        type Service = MetricsService<S>;
+
+        fn layer(&self, inner: S) -> Self::Service {
+            MetricsService { inner }
+        }
+    }
+
+    #[derive(Clone)]
+    pub struct MetricsService<S> {
+        inner: S,
+    }
+
+    impl<S> Service<Request<Body>> for MetricsService<S>
+    where
+        S: Service<Request<Body>, Response = Response<Body>>,
+        S::Error: Send + 'static,
+        S::Future: Send + 'static,
+    {
+        type Response = Response<Body>;
+        type Error = S::Error;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.inner.poll_ready(cx)
+        }
+
+        fn call(&mut self, request: Request<Body>) -> Self::Future {
+            if request.method() == Method::GET && request.uri().path() == METRICS_PATH {
+                let encoder = TextEncoder::new();
+                let mut buffer = Vec::new();
+                encoder
+                    .encode(&prometheus::gather(), &mut buffer)
+                    .expect("encode metrics");
+                let mut response = Response::new(Body::from(buffer));
+                response.headers_mut().insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_str(encoder.format_type()).unwrap(),
+                );
+                return Box::pin(async move { Ok(response) });
+            }
+            Box::pin(self.inner.call(request))
+        }
+    }
+}
+
+// StatsD client shared by all recordings, metrics are not pushed if it's never set
+static STATSD: OnceLock<StatsdClient> = OnceLock::new();
+
+pub fn set_statsd_client(client: StatsdClient) {
+    let _ = STATSD.set(client);
+}
+
+pub fn observe_decode_request(succeeded: bool, latency: Duration) {
+    let outcome = if succeeded { "ok" } else { "error" };
+    #[cfg(feature = "metrics")]
+    {
+        exporter::DECODE_REQUESTS
+            .with_label_values(&[outcome])
+            .inc();
+        exporter::DECODE_DURATION.observe(latency.as_secs_f64());
+    }
+    if let Some(statsd) = STATSD.get() {
+        statsd.count("decode.requests", 1, &[("outcome", outcome)]);
+        statsd.timing("decode.duration", latency, &[]);
+    }
+}
+
+// only stages taking notable time are timed
+pub fn observe_decode_phase(stage: DecodeStage, latency: Duration) {
+    let phase = match stage {
+        DecodeStage::FetchSpore => "fetch_spore",
+        DecodeStage::DownloadDecoder => "fetch_decoder",
+        DecodeStage::Execute => "execute",
+        _ => return,
+    };
+    slow_decode::record_phase(phase, latency);
+    #[cfg(feature = "metrics")]
+    exporter::DECODE_PHASE_DURATION
+        .with_label_values(&[phase])
+        .observe(latency.as_secs_f64());
+    if let Some(statsd) = STATSD.get() {
+        statsd.timing("decode.phase_duration", latency, &[("phase", phase)]);
+    }
+}
+
+// cluster whose decoder is slow stands out here, while it's averaged away in overall latency
+pub fn observe_cluster_decode(cluster_id: &H256, latency: Duration) {
+    let cluster_id = format!("0x{cluster_id}");
+    #[cfg(feature = "metrics")]
+    exporter::CLUSTER_DECODE_DURATION
+        .with_label_values(&[&cluster_id])
+        .observe(latency.as_secs_f64());
+    if let Some(statsd) = STATSD.get() {
+        statsd.timing(
+            "decode.cluster_duration",
+            latency,
+            &[("cluster_id", &cluster_id)],
+        );
+    }
+}
+
+pub fn observe_rpc(method: &str, latency: Duration) {
+    #[cfg(feature = "metrics")]
+    exporter::RPC_DURATION
+        .with_label_values(&[method])
+        .observe(latency.as_secs_f64());
+    if let Some(statsd) = STATSD.get() {
+        statsd.timing("rpc.duration", latency, &[("method", method)]);
+    }
+}
+
+// json-rpc middleware timing each call by its method, including calls in batches
+#[derive(Clone)]
+pub struct RpcLatency<S> {
+    inner: S,
+}
+
+impl<S> RpcLatency<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<'a, S> RpcServiceT<'a> for RpcLatency<S>
+where
+    S: RpcServiceT<'a> + Send + Sync,
+    S::Future: 'a,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let method = request.method_name().to_string();
+        let started_at = Instant::now();
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await;
+            // method names are given by clients, so unknown ones would flood labels
+            let method = match response.as_error_code() {
+                Some(code) if code == ErrorCode::MethodNotFound.code() => "unknown",
+                _ => method.as_str(),
+            };
+            observe_rpc(method, started_at.elapsed());
+            response
+        })
+    }
+}
+
+pub fn observe_render_cache(hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    #[cfg(feature = "metrics")]
+    exporter::RENDER_CACHE.with_label_values(&[result]).inc();
+    if let Some(statsd) = STATSD.get() {
+        statsd.count("render_cache", 1, &[("result", result)]);
+    }
+}
+
+// count request sent to CKB node and whether it failed, result is passed through
+pub fn observe_ckb_rpc<T, E>(method: &str, result: Result<T, E>) -> Result<T, E> {
+    #[cfg(feature = "metrics")]
+    {
+        exporter::CKB_RPC_REQUESTS
+            .with_label_values(&[method])
+            .inc();
+        if result.is_err() {
+            exporter::CKB_RPC_ERRORS.with_label_values(&[method]).inc();
+        }
+    }
+    if let Some(statsd) = STATSD.get() {
+        statsd.count("ckb_rpc.requests", 1, &[("method", method)]);
+        if result.is_err() {
+            statsd.count("ckb_rpc.errors", 1, &[("method", method)]);
+        }
+    }
+    result
+}
@@ -0,0 +1,203 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream;
+use hyper::body::HttpBody;
+use std::time::Duration;
+
+use hyper::header::{HeaderName, HeaderValue, ACCEPT, CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use jsonrpsee::types::ErrorObjectOwned;
+use serde_json::Value;
+use tower::{Layer, Service};
+use tower_http::compression::{CompressionBody, CompressionLayer};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::request_id::REQUEST_ID_HEADER;
+use crate::types::{CorsSettings, Error, ResponseCompressionSettings};
+
+// requests rejected by middlewares shared by json-rpc and gRPC servers are responded in the
+// protocol of the server, which is json-rpc error in body over HTTP
+pub trait RejectionBody: Sized {
+    fn rejection(status: StatusCode, error: Error) -> Response<Self>;
+}
+
+impl RejectionBody for Body {
+    fn rejection(status: StatusCode, error: Error) -> Response<Self> {
+        let body = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": ErrorObjectOwned::from(error),
+        });
+        let mut response = Response::new(Body::from(body.to_string()));
+        *response.status_mut() = status;
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        response
+    }
+}
+
+// compress responses in encodings both enabled in settings and accepted by client
+pub fn compression_layer(settings: &ResponseCompressionSettings) -> CompressionLayer {
+    CompressionLayer::new().gzip(settings.gzip).br(settings.br)
+}
+
+// answer preflight requests and attach CORS headers, invalid entries are rejected on start, so
+// they are simply skipped here
+pub fn cors_layer(settings: &CorsSettings) -> CorsLayer {
+    let allowed_origins = if settings.allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            settings
+                .allowed_origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        )
+    };
+    let allowed_methods = settings
+        .allowed_methods
+        .iter()
+        .filter_map(|method| Method::from_bytes(method.as_bytes()).ok())
+        .collect::<Vec<_>>();
+    let allowed_headers = settings
+        .allowed_headers
+        .iter()
+        .filter_map(|header| HeaderName::from_bytes(header.as_bytes()).ok())
+        .collect::<Vec<_>>();
+    let layer = CorsLayer::new()
+        .allow_origin(allowed_origins)
+        .allow_methods(allowed_methods)
+        .allow_headers(allowed_headers)
+        .expose_headers([REQUEST_ID_HEADER]);
+    match settings.max_age_secs {
+        Some(max_age_secs) => layer.max_age(Duration::from_secs(max_age_secs)),
+        None => layer,
+    }
+}
+
+// jsonrpsee requires the error of response body implementing `std::error::Error`, which the boxed
+// error of compression body doesn't, so convert it back into hyper body
+pub fn compressed_into_hyper_body(body: CompressionBody<hyper::Body>) -> hyper::Body {
+    hyper::Body::wrap_stream(stream::unfold(Box::pin(body), |mut body| async move {
+        body.data().await.map(|chunk| (chunk, body))
+    }))
+}
+
+// compact binary encodings of response, negotiated by `encoding` query param of request url,
+// like `/?encoding=cbor`, or by its `Accept` header, JSON is kept if neither is present
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseEncoding {
+    Cbor,
+    MessagePack,
+}
+
+impl ResponseEncoding {
+    pub fn negotiate<B>(request: &Request<B>) -> Option<Self> {
+        let query_encoding = request.uri().query().and_then(|query| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("encoding="))
+        });
+        if let Some(encoding) = query_encoding {
+            return match encoding {
+                "cbor" => Some(Self::Cbor),
+                "msgpack" => Some(Self::MessagePack),
+                _ => None,
+            };
+        }
+        let accept = request.headers().get(ACCEPT)?.to_str().ok()?;
+        accept.split(',').find_map(|media_type| {
+            match media_type.split(';').next().unwrap_or_default().trim() {
+                "application/cbor" => Some(Self::Cbor),
+                "application/msgpack" | "application/x-msgpack" => Some(Self::MessagePack),
+                _ => None,
+            }
+        })
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Cbor => "application/cbor",
+            Self::MessagePack => "application/msgpack",
+        }
+    }
+
+    pub fn encode(&self, value: &Value) -> Option<Vec<u8>> {
+        match self {
+            Self::Cbor => {
+                let mut buffer = Vec::new();
+                ciborium::into_writer(value, &mut buffer).ok()?;
+                Some(buffer)
+            }
+            Self::MessagePack => rmp_serde::to_vec_named(value).ok(),
+        }
+    }
+}
+
+// transcode JSON responses of jsonrpsee into the negotiated binary encoding, should be placed
+// inside the compression layer so that binary payloads are compressed as well
+#[derive(Clone, Default)]
+pub struct ResponseEncodingLayer;
+
+impl<S> Layer<S> for ResponseEncodingLayer {
+    type Service = ResponseEncodingService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ResponseEncodingService { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct ResponseEncodingService<S> {
+    inner: S,
+}
+
+impl<S> Service<Request<Body>> for ResponseEncodingService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let encoding = ResponseEncoding::negotiate(&request);
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            match encoding {
+                Some(encoding) => Ok(encode_response(response, encoding).await),
+                None => Ok(response),
+            }
+        })
+    }
+}
+
+// responses not in JSON, like those rejected by http checks, are passed through untouched
+async fn encode_response(response: Response<Body>, encoding: ResponseEncoding) -> Response<Body> {
+    let (mut parts, body) = response.into_parts();
+    let Ok(payload) = hyper::body::to_bytes(body).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+    let encoded = serde_json::from_slice::<Value>(&payload)
+        .ok()
+        .and_then(|value| encoding.encode(&value));
+    let Some(encoded) = encoded else {
+        return Response::from_parts(parts, Body::from(payload));
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(encoding.content_type()),
+    );
+    Response::from_parts(parts, Body::from(encoded))
+}
@@ -0,0 +1,42 @@
+use serde_json::{json, Value};
+
+use crate::types::Error;
+
+// method params, result shapes and schemas are maintained in JSON along with `DecoderRpc`
+const OPENRPC_TEMPLATE: &str = include_str!("openrpc.json");
+
+// methods which respond errors listed in `Error`
+const FALLIBLE_METHODS: [&str; 5] = [
+    "dob_decode",
+    "dob_batch_decode",
+    "dob_decode_by_owner",
+    "dob_cache_status",
+    "dob_cluster_stats",
+];
+
+// OpenRPC document served by `rpc.discover`, for generating client SDKs in other languages,
+// with server version and error codes filled from code so that they never go stale
+pub fn openrpc_document() -> Value {
+    let mut document: Value = serde_json::from_str(OPENRPC_TEMPLATE).expect("openrpc template");
+    document["info"]["version"] = json!(env!("CARGO_PKG_VERSION"));
+    let errors = Error::ALL
+        .iter()
+        .map(|error| {
+            let error_object = json!({ "code": *error as i32, "message": error.to_string() });
+            (format!("{error:?}"), error_object)
+        })
+        .collect::<serde_json::Map<_, _>>();
+    let error_refs = errors
+        .keys()
+        .map(|name| json!({ "$ref": format!("#/components/errors/{name}") }))
+        .collect::<Vec<_>>();
+    document["components"]["errors"] = Value::Object(errors);
+    if let Some(methods) = document["methods"].as_array_mut() {
+        for method in methods {
+            if FALLIBLE_METHODS.contains(&method["name"].as_str().unwrap_or_default()) {
+                method["errors"] = json!(error_refs);
+            }
+        }
+    }
+    document
+}
@@ -0,0 +1,151 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use jsonrpsee::tracing;
+use pprof::protos::Message;
+use tower::{Layer, Service};
+
+pub const PROFILE_PATH: &str = "/debug/pprof/profile";
+
+// sampling duration if not given, and the upper bound
+const DEFAULT_PROFILE_SECS: u64 = 10;
+const MAX_PROFILE_SECS: u64 = 60;
+
+// samples per second, an odd rate keeps sampling off the beat of periodic tasks
+const SAMPLING_FREQUENCY: i32 = 99;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+    // gzip-free protobuf understood by `go tool pprof`
+    Protobuf,
+    Flamegraph,
+}
+
+// query like `seconds=30&format=flamegraph`, unknown keys are ignored
+pub fn parse_profile_query(query: Option<&str>) -> Result<(Duration, ProfileFormat), String> {
+    let mut seconds = DEFAULT_PROFILE_SECS;
+    let mut format = ProfileFormat::Protobuf;
+    for (key, value) in query
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+    {
+        match key {
+            "seconds" => {
+                seconds = value
+                    .parse()
+                    .ok()
+                    .filter(|seconds| (1..=MAX_PROFILE_SECS).contains(seconds))
+                    .ok_or_else(|| format!("seconds should be between 1 and {MAX_PROFILE_SECS}"))?
+            }
+            "format" => {
+                format = match value {
+                    "protobuf" => ProfileFormat::Protobuf,
+                    "flamegraph" => ProfileFormat::Flamegraph,
+                    _ => return Err("format should be either protobuf or flamegraph".to_string()),
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((Duration::from_secs(seconds), format))
+}
+
+// sample the whole process, blocking the calling thread for the duration
+fn profile(duration: Duration, format: ProfileFormat) -> Result<Vec<u8>, String> {
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(SAMPLING_FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .map_err(|error| error.to_string())?;
+    std::thread::sleep(duration);
+    let report = guard.report().build().map_err(|error| error.to_string())?;
+    let mut body = Vec::new();
+    match format {
+        ProfileFormat::Protobuf => report
+            .pprof()
+            .map_err(|error| error.to_string())?
+            .encode(&mut body)
+            .map_err(|error| error.to_string())?,
+        ProfileFormat::Flamegraph => report
+            .flamegraph(&mut body)
+            .map_err(|error| error.to_string())?,
+    }
+    Ok(body)
+}
+
+// serve CPU profile on `GET /debug/pprof/profile`, only on the admin listener since sampling slows
+// down the whole process, and only one profile can be taken at a time
+#[derive(Clone, Default)]
+pub struct ProfilingLayer;
+
+impl<S> Layer<S> for ProfilingLayer {
+    type Service = ProfilingService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ProfilingService { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct ProfilingService<S> {
+    inner: S,
+}
+
+impl<S> Service<Request<Body>> for ProfilingService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if request.method() != Method::GET || request.uri().path() != PROFILE_PATH {
+            return Box::pin(self.inner.call(request));
+        }
+        let (duration, format) = match parse_profile_query(request.uri().query()) {
+            Ok(query) => query,
+            Err(error) => {
+                return Box::pin(async move { Ok(text_response(StatusCode::BAD_REQUEST, error)) })
+            }
+        };
+        Box::pin(async move {
+            tracing::info!("profiling for {duration:?} in {format:?}");
+            let profile = tokio::task::spawn_blocking(move || profile(duration, format))
+                .await
+                .unwrap_or_else(|error| Err(error.to_string()));
+            let body = match profile {
+                Ok(body) => body,
+                Err(error) => {
+                    return Ok(text_response(StatusCode::SERVICE_UNAVAILABLE, error));
+                }
+            };
+            let content_type = match format {
+                ProfileFormat::Protobuf => "application/octet-stream",
+                ProfileFormat::Flamegraph => "image/svg+xml",
+            };
+            let mut response = Response::new(Body::from(body));
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+            Ok(response)
+        })
+    }
+}
+
+fn text_response(status: StatusCode, text: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(text));
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use crate::types::{DecodePipeline, ProtocolVersionInfo, Settings};
+
+// protocol versions served by the server along with their decode pipelines, builtin pipelines of
+// `dob/0` and `dob/1` can be overridden, and new versions mapped onto existing pipelines, through
+// `protocol_pipelines` in settings, so that decoding itself is untouched by new versions
+pub struct ProtocolRegistry {
+    versions: Vec<(String, Option<DecodePipeline>)>,
+}
+
+impl ProtocolRegistry {
+    pub fn new(settings: &Settings) -> Self {
+        Self::with_pipelines(&settings.protocol_versions, &settings.protocol_pipelines)
+    }
+
+    pub fn with_pipelines(
+        protocol_versions: &[String],
+        pipelines: &HashMap<String, DecodePipeline>,
+    ) -> Self {
+        let versions = protocol_versions
+            .iter()
+            .map(|version| {
+                let pipeline = pipelines
+                    .get(version)
+                    .copied()
+                    .or_else(|| DecodePipeline::builtin(version));
+                (version.clone(), pipeline)
+            })
+            .collect();
+        Self { versions }
+    }
+
+    pub fn versions(&self) -> Vec<String> {
+        self.versions
+            .iter()
+            .map(|(version, _)| version.clone())
+            .collect()
+    }
+
+    pub fn infos(&self) -> Vec<ProtocolVersionInfo> {
+        self.versions
+            .iter()
+            .map(|(version, pipeline)| ProtocolVersionInfo::with_pipeline(version, *pipeline))
+            .collect()
+    }
+
+    // pipeline of the version equal to content type without its parameters
+    pub fn find(&self, version: &str) -> Option<DecodePipeline> {
+        self.versions
+            .iter()
+            .find(|(expected, _)| expected == version)
+            .map(|(_, pipeline)| Self::pipeline_or_default(*pipeline))
+    }
+
+    // pipeline of the first version which content type starts with, for lenient matching
+    pub fn find_by_prefix(&self, content_type: &str) -> Option<DecodePipeline> {
+        self.versions
+            .iter()
+            .find(|(expected, _)| content_type.starts_with(expected.as_str()))
+            .map(|(_, pipeline)| Self::pipeline_or_default(*pipeline))
+    }
+
+    // versions without pipeline run every decoder declared by their clusters
+    fn pipeline_or_default(pipeline: Option<DecodePipeline>) -> DecodePipeline {
+        pipeline.unwrap_or(DecodePipeline::Compose)
+    }
+}
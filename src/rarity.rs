@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ckb_types::H256;
+use lru::LruCache;
+use serde::Serialize;
+
+use crate::concurrency::AdaptiveConcurrency;
+use crate::decoder::DOBDecoder;
+use crate::server::{batch_decode_dob, DecodeOptions};
+use crate::types::{DecodeFailure, DecodeStage, RenderedTraits, TraitValue};
+
+// max clusters whose rarity report is kept in memory
+const CLUSTER_RARITY_CAPACITY: usize = 64;
+
+// occurrences of a trait value among decoded spores of the cluster
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TraitValueCount {
+    pub value: TraitValue,
+    pub count: u64,
+    pub frequency: f64,
+}
+
+// values of a trait from the most common to the rarest
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TraitDistribution {
+    pub name: String,
+    pub values: Vec<TraitValueCount>,
+}
+
+// score sums up inverse frequencies of trait values of the spore, where rank 1 is the rarest and
+// spores of equal scores share a rank
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SporeRarity {
+    pub spore_id: H256,
+    pub score: f64,
+    pub rank: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ClusterRarityReport {
+    // spores taken into statistics
+    pub spores: usize,
+    // spores failed to decode, or whose output isn't in the conventional structure
+    pub failures: usize,
+    pub traits: Vec<TraitDistribution>,
+    // spores in order of rank
+    pub rarity: Vec<SporeRarity>,
+}
+
+// rarity reports of clusters, which are rebuilt once expired along with serial index of cluster
+pub struct ClusterRarity(Mutex<LruCache<[u8; 32], (Instant, Arc<ClusterRarityReport>)>>);
+
+impl Default for ClusterRarity {
+    fn default() -> Self {
+        Self(Mutex::new(LruCache::new(
+            NonZeroUsize::new(CLUSTER_RARITY_CAPACITY).unwrap(),
+        )))
+    }
+}
+
+impl ClusterRarity {
+    // decode all live spores of the cluster in batch, most of which are served by render cache
+    pub async fn report(
+        &self,
+        decoder: &DOBDecoder,
+        concurrency: &AdaptiveConcurrency,
+        cluster_id: [u8; 32],
+    ) -> Result<Arc<ClusterRarityReport>, DecodeFailure> {
+        let ttl = Duration::from_secs(decoder.setting().cluster_index_ttl_secs);
+        if let Some((built_at, report)) = self.0.lock().unwrap().get(&cluster_id) {
+            if built_at.elapsed() < ttl {
+                return Ok(report.clone());
+            }
+        }
+        let built_at = Instant::now();
+        let index = decoder
+            .cluster_serial_index(cluster_id)
+            .await
+            .map_err(|error| {
+                DecodeFailure::new(error, DecodeStage::FetchSpore).with_cluster_id(cluster_id)
+            })?;
+        let options = DecodeOptions {
+            include_traits: true,
+            ..Default::default()
+        };
+        let results = batch_decode_dob(
+            decoder,
+            index.spore_ids.iter().map(hex::encode).collect(),
+            &options,
+            concurrency,
+        )
+        .await;
+        let mut failures = 0;
+        let spores = index
+            .spore_ids
+            .iter()
+            .zip(results)
+            .filter_map(|(spore_id, (_, result))| {
+                let rendered_traits = result.ok().and_then(|result| result.rendered_traits);
+                if rendered_traits.is_none() {
+                    failures += 1;
+                }
+                Some((*spore_id, rendered_traits?))
+            })
+            .collect::<Vec<_>>();
+        let report = Arc::new(aggregate_rarity(&spores, failures));
+        self.0
+            .lock()
+            .unwrap()
+            .put(cluster_id, (built_at, report.clone()));
+        Ok(report)
+    }
+}
+
+// count trait values over spores, render hints of `prev.*` and SVG images are left out since
+// they don't make up traits, and spores lacking a trait are not counted for it
+pub fn aggregate_rarity(
+    spores: &[([u8; 32], RenderedTraits)],
+    failures: usize,
+) -> ClusterRarityReport {
+    let mut trait_names = Vec::new();
+    let mut counts = HashMap::<(String, String), (TraitValue, u64)>::new();
+    let mut spore_keys = Vec::new();
+    for (_, rendered_traits) in spores {
+        let mut keys = Vec::new();
+        for item in &rendered_traits.0 {
+            if item.name.starts_with("prev") {
+                continue;
+            }
+            for value in &item.traits {
+                if matches!(value, TraitValue::Svg(_)) {
+                    continue;
+                }
+                let key = (item.name.clone(), serde_json::to_string(value).unwrap());
+                if !trait_names.contains(&item.name) {
+                    trait_names.push(item.name.clone());
+                }
+                counts.entry(key.clone()).or_insert((value.clone(), 0)).1 += 1;
+                keys.push(key);
+            }
+        }
+        spore_keys.push(keys);
+    }
+
+    let frequency = |count: u64| count as f64 / spores.len() as f64;
+    let traits = trait_names
+        .into_iter()
+        .map(|name| {
+            let mut values = counts
+                .iter()
+                .filter(|((trait_name, _), _)| *trait_name == name)
+                .map(|(_, (value, count))| TraitValueCount {
+                    value: value.clone(),
+                    count: *count,
+                    frequency: frequency(*count),
+                })
+                .collect::<Vec<_>>();
+            values.sort_by(|a, b| {
+                b.count.cmp(&a.count).then_with(|| {
+                    serde_json::to_string(&a.value)
+                        .unwrap()
+                        .cmp(&serde_json::to_string(&b.value).unwrap())
+                })
+            });
+            TraitDistribution { name, values }
+        })
+        .collect();
+
+    let mut rarity = spores
+        .iter()
+        .zip(spore_keys)
+        .map(|((spore_id, _), keys)| SporeRarity {
+            spore_id: (*spore_id).into(),
+            score: keys.iter().map(|key| 1.0 / frequency(counts[key].1)).sum(),
+            rank: 0,
+        })
+        .collect::<Vec<_>>();
+    rarity.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.spore_id.0.cmp(&b.spore_id.0))
+    });
+    for index in 0..rarity.len() {
+        rarity[index].rank = match index {
+            0 => 1,
+            _ if rarity[index].score == rarity[index - 1].score => rarity[index - 1].rank,
+            _ => index + 1,
+        };
+    }
+    ClusterRarityReport {
+        spores: spores.len(),
+        failures,
+        traits,
+        rarity,
+    }
+}
@@ -0,0 +1,69 @@
+use std::io::Cursor;
+use std::sync::{Arc, OnceLock};
+
+use image::codecs::webp::WebPEncoder;
+use image::ExtendedColorType;
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{fontdb, Options, Tree};
+
+use crate::render::RenderFormat;
+use crate::types::Error;
+
+// system fonts are loaded once, texts in composed SVG can't be drawn without them
+fn font_database() -> Arc<fontdb::Database> {
+    static FONT_DATABASE: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+    FONT_DATABASE
+        .get_or_init(|| {
+            let mut database = fontdb::Database::new();
+            database.load_system_fonts();
+            Arc::new(database)
+        })
+        .clone()
+}
+
+// rasterize SVG for clients which can't render SVG, like chat bots, in its own size or scaled to
+// fit its longest side into the thumbnail size
+pub fn rasterize(svg: &str, format: RenderFormat, size: Option<u32>) -> Result<Vec<u8>, Error> {
+    let options = Options {
+        fontdb: font_database(),
+        ..Default::default()
+    };
+    let tree = Tree::from_str(svg, &options).map_err(|_| Error::RasterizeError)?;
+    let tree_size = tree.size();
+    let scale = size
+        .map(|size| size as f32 / tree_size.width().max(tree_size.height()))
+        .unwrap_or(1.0);
+    let scaled = |length: f32| ((length * scale).ceil() as u32).max(1);
+    let mut pixmap = Pixmap::new(scaled(tree_size.width()), scaled(tree_size.height()))
+        .ok_or(Error::RasterizeError)?;
+    resvg::render(
+        &tree,
+        Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    match format {
+        RenderFormat::Png => pixmap.encode_png().map_err(|_| Error::RasterizeError),
+        RenderFormat::Webp => {
+            // pixels of pixmap are premultiplied by alpha, while encoder expects straight ones
+            let pixels = pixmap
+                .pixels()
+                .iter()
+                .flat_map(|pixel| {
+                    let color = pixel.demultiply();
+                    [color.red(), color.green(), color.blue(), color.alpha()]
+                })
+                .collect::<Vec<_>>();
+            let mut image = Cursor::new(Vec::new());
+            WebPEncoder::new_lossless(&mut image)
+                .encode(
+                    &pixels,
+                    pixmap.width(),
+                    pixmap.height(),
+                    ExtendedColorType::Rgba8,
+                )
+                .map_err(|_| Error::RasterizeError)?;
+            Ok(image.into_inner())
+        }
+        RenderFormat::Svg => Ok(svg.as_bytes().to_vec()),
+    }
+}
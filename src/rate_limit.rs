@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, Ipv6Addr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use hyper::header::{HeaderValue, RETRY_AFTER};
+use hyper::{Body, Request, Response, StatusCode};
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::server::MethodResponse;
+use jsonrpsee::types::ErrorObjectOwned;
+use tower::{Layer, Service};
+
+use crate::forwarded::ClientIp;
+use crate::middleware::RejectionBody;
+use crate::types::{Error, RateLimitSettings};
+
+// clients tracked at most, those beyond share the bucket of unknown clients until some are evicted
+const MAX_TRACKED_CLIENTS: usize = 10000;
+
+// buckets refilled to full are evicted this often, since idle clients are the same as new ones
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+// limiter shared by all `RateLimitLayer`s, replaced on reloading settings
+static RATE_LIMITER: RwLock<Option<Arc<RateLimiter>>> = RwLock::new(None);
+
+tokio::task_local! {
+    // client of the http request passing `RateLimitService`, picked up by `RpcRateLimit` built
+    // for the request or the WebSocket connection upgraded from it
+    pub(crate) static CLIENT_IP: Option<IpAddr>;
+}
+
+// replace the limiter if settings changed, buckets are kept otherwise, none stops limiting
+pub fn set_rate_limit(settings: Option<RateLimitSettings>) {
+    let mut limiter = RATE_LIMITER.write().unwrap();
+    if limiter.as_ref().map(|limiter| &limiter.settings) != settings.as_ref() {
+        *limiter = settings.map(|settings| Arc::new(RateLimiter::new(settings)));
+    }
+}
+
+// tokens are refilled at `requests_per_second` up to `burst`, each request takes one
+#[derive(Debug, Clone, Copy)]
+pub struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(burst: u32, now: Instant) -> Self {
+        Self {
+            tokens: burst as f64,
+            refilled_at: now,
+        }
+    }
+
+    // take a token, or tell how long to wait for the next one
+    pub fn acquire(&mut self, settings: &RateLimitSettings, now: Instant) -> Result<(), Duration> {
+        self.refill(settings, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / settings.requests_per_second,
+            ))
+        }
+    }
+
+    fn refill(&mut self, settings: &RateLimitSettings, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * settings.requests_per_second).min(settings.burst as f64);
+        self.refilled_at = now;
+    }
+
+    fn is_full(&mut self, settings: &RateLimitSettings, now: Instant) -> bool {
+        self.refill(settings, now);
+        self.tokens >= settings.burst as f64
+    }
+}
+
+// token buckets keyed by client IP, so that a single scraper can't starve the VM pool for others,
+// unknown clients, like those over Unix domain socket without `X-Forwarded-For`, share one bucket
+// keyed by none, so that they can't bypass the limit
+pub struct RateLimiter {
+    settings: RateLimitSettings,
+    buckets: Mutex<HashMap<Option<IpAddr>, TokenBucket>>,
+    swept_at: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(settings: RateLimitSettings) -> Self {
+        Self {
+            settings,
+            buckets: Mutex::new(HashMap::new()),
+            swept_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn acquire(&self, client_ip: Option<IpAddr>) -> Result<(), Duration> {
+        self.acquire_at(client_ip, Instant::now())
+    }
+
+    pub fn acquire_at(&self, client_ip: Option<IpAddr>, now: Instant) -> Result<(), Duration> {
+        let client_ip = client_ip.map(client_key);
+        let mut buckets = self.buckets.lock().unwrap();
+        let mut swept_at = self.swept_at.lock().unwrap();
+        if now.saturating_duration_since(*swept_at) >= SWEEP_INTERVAL
+            || buckets.len() >= MAX_TRACKED_CLIENTS
+        {
+            buckets.retain(|_, bucket| !bucket.is_full(&self.settings, now));
+            *swept_at = now;
+        }
+        let client_ip = client_ip.filter(|client_ip| {
+            buckets.len() < MAX_TRACKED_CLIENTS || buckets.contains_key(&Some(*client_ip))
+        });
+        buckets
+            .entry(client_ip)
+            .or_insert_with(|| TokenBucket::new(self.settings.burst, now))
+            .acquire(&self.settings, now)
+    }
+
+    // clients with buckets not refilled yet, including unknown clients
+    pub fn tracked_clients(&self) -> usize {
+        self.buckets.lock().unwrap().len()
+    }
+}
+
+// IPv6 hosts are usually assigned a whole /64, so they are keyed by the prefix, otherwise a single
+// host could rotate addresses to take fresh buckets and fill up the table
+fn client_key(client_ip: IpAddr) -> IpAddr {
+    match client_ip {
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => IpAddr::V4(ip),
+            None => {
+                let [a, b, c, d, ..] = ip.segments();
+                IpAddr::V6(Ipv6Addr::new(a, b, c, d, 0, 0, 0, 0))
+            }
+        },
+        ip => ip,
+    }
+}
+
+// reject requests over the limit with status 429 and error `RateLimitExceeded`, along with
+// `Retry-After` in seconds, all requests are passed through if not configured
+#[derive(Clone)]
+pub struct RateLimitLayer;
+
+impl RateLimitLayer {
+    pub fn new(settings: Option<RateLimitSettings>) -> Self {
+        set_rate_limit(settings);
+        Self
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+}
+
+impl<S, ResBody> Service<Request<Body>> for RateLimitService<S>
+where
+    S: Service<Request<Body>, Response = Response<ResBody>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+    ResBody: RejectionBody + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let client_ip = request
+            .extensions()
+            .get::<ClientIp>()
+            .map(|client_ip| client_ip.0);
+        let limiter = RATE_LIMITER.read().unwrap().clone();
+        if let Some(limiter) = limiter {
+            if let Err(retry_after) = limiter.acquire(client_ip) {
+                return Box::pin(async move { Ok(rate_limited_response(retry_after)) });
+            }
+        }
+        // json-rpc services are built right in the call, where they learn the client
+        Box::pin(CLIENT_IP.sync_scope(client_ip, || self.inner.call(request)))
+    }
+}
+
+// json-rpc middleware taking a token for each call, so that a batch of calls or a WebSocket
+// session costs as many tokens as its calls, while the first call is paid by the http request
+// it comes in, or the upgrade request of WebSocket, calls are rejected with error
+// `RateLimitExceeded` once over the limit
+pub struct RpcRateLimit<S> {
+    inner: S,
+    // none if not built behind `RateLimitService`, like on the admin listener
+    client_ip: Option<Option<IpAddr>>,
+    prepaid: AtomicBool,
+}
+
+impl<S> RpcRateLimit<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            client_ip: CLIENT_IP.try_with(|client_ip| *client_ip).ok(),
+            prepaid: AtomicBool::new(true),
+        }
+    }
+}
+
+impl<'a, S> RpcServiceT<'a> for RpcRateLimit<S>
+where
+    S: RpcServiceT<'a> + Send + Sync,
+    S::Future: 'a,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, request: jsonrpsee::types::Request<'a>) -> Self::Future {
+        let limiter = RATE_LIMITER.read().unwrap().clone();
+        if let (Some(client_ip), Some(limiter)) = (self.client_ip, limiter) {
+            if !self.prepaid.swap(false, Ordering::Relaxed) && limiter.acquire(client_ip).is_err() {
+                let error = ErrorObjectOwned::from(Error::RateLimitExceeded);
+                return Box::pin(async move { MethodResponse::error(request.id, error) });
+            }
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+fn rate_limited_response<ResBody: RejectionBody>(retry_after: Duration) -> Response<ResBody> {
+    let mut response = ResBody::rejection(StatusCode::TOO_MANY_REQUESTS, Error::RateLimitExceeded);
+    response.headers_mut().insert(
+        RETRY_AFTER,
+        HeaderValue::from(retry_after.as_secs_f64().ceil() as u64),
+    );
+    response
+}
@@ -0,0 +1,55 @@
+use ckb_crypto::secp::{Pubkey, Signature};
+use ckb_types::H256;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Error, OnchainDecoderDeployment};
+
+// registry of decoder deployments published by a remote source, the signature is required
+// for registries served by url, made over blake2b hash of the JSON serialized `deployments`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DecoderRegistry {
+    pub deployments: Vec<OnchainDecoderDeployment>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl DecoderRegistry {
+    pub fn signing_message(&self) -> H256 {
+        let deployments = serde_json::to_vec(&self.deployments).unwrap();
+        ckb_hash::blake2b_256(deployments).into()
+    }
+}
+
+// parse registry document, verifying its signature against the compressed public key if set,
+// registries read from on-chain cell are trusted through their type_id instead
+pub fn parse_decoder_registry(
+    document: &[u8],
+    public_key: Option<&str>,
+) -> Result<Vec<OnchainDecoderDeployment>, Error> {
+    let registry: DecoderRegistry =
+        serde_json::from_slice(document).map_err(|_| Error::DecoderRegistryInvalid)?;
+    if let Some(public_key) = public_key {
+        let signature = registry
+            .signature
+            .as_deref()
+            .ok_or(Error::DecoderRegistryInvalid)?;
+        let signature = decode_hex(signature)
+            .and_then(|signature| Signature::from_slice(&signature).ok())
+            .ok_or(Error::DecoderRegistryInvalid)?;
+        let public_key = decode_hex(public_key)
+            .and_then(|public_key| Pubkey::from_slice(&public_key).ok())
+            .ok_or(Error::DecoderRegistryInvalid)?;
+        let signer = signature
+            .recover(&registry.signing_message())
+            .map_err(|_| Error::DecoderRegistryInvalid)?;
+        if signer != public_key {
+            return Err(Error::DecoderRegistryInvalid);
+        }
+    }
+    Ok(registry.deployments)
+}
+
+fn decode_hex(hexed: &str) -> Option<Vec<u8>> {
+    hex::decode(hexed.strip_prefix("0x").unwrap_or(hexed)).ok()
+}
@@ -0,0 +1,218 @@
+#[cfg(not(feature = "shuttle"))]
+use std::fs;
+
+use serde_json::Value;
+
+use crate::decoder::DOBDecoder;
+#[cfg(not(feature = "shuttle"))]
+use crate::server::dob_cache_path;
+use crate::types::Error;
+
+// format of composed image served on render route, PNG and WebP are rasterized from SVG, which
+// requires feature `raster`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderFormat {
+    #[default]
+    Svg,
+    Png,
+    Webp,
+}
+
+impl RenderFormat {
+    pub const ALL: [RenderFormat; 3] = [RenderFormat::Svg, RenderFormat::Png, RenderFormat::Webp];
+
+    pub fn parse(format: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.extension().eq_ignore_ascii_case(format))
+    }
+
+    // raster formats are only supported under feature `raster`
+    pub fn supported(self) -> bool {
+        self == RenderFormat::Svg || cfg!(feature = "raster")
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            RenderFormat::Svg => "svg",
+            RenderFormat::Png => "png",
+            RenderFormat::Webp => "webp",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            RenderFormat::Svg => "image/svg+xml",
+            RenderFormat::Png => "image/png",
+            RenderFormat::Webp => "image/webp",
+        }
+    }
+}
+
+// longest sides of thumbnails in pixels, limited to a few so that cached sizes stay bounded
+pub const THUMBNAIL_SIZES: [u32; 5] = [64, 128, 256, 512, 1024];
+
+// composed image in a format, optionally scaled into thumbnail, SVG is never scaled since it fits
+// any size on client side already
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderTarget {
+    pub format: RenderFormat,
+    pub size: Option<u32>,
+}
+
+impl RenderTarget {
+    pub fn new(format: RenderFormat, size: Option<u32>) -> Self {
+        Self {
+            format,
+            size: size.filter(|_| format != RenderFormat::Svg),
+        }
+    }
+
+    // extension of cache entry, like `png` or `256.png` for thumbnails
+    pub fn cache_extension(self) -> String {
+        match self.size {
+            Some(size) => format!("{size}.{}", self.format.extension()),
+            None => self.format.extension().to_string(),
+        }
+    }
+
+    // every target which may have been cached for a spore
+    pub fn all() -> impl Iterator<Item = RenderTarget> {
+        RenderFormat::ALL.into_iter().flat_map(|format| {
+            std::iter::once(None)
+                .chain(THUMBNAIL_SIZES.map(Some))
+                .filter(move |size| size.is_none() || format != RenderFormat::Svg)
+                .map(move |size| RenderTarget::new(format, size))
+        })
+    }
+}
+
+// canvas of SVG composed from text traits, in pixels
+const CANVAS_SIZE: u32 = 500;
+const LINE_HEIGHT: u32 = 36;
+
+// assemble final SVG of the spore from render output, an image trait carrying SVG markup, like
+// `IMAGE` from DOB/1 decoders, is served as it is, otherwise traits are laid out as text lines
+// over the background hinted by `prev.bgcolor` and `prev.bg` of DOB/0 clusters
+pub fn compose_svg(render_output: &Value) -> String {
+    let mut background_color = "#ffffff".to_string();
+    let mut background_image = None;
+    let mut lines = Vec::new();
+    for item in render_output.as_array().into_iter().flatten() {
+        let trait_name = item["name"].as_str().unwrap_or_default();
+        let values = item["traits"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.as_object()?.values().next());
+        for value in values {
+            match (trait_name.to_lowercase().as_str(), value.as_str()) {
+                ("image", Some(image)) if image.trim_start().starts_with("<svg") => {
+                    return image.to_string();
+                }
+                ("prev.bgcolor", Some(color)) => background_color = color.to_string(),
+                ("prev.bg", Some(image)) if image.starts_with("http") => {
+                    background_image = Some(image.to_string())
+                }
+                _ if trait_name.starts_with("prev") => {}
+                (_, Some(text)) => lines.push(format!("{trait_name}: {text}")),
+                (_, None) => lines.push(format!("{trait_name}: {value}")),
+            }
+        }
+    }
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{CANVAS_SIZE}" height="{CANVAS_SIZE}" viewBox="0 0 {CANVAS_SIZE} {CANVAS_SIZE}">"#
+    );
+    svg.push_str(&format!(
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        escape_xml(&background_color)
+    ));
+    if let Some(image) = background_image {
+        svg.push_str(&format!(
+            r#"<image href="{}" width="100%" height="100%" preserveAspectRatio="xMidYMid slice"/>"#,
+            escape_xml(&image)
+        ));
+    }
+    for (index, line) in lines.iter().enumerate() {
+        svg.push_str(&format!(
+            r#"<text x="24" y="{}" font-family="sans-serif" font-size="24">{}</text>"#,
+            LINE_HEIGHT * (index as u32 + 1) + 12,
+            escape_xml(line)
+        ));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// turn composed SVG into bytes of the requested format
+pub fn render_image(svg: String, target: RenderTarget) -> Result<Vec<u8>, Error> {
+    match target.format {
+        RenderFormat::Svg => Ok(svg.into_bytes()),
+        #[cfg(feature = "raster")]
+        format => crate::raster::rasterize(&svg, format, target.size),
+        #[cfg(not(feature = "raster"))]
+        _ => Err(Error::RenderFormatUnsupported),
+    }
+}
+
+// composed image is cached aside the render cache entry of the spore, like `{spore_id}.svg` or
+// `{spore_id}.256.png`
+#[cfg(not(feature = "shuttle"))]
+pub fn read_image_from_cache(
+    decoder: &DOBDecoder,
+    spore_id: &[u8; 32],
+    target: RenderTarget,
+) -> Option<Vec<u8>> {
+    let cache_path = dob_cache_path(&decoder.setting().dobs_cache_directory, spore_id);
+    fs::read(cache_path.with_extension(target.cache_extension())).ok()
+}
+
+// shuttle version
+#[cfg(feature = "shuttle")]
+pub fn read_image_from_cache(
+    decoder: &DOBDecoder,
+    spore_id: &[u8; 32],
+    target: RenderTarget,
+) -> Option<Vec<u8>> {
+    let cache_path = format!("{}.{}", hex::encode(spore_id), target.cache_extension());
+    decoder.persist.load::<Vec<u8>>(cache_path.as_str()).ok()
+}
+
+// no shuttle version
+#[cfg(not(feature = "shuttle"))]
+pub fn write_image_to_cache(
+    decoder: &DOBDecoder,
+    spore_id: &[u8; 32],
+    target: RenderTarget,
+    image: &[u8],
+) -> Result<(), Error> {
+    let cache_path = dob_cache_path(&decoder.setting().dobs_cache_directory, spore_id);
+    if let Some(shard_directory) = cache_path.parent() {
+        fs::create_dir_all(shard_directory).map_err(|_| Error::DOBRenderCacheNotFound)?;
+    }
+    fs::write(cache_path.with_extension(target.cache_extension()), image)
+        .map_err(|_| Error::DOBRenderCacheNotFound)
+}
+
+// shuttle version
+#[cfg(feature = "shuttle")]
+pub fn write_image_to_cache(
+    decoder: &DOBDecoder,
+    spore_id: &[u8; 32],
+    target: RenderTarget,
+    image: &[u8],
+) -> Result<(), Error> {
+    let cache_path = format!("{}.{}", hex::encode(spore_id), target.cache_extension());
+    decoder
+        .persist
+        .save::<Vec<u8>>(cache_path.as_str(), image.to_vec())
+        .map_err(|_| Error::DOBRenderCacheNotFound)
+}
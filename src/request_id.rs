@@ -0,0 +1,111 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::{Body, Request, Response};
+use jsonrpsee::tracing::{self, Instrument};
+use tower::{Layer, Service};
+
+use crate::forwarded::ClientIp;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// ids given by clients longer than this are replaced, so that logs can't be flooded through them
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+lazy_static::lazy_static! {
+    // ids are unique across restarts as long as the clock doesn't go back
+    static ref REQUEST_ID_PREFIX: String = format!(
+        "{:x}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+    );
+}
+
+static REQUEST_COUNT: AtomicU64 = AtomicU64::new(0);
+
+// id of the request being served by the current task, none outside of `RequestIdLayer`
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+// take `X-Request-Id` from proxies or clients if it's sane, otherwise generate one
+pub fn request_id<B>(request: &Request<B>) -> String {
+    request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LENGTH
+                && id.bytes().all(|byte| byte.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| {
+            let count = REQUEST_COUNT.fetch_add(1, Ordering::Relaxed);
+            format!("{}-{count:x}", *REQUEST_ID_PREFIX)
+        })
+}
+
+// tag each request with an id, which is attached to all logs and spans while serving it, returned
+// in `X-Request-Id` header and in `data` of JSON-RPC errors, so that complaints can be traced
+#[derive(Clone, Default)]
+pub struct RequestIdLayer;
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestIdService { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequestIdService<S> {
+    inner: S,
+}
+
+impl<S> Service<Request<Body>> for RequestIdService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let request_id = request_id(&request);
+        let span = match request.extensions().get::<ClientIp>() {
+            Some(client_ip) => {
+                tracing::info_span!("request", request_id = %request_id, client_ip = %client_ip.0)
+            }
+            None => tracing::info_span!("request", request_id = %request_id),
+        };
+        let response = REQUEST_ID.scope(request_id.clone(), self.inner.call(request));
+        Box::pin(
+            async move {
+                let mut response = response.await?;
+                if let Ok(value) = HeaderValue::from_str(&request_id) {
+                    response.headers_mut().insert(REQUEST_ID_HEADER, value);
+                }
+                Ok(response)
+            }
+            .instrument(span),
+        )
+    }
+}
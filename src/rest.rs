@@ -0,0 +1,510 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use httpdate::HttpDate;
+use hyper::body::HttpBody;
+use hyper::header::{
+    HeaderValue, CACHE_CONTROL, CONTENT_TYPE, IF_MODIFIED_SINCE, LAST_MODIFIED, VARY,
+};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use jsonrpsee::{tracing, types::ErrorObjectOwned};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tower::{Layer, Service};
+
+use crate::assets::Asset;
+use crate::render::{
+    compose_svg, read_image_from_cache, render_image, write_image_to_cache, RenderFormat,
+    RenderTarget, THUMBNAIL_SIZES,
+};
+use crate::server::{
+    batch_decode_dob, decode_dob, dob_cache_modified, BatchDecodeEntry, DecodeOptions,
+    DecoderStandaloneServer, SporeIdParam,
+};
+use crate::types::{DecodeFailure, DecodeStage, Error};
+
+pub const DOB_PATH_PREFIX: &str = "/dob/";
+pub const DOB_BATCH_PATH: &str = "/dob/batch";
+pub const DECODE_PATH_PREFIX: &str = "/decode/";
+pub const METADATA_PATH_PREFIX: &str = "/metadata/";
+pub const RENDER_PATH_PREFIX: &str = "/render/";
+pub const ASSETS_PATH_PREFIX: &str = "/assets/";
+
+// request bodies of plain HTTP routes are no more than an array of spore ids or a query
+const MAX_REQUEST_BODY_BYTES: usize = 1024 * 1024;
+
+// query params of REST routes, same as optional params of `dob_decode`, with `traits` joined
+// by comma, like `/dob/0x...?include_metadata=true&traits=Name,Level&include_traits=true`
+pub fn parse_decode_options(query: Option<&str>) -> DecodeOptions {
+    let mut options = DecodeOptions::default();
+    for (key, value) in query
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+    {
+        match key {
+            "anchor_block_hash" => options.anchor_block_hash = Some(value.to_string()),
+            "include_metadata" => options.include_metadata = value == "true",
+            "include_traits" => options.include_traits = value == "true",
+            "include_owner" => options.include_owner = value == "true",
+            "refresh" => options.refresh = value == "true",
+            "traits" => {
+                options.traits = Some(
+                    value
+                        .split(',')
+                        .filter(|name| !name.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                )
+            }
+            _ => {}
+        }
+    }
+    options
+}
+
+// serve `GET /dob/{spore_id}` and `POST /dob/batch` for web frontends without a json-rpc
+// client, and `GET /decode/{spore_id}` with caching headers for CDNs, sharing decoder and cache
+// with the json-rpc server, as well as `GET /render/{spore_id}.{svg|png|webp}` serving composed image,
+// disabled if server is not set
+#[derive(Clone, Default)]
+pub struct RestApiLayer {
+    server: Option<DecoderStandaloneServer>,
+}
+
+impl RestApiLayer {
+    pub fn new(server: Option<DecoderStandaloneServer>) -> Self {
+        Self { server }
+    }
+}
+
+impl<S> Layer<S> for RestApiLayer {
+    type Service = RestApiService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RestApiService {
+            inner,
+            server: self.server.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RestApiService<S> {
+    inner: S,
+    server: Option<DecoderStandaloneServer>,
+}
+
+impl<S> Service<Request<Body>> for RestApiService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let Some(server) = self.server.clone() else {
+            return Box::pin(self.inner.call(request));
+        };
+        let path = request.uri().path();
+        if request.method() == Method::POST && path == DOB_BATCH_PATH {
+            return Box::pin(async move { Ok(batch_decode(&server, request).await) });
+        }
+        if request.method() == Method::GET {
+            if let Some(hexed_spore_id) = path.strip_prefix(DECODE_PATH_PREFIX) {
+                let hexed_spore_id = hexed_spore_id.to_string();
+                let options = parse_decode_options(request.uri().query());
+                let if_modified_since = request
+                    .headers()
+                    .get(IF_MODIFIED_SINCE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<HttpDate>().ok());
+                return Box::pin(async move {
+                    Ok(
+                        cacheable_decode(&server, hexed_spore_id, &options, if_modified_since)
+                            .await,
+                    )
+                });
+            }
+            if let Some(hexed_spore_id) = path.strip_prefix(METADATA_PATH_PREFIX) {
+                let hexed_spore_id = hexed_spore_id.trim_end_matches(".json").to_string();
+                return Box::pin(async move {
+                    let options = DecodeOptions {
+                        include_metadata: true,
+                        ..Default::default()
+                    };
+                    let result =
+                        decode_dob(server.decoder(), hexed_spore_id.clone(), &options).await;
+                    Ok(match result {
+                        Ok(result) => {
+                            let description = result
+                                .metadata
+                                .as_ref()
+                                .map(|metadata| metadata.cluster_description.as_str());
+                            let metadata =
+                                nft_metadata(&hexed_spore_id, &result.render_output, description);
+                            json_response(StatusCode::OK, &metadata)
+                        }
+                        Err(failure) => failure_response(failure),
+                    })
+                });
+            }
+            if let Some(hexed_spore_id) = path.strip_prefix(RENDER_PATH_PREFIX) {
+                let parsed = parse_render_target(hexed_spore_id, request.uri().query());
+                return Box::pin(async move {
+                    Ok(match parsed {
+                        Ok((hexed_spore_id, target)) => {
+                            render(&server, hexed_spore_id, target).await
+                        }
+                        Err(error) => {
+                            failure_response(DecodeFailure::new(error, DecodeStage::ParseRequest))
+                        }
+                    })
+                });
+            }
+            if let Some(asset_path) = path.strip_prefix(ASSETS_PATH_PREFIX) {
+                // `/assets/{scheme}/{path}` serves the asset of `{scheme}://{path}`
+                let uri = asset_path.replacen('/', "://", 1);
+                return Box::pin(async move { Ok(resolve_asset(&server, &uri).await) });
+            }
+            if let Some(hexed_spore_id) = path.strip_prefix(DOB_PATH_PREFIX) {
+                let hexed_spore_id = hexed_spore_id.to_string();
+                let options = parse_decode_options(request.uri().query());
+                return Box::pin(async move {
+                    let result = decode_dob(server.decoder(), hexed_spore_id, &options).await;
+                    Ok(match result {
+                        Ok(result) => json_response(StatusCode::OK, &result),
+                        Err(failure) => failure_response(failure),
+                    })
+                });
+            }
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+async fn batch_decode(server: &DecoderStandaloneServer, request: Request<Body>) -> Response<Body> {
+    let options = parse_decode_options(request.uri().query());
+    let spore_ids: Vec<SporeIdParam> = match read_body(request.into_body())
+        .await
+        .and_then(|body| serde_json::from_slice(&body).ok())
+    {
+        Some(spore_ids) => spore_ids,
+        None => {
+            return failure_response(DecodeFailure::new(
+                Error::HexedSporeIdParseError,
+                DecodeStage::ParseRequest,
+            ))
+        }
+    };
+    if let Err(failure) = server.check_batch_size(spore_ids.len()) {
+        return failure_response(failure);
+    }
+    let entries = batch_decode_dob(
+        server.decoder(),
+        spore_ids.iter().map(SporeIdParam::to_hex).collect(),
+        &options,
+        server.batch_concurrency(),
+    )
+    .await
+    .into_iter()
+    .zip(spore_ids)
+    .map(|((_, result), spore_id)| BatchDecodeEntry {
+        spore_id,
+        result: result.map_err(ErrorObjectOwned::from),
+    })
+    .collect::<Vec<_>>();
+    json_response(StatusCode::OK, &entries)
+}
+
+// render output is deterministic per spore, so results are cacheable by CDNs for a long time,
+// except provisional ones which may be invalidated by reorg, `Last-Modified` comes from the time
+// result got into render cache, answering 304 if the CDN revalidates with a later time
+async fn cacheable_decode(
+    server: &DecoderStandaloneServer,
+    hexed_spore_id: String,
+    options: &DecodeOptions,
+    if_modified_since: Option<HttpDate>,
+) -> Response<Body> {
+    let spore_id = parse_spore_id(&hexed_spore_id);
+    let (mut response, cacheable) =
+        match decode_dob(server.decoder(), hexed_spore_id, options).await {
+            Ok(result) => (json_response(StatusCode::OK, &result), !result.provisional),
+            Err(failure) => (failure_response(failure), false),
+        };
+    if !cacheable {
+        response
+            .headers_mut()
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        return response;
+    }
+    let max_age = server.decoder().setting().decode_cache_max_age_secs;
+    let headers = response.headers_mut();
+    headers.insert(CACHE_CONTROL, public_cache_control(max_age));
+    // binary encodings are negotiated by `Accept` header as well
+    headers.insert(VARY, HeaderValue::from_static("accept"));
+    // anchored results are decoded aside from render cache
+    let last_modified = spore_id
+        .filter(|_| options.anchor_block_hash.is_none())
+        .and_then(|spore_id| dob_cache_modified(server.decoder(), &spore_id))
+        .map(HttpDate::from);
+    if let Some(last_modified) = last_modified {
+        headers.insert(
+            LAST_MODIFIED,
+            HeaderValue::from_str(&last_modified.to_string()).unwrap(),
+        );
+        if if_modified_since.is_some_and(|since| since >= last_modified) {
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+            *response.body_mut() = Body::empty();
+        }
+    }
+    response
+}
+
+// format of render route comes from `format` in query, or extension of the path, like
+// `/render/0x...png` or `/render/0x...svg?format=webp`, in SVG if neither is given, and raster
+// formats can be scaled into thumbnails by `size`, like `/render/0x...png?size=256`
+pub fn parse_render_target(
+    file_name: &str,
+    query: Option<&str>,
+) -> Result<(String, RenderTarget), Error> {
+    let (hexed_spore_id, extension) = match file_name.rsplit_once('.') {
+        Some((hexed_spore_id, extension)) => (hexed_spore_id, Some(extension)),
+        None => (file_name, None),
+    };
+    let param = |name: &str| {
+        query
+            .unwrap_or_default()
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find_map(|(key, value)| (key == name).then_some(value))
+    };
+    let format = match param("format").or(extension) {
+        Some(format) => RenderFormat::parse(format)
+            .filter(|format| format.supported())
+            .ok_or(Error::RenderFormatUnsupported)?,
+        None => RenderFormat::default(),
+    };
+    let size = match param("size") {
+        Some(size) => Some(
+            size.parse::<u32>()
+                .ok()
+                .filter(|size| THUMBNAIL_SIZES.contains(size))
+                .ok_or(Error::RenderSizeUnsupported)?,
+        ),
+        None => None,
+    };
+    Ok((hexed_spore_id.to_string(), RenderTarget::new(format, size)))
+}
+
+// composed image is cached on disk next to the render cache entry in each requested format and
+// size, except for provisional results
+async fn render(
+    server: &DecoderStandaloneServer,
+    hexed_spore_id: String,
+    target: RenderTarget,
+) -> Response<Body> {
+    let max_age = server.decoder().setting().decode_cache_max_age_secs;
+    let spore_id = parse_spore_id(&hexed_spore_id);
+    if let Some(image) =
+        spore_id.and_then(|spore_id| read_image_from_cache(server.decoder(), &spore_id, target))
+    {
+        return image_response(image, target.format, public_cache_control(max_age));
+    }
+    let options = DecodeOptions::default();
+    let result = match decode_dob(server.decoder(), hexed_spore_id, &options).await {
+        Ok(result) => result,
+        Err(failure) => return failure_response(failure),
+    };
+    let svg = compose_svg(&result.render_output);
+    // rasterization is CPU heavy, keep it away from the async runtime
+    let image = tokio::task::spawn_blocking(move || render_image(svg, target))
+        .await
+        .unwrap_or(Err(Error::RasterizeError));
+    let image = match image {
+        Ok(image) => image,
+        Err(error) => {
+            let failure = DecodeFailure::new(error, DecodeStage::Render);
+            return failure_response(match spore_id {
+                Some(spore_id) => failure.with_spore_id(spore_id),
+                None => failure,
+            });
+        }
+    };
+    if result.provisional {
+        return image_response(image, target.format, HeaderValue::from_static("no-store"));
+    }
+    if let Some(spore_id) = spore_id {
+        if let Err(error) = write_image_to_cache(server.decoder(), &spore_id, target, &image) {
+            tracing::warn!(
+                "failed to cache composed {} of 0x{}: {error}",
+                target.cache_extension(),
+                hex::encode(spore_id)
+            );
+        }
+    }
+    image_response(image, target.format, public_cache_control(max_age))
+}
+
+// assets are immutable under their uri, so are cacheable as long as render results
+async fn resolve_asset(server: &DecoderStandaloneServer, uri: &str) -> Response<Body> {
+    let asset = match server.decoder().assets() {
+        Some(assets) => assets.resolve(uri).await,
+        None => Err(Error::AssetUriInvalid),
+    };
+    match asset {
+        Ok(asset) => asset_response(
+            asset,
+            public_cache_control(server.decoder().setting().decode_cache_max_age_secs),
+        ),
+        Err(Error::AssetUriInvalid) => failure_response(DecodeFailure::new(
+            Error::AssetUriInvalid,
+            DecodeStage::ParseRequest,
+        )),
+        Err(error) => failure_response(DecodeFailure::new(error, DecodeStage::FetchAsset)),
+    }
+}
+
+fn asset_response(asset: Asset, cache_control: HeaderValue) -> Response<Body> {
+    let content_type = HeaderValue::from_str(&asset.content_type)
+        .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+    let mut response = Response::new(Body::from(asset.content));
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, content_type);
+    headers.insert(CACHE_CONTROL, cache_control);
+    response
+}
+
+fn image_response(
+    image: Vec<u8>,
+    format: RenderFormat,
+    cache_control: HeaderValue,
+) -> Response<Body> {
+    let mut response = Response::new(Body::from(image));
+    let headers = response.headers_mut();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    headers.insert(CACHE_CONTROL, cache_control);
+    response
+}
+
+fn public_cache_control(max_age: u64) -> HeaderValue {
+    HeaderValue::from_str(&format!("public, max-age={max_age}")).unwrap()
+}
+
+fn parse_spore_id(hexed_spore_id: &str) -> Option<[u8; 32]> {
+    hex::decode(hexed_spore_id.strip_prefix("0x").unwrap_or(hexed_spore_id))
+        .ok()
+        .and_then(|spore_id| <[u8; 32]>::try_from(spore_id).ok())
+}
+
+// transform render output into token-URI metadata understood by NFT marketplaces, traits named
+// `name` or `image` fill the same fields, and render hints of `prev.*` are left out of attributes
+// except `prev.bg` which serves as image if no image trait exists
+pub fn nft_metadata(
+    hexed_spore_id: &str,
+    render_output: &Value,
+    description: Option<&str>,
+) -> Value {
+    let hexed_spore_id = hexed_spore_id.strip_prefix("0x").unwrap_or(hexed_spore_id);
+    let mut name = None;
+    let mut image = None;
+    let mut preview_image = None;
+    let mut attributes = Vec::new();
+    for item in render_output.as_array().into_iter().flatten() {
+        let trait_name = item["name"].as_str().unwrap_or_default();
+        let values = item["traits"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.as_object()?.values().next());
+        for value in values {
+            match trait_name.to_lowercase().as_str() {
+                "name" if value.is_string() => name = Some(value.clone()),
+                "image" if value.is_string() => image = value.as_str().map(image_uri),
+                "prev.bg" => preview_image = value.as_str().map(image_uri),
+                _ if trait_name.starts_with("prev") => {}
+                _ if value.is_number() => attributes.push(json!({
+                    "trait_type": trait_name,
+                    "display_type": "number",
+                    "value": value,
+                })),
+                _ => attributes.push(json!({ "trait_type": trait_name, "value": value })),
+            }
+        }
+    }
+    // spores without name trait are named by the leading part of spore id
+    let name = name.unwrap_or_else(|| {
+        json!(format!(
+            "DOB #{}",
+            hexed_spore_id.get(..8).unwrap_or(hexed_spore_id)
+        ))
+    });
+    let mut metadata = json!({
+        "name": name,
+        "description": description.unwrap_or_default(),
+        "attributes": attributes,
+    });
+    if let Some(image) = image.or(preview_image) {
+        metadata["image"] = json!(image);
+    }
+    metadata
+}
+
+// inline SVG markups are turned into data URIs, while links are kept as they are
+fn image_uri(image: &str) -> String {
+    if !image.trim_start().starts_with("<svg") {
+        return image.to_string();
+    }
+    let encoded = image
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect::<String>();
+    format!("data:image/svg+xml;charset=utf-8,{encoded}")
+}
+
+// collect request body, giving up once it outgrows the limit
+pub(crate) async fn read_body(mut body: Body) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        bytes.extend_from_slice(&chunk.ok()?);
+        if bytes.len() > MAX_REQUEST_BODY_BYTES {
+            return None;
+        }
+    }
+    Some(bytes)
+}
+
+// error object in the same shape as `error` of json-rpc responses
+fn failure_response(failure: DecodeFailure) -> Response<Body> {
+    let status = match (failure.stage, failure.error) {
+        (DecodeStage::ParseRequest, _) => StatusCode::BAD_REQUEST,
+        (_, Error::SporeIdNotFound | Error::AssetNotFound) => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    json_response(status, &ErrorObjectOwned::from(failure))
+}
+
+fn json_response<T: Serialize>(status: StatusCode, value: &T) -> Response<Body> {
+    let mut response = Response::new(Body::from(serde_json::to_vec(value).unwrap()));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
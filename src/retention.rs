@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+#[cfg(not(feature = "shuttle"))]
+use std::{fs, path::Path};
+use std::{path::PathBuf, sync::Mutex, time::Duration, time::Instant, time::SystemTime};
+
+#[cfg(not(feature = "shuttle"))]
+use jsonrpsee::tracing;
+use serde::Serialize;
+
+use crate::decoder::DOBDecoder;
+#[cfg(not(feature = "shuttle"))]
+use crate::invalidation::remove_image_caches;
+#[cfg(not(feature = "shuttle"))]
+use crate::server::read_dob_from_cache;
+
+// scores below this are forgotten, which equals to a single request after about 7 half-lives
+const DORMANT_SCORE: f64 = 0.01;
+
+// request frequency of clusters, where each request adds 1 to the score which then decays by
+// half every half-life, so that a long dormant cluster scores near 0 regardless of its history
+pub struct ClusterActivity {
+    half_life: Duration,
+    scores: Mutex<HashMap<[u8; 32], (f64, Instant)>>,
+}
+
+impl ClusterActivity {
+    pub fn new(half_life_secs: u64) -> Self {
+        Self {
+            half_life: Duration::from_secs(half_life_secs.max(1)),
+            scores: Mutex::default(),
+        }
+    }
+
+    pub fn record(&self, cluster_id: &[u8; 32]) {
+        let now = Instant::now();
+        let mut scores = self.scores.lock().unwrap();
+        let (score, updated_at) = scores.entry(*cluster_id).or_insert((0.0, now));
+        *score = self.decay(*score, now.duration_since(*updated_at)) + 1.0;
+        *updated_at = now;
+    }
+
+    pub fn score(&self, cluster_id: &[u8; 32]) -> f64 {
+        let scores = self.scores.lock().unwrap();
+        scores
+            .get(cluster_id)
+            .map(|(score, updated_at)| self.decay(*score, updated_at.elapsed()))
+            .unwrap_or_default()
+    }
+
+    // forget clusters which have become dormant, keeping the tracker small
+    pub fn prune(&self) {
+        let mut scores = self.scores.lock().unwrap();
+        scores.retain(|_, (score, updated_at)| {
+            self.decay(*score, updated_at.elapsed()) >= DORMANT_SCORE
+        });
+    }
+
+    fn decay(&self, score: f64, elapsed: Duration) -> f64 {
+        score * 0.5f64.powf(elapsed.as_secs_f64() / self.half_life.as_secs_f64())
+    }
+}
+
+// render cache entry considered in retention sweep
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+    pub cluster_id: Option<[u8; 32]>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SweepReport {
+    pub removed: usize,
+    pub freed_bytes: u64,
+    pub remaining_bytes: u64,
+}
+
+// pick entries to evict until total size fits in the cap, entries of dormant clusters go first
+// and then older ones, while entries of clusters scored at or above `pinned_score` are never
+// picked, so the cap may be exceeded if pinned entries alone outgrow it
+pub(crate) fn select_evictions(
+    mut entries: Vec<CacheEntry>,
+    max_bytes: u64,
+    score: impl Fn(&[u8; 32]) -> f64,
+    pinned_score: f64,
+) -> Vec<CacheEntry> {
+    let mut total_bytes: u64 = entries.iter().map(|entry| entry.size).sum();
+    if total_bytes <= max_bytes {
+        return Vec::new();
+    }
+    let mut candidates = entries
+        .drain(..)
+        .map(|entry| {
+            let score = entry.cluster_id.as_ref().map(&score).unwrap_or_default();
+            (score, entry)
+        })
+        .filter(|(score, _)| *score < pinned_score)
+        .collect::<Vec<_>>();
+    candidates.sort_by(|(score_a, entry_a), (score_b, entry_b)| {
+        score_a
+            .total_cmp(score_b)
+            .then(entry_a.modified.cmp(&entry_b.modified))
+    });
+    let mut evictions = Vec::new();
+    for (_, entry) in candidates {
+        if total_bytes <= max_bytes {
+            break;
+        }
+        total_bytes -= entry.size;
+        evictions.push(entry);
+    }
+    evictions
+}
+
+// evict render cache entries over the configured disk cap
+#[cfg(not(feature = "shuttle"))]
+pub fn sweep_dob_cache(decoder: &DOBDecoder) -> SweepReport {
+    fn walk(directory: &Path, entries: &mut Vec<CacheEntry>) {
+        let Ok(directory_entries) = fs::read_dir(directory) else {
+            return;
+        };
+        for directory_entry in directory_entries.flatten() {
+            let path = directory_entry.path();
+            if path.is_dir() {
+                walk(&path, entries);
+                continue;
+            }
+            if !path.extension().is_some_and(|extension| extension == "dob") {
+                continue;
+            }
+            let Ok(metadata) = directory_entry.metadata() else {
+                continue;
+            };
+            let cluster_id = read_dob_from_cache(path.clone())
+                .ok()
+                .and_then(|(_, _, cache_info)| cache_info.cluster_id)
+                .map(|cluster_id| cluster_id.0);
+            entries.push(CacheEntry {
+                path,
+                size: metadata.len(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                cluster_id,
+            });
+        }
+    }
+
+    let retention = &decoder.setting().dobs_cache_retention;
+    let activity = decoder.cluster_activity();
+    activity.prune();
+    let mut entries = Vec::new();
+    walk(&decoder.setting().dobs_cache_directory, &mut entries);
+    let total_bytes: u64 = entries.iter().map(|entry| entry.size).sum();
+    let evictions = select_evictions(
+        entries,
+        retention.max_bytes,
+        |cluster_id| activity.score(cluster_id),
+        retention.pinned_score,
+    );
+    let mut report = SweepReport {
+        remaining_bytes: total_bytes,
+        ..Default::default()
+    };
+    for entry in evictions {
+        // composed images go along with their render cache entry
+        remove_image_caches(&entry.path);
+        match fs::remove_file(&entry.path) {
+            Ok(()) => {
+                report.removed += 1;
+                report.freed_bytes += entry.size;
+                report.remaining_bytes -= entry.size;
+            }
+            Err(error) => tracing::warn!("failed to evict cache {:?}: {error}", entry.path),
+        }
+    }
+    report
+}
+
+// shuttle persist doesn't expose sizes of its entries, so nothing is swept
+#[cfg(feature = "shuttle")]
+pub fn sweep_dob_cache(decoder: &DOBDecoder) -> SweepReport {
+    decoder.cluster_activity().prune();
+    SweepReport::default()
+}
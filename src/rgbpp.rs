@@ -0,0 +1,45 @@
+use ckb_types::{
+    core::ScriptHashType,
+    h256,
+    packed::Script,
+    prelude::{Builder, Pack},
+    H256,
+};
+
+use crate::types::{Error, Network};
+
+// code hashes of RGB++ lock, which binds CKB cells to a bitcoin UTXO
+const RGBPP_LOCK_CODE_HASH_MAINNET: H256 =
+    h256!("0xbc6c568a1a0d0a09f6844dc9d74ddb4343c32143ff25f727c59edf4fb72d6936");
+const RGBPP_LOCK_CODE_HASH_TESTNET: H256 =
+    h256!("0x61ca7a4796a4eb19ca4f0d065cb9b10ddcf002f10f7cbb810c706cb6bb5c3248");
+
+// parse bitcoin outpoint in the form of `{txid}:{vout}`, where txid is in the usual display order
+pub fn parse_btc_outpoint(outpoint: &str) -> Result<([u8; 32], u32), Error> {
+    let (txid, vout) = outpoint
+        .split_once(':')
+        .ok_or(Error::BtcOutpointParseError)?;
+    let txid = txid.strip_prefix("0x").unwrap_or(txid);
+    let txid: [u8; 32] = hex::decode(txid)
+        .ok()
+        .and_then(|txid| txid.try_into().ok())
+        .ok_or(Error::BtcOutpointParseError)?;
+    let vout = vout.parse().map_err(|_| Error::BtcOutpointParseError)?;
+    Ok((txid, vout))
+}
+
+// RGB++ lock of cells bound to the UTXO, whose args are vout in little endian followed by txid in
+// the internal byte order, which is the reverse of the display order
+pub fn rgbpp_lock_script(network: Network, txid: [u8; 32], vout: u32) -> Script {
+    let code_hash = match network {
+        Network::Mainnet => RGBPP_LOCK_CODE_HASH_MAINNET,
+        Network::Testnet => RGBPP_LOCK_CODE_HASH_TESTNET,
+    };
+    let mut args = vout.to_le_bytes().to_vec();
+    args.extend(txid.iter().rev());
+    Script::new_builder()
+        .code_hash(code_hash.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(args.pack())
+        .build()
+}
@@ -1,42 +1,552 @@
 #[cfg(not(feature = "shuttle"))]
-use std::{fs, path::PathBuf};
+use std::fs;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant, SystemTime},
+};
 
-use jsonrpsee::core::async_trait;
-use jsonrpsee::{proc_macros::rpc, tracing, types::ErrorCode};
+use ckb_types::{packed, H256};
+use futures::stream::{FuturesUnordered, StreamExt};
+use jsonrpsee::core::{async_trait, StringError, SubscriptionResult};
+use jsonrpsee::{
+    proc_macros::rpc, tracing, types::ErrorObjectOwned, PendingSubscriptionSink,
+    SubscriptionMessage,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
-use crate::decoder::DOBDecoder;
-use crate::types::Error;
+use crate::access;
+use crate::address::{encode_address, parse_address};
+use crate::canary::{CanaryMonitor, CanaryStatus};
+use crate::concurrency::AdaptiveConcurrency;
+use crate::config::SettingsSource;
+use crate::crawler::{ClusterCrawler, CrawlReport};
+use crate::decoder::{extract_dna, DOBDecoder, DecodeContext, DecodeContexts};
+use crate::error_reporting;
+use crate::info::{ServerInfo, ServerInfoCollector};
+#[cfg(feature = "cache_invalidation")]
+use crate::invalidation::InvalidationBus;
+use crate::invalidation::{apply_invalidation_blocking, InvalidationEvent};
+use crate::memory;
+use crate::metrics;
+use crate::openrpc;
+use crate::rarity::{ClusterRarity, ClusterRarityReport};
+use crate::rgbpp::{parse_btc_outpoint, rgbpp_lock_script};
+use crate::slow_decode;
+use crate::stats::{ClusterStatsEntry, ClusterStatsReport};
+use crate::types::{
+    ClusterDecoderOverride, ClusterDescriptionField, DOBDecoderFormat, DecodeFailure,
+    DecodePipeline, DecodeStage, DecoderStatus, Error, MemoryUsage, OwnedSporesCursor,
+    ProtocolVersionInfo, RawDecoderParam, RenderedTraits, Settings, SporeCellContent, SporeCheck,
+    TraitFilter, TraitSearchPage, UpstreamSettings,
+};
+use crate::validation::{validate_metadata, MetadataValidation};
 #[cfg(feature = "shuttle")]
 use shuttle_persist::PersistInstance;
 
 // decoding result contains rendered result from native decoder and DNA string for optional use
 #[derive(Serialize, Clone, Debug, PartialEq, Eq, Deserialize)]
 pub struct ServerDecodeResult {
+    pub(crate) render_output: Value,
+    pub(crate) dob_content: Value,
+    // spore is under required confirmations, so the result may be invalidated by reorg
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) provisional: bool,
+    // ingredients of decoding, only present if requested with `include_metadata`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) metadata: Option<DecodeMetadata>,
+    // typed traits parsed from render output, only present if requested with `include_traits`
+    // and the output is in the conventional structure, never read back from upstream servers
+    #[serde(default)]
+    #[serde(skip_deserializing)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) rendered_traits: Option<RenderedTraits>,
+    // lock script of the spore cell, only present if requested with `include_owner`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) owner: Option<SporeOwner>,
+}
+
+// current owner of the spore, address is rendered only for locks of well-known wallets, which
+// are secp256k1, omnilock and JoyID
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct SporeOwner {
+    pub lock_script: ckb_jsonrpc_types::Script,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+}
+
+// DNA and cluster information used in decoding, saving downstream tools another cluster fetch
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct DecodeMetadata {
+    pub dna: String,
+    pub cluster_id: H256,
+    pub cluster_description: String,
+    pub pattern: Value,
+}
+
+// spore id in requests, either a hex string or an array of 32 bytes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum SporeIdParam {
+    Hex(String),
+    Bytes(Vec<u8>),
+}
+
+impl SporeIdParam {
+    // byte array is converted into hex, so that both forms go through the same validation
+    pub fn to_hex(&self) -> String {
+        match self {
+            SporeIdParam::Hex(hexed_spore_id) => hexed_spore_id.clone(),
+            SporeIdParam::Bytes(spore_id) => hex::encode(spore_id),
+        }
+    }
+}
+
+// entry of batch decoding results, echoing back the spore id exactly as requested
+#[derive(Serialize)]
+pub(crate) struct BatchDecodeEntry {
+    pub(crate) spore_id: SporeIdParam,
+    #[serde(flatten)]
+    pub(crate) result: Result<ServerDecodeResult, ErrorObjectOwned>,
+}
+
+// result pushed in streaming batch decoding, along with its index in request
+#[derive(Serialize)]
+struct StreamedDecodeEntry {
+    index: usize,
+    #[serde(flatten)]
+    entry: BatchDecodeEntry,
+}
+
+// last message of streaming batch decoding
+#[derive(Serialize)]
+struct BatchDecodeCompletion {
+    completed: bool,
+    total: usize,
+    failed: usize,
+}
+
+// owner of spores, either a CKB address or a lock script in JSON
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum OwnerParam {
+    Address(String),
+    LockScript(ckb_jsonrpc_types::Script),
+}
+
+impl OwnerParam {
+    pub fn lock_script(&self) -> Result<packed::Script, Error> {
+        match self {
+            OwnerParam::Address(address) => parse_address(address),
+            OwnerParam::LockScript(lock_script) => Ok(lock_script.clone().into()),
+        }
+    }
+}
+
+// decoding result of the spore resolved by its serial in cluster or its outpoint
+#[derive(Serialize)]
+struct SerialDecodeResult {
+    spore_id: H256,
+    #[serde(flatten)]
+    result: ServerDecodeResult,
+}
+
+// decoder output of raw decoding or dry run, in the same form as that of spores
+#[derive(Serialize)]
+struct RawDnaDecodeResult {
     render_output: Value,
-    dob_content: Value,
 }
 
+// entry of batch DNA decoding results, echoing back the DNA as requested
+#[derive(Serialize)]
+struct DnaDecodeEntry {
+    dna: String,
+    #[serde(flatten)]
+    result: Result<RawDnaDecodeResult, ErrorObjectOwned>,
+}
+
+// one page of decoded spores owned by the address or in the cluster, `next_cursor` is absent on
+// the last page
+#[derive(Serialize)]
+struct OwnedDobsPage {
+    spores: Vec<BatchDecodeEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+// cluster description along with its live cell and decoders, the first of which decodes traits
+// and the others are following DOB/1 stages, operator's override of the decoder if any
+#[derive(Serialize)]
+pub struct ClusterInfo {
+    pub cluster_id: H256,
+    pub out_point: ckb_jsonrpc_types::OutPoint,
+    pub description: ClusterDescriptionField,
+    pub decoders: Vec<DecoderStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoder_override: Option<ClusterDecoderOverride>,
+}
+
+// whether render result of the spore is cached, along with its recorded cache info
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct DOBCacheStatus {
+    pub cached: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_info: Option<DOBCacheInfo>,
+}
+
+// optional behaviors applied on decoding requests
+#[derive(Clone, Debug, Default)]
+pub struct DecodeOptions {
+    // hex string of block hash which all cell lookups will be performed against
+    pub anchor_block_hash: Option<String>,
+    // attach DNA, cluster description and pattern to the result
+    pub include_metadata: bool,
+    // names of traits kept in render output, all traits are kept if not specified
+    pub traits: Option<Vec<String>>,
+    // attach typed traits parsed from render output to the result
+    pub include_traits: bool,
+    // attach lock script of the spore cell, along with its address if recognizable
+    pub include_owner: bool,
+    // skip reading render cache and decode afresh, then overwrite the cache entry
+    pub refresh: bool,
+}
+
+// extra information recorded in the third line of a cache entry, missing in legacy entries
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DOBCacheInfo {
+    #[serde(default)]
+    pub cluster_id: Option<H256>,
+    #[serde(default)]
+    pub cluster_data_hash: Option<H256>,
+}
+
+// spores listed in one page of `dob_decode_by_owner` and `dob_decode_cluster` if not specified,
+// and the upper bound
+const DEFAULT_OWNED_SPORES_LIMIT: u32 = 20;
+const MAX_OWNED_SPORES_LIMIT: u32 = 100;
+
+// spores listed in one page of `dob_search_traits` if not specified, and the upper bound
+const DEFAULT_TRAIT_SEARCH_LIMIT: u32 = 100;
+const MAX_TRAIT_SEARCH_LIMIT: u32 = 1000;
+
 #[rpc(server)]
 trait DecoderRpc {
     #[method(name = "dob_protocol_version")]
     async fn protocol_versions(&self) -> Vec<String>;
 
+    #[method(name = "dob_protocol_versions_v2")]
+    async fn protocol_version_infos(&self) -> Vec<ProtocolVersionInfo>;
+
     #[method(name = "dob_decode")]
-    async fn decode(&self, hexed_spore_id: String) -> Result<Value, ErrorCode>;
+    async fn decode(
+        &self,
+        hexed_spore_id: SporeIdParam,
+        anchor_block_hash: Option<String>,
+        include_metadata: Option<bool>,
+        traits: Option<Vec<String>>,
+        include_traits: Option<bool>,
+    ) -> Result<Value, ErrorObjectOwned>;
+
+    #[method(name = "dob_decode_by_serial")]
+    async fn decode_by_serial(
+        &self,
+        cluster_id: String,
+        serial: u64,
+        anchor_block_hash: Option<String>,
+        include_metadata: Option<bool>,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned>;
 
     #[method(name = "dob_batch_decode")]
-    async fn batch_decode(&self, hexed_spore_ids: Vec<String>) -> Result<Vec<Value>, ErrorCode>;
+    async fn batch_decode(
+        &self,
+        hexed_spore_ids: Vec<SporeIdParam>,
+        anchor_block_hash: Option<String>,
+        network: Option<String>,
+    ) -> Result<Vec<Value>, ErrorObjectOwned>;
+
+    #[subscription(
+        name = "dob_subscribe_batch_decode" => "dob_batch_decode_result",
+        unsubscribe = "dob_unsubscribe_batch_decode",
+        item = Value
+    )]
+    async fn subscribe_batch_decode(
+        &self,
+        hexed_spore_ids: Vec<SporeIdParam>,
+        anchor_block_hash: Option<String>,
+        network: Option<String>,
+    ) -> SubscriptionResult;
+
+    #[method(name = "dob_decode_by_owner")]
+    async fn decode_by_owner(
+        &self,
+        address_or_lock_script: OwnerParam,
+        cursor: Option<String>,
+        limit: Option<u32>,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned>;
+
+    #[method(name = "dob_decode_by_outpoint")]
+    async fn decode_by_outpoint(
+        &self,
+        tx_hash: String,
+        index: u32,
+        include_metadata: Option<bool>,
+        include_owner: Option<bool>,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned>;
+
+    #[method(name = "dob_decode_verbose")]
+    async fn decode_verbose(
+        &self,
+        hexed_spore_id: SporeIdParam,
+        network: Option<String>,
+    ) -> Result<VerboseDecodeResult, ErrorObjectOwned>;
+
+    #[method(name = "dob_decode_by_btc_outpoint")]
+    async fn decode_by_btc_outpoint(
+        &self,
+        btc_outpoint: String,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned>;
+
+    #[method(name = "dob_decode_cluster")]
+    async fn decode_cluster(
+        &self,
+        cluster_id: String,
+        cursor: Option<String>,
+        limit: Option<u32>,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned>;
+
+    #[method(name = "dob_decode_raw")]
+    async fn decode_raw(
+        &self,
+        dna: String,
+        pattern: Value,
+        decoder: RawDecoderParam,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned>;
+
+    #[method(name = "dob_decode_dry_run")]
+    async fn decode_dry_run(
+        &self,
+        cluster_id: String,
+        dna: String,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned>;
+
+    #[method(name = "dob_batch_decode_dna")]
+    async fn batch_decode_dna(
+        &self,
+        cluster_id: String,
+        dnas: Vec<String>,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned>;
+
+    #[method(name = "dob_validate_metadata")]
+    async fn validate_metadata(
+        &self,
+        description: Value,
+        sample_dna: Option<String>,
+        network: Option<String>,
+    ) -> Result<MetadataValidation, ErrorObjectOwned>;
+
+    #[method(name = "dob_cache_status")]
+    async fn cache_status(
+        &self,
+        hexed_spore_id: SporeIdParam,
+        network: Option<String>,
+    ) -> Result<DOBCacheStatus, ErrorObjectOwned>;
+
+    #[method(name = "dob_check")]
+    async fn check(
+        &self,
+        hexed_spore_id: SporeIdParam,
+        network: Option<String>,
+    ) -> Result<SporeCheck, ErrorObjectOwned>;
+
+    #[method(name = "dob_canary_status")]
+    async fn canary_status(&self) -> Option<CanaryStatus>;
+
+    #[method(name = "dob_cluster_info")]
+    async fn cluster_info(
+        &self,
+        cluster_id: String,
+        network: Option<String>,
+    ) -> Result<ClusterInfo, ErrorObjectOwned>;
+
+    #[method(name = "dob_cluster_stats")]
+    async fn cluster_stats(
+        &self,
+        cluster_id: String,
+        network: Option<String>,
+    ) -> Result<Option<ClusterStatsReport>, ErrorObjectOwned>;
+
+    #[method(name = "dob_stats")]
+    async fn stats(&self) -> Vec<ClusterStatsEntry>;
+
+    #[method(name = "dob_cluster_rarity")]
+    async fn cluster_rarity(
+        &self,
+        cluster_id: String,
+        network: Option<String>,
+    ) -> Result<ClusterRarityReport, ErrorObjectOwned>;
+
+    #[method(name = "dob_crawl_status")]
+    async fn crawl_status(
+        &self,
+        cluster_id: String,
+    ) -> Result<Option<CrawlReport>, ErrorObjectOwned>;
+
+    #[method(name = "dob_search_traits")]
+    async fn search_traits(
+        &self,
+        cluster_id: String,
+        filters: Vec<TraitFilter>,
+        cursor: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<TraitSearchPage, ErrorObjectOwned>;
+
+    #[method(name = "dob_memory_usage")]
+    async fn memory_usage(&self) -> MemoryUsage;
+
+    #[method(name = "dob_server_info")]
+    async fn server_info(&self) -> ServerInfo;
+
+    #[method(name = "rpc.discover")]
+    async fn discover(&self) -> Value;
 }
 
+#[derive(Clone)]
 pub struct DecoderStandaloneServer {
-    decoder: DOBDecoder,
+    decoder: Arc<DOBDecoder>,
+    // decoders of other networks in `networks` settings, by their names
+    networks: Arc<HashMap<String, Arc<DOBDecoder>>>,
+    batch_concurrency: Arc<AdaptiveConcurrency>,
+    #[cfg(feature = "cache_invalidation")]
+    invalidation_bus: Option<Arc<InvalidationBus>>,
+    canary: Option<Arc<CanaryMonitor>>,
+    rarity: Arc<ClusterRarity>,
+    crawler: Arc<ClusterCrawler>,
+    settings_source: Option<SettingsSource>,
+    info: Arc<ServerInfoCollector>,
 }
 
 impl DecoderStandaloneServer {
-    pub fn new(decoder: DOBDecoder) -> Self {
-        Self { decoder }
+    pub fn new(decoder: Arc<DOBDecoder>) -> Self {
+        let batch_concurrency = Arc::new(AdaptiveConcurrency::new(
+            &decoder.setting().batch_concurrency,
+        ));
+        Self {
+            crawler: Arc::new(ClusterCrawler::new(decoder.setting())),
+            decoder,
+            networks: Arc::default(),
+            batch_concurrency,
+            #[cfg(feature = "cache_invalidation")]
+            invalidation_bus: None,
+            canary: None,
+            rarity: Arc::default(),
+            settings_source: None,
+            info: Arc::default(),
+        }
+    }
+
+    pub fn decoder(&self) -> &DOBDecoder {
+        &self.decoder
+    }
+
+    // decoder of the named network, or the primary one if not specified
+    pub fn network_decoder(&self, network: Option<&str>) -> Result<&DOBDecoder, DecodeFailure> {
+        match network {
+            Some(name) => self.networks.get(name).map(AsRef::as_ref).ok_or_else(|| {
+                DecodeFailure::new(Error::NetworkNotFound, DecodeStage::ParseRequest)
+            }),
+            None => Ok(&self.decoder),
+        }
+    }
+
+    pub fn networks(&self) -> &HashMap<String, Arc<DOBDecoder>> {
+        &self.networks
+    }
+
+    pub fn with_networks(mut self, networks: HashMap<String, Arc<DOBDecoder>>) -> Self {
+        self.networks = Arc::new(networks);
+        self
+    }
+
+    pub fn batch_concurrency(&self) -> &AdaptiveConcurrency {
+        &self.batch_concurrency
+    }
+
+    // spore ids or DNAs in one batch are capped by `request_limits.max_batch_size`
+    pub fn check_batch_size(&self, batch_size: usize) -> Result<(), DecodeFailure> {
+        if batch_size > self.decoder.setting().request_limits.max_batch_size {
+            return Err(DecodeFailure::new(
+                Error::BatchSizeExceeded,
+                DecodeStage::ParseRequest,
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn settings_source(&self) -> Option<&SettingsSource> {
+        self.settings_source.as_ref()
+    }
+
+    // settings read again by `admin_reload_settings`
+    pub fn with_settings_source(mut self, settings_source: SettingsSource) -> Self {
+        self.settings_source = Some(settings_source);
+        self
+    }
+
+    pub fn with_canary(mut self, canary: Arc<CanaryMonitor>) -> Self {
+        self.canary = Some(canary);
+        self
+    }
+
+    // share the crawler running configured clusters, so that triggered crawls don't overlap
+    pub fn with_crawler(mut self, crawler: Arc<ClusterCrawler>) -> Self {
+        self.crawler = crawler;
+        self
+    }
+
+    // decode all live spores of the cluster into render cache in background, returns progress
+    // of the crawl which is already running if any
+    pub fn crawl_in_background(&self, cluster_id: [u8; 32]) -> CrawlReport {
+        match self.crawler.start(cluster_id) {
+            Ok(report) => {
+                let (crawler, decoder) = (self.crawler.clone(), self.decoder.clone());
+                tokio::spawn(async move { crawler.proceed(&decoder, cluster_id).await });
+                report
+            }
+            Err(report) => report,
+        }
+    }
+
+    // broadcast invalidation events to other replicas through the bus
+    #[cfg(feature = "cache_invalidation")]
+    pub fn with_invalidation_bus(mut self, invalidation_bus: Arc<InvalidationBus>) -> Self {
+        self.invalidation_bus = Some(invalidation_bus);
+        self
+    }
+
+    // drop stale cache entries locally, then broadcast to other replicas if possible, returns the
+    // number of render results dropped locally
+    pub(crate) async fn invalidate(&self, event: InvalidationEvent) -> Result<usize, Error> {
+        let removed = apply_invalidation_blocking(self.decoder.clone(), event.clone()).await;
+        #[cfg(feature = "cache_invalidation")]
+        if let Some(invalidation_bus) = &self.invalidation_bus {
+            invalidation_bus.publish(event).await?;
+        }
+        Ok(removed)
     }
 }
 
@@ -46,99 +556,1436 @@ impl DecoderRpcServer for DecoderStandaloneServer {
         self.decoder.protocol_versions()
     }
 
-    // decode DNA in particular spore DOB cell
-    async fn decode(&self, hexed_spore_id: String) -> Result<Value, ErrorCode> {
-        let decoded_data = decode_dob(&self.decoder, hexed_spore_id).await;
+    // protocol versions with their numeric versions and supported features
+    async fn protocol_version_infos(&self) -> Vec<ProtocolVersionInfo> {
+        self.decoder.protocol_version_infos()
+    }
+
+    // decode DNA in particular spore DOB cell, options added since are only taken by `dob2_decode`
+    async fn decode(
+        &self,
+        hexed_spore_id: SporeIdParam,
+        anchor_block_hash: Option<String>,
+        include_metadata: Option<bool>,
+        traits: Option<Vec<String>>,
+        include_traits: Option<bool>,
+    ) -> Result<Value, ErrorObjectOwned> {
+        let options = DecodeOptions {
+            anchor_block_hash,
+            include_metadata: include_metadata.unwrap_or_default(),
+            traits,
+            include_traits: include_traits.unwrap_or_default(),
+            ..Default::default()
+        };
+        let decoded_data = decode_dob(&self.decoder, hexed_spore_id.to_hex(), &options).await;
         match decoded_data {
             Ok(result) => Ok(json!(result)),
             Err(error) => Err(error.into()),
         }
     }
 
-    // decode DNA from a set
-    async fn batch_decode(&self, hexed_spore_ids: Vec<String>) -> Result<Vec<Value>, ErrorCode> {
-        let results = batch_decode_dob(&self.decoder, hexed_spore_ids)
+    // resolve spore like "Unicorn #123" by its serial in cluster, then decode it, along with the
+    // resolved spore id
+    async fn decode_by_serial(
+        &self,
+        cluster_id: String,
+        serial: u64,
+        anchor_block_hash: Option<String>,
+        include_metadata: Option<bool>,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let hexed_cluster_id = cluster_id.strip_prefix("0x").unwrap_or(&cluster_id);
+        let cluster_id = H256::from_str(hexed_cluster_id).map_err(|_| {
+            DecodeFailure::new(Error::HexedClusterIdParseError, DecodeStage::ParseRequest)
+        })?;
+        ensure_cluster_access(&cluster_id.0)?;
+        let spore_id = decoder
+            .resolve_spore_by_serial(cluster_id.0, serial)
             .await
+            .map_err(|error| {
+                DecodeFailure::new(error, DecodeStage::FetchSpore).with_cluster_id(cluster_id.0)
+            })?;
+        let options = DecodeOptions {
+            anchor_block_hash,
+            include_metadata: include_metadata.unwrap_or_default(),
+            ..Default::default()
+        };
+        let result = decode_dob(decoder, hex::encode(spore_id), &options).await?;
+        Ok(json!(SerialDecodeResult {
+            spore_id: spore_id.into(),
+            result,
+        }))
+    }
+
+    // decode DNA from a set
+    async fn batch_decode(
+        &self,
+        hexed_spore_ids: Vec<SporeIdParam>,
+        anchor_block_hash: Option<String>,
+        network: Option<String>,
+    ) -> Result<Vec<Value>, ErrorObjectOwned> {
+        self.check_batch_size(hexed_spore_ids.len())?;
+        let decoder = self.network_decoder(network.as_deref())?;
+        let options = DecodeOptions {
+            anchor_block_hash,
+            ..Default::default()
+        };
+        let results = batch_decode_dob(
+            decoder,
+            hexed_spore_ids.iter().map(SporeIdParam::to_hex).collect(),
+            &options,
+            &self.batch_concurrency,
+        )
+        .await
+        .into_iter()
+        .zip(hexed_spore_ids)
+        .map(|((_, result), spore_id)| {
+            json!(BatchDecodeEntry {
+                spore_id,
+                result: result.map_err(ErrorObjectOwned::from),
+            })
+        })
+        .collect::<Vec<_>>();
+        Ok(results)
+    }
+
+    // push each result of a batch as soon as it's ready, then a completion message at last
+    async fn subscribe_batch_decode(
+        &self,
+        pending: PendingSubscriptionSink,
+        hexed_spore_ids: Vec<SporeIdParam>,
+        anchor_block_hash: Option<String>,
+        network: Option<String>,
+    ) -> SubscriptionResult {
+        let decoder = match self
+            .check_batch_size(hexed_spore_ids.len())
+            .and_then(|()| self.network_decoder(network.as_deref()))
+        {
+            Ok(decoder) => decoder,
+            Err(failure) => {
+                pending.reject(ErrorObjectOwned::from(failure)).await;
+                return Ok(());
+            }
+        };
+        let sink = pending.accept().await?;
+        let options = DecodeOptions {
+            anchor_block_hash,
+            ..Default::default()
+        };
+        let (sender, mut receiver) = futures::channel::mpsc::unbounded();
+        let decoding = batch_decode_dob_each(
+            decoder,
+            hexed_spore_ids.iter().map(SporeIdParam::to_hex).collect(),
+            &options,
+            &self.batch_concurrency,
+            // stop decoding if the subscriber is gone
+            move |index, result| sender.unbounded_send((index, result)).is_ok(),
+        );
+        let streaming = async {
+            let mut failed = 0;
+            while let Some((index, result)) = receiver.next().await {
+                if result.is_err() {
+                    failed += 1;
+                }
+                let entry = StreamedDecodeEntry {
+                    index,
+                    entry: BatchDecodeEntry {
+                        spore_id: hexed_spore_ids[index].clone(),
+                        result: result.map_err(ErrorObjectOwned::from),
+                    },
+                };
+                sink.send(SubscriptionMessage::from_json(&entry)?).await?;
+            }
+            let completion = BatchDecodeCompletion {
+                completed: true,
+                total: hexed_spore_ids.len(),
+                failed,
+            };
+            sink.send(SubscriptionMessage::from_json(&completion)?)
+                .await?;
+            Ok::<_, StringError>(())
+        };
+        let ((), streamed) = futures::join!(decoding, streaming);
+        streamed
+    }
+
+    // list spores owned by the address or lock script, then decode them in batch
+    async fn decode_by_owner(
+        &self,
+        address_or_lock_script: OwnerParam,
+        cursor: Option<String>,
+        limit: Option<u32>,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let lock_script = address_or_lock_script.lock_script()?;
+        let cursor = cursor
+            .as_deref()
+            .map(OwnedSporesCursor::decode)
+            .transpose()?
+            .unwrap_or_default();
+        let limit = limit
+            .unwrap_or(DEFAULT_OWNED_SPORES_LIMIT)
+            .clamp(1, MAX_OWNED_SPORES_LIMIT);
+        let (spore_ids, next_cursor) = decoder
+            .fetch_owned_spore_ids(lock_script, cursor, limit)
+            .await?;
+        let spores = batch_decode_dob(
+            decoder,
+            spore_ids.iter().map(hex::encode).collect(),
+            &DecodeOptions::default(),
+            &self.batch_concurrency,
+        )
+        .await
+        .into_iter()
+        .map(|(hexed_spore_id, result)| BatchDecodeEntry {
+            spore_id: SporeIdParam::Hex(hexed_spore_id),
+            result: result.map_err(ErrorObjectOwned::from),
+        })
+        .collect();
+        Ok(json!(OwnedDobsPage {
+            spores,
+            next_cursor: next_cursor.as_ref().map(OwnedSporesCursor::encode),
+        }))
+    }
+
+    // decode the spore cell at CKB outpoint, for callers tracking outpoints like wallets with
+    // their own UTXO sets, along with spore id taken from the cell
+    async fn decode_by_outpoint(
+        &self,
+        tx_hash: String,
+        index: u32,
+        include_metadata: Option<bool>,
+        include_owner: Option<bool>,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let hexed_tx_hash = tx_hash.strip_prefix("0x").unwrap_or(&tx_hash);
+        let tx_hash = H256::from_str(hexed_tx_hash).map_err(|_| {
+            DecodeFailure::new(Error::HexedTxHashParseError, DecodeStage::ParseRequest)
+        })?;
+        let options = DecodeOptions {
+            include_metadata: include_metadata.unwrap_or_default(),
+            include_owner: include_owner.unwrap_or_default(),
+            ..Default::default()
+        };
+        let (spore_id, result) = decode_dob_by_outpoint(decoder, tx_hash, index, &options).await?;
+        Ok(json!(SerialDecodeResult {
+            spore_id: spore_id.into(),
+            result,
+        }))
+    }
+
+    // decode bypassing render cache with provenance, timing and cycles attached, for operators
+    // looking into reports of slow or wrong spores
+    async fn decode_verbose(
+        &self,
+        hexed_spore_id: SporeIdParam,
+        network: Option<String>,
+    ) -> Result<VerboseDecodeResult, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let hexed_spore_id = hexed_spore_id.to_hex();
+        let hexed_spore_id = hexed_spore_id.strip_prefix("0x").unwrap_or(&hexed_spore_id);
+        let spore_id: [u8; 32] = hex::decode(hexed_spore_id)
+            .map_err(|_| Error::HexedSporeIdParseError)?
+            .try_into()
+            .map_err(|_| Error::SporeIdLengthInvalid)?;
+        Ok(decode_dob_verbose(decoder, spore_id).await?)
+    }
+
+    // decode spores bound to the bitcoin UTXO through RGB++ lock, which are usually one but may
+    // be more, for BTC-native wallets knowing nothing about spore ids
+    async fn decode_by_btc_outpoint(
+        &self,
+        btc_outpoint: String,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let (txid, vout) = parse_btc_outpoint(&btc_outpoint)?;
+        let lock_script = rgbpp_lock_script(decoder.setting().network, txid, vout);
+        let (spore_ids, _) = decoder
+            .fetch_owned_spore_ids(lock_script, Default::default(), MAX_OWNED_SPORES_LIMIT)
+            .await?;
+        if spore_ids.is_empty() {
+            return Err(Error::SporeIdNotFound.into());
+        }
+        let entries = batch_decode_dob(
+            decoder,
+            spore_ids.iter().map(hex::encode).collect(),
+            &DecodeOptions::default(),
+            &self.batch_concurrency,
+        )
+        .await
+        .into_iter()
+        .map(|(hexed_spore_id, result)| BatchDecodeEntry {
+            spore_id: SporeIdParam::Hex(hexed_spore_id),
+            result: result.map_err(ErrorObjectOwned::from),
+        })
+        .collect::<Vec<_>>();
+        Ok(json!(entries))
+    }
+
+    // list live spores in the cluster then decode them in batch, where cluster metadata is
+    // fetched once and shared by the whole page
+    async fn decode_cluster(
+        &self,
+        cluster_id: String,
+        cursor: Option<String>,
+        limit: Option<u32>,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let hexed_cluster_id = cluster_id.strip_prefix("0x").unwrap_or(&cluster_id);
+        let cluster_id =
+            H256::from_str(hexed_cluster_id).map_err(|_| Error::HexedClusterIdParseError)?;
+        ensure_cluster_access(&cluster_id.0)?;
+        let cursor = cursor
+            .as_deref()
+            .map(OwnedSporesCursor::decode)
+            .transpose()?
+            .unwrap_or_default();
+        let limit = limit
+            .unwrap_or(DEFAULT_OWNED_SPORES_LIMIT)
+            .clamp(1, MAX_OWNED_SPORES_LIMIT);
+        let (spore_ids, next_cursor) = decoder
+            .fetch_cluster_spore_ids(cluster_id.0, cursor, limit)
+            .await?;
+        let spores = batch_decode_dob(
+            decoder,
+            spore_ids.iter().map(hex::encode).collect(),
+            &DecodeOptions::default(),
+            &self.batch_concurrency,
+        )
+        .await
+        .into_iter()
+        .map(|(hexed_spore_id, result)| BatchDecodeEntry {
+            spore_id: SporeIdParam::Hex(hexed_spore_id),
+            result: result.map_err(ErrorObjectOwned::from),
+        })
+        .collect();
+        Ok(json!(OwnedDobsPage {
+            spores,
+            next_cursor: next_cursor.as_ref().map(OwnedSporesCursor::encode),
+        }))
+    }
+
+    // run decoder over DNA and pattern supplied by caller, without any spore or cluster involved
+    async fn decode_raw(
+        &self,
+        dna: String,
+        pattern: Value,
+        decoder: RawDecoderParam,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned> {
+        let network_decoder = self.network_decoder(network.as_deref())?;
+        let hexed_dna = dna.strip_prefix("0x").unwrap_or(&dna);
+        if hex::decode(hexed_dna).is_err() {
+            return Err(
+                DecodeFailure::new(Error::HexedDNAParseError, DecodeStage::ParseRequest).into(),
+            );
+        }
+        let render_output = network_decoder
+            .decode_dna_raw(hexed_dna, &pattern, &decoder)
+            .await?;
+        Ok(json!(RawDnaDecodeResult {
+            render_output: serde_json::from_str(&render_output)
+                .unwrap_or(Value::String(render_output)),
+        }))
+    }
+
+    // render DNA as if it were minted into the cluster, so that minting sites can preview spores
+    // before creating them
+    async fn decode_dry_run(
+        &self,
+        cluster_id: String,
+        dna: String,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let hexed_cluster_id = cluster_id.strip_prefix("0x").unwrap_or(&cluster_id);
+        let cluster_id = H256::from_str(hexed_cluster_id).map_err(|_| {
+            DecodeFailure::new(Error::HexedClusterIdParseError, DecodeStage::ParseRequest)
+        })?;
+        ensure_cluster_access(&cluster_id.0)?;
+        let context = decoder
+            .decode_context(cluster_id.0, None, &DecodeContexts::default())
+            .await?;
+        let result = decode_dna_in_cluster(decoder, &dna, &context)?;
+        Ok(json!(result))
+    }
+
+    // render DNAs against one cluster, whose metadata and decoders are prepared only once, each
+    // entry echoes back its DNA along with either `Ok` result or `Err` error object
+    async fn batch_decode_dna(
+        &self,
+        cluster_id: String,
+        dnas: Vec<String>,
+        network: Option<String>,
+    ) -> Result<Value, ErrorObjectOwned> {
+        self.check_batch_size(dnas.len())?;
+        let decoder = self.network_decoder(network.as_deref())?;
+        let hexed_cluster_id = cluster_id.strip_prefix("0x").unwrap_or(&cluster_id);
+        let cluster_id = H256::from_str(hexed_cluster_id).map_err(|_| {
+            DecodeFailure::new(Error::HexedClusterIdParseError, DecodeStage::ParseRequest)
+        })?;
+        ensure_cluster_access(&cluster_id.0)?;
+        let context = decoder
+            .decode_context(cluster_id.0, None, &DecodeContexts::default())
+            .await?;
+        let entries = dnas
             .into_iter()
-            .map(|result| json!(result))
+            .map(|dna| {
+                let result =
+                    decode_dna_in_cluster(decoder, &dna, &context).map_err(ErrorObjectOwned::from);
+                DnaDecodeEntry { dna, result }
+            })
             .collect::<Vec<_>>();
-        Ok(results)
+        Ok(json!(entries))
+    }
+
+    // lint candidate cluster description before deploying it, problems are reported in result
+    // rather than as errors
+    async fn validate_metadata(
+        &self,
+        description: Value,
+        sample_dna: Option<String>,
+        network: Option<String>,
+    ) -> Result<MetadataValidation, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let sample_dna = sample_dna.map(|dna| dna.trim_start_matches("0x").to_string());
+        Ok(validate_metadata(decoder, &description, sample_dna).await)
+    }
+
+    // inspect render cache of the spore without decoding it
+    async fn cache_status(
+        &self,
+        hexed_spore_id: SporeIdParam,
+        network: Option<String>,
+    ) -> Result<DOBCacheStatus, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let hexed_spore_id = hexed_spore_id.to_hex();
+        let hexed_spore_id = hexed_spore_id.strip_prefix("0x").unwrap_or(&hexed_spore_id);
+        let spore_id: [u8; 32] = hex::decode(hexed_spore_id)
+            .map_err(|_| Error::HexedSporeIdParseError)?
+            .try_into()
+            .map_err(|_| Error::SporeIdLengthInvalid)?;
+        Ok(dob_cache_status(decoder, &spore_id))
+    }
+
+    // tell whether the spore is a decodable DOB without running decoder, for classifying spores
+    // in bulk
+    async fn check(
+        &self,
+        hexed_spore_id: SporeIdParam,
+        network: Option<String>,
+    ) -> Result<SporeCheck, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let hexed_spore_id = hexed_spore_id.to_hex();
+        let hexed_spore_id = hexed_spore_id.strip_prefix("0x").unwrap_or(&hexed_spore_id);
+        let spore_id: [u8; 32] = hex::decode(hexed_spore_id)
+            .map_err(|_| Error::HexedSporeIdParseError)?
+            .try_into()
+            .map_err(|_| Error::SporeIdLengthInvalid)?;
+        Ok(decoder.check_spore(spore_id).await?)
+    }
+
+    // outcome of canary decodings, null if canary is not configured
+    async fn canary_status(&self) -> Option<CanaryStatus> {
+        self.canary.as_ref().map(|canary| canary.status())
+    }
+
+    // description and decoders of the cluster, so that collection pages don't have to decode a
+    // spore for them
+    async fn cluster_info(
+        &self,
+        cluster_id: String,
+        network: Option<String>,
+    ) -> Result<ClusterInfo, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let hexed_cluster_id = cluster_id.strip_prefix("0x").unwrap_or(&cluster_id);
+        let cluster_id =
+            H256::from_str(hexed_cluster_id).map_err(|_| Error::HexedClusterIdParseError)?;
+        ensure_cluster_access(&cluster_id.0)?;
+        let cluster = decoder.fetch_dob_metadata(cluster_id.0, None).await?;
+        let out_point = decoder.fetch_cluster_out_point(cluster_id.0).await?;
+        let decoders = std::iter::once(&cluster.metadata.dob.decoder)
+            .chain(
+                cluster
+                    .metadata
+                    .dob
+                    .stages
+                    .iter()
+                    .map(|stage| &stage.decoder),
+            )
+            .map(|cluster_decoder| decoder.decoder_status(cluster_decoder))
+            .collect();
+        let decoder_override = decoder.cluster_decoder_override(&cluster_id.0);
+        Ok(ClusterInfo {
+            cluster_id,
+            out_point,
+            description: cluster.metadata.clone(),
+            decoders,
+            decoder_override,
+        })
+    }
+
+    // decoding statistics of the cluster since server started, null if never requested
+    async fn cluster_stats(
+        &self,
+        cluster_id: String,
+        network: Option<String>,
+    ) -> Result<Option<ClusterStatsReport>, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let hexed_cluster_id = cluster_id.strip_prefix("0x").unwrap_or(&cluster_id);
+        let cluster_id =
+            H256::from_str(hexed_cluster_id).map_err(|_| Error::HexedClusterIdParseError)?;
+        ensure_cluster_access(&cluster_id.0)?;
+        Ok(decoder.cluster_stats().report(&cluster_id.0))
+    }
+
+    // decoding statistics of all tracked clusters of the primary network since server started, the
+    // busiest first
+    async fn stats(&self) -> Vec<ClusterStatsEntry> {
+        self.decoder.cluster_stats().reports()
+    }
+
+    // trait distribution and rarity of all live spores in the cluster
+    async fn cluster_rarity(
+        &self,
+        cluster_id: String,
+        network: Option<String>,
+    ) -> Result<ClusterRarityReport, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let hexed_cluster_id = cluster_id.strip_prefix("0x").unwrap_or(&cluster_id);
+        let cluster_id =
+            H256::from_str(hexed_cluster_id).map_err(|_| Error::HexedClusterIdParseError)?;
+        ensure_cluster_access(&cluster_id.0)?;
+        let report = self
+            .rarity
+            .report(decoder, &self.batch_concurrency, cluster_id.0)
+            .await?;
+        Ok(report.as_ref().clone())
+    }
+
+    // progress of crawling the cluster, crawler only works on the primary network
+    async fn crawl_status(
+        &self,
+        cluster_id: String,
+    ) -> Result<Option<CrawlReport>, ErrorObjectOwned> {
+        let hexed_cluster_id = cluster_id.strip_prefix("0x").unwrap_or(&cluster_id);
+        let cluster_id =
+            H256::from_str(hexed_cluster_id).map_err(|_| Error::HexedClusterIdParseError)?;
+        ensure_cluster_access(&cluster_id.0)?;
+        Ok(self.crawler.report(&cluster_id.0))
+    }
+
+    // look up spores in the cluster by trait values from local index, which only knows spores
+    // decoded on the primary network by this server so far
+    async fn search_traits(
+        &self,
+        cluster_id: String,
+        filters: Vec<TraitFilter>,
+        cursor: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<TraitSearchPage, ErrorObjectOwned> {
+        let hexed_cluster_id = cluster_id.strip_prefix("0x").unwrap_or(&cluster_id);
+        let cluster_id =
+            H256::from_str(hexed_cluster_id).map_err(|_| Error::HexedClusterIdParseError)?;
+        ensure_cluster_access(&cluster_id.0)?;
+        let after = cursor
+            .map(|cursor| {
+                let hexed_cursor = cursor.strip_prefix("0x").unwrap_or(&cursor);
+                H256::from_str(hexed_cursor).map_err(|_| Error::TraitSearchCursorParseError)
+            })
+            .transpose()?
+            .map(|spore_id| spore_id.0);
+        let limit = limit
+            .unwrap_or(DEFAULT_TRAIT_SEARCH_LIMIT)
+            .clamp(1, MAX_TRAIT_SEARCH_LIMIT);
+        let page = search_trait_index(
+            &self.decoder,
+            &cluster_id.0,
+            &filters,
+            after,
+            limit as usize,
+        )?;
+        Ok(page)
+    }
+
+    // report process and cache memory usage for tuning cache capacities
+    async fn memory_usage(&self) -> MemoryUsage {
+        memory::memory_usage(&self.decoder)
+    }
+
+    // report build, configuration and cache facts for monitoring, cache directories are walked
+    // off the async runtime
+    async fn server_info(&self) -> ServerInfo {
+        let decoder = self.decoder.clone();
+        let info = self.info.clone();
+        tokio::task::spawn_blocking(move || info.server_info(&decoder))
+            .await
+            .expect("collect server info")
+    }
+
+    // OpenRPC document describing methods above
+    async fn discover(&self) -> Value {
+        openrpc::openrpc_document()
     }
 }
 
 pub async fn decode_dob(
     decoder: &DOBDecoder,
     hexed_spore_id: String,
-) -> Result<ServerDecodeResult, ErrorCode> {
+    options: &DecodeOptions,
+) -> Result<ServerDecodeResult, DecodeFailure> {
+    decode_dob_with_contexts(decoder, hexed_spore_id, options, &DecodeContexts::default()).await
+}
+
+// decode with contexts shared among spores in one batch, so that per-cluster preparation like
+// decoder resolving and pattern stringifying happens only once for each cluster
+#[tracing::instrument(name = "decode_dob", skip_all, fields(spore_id = %hexed_spore_id))]
+async fn decode_dob_with_contexts(
+    decoder: &DOBDecoder,
+    hexed_spore_id: String,
+    options: &DecodeOptions,
+    contexts: &DecodeContexts,
+) -> Result<ServerDecodeResult, DecodeFailure> {
     let hexed_spore_id = hexed_spore_id.strip_prefix("0x").unwrap_or(&hexed_spore_id);
     tracing::info!("decoding hexed_spore_id: {}", hexed_spore_id);
     let spore_id: [u8; 32] = hex::decode(hexed_spore_id)
-        .map_err(|_| Error::HexedSporeIdParseError)?
-        .try_into()
-        .map_err(|_| Error::SporeIdLengthInvalid)?;
-    #[cfg(not(feature = "shuttle"))]
-    let (render_output, dob_content) = {
-        let mut cache_path = decoder.setting().dobs_cache_directory.clone();
-        cache_path.push(format!("{}.dob", hex::encode(spore_id)));
-        let (render_output, dob_content) = if cache_path.exists() {
-            read_dob_from_cache(cache_path)?
-        } else {
-            let ((content, dna), metadata) = decoder.fetch_decode_ingredients(spore_id).await?;
-            let render_output = decoder.decode_dna(&dna, metadata).await?;
-            write_dob_to_cache(&render_output, &content, cache_path)?;
-            (render_output, content)
-        };
-        (render_output, dob_content)
+        .map_err(|_| Error::HexedSporeIdParseError)
+        .and_then(|spore_id| spore_id.try_into().map_err(|_| Error::SporeIdLengthInvalid))
+        .map_err(|error| DecodeFailure::new(error, DecodeStage::ParseRequest))?;
+    let start = Instant::now();
+    let (result, trace) = slow_decode::trace_decode(async {
+        let raw_result = match &options.anchor_block_hash {
+            Some(anchor_block_hash) => {
+                decode_dob_at_anchor(decoder, spore_id, anchor_block_hash, contexts).await
+            }
+            None => decode_dob_with_cache(decoder, spore_id, options.refresh, contexts).await,
+        }
+        .map_err(|failure| record_failure(decoder, failure));
+        match raw_result {
+            Ok(raw_result) => assemble_decode_result(decoder, spore_id, raw_result, options).await,
+            Err(failure) => Err(failure),
+        }
+    })
+    .await;
+    let elapsed = start.elapsed();
+    metrics::observe_decode_request(result.is_ok(), elapsed);
+    if let Some(cluster_id) = &trace.cluster_id {
+        metrics::observe_cluster_decode(cluster_id, elapsed);
+    }
+    if let Some(threshold_ms) = decoder.setting().slow_decode_threshold_ms {
+        if elapsed >= Duration::from_millis(threshold_ms) {
+            slow_decode::log_slow_decode(&spore_id, elapsed, &trace);
+        }
+    }
+    result
+}
+
+// decode the spore cell at the outpoint, which is loaded directly instead of looked up through
+// indexer, result is fresh from chain without touching render cache, like anchored decoding
+pub async fn decode_dob_by_outpoint(
+    decoder: &DOBDecoder,
+    tx_hash: H256,
+    out_index: u32,
+    options: &DecodeOptions,
+) -> Result<([u8; 32], ServerDecodeResult), DecodeFailure> {
+    tracing::info!("decoding spore at outpoint {tx_hash}:{out_index}");
+    let (spore_id, spore) = decoder
+        .fetch_dob_content_by_outpoint(tx_hash, out_index)
+        .await
+        .map_err(|error| DecodeFailure::new(error, DecodeStage::FetchSpore))?;
+    let raw_result =
+        decode_spore_content(decoder, spore_id, spore, None, &DecodeContexts::default())
+            .await
+            .map_err(|failure| record_failure(decoder, failure))?;
+    let result = assemble_decode_result(decoder, spore_id, raw_result, options).await?;
+    Ok((spore_id, result))
+}
+
+// ingredients of a verbose decoding, telling where each of them is loaded from
+#[derive(Serialize, Clone)]
+pub struct DecodeProvenance {
+    // render cache is bypassed by verbose decoding, this tells whether the spore is served from it
+    // in regular decoding
+    pub cached: bool,
+    pub spore_out_point: ckb_jsonrpc_types::OutPoint,
+    pub cluster_id: H256,
+    pub cluster_out_point: ckb_jsonrpc_types::OutPoint,
+    // decoder actually run, which is the one pinned by operator if overridden, absent if that's a
+    // local binary, decoders of following DOB/1 stages are those declared by the cluster
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoder: Option<DOBDecoderFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoder_out_point: Option<ckb_jsonrpc_types::OutPoint>,
+}
+
+// milliseconds spent in each phase of a verbose decoding, preparing covers fetching cluster and
+// loading decoders, either from cache directory or chain
+#[derive(Serialize, Clone, Debug)]
+pub struct DecodeTiming {
+    pub fetch_spore_ms: f64,
+    pub prepare_ms: f64,
+    pub execute_ms: f64,
+    pub total_ms: f64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct VerboseDecodeResult {
+    #[serde(flatten)]
+    pub result: ServerDecodeResult,
+    pub provenance: DecodeProvenance,
+    pub timing: DecodeTiming,
+    // cycles consumed by decoders, stages served from stage output cache consume none
+    pub cycles: u64,
+}
+
+// decode the spore afresh from chain like anchored decoding, measuring each phase and reporting
+// where spore, cluster and decoder come from, for digging into slow or wrong render results
+pub async fn decode_dob_verbose(
+    decoder: &DOBDecoder,
+    spore_id: [u8; 32],
+) -> Result<VerboseDecodeResult, DecodeFailure> {
+    tracing::info!("decoding verbosely spore_id {}", hex::encode(spore_id));
+    let cached = dob_cache_status(decoder, &spore_id).cached;
+    let record_failure = |failure| record_failure(decoder, failure);
+    let started_at = Instant::now();
+    let spore = decoder
+        .fetch_dob_content(spore_id, None)
+        .await
+        .map_err(|error| DecodeFailure::new(error, DecodeStage::FetchSpore).with_spore_id(spore_id))
+        .map_err(record_failure)?;
+    let fetched_at = Instant::now();
+    ensure_cluster_access(&spore.cluster_id).map_err(|failure| failure.with_spore_id(spore_id))?;
+    let context = decoder
+        .decode_context(spore.cluster_id, None, &DecodeContexts::default())
+        .await
+        .map_err(|failure| record_failure(failure.with_spore_id(spore_id)))?;
+    let prepared_at = Instant::now();
+    let spore_out_point = spore.out_point.clone();
+    let mut cycles = 0;
+    let raw_result = decode_spore_in_context(decoder, spore_id, spore, &context, &mut cycles)
+        .map_err(record_failure)?;
+    let executed_at = Instant::now();
+    let elapsed_ms = |from: Instant, to: Instant| (to - from).as_secs_f64() * 1000.0;
+    let timing = DecodeTiming {
+        fetch_spore_ms: elapsed_ms(started_at, fetched_at),
+        prepare_ms: elapsed_ms(fetched_at, prepared_at),
+        execute_ms: elapsed_ms(prepared_at, executed_at),
+        total_ms: elapsed_ms(started_at, executed_at),
     };
-    #[cfg(feature = "shuttle")]
-    let (render_output, dob_content) = {
-        let cache_path = format!("{}.dob", hex::encode(spore_id));
-        let (render_output, dob_content) =
-            if decoder.persist.load::<String>(cache_path.as_str()).is_ok() {
-                read_dob_from_cache(cache_path, &decoder.persist)?
-            } else {
-                let ((content, dna), metadata) = decoder.fetch_decode_ingredients(spore_id).await?;
-                let render_output = decoder.decode_dna(&dna, metadata).await?;
-                write_dob_to_cache(&render_output, &content, cache_path, &decoder.persist)?;
-                (render_output, content)
-            };
-        (render_output, dob_content)
+
+    // outpoints are looked up after timing, so that they don't count into any phase
+    let cluster_out_point = decoder
+        .fetch_cluster_out_point(context.cluster_id)
+        .await
+        .map_err(|error| {
+            DecodeFailure::new(error, DecodeStage::FetchCluster)
+                .with_spore_id(spore_id)
+                .with_cluster_id(context.cluster_id)
+        })?;
+    let decoder_out_point = match &context.decoder {
+        Some(decoder_format) => {
+            decoder
+                .locate_decoder_cell(decoder_format)
+                .await
+                .map_err(|error| {
+                    DecodeFailure::new(error, DecodeStage::DownloadDecoder)
+                        .with_spore_id(spore_id)
+                        .with_cluster_id(context.cluster_id)
+                        .with_decoder_hash(decoder_format.hash.clone())
+                })?
+        }
+        None => None,
+    };
+    let result =
+        assemble_decode_result(decoder, spore_id, raw_result, &DecodeOptions::default()).await?;
+    Ok(VerboseDecodeResult {
+        result,
+        provenance: DecodeProvenance {
+            cached,
+            spore_out_point,
+            cluster_id: context.cluster_id.into(),
+            cluster_out_point,
+            decoder: context.decoder.clone(),
+            decoder_out_point,
+        },
+        timing,
+        cycles,
+    })
+}
+
+// record decoding failure in cluster statistics, and report it if it's severe
+fn record_failure(decoder: &DOBDecoder, failure: DecodeFailure) -> DecodeFailure {
+    decoder.cluster_stats().record_failure(&failure);
+    error_reporting::report_failure(&failure);
+    failure
+}
+
+// clusters are checked against the api key of the request as soon as they are known, before
+// anything of them is fetched or run in VM
+pub(crate) fn ensure_cluster_access(cluster_id: &[u8; 32]) -> Result<(), DecodeFailure> {
+    access::check_cluster_access(Some(&H256::from(*cluster_id))).map_err(|error| {
+        DecodeFailure::new(error, DecodeStage::FetchCluster).with_cluster_id(*cluster_id)
+    })
+}
+
+// attach requested extras to the raw result and record the request in cluster statistics, results
+// of unknown clusters, like those from upstream or legacy cache entries, are checked here at last
+async fn assemble_decode_result(
+    decoder: &DOBDecoder,
+    spore_id: [u8; 32],
+    raw_result: RawDecodeResult,
+    options: &DecodeOptions,
+) -> Result<ServerDecodeResult, DecodeFailure> {
+    access::check_cluster_access(raw_result.cache_info.cluster_id.as_ref()).map_err(|error| {
+        DecodeFailure::new(error, DecodeStage::FetchCluster).with_spore_id(spore_id)
+    })?;
+    metrics::observe_render_cache(raw_result.from_cache);
+    if let Some(cluster_id) = &raw_result.cache_info.cluster_id {
+        slow_decode::record_cluster(cluster_id.clone());
+        decoder.cluster_activity().record(&cluster_id.0);
+        decoder
+            .cluster_stats()
+            .record_request(&cluster_id.0, raw_result.from_cache);
+    }
+    let metadata = if options.include_metadata {
+        Some(fetch_decode_metadata(decoder, spore_id, &raw_result).await?)
+    } else {
+        None
+    };
+    let owner = if options.include_owner {
+        Some(fetch_spore_owner(decoder, spore_id, &raw_result).await?)
+    } else {
+        None
     };
 
+    // decoders are free to print anything, which is passed through as a string if not json
+    let mut render_output = serde_json::from_str(&raw_result.render_output)
+        .unwrap_or_else(|_| Value::String(raw_result.render_output.clone()));
+    if let Some(traits) = &options.traits {
+        filter_traits(&mut render_output, traits);
+    }
+    let rendered_traits = options
+        .include_traits
+        .then(|| RenderedTraits::parse(&render_output).ok())
+        .flatten();
     let result = ServerDecodeResult {
-        render_output: serde_json::from_str(render_output.as_str()).unwrap(),
-        dob_content,
+        render_output,
+        dob_content: raw_result.dob_content,
+        provisional: raw_result.provisional,
+        metadata,
+        rendered_traits,
+        owner,
     };
     tracing::info!(
-        "spore_id {hexed_spore_id}, result: {}",
+        "spore_id {}, result: {}",
+        hex::encode(spore_id),
         result.render_output
     );
     Ok(result)
 }
 
+// render DNA as if it were minted into the cluster prepared in context, since protocol version of
+// the spore is unknown yet, every stage declared by the cluster runs
+fn decode_dna_in_cluster(
+    decoder: &DOBDecoder,
+    dna: &str,
+    context: &DecodeContext,
+) -> Result<RawDnaDecodeResult, DecodeFailure> {
+    let hexed_dna = dna.strip_prefix("0x").unwrap_or(dna);
+    if hex::decode(hexed_dna).is_err() {
+        return Err(DecodeFailure::new(
+            Error::HexedDNAParseError,
+            DecodeStage::ParseRequest,
+        ));
+    }
+    // no spore exists yet, so render debugging only matches by cluster
+    let render_output = decoder
+        .decode_dna_in_context(&[0u8; 32], hexed_dna, context, DecodePipeline::Compose)
+        .map_err(|error| {
+            DecodeFailure::new(error, DecodeStage::Execute)
+                .with_cluster_id(context.cluster_id)
+                .with_decoder_hash(context.cluster.metadata.dob.decoder.hash.clone())
+        })?;
+    Ok(RawDnaDecodeResult {
+        render_output: serde_json::from_str(&render_output).unwrap_or(Value::String(render_output)),
+    })
+}
+
+// keep only traits with requested names in render output, which is an array of objects like
+// `{"name": "Horn", "traits": [...]}`, filtered after caching so that cache is always complete
+pub(crate) fn filter_traits(render_output: &mut Value, names: &[String]) {
+    if let Value::Array(traits) = render_output {
+        traits.retain(|item| {
+            item.get("name")
+                .and_then(Value::as_str)
+                .is_some_and(|name| names.iter().any(|expected| expected == name))
+        });
+    }
+}
+
+// serve render result from cache, or decode and cache it on miss unless provisional, a refresh
+// always decodes and overwrites the cached entry
+async fn decode_dob_with_cache(
+    decoder: &DOBDecoder,
+    spore_id: [u8; 32],
+    refresh: bool,
+    contexts: &DecodeContexts,
+) -> Result<RawDecodeResult, DecodeFailure> {
+    let cache_failure =
+        |stage| move |error| DecodeFailure::new(error, stage).with_spore_id(spore_id);
+    #[cfg(not(feature = "shuttle"))]
+    {
+        let cache_path = dob_cache_path(&decoder.setting().dobs_cache_directory, &spore_id);
+        migrate_legacy_dob_cache(&decoder.setting().dobs_cache_directory, &spore_id);
+        if !refresh && cache_path.exists() {
+            let (render_output, dob_content, cache_info) = tracing::info_span!("read_dob_cache")
+                .in_scope(|| read_dob_from_cache(cache_path))
+                .map_err(cache_failure(DecodeStage::ReadCache))?;
+            if let Some(cluster_id) = &cache_info.cluster_id {
+                ensure_cluster_access(&cluster_id.0)
+                    .map_err(|failure| failure.with_spore_id(spore_id))?;
+            }
+            let result = RawDecodeResult {
+                render_output,
+                dob_content,
+                cache_info,
+                provisional: false,
+                from_cache: true,
+                from_upstream: false,
+                lock_script: None,
+            };
+            index_traits(decoder, &spore_id, &result);
+            return Ok(result);
+        }
+        let result = decode_dob_onchain_or_upstream(decoder, spore_id, contexts).await?;
+        let result = prefetch_assets(decoder, result).await;
+        if result.cacheable(decoder.setting()) {
+            tracing::info_span!("write_dob_cache")
+                .in_scope(|| {
+                    write_dob_to_cache(
+                        &result.render_output,
+                        &result.dob_content,
+                        &result.cache_info,
+                        cache_path,
+                    )
+                })
+                .map_err(cache_failure(DecodeStage::WriteCache))?;
+        }
+        index_traits(decoder, &spore_id, &result);
+        Ok(result)
+    }
+    #[cfg(feature = "shuttle")]
+    {
+        let cache_path = format!("{}.dob", hex::encode(spore_id));
+        if !refresh && decoder.persist.load::<String>(cache_path.as_str()).is_ok() {
+            let (render_output, dob_content, cache_info) = tracing::info_span!("read_dob_cache")
+                .in_scope(|| read_dob_from_cache(cache_path, &decoder.persist))
+                .map_err(cache_failure(DecodeStage::ReadCache))?;
+            if let Some(cluster_id) = &cache_info.cluster_id {
+                ensure_cluster_access(&cluster_id.0)
+                    .map_err(|failure| failure.with_spore_id(spore_id))?;
+            }
+            let result = RawDecodeResult {
+                render_output,
+                dob_content,
+                cache_info,
+                provisional: false,
+                from_cache: true,
+                from_upstream: false,
+                lock_script: None,
+            };
+            index_traits(decoder, &spore_id, &result);
+            return Ok(result);
+        }
+        let result = decode_dob_onchain_or_upstream(decoder, spore_id, contexts).await?;
+        let result = prefetch_assets(decoder, result).await;
+        if result.cacheable(decoder.setting()) {
+            tracing::info_span!("write_dob_cache")
+                .in_scope(|| {
+                    write_dob_to_cache(
+                        &result.render_output,
+                        &result.dob_content,
+                        &result.cache_info,
+                        cache_path,
+                        &decoder.persist,
+                    )
+                })
+                .map_err(cache_failure(DecodeStage::WriteCache))?;
+        }
+        index_traits(decoder, &spore_id, &result);
+        Ok(result)
+    }
+}
+
+// point assets referenced in fresh results at their local copies before caching, so that clients
+// don't depend on availability of third-party hosts
+async fn prefetch_assets(decoder: &DOBDecoder, mut result: RawDecodeResult) -> RawDecodeResult {
+    if let Some(assets) = decoder.assets() {
+        result.render_output = assets.prefetch_render_output(&result.render_output).await;
+    }
+    result
+}
+
+// keep trait index up to date with confirmed results, cached ones are indexed as well unless
+// present already, so that index catches up with render cache built before it's enabled
+#[cfg(feature = "trait_index")]
+fn index_traits(decoder: &DOBDecoder, spore_id: &[u8; 32], result: &RawDecodeResult) {
+    let Some(trait_index) = decoder.trait_index() else {
+        return;
+    };
+    let Some(cluster_id) = &result.cache_info.cluster_id else {
+        return;
+    };
+    if result.provisional || (result.from_cache && trait_index.contains(spore_id)) {
+        return;
+    }
+    // spores whose output isn't in the conventional structure are recorded without traits
+    let rendered_traits = serde_json::from_str(&result.render_output)
+        .ok()
+        .and_then(|render_output| RenderedTraits::parse(&render_output).ok())
+        .unwrap_or(RenderedTraits(Vec::new()));
+    // failures are logged by index, which don't fail decoding
+    let _ = trait_index.insert(spore_id, &cluster_id.0, &rendered_traits);
+}
+
+#[cfg(not(feature = "trait_index"))]
+fn index_traits(_: &DOBDecoder, _: &[u8; 32], _: &RawDecodeResult) {}
+
+#[cfg(feature = "trait_index")]
+fn search_trait_index(
+    decoder: &DOBDecoder,
+    cluster_id: &[u8; 32],
+    filters: &[TraitFilter],
+    after: Option<[u8; 32]>,
+    limit: usize,
+) -> Result<TraitSearchPage, Error> {
+    decoder
+        .trait_index()
+        .ok_or(Error::TraitIndexDisabled)?
+        .search(cluster_id, filters, after, limit)
+}
+
+#[cfg(not(feature = "trait_index"))]
+fn search_trait_index(
+    _: &DOBDecoder,
+    _: &[u8; 32],
+    _: &[TraitFilter],
+    _: Option<[u8; 32]>,
+    _: usize,
+) -> Result<TraitSearchPage, Error> {
+    Err(Error::TraitIndexDisabled)
+}
+
+// anchored decoding always goes on-chain to keep result reproducible on that fork, since cache
+// doesn't know whether the spore exists at the anchor block
+async fn decode_dob_at_anchor(
+    decoder: &DOBDecoder,
+    spore_id: [u8; 32],
+    hexed_anchor_block_hash: &str,
+    contexts: &DecodeContexts,
+) -> Result<RawDecodeResult, DecodeFailure> {
+    let hexed_anchor_block_hash = hexed_anchor_block_hash
+        .strip_prefix("0x")
+        .unwrap_or(hexed_anchor_block_hash);
+    let anchor_block_number = match H256::from_str(hexed_anchor_block_hash) {
+        Ok(anchor_block_hash) => decoder.resolve_anchor_block(anchor_block_hash).await,
+        Err(_) => Err(Error::HexedBlockHashParseError),
+    }
+    .map_err(|error| {
+        DecodeFailure::new(error, DecodeStage::ParseRequest).with_spore_id(spore_id)
+    })?;
+    decode_dob_onchain(decoder, spore_id, Some(anchor_block_number), contexts).await
+}
+
+// collect DNA, cluster description and pattern used in decoding, cluster id of legacy cache
+// entries is missing so that spore has to be fetched again
+async fn fetch_decode_metadata(
+    decoder: &DOBDecoder,
+    spore_id: [u8; 32],
+    raw_result: &RawDecodeResult,
+) -> Result<DecodeMetadata, DecodeFailure> {
+    let spore_failure =
+        |error| DecodeFailure::new(error, DecodeStage::FetchSpore).with_spore_id(spore_id);
+    let dna = extract_dna(&raw_result.dob_content).map_err(spore_failure)?;
+    let cluster_id = match &raw_result.cache_info.cluster_id {
+        Some(cluster_id) => cluster_id.0,
+        None => {
+            decoder
+                .fetch_dob_content(spore_id, None)
+                .await
+                .map_err(spore_failure)?
+                .cluster_id
+        }
+    };
+    // cluster is immutable, so metadata fetched without anchor is the same
+    let cluster = decoder
+        .fetch_dob_metadata(cluster_id, None)
+        .await
+        .map_err(|error| {
+            DecodeFailure::new(error, DecodeStage::FetchCluster)
+                .with_spore_id(spore_id)
+                .with_cluster_id(cluster_id)
+        })?;
+    Ok(DecodeMetadata {
+        dna,
+        cluster_id: cluster_id.into(),
+        cluster_description: cluster.metadata.description.clone(),
+        pattern: cluster.metadata.dob.pattern.clone(),
+    })
+}
+
+// owner is known to results fresh from chain, while the others have to fetch the spore cell again
+// since spores are transferred after being cached
+async fn fetch_spore_owner(
+    decoder: &DOBDecoder,
+    spore_id: [u8; 32],
+    raw_result: &RawDecodeResult,
+) -> Result<SporeOwner, DecodeFailure> {
+    let lock_script = match &raw_result.lock_script {
+        Some(lock_script) => lock_script.clone(),
+        None => decoder.fetch_spore_owner(spore_id).await.map_err(|error| {
+            DecodeFailure::new(error, DecodeStage::FetchSpore).with_spore_id(spore_id)
+        })?,
+    };
+    let address = encode_address(&lock_script.clone().into(), decoder.setting().network);
+    Ok(SporeOwner {
+        lock_script,
+        address,
+    })
+}
+
+// decoding result before being assembled into response, from chain, cache or upstream server
+struct RawDecodeResult {
+    render_output: String,
+    dob_content: Value,
+    cache_info: DOBCacheInfo,
+    provisional: bool,
+    from_cache: bool,
+    from_upstream: bool,
+    // lock script of the spore cell, only known to results fresh from chain
+    lock_script: Option<ckb_jsonrpc_types::Script>,
+}
+
+impl RawDecodeResult {
+    // provisional results are never cached, nor are those of upstream unless configured
+    fn cacheable(&self, settings: &Settings) -> bool {
+        let cache_upstream = settings
+            .upstream
+            .as_ref()
+            .is_some_and(|upstream| upstream.cache_results);
+        !self.provisional && (!self.from_upstream || cache_upstream)
+    }
+}
+
+// decode on-chain locally, falling back to upstream server if decoder is unavailable or fails
+// in VM, while in read-only mode cache misses are always served by upstream
+async fn decode_dob_onchain_or_upstream(
+    decoder: &DOBDecoder,
+    spore_id: [u8; 32],
+    contexts: &DecodeContexts,
+) -> Result<RawDecodeResult, DecodeFailure> {
+    let Some(upstream) = &decoder.setting().upstream else {
+        return decode_dob_onchain(decoder, spore_id, None, contexts).await;
+    };
+    if upstream.read_only {
+        return decode_dob_from_upstream(upstream, spore_id).await;
+    }
+    match decode_dob_onchain(decoder, spore_id, None, contexts).await {
+        Err(failure)
+            if matches!(
+                failure.stage,
+                DecodeStage::DownloadDecoder | DecodeStage::Execute
+            ) =>
+        {
+            tracing::warn!(
+                "local decoding of spore {} failed ({}), fallback to upstream",
+                hex::encode(spore_id),
+                failure.error
+            );
+            // upstream failure is less informative than the local one
+            decode_dob_from_upstream(upstream, spore_id)
+                .await
+                .map_err(|_| failure)
+        }
+        result => result,
+    }
+}
+
+// decode on-chain without touching render cache or upstream, for checking decoding pipeline
+pub async fn decode_dob_uncached(
+    decoder: &DOBDecoder,
+    spore_id: [u8; 32],
+) -> Result<Value, DecodeFailure> {
+    let result = decode_dob_onchain(decoder, spore_id, None, &DecodeContexts::default()).await?;
+    Ok(serde_json::from_str(&result.render_output).unwrap_or(Value::String(result.render_output)))
+}
+
+// proxy decoding to `dob_decode` of upstream server, cluster id is unknown in its result
+async fn decode_dob_from_upstream(
+    upstream: &UpstreamSettings,
+    spore_id: [u8; 32],
+) -> Result<RawDecodeResult, DecodeFailure> {
+    static UPSTREAM_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    let upstream_failure = || {
+        DecodeFailure::new(Error::UpstreamDecodeError, DecodeStage::Upstream)
+            .with_spore_id(spore_id)
+    };
+    let request = json!({
+        "id": 0,
+        "jsonrpc": "2.0",
+        "method": "dob_decode",
+        "params": [hex::encode(spore_id)],
+    });
+    let response: Value = UPSTREAM_CLIENT
+        .get_or_init(reqwest::Client::new)
+        .post(&upstream.url)
+        .timeout(Duration::from_millis(upstream.timeout_ms))
+        .json(&request)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|_| upstream_failure())?
+        .json()
+        .await
+        .map_err(|_| upstream_failure())?;
+    let result: ServerDecodeResult = response
+        .get("result")
+        .and_then(|result| serde_json::from_value(result.clone()).ok())
+        .ok_or_else(upstream_failure)?;
+    Ok(RawDecodeResult {
+        render_output: result.render_output.to_string(),
+        dob_content: result.dob_content,
+        cache_info: DOBCacheInfo::default(),
+        provisional: result.provisional,
+        from_cache: false,
+        from_upstream: true,
+        lock_script: None,
+    })
+}
+
+// fetch ingredients from chain and run the decoder, without touching the cache, failures are
+// tagged with the stage and ids involved
+async fn decode_dob_onchain(
+    decoder: &DOBDecoder,
+    spore_id: [u8; 32],
+    anchor_block_number: Option<u64>,
+    contexts: &DecodeContexts,
+) -> Result<RawDecodeResult, DecodeFailure> {
+    let spore = decoder
+        .fetch_dob_content(spore_id, anchor_block_number)
+        .await
+        .map_err(|error| {
+            DecodeFailure::new(error, DecodeStage::FetchSpore).with_spore_id(spore_id)
+        })?;
+    decode_spore_content(decoder, spore_id, spore, anchor_block_number, contexts).await
+}
+
+// run the decoder over DOB content of the fetched spore cell
+async fn decode_spore_content(
+    decoder: &DOBDecoder,
+    spore_id: [u8; 32],
+    spore: SporeCellContent,
+    anchor_block_number: Option<u64>,
+    contexts: &DecodeContexts,
+) -> Result<RawDecodeResult, DecodeFailure> {
+    ensure_cluster_access(&spore.cluster_id).map_err(|failure| failure.with_spore_id(spore_id))?;
+    let context = decoder
+        .decode_context(spore.cluster_id, anchor_block_number, contexts)
+        .await
+        .map_err(|failure| failure.with_spore_id(spore_id))?;
+    decode_spore_in_context(decoder, spore_id, spore, &context, &mut 0)
+}
+
+// run decoders prepared in context over DOB content of the spore, adding up consumed cycles
+fn decode_spore_in_context(
+    decoder: &DOBDecoder,
+    spore_id: [u8; 32],
+    spore: SporeCellContent,
+    context: &DecodeContext,
+    cycles: &mut u64,
+) -> Result<RawDecodeResult, DecodeFailure> {
+    let render_output = decoder
+        .decode_dna_in_context_metered(&spore_id, &spore.dna, context, spore.pipeline, cycles)
+        .map_err(|error| {
+            DecodeFailure::new(error, DecodeStage::Execute)
+                .with_spore_id(spore_id)
+                .with_cluster_id(spore.cluster_id)
+                .with_decoder_hash(context.cluster.metadata.dob.decoder.hash.clone())
+        })?;
+    Ok(RawDecodeResult {
+        render_output,
+        dob_content: spore.dob_content,
+        cache_info: DOBCacheInfo {
+            cluster_id: Some(spore.cluster_id.into()),
+            cluster_data_hash: Some(context.cluster.cluster_data_hash.into()),
+        },
+        provisional: spore.provisional,
+        from_cache: false,
+        from_upstream: false,
+        lock_script: Some(spore.lock_script),
+    })
+}
+
+// decode a batch of spores with parallelism adjusted by observed latency and errors, results
+// are returned in the same order as requested, each paired with its requested spore id
 pub async fn batch_decode_dob(
     decoder: &DOBDecoder,
     hexed_spore_ids: Vec<String>,
-) -> Vec<Result<ServerDecodeResult, ErrorCode>> {
-    let mut await_results = Vec::new();
-    for hexed_spore_id in hexed_spore_ids {
-        await_results.push(decode_dob(decoder, hexed_spore_id));
+    options: &DecodeOptions,
+    concurrency: &AdaptiveConcurrency,
+) -> Vec<(String, Result<ServerDecodeResult, DecodeFailure>)> {
+    let mut results = hexed_spore_ids.iter().map(|_| None).collect::<Vec<_>>();
+    batch_decode_dob_each(
+        decoder,
+        hexed_spore_ids.clone(),
+        options,
+        concurrency,
+        |index, result| {
+            results[index] = Some(result);
+            true
+        },
+    )
+    .await;
+    hexed_spore_ids
+        .into_iter()
+        .zip(results.into_iter().map(Option::unwrap))
+        .collect()
+}
+
+// decode a batch of spores like `batch_decode_dob`, but hand over each result along with its
+// index in request as soon as it's ready, no more spores are started once `on_result` says no
+pub async fn batch_decode_dob_each(
+    decoder: &DOBDecoder,
+    hexed_spore_ids: Vec<String>,
+    options: &DecodeOptions,
+    concurrency: &AdaptiveConcurrency,
+    mut on_result: impl FnMut(usize, Result<ServerDecodeResult, DecodeFailure>) -> bool,
+) {
+    let mut pending_spore_ids = hexed_spore_ids.into_iter().enumerate();
+    let mut pending_results = FuturesUnordered::new();
+    let contexts = DecodeContexts::default();
+    let contexts = &contexts;
+    let mut proceeding = true;
+    loop {
+        while proceeding && pending_results.len() < concurrency.limit() {
+            let Some((index, hexed_spore_id)) = pending_spore_ids.next() else {
+                break;
+            };
+            pending_results.push(async move {
+                let start = Instant::now();
+                let result =
+                    decode_dob_with_contexts(decoder, hexed_spore_id, options, contexts).await;
+                (index, start.elapsed(), result)
+            });
+        }
+        let Some((index, latency, result)) = pending_results.next().await else {
+            break;
+        };
+        concurrency.record(latency, result.as_ref().err().map(|failure| failure.error));
+        proceeding &= on_result(index, result);
+    }
+}
+
+// shard cache entries into two-level prefix directories, like `ab/cd/abcd...ef.dob`, to avoid
+// a crawling flat directory for large collections
+#[cfg(not(feature = "shuttle"))]
+pub fn dob_cache_path(cache_directory: &Path, spore_id: &[u8; 32]) -> PathBuf {
+    let hexed_spore_id = hex::encode(spore_id);
+    let mut cache_path = cache_directory.to_path_buf();
+    cache_path.push(&hexed_spore_id[0..2]);
+    cache_path.push(&hexed_spore_id[2..4]);
+    cache_path.push(format!("{hexed_spore_id}.dob"));
+    cache_path
+}
+
+// move cache entry from legacy flat directory into its sharded location if present
+#[cfg(not(feature = "shuttle"))]
+fn migrate_legacy_dob_cache(cache_directory: &Path, spore_id: &[u8; 32]) {
+    let mut legacy_cache_path = cache_directory.to_path_buf();
+    legacy_cache_path.push(format!("{}.dob", hex::encode(spore_id)));
+    if !legacy_cache_path.exists() {
+        return;
+    }
+    let cache_path = dob_cache_path(cache_directory, spore_id);
+    if let Some(shard_directory) = cache_path.parent() {
+        let _ = fs::create_dir_all(shard_directory);
+    }
+    if let Err(error) = fs::rename(&legacy_cache_path, &cache_path) {
+        tracing::warn!("failed to migrate legacy cache {legacy_cache_path:?}: {error}");
     }
-    futures::future::join_all(await_results).await
 }
 
 // no shuttle version
 #[cfg(not(feature = "shuttle"))]
-pub fn read_dob_from_cache(cache_path: PathBuf) -> Result<(String, Value), Error> {
-    let file_content = fs::read_to_string(cache_path).map_err(|_| Error::DOBRenderCacheNotFound)?;
+pub fn dob_cache_status(decoder: &DOBDecoder, spore_id: &[u8; 32]) -> DOBCacheStatus {
+    let cache_directory = &decoder.setting().dobs_cache_directory;
+    migrate_legacy_dob_cache(cache_directory, spore_id);
+    let cache_path = dob_cache_path(cache_directory, spore_id);
+    let cache_info = read_dob_from_cache(cache_path)
+        .ok()
+        .map(|(_, _, cache_info)| cache_info);
+    DOBCacheStatus {
+        cached: cache_info.is_some(),
+        cache_info,
+    }
+}
+
+// time when render result of the spore was cached
+#[cfg(not(feature = "shuttle"))]
+pub fn dob_cache_modified(decoder: &DOBDecoder, spore_id: &[u8; 32]) -> Option<SystemTime> {
+    let cache_path = dob_cache_path(&decoder.setting().dobs_cache_directory, spore_id);
+    fs::metadata(cache_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// shuttle persist doesn't record modification time of its entries
+#[cfg(feature = "shuttle")]
+pub fn dob_cache_modified(_decoder: &DOBDecoder, _spore_id: &[u8; 32]) -> Option<SystemTime> {
+    None
+}
+
+// shuttle version
+#[cfg(feature = "shuttle")]
+pub fn dob_cache_status(decoder: &DOBDecoder, spore_id: &[u8; 32]) -> DOBCacheStatus {
+    let cache_path = format!("{}.dob", hex::encode(spore_id));
+    let cache_info = read_dob_from_cache(cache_path, &decoder.persist)
+        .ok()
+        .map(|(_, _, cache_info)| cache_info);
+    DOBCacheStatus {
+        cached: cache_info.is_some(),
+        cache_info,
+    }
+}
+
+// cache entry is composed of render result, DOB content and optional cache info in lines
+pub(crate) fn parse_dob_cache_content(
+    file_content: &str,
+) -> Result<(String, Value, DOBCacheInfo), Error> {
     let mut lines = file_content.split('\n');
     let (Some(result), Some(content)) = (lines.next(), lines.next()) else {
         return Err(Error::DOBRenderCacheModified);
     };
-    match serde_json::from_str(content) {
-        Ok(content) => Ok((result.to_string(), content)),
-        Err(_) => Err(Error::DOBRenderCacheModified),
-    }
+    let content = serde_json::from_str(content).map_err(|_| Error::DOBRenderCacheModified)?;
+    let cache_info = match lines.next() {
+        Some(cache_info) => {
+            serde_json::from_str(cache_info).map_err(|_| Error::DOBRenderCacheModified)?
+        }
+        None => DOBCacheInfo::default(),
+    };
+    Ok((result.to_string(), content, cache_info))
+}
+
+// no shuttle version
+#[cfg(not(feature = "shuttle"))]
+pub fn read_dob_from_cache(cache_path: PathBuf) -> Result<(String, Value, DOBCacheInfo), Error> {
+    let file_content = fs::read_to_string(cache_path).map_err(|_| Error::DOBRenderCacheNotFound)?;
+    parse_dob_cache_content(&file_content)
 }
 
 // shuttle version
@@ -146,18 +1993,11 @@ pub fn read_dob_from_cache(cache_path: PathBuf) -> Result<(String, Value), Error
 pub fn read_dob_from_cache(
     cache_path: String,
     persist: &PersistInstance,
-) -> Result<(String, Value), Error> {
+) -> Result<(String, Value, DOBCacheInfo), Error> {
     let file_content: String = persist
         .load::<String>(cache_path.as_str())
         .map_err(|_| Error::DOBRenderCacheNotFound)?;
-    let mut lines = file_content.split('\n');
-    let (Some(result), Some(content)) = (lines.next(), lines.next()) else {
-        return Err(Error::DOBRenderCacheModified);
-    };
-    match serde_json::from_str(content) {
-        Ok(content) => Ok((result.to_string(), content)),
-        Err(_) => Err(Error::DOBRenderCacheModified),
-    }
+    parse_dob_cache_content(&file_content)
 }
 
 // no shuttle version
@@ -165,10 +2005,15 @@ pub fn read_dob_from_cache(
 pub fn write_dob_to_cache(
     render_result: &str,
     dob_content: &Value,
+    cache_info: &DOBCacheInfo,
     cache_path: PathBuf,
 ) -> Result<(), Error> {
     let json_dob_content = serde_json::to_string(dob_content).unwrap();
-    let file_content = format!("{render_result}\n{json_dob_content}");
+    let json_cache_info = serde_json::to_string(cache_info).unwrap();
+    let file_content = format!("{render_result}\n{json_dob_content}\n{json_cache_info}");
+    if let Some(shard_directory) = cache_path.parent() {
+        fs::create_dir_all(shard_directory).map_err(|_| Error::DOBRenderCacheNotFound)?;
+    }
     fs::write(cache_path, file_content).map_err(|_| Error::DOBRenderCacheNotFound)?;
     Ok(())
 }
@@ -178,12 +2023,14 @@ pub fn write_dob_to_cache(
 pub fn write_dob_to_cache(
     render_result: &str,
     dob_content: &Value,
+    cache_info: &DOBCacheInfo,
     cache_path: String,
     persist: &PersistInstance,
 ) -> Result<(), Error> {
     let json_dob_content = serde_json::to_string(dob_content).unwrap();
-    let file_content = format!("{render_result}\n{json_dob_content}");
-    println!("save to persist! cache_path: {:?}", cache_path);
+    let json_cache_info = serde_json::to_string(cache_info).unwrap();
+    let file_content = format!("{render_result}\n{json_dob_content}\n{json_cache_info}");
+    tracing::debug!("save to persist! cache_path: {cache_path:?}");
     persist
         .save::<String>(cache_path.as_str(), file_content)
         .map_err(|_| Error::DOBRenderCacheNotFound)?;
@@ -0,0 +1,208 @@
+use std::str::FromStr;
+
+use ckb_types::H256;
+use jsonrpsee::core::async_trait;
+use jsonrpsee::{proc_macros::rpc, types::ErrorObjectOwned};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::server::{
+    batch_decode_dob, decode_dob, ensure_cluster_access, DecodeMetadata, DecodeOptions,
+    DecoderStandaloneServer, ServerDecodeResult, SporeIdParam, SporeOwner,
+};
+use crate::types::{DecodeFailure, DecodeStage, Error, ProtocolVersionInfo};
+
+// decoding result in v2 envelope, which always carries the normalized spore id
+#[derive(Serialize, Clone, Debug)]
+pub struct DecodeResponse {
+    pub spore_id: H256,
+    pub render_output: Value,
+    pub dob_content: Value,
+    pub provisional: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<DecodeMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<SporeOwner>,
+}
+
+impl DecodeResponse {
+    fn new(spore_id: H256, result: ServerDecodeResult) -> Self {
+        Self {
+            spore_id,
+            render_output: result.render_output,
+            dob_content: result.dob_content,
+            provisional: result.provisional,
+            metadata: result.metadata,
+            owner: result.owner,
+        }
+    }
+}
+
+// item of v2 batch decoding, with either `result` or structured `error` present
+#[derive(Serialize, Clone, Debug)]
+pub struct BatchDecodeItem {
+    pub spore_id: SporeIdParam,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<DecodeResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorObjectOwned>,
+}
+
+// v2 methods take params in object, like `{"spore_id": "0x..", "include_metadata": true}`, and
+// positional params are accepted as well, while `dob_*` methods are kept untouched, so options
+// introduced later are only added here
+#[rpc(server, namespace = "dob2")]
+trait DecoderRpcV2 {
+    #[method(name = "protocol_versions")]
+    async fn protocol_versions(&self) -> Vec<ProtocolVersionInfo>;
+
+    #[method(name = "decode")]
+    async fn decode(
+        &self,
+        spore_id: SporeIdParam,
+        anchor_block_hash: Option<String>,
+        include_metadata: Option<bool>,
+        traits: Option<Vec<String>>,
+        network: Option<String>,
+        include_owner: Option<bool>,
+        refresh: Option<bool>,
+    ) -> Result<DecodeResponse, ErrorObjectOwned>;
+
+    #[method(name = "decode_by_serial")]
+    async fn decode_by_serial(
+        &self,
+        cluster_id: String,
+        serial: u64,
+        anchor_block_hash: Option<String>,
+        include_metadata: Option<bool>,
+        traits: Option<Vec<String>>,
+        network: Option<String>,
+    ) -> Result<DecodeResponse, ErrorObjectOwned>;
+
+    #[method(name = "batch_decode")]
+    async fn batch_decode(
+        &self,
+        spore_ids: Vec<SporeIdParam>,
+        anchor_block_hash: Option<String>,
+        include_metadata: Option<bool>,
+        traits: Option<Vec<String>>,
+        network: Option<String>,
+    ) -> Result<Vec<BatchDecodeItem>, ErrorObjectOwned>;
+}
+
+#[async_trait]
+impl DecoderRpcV2Server for DecoderStandaloneServer {
+    async fn protocol_versions(&self) -> Vec<ProtocolVersionInfo> {
+        self.decoder().protocol_version_infos()
+    }
+
+    async fn decode(
+        &self,
+        spore_id: SporeIdParam,
+        anchor_block_hash: Option<String>,
+        include_metadata: Option<bool>,
+        traits: Option<Vec<String>>,
+        network: Option<String>,
+        include_owner: Option<bool>,
+        refresh: Option<bool>,
+    ) -> Result<DecodeResponse, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let options = DecodeOptions {
+            anchor_block_hash,
+            include_metadata: include_metadata.unwrap_or_default(),
+            traits,
+            include_owner: include_owner.unwrap_or_default(),
+            refresh: refresh.unwrap_or_default(),
+            ..Default::default()
+        };
+        let hexed_spore_id = spore_id.to_hex();
+        let result = decode_dob(decoder, hexed_spore_id.clone(), &options).await?;
+        Ok(DecodeResponse::new(
+            normalize_spore_id(&hexed_spore_id),
+            result,
+        ))
+    }
+
+    // resolve spore like "Unicorn #123" by its serial in cluster, then decode it
+    async fn decode_by_serial(
+        &self,
+        cluster_id: String,
+        serial: u64,
+        anchor_block_hash: Option<String>,
+        include_metadata: Option<bool>,
+        traits: Option<Vec<String>>,
+        network: Option<String>,
+    ) -> Result<DecodeResponse, ErrorObjectOwned> {
+        let decoder = self.network_decoder(network.as_deref())?;
+        let hexed_cluster_id = cluster_id.strip_prefix("0x").unwrap_or(&cluster_id);
+        let cluster_id = H256::from_str(hexed_cluster_id).map_err(|_| {
+            DecodeFailure::new(Error::HexedClusterIdParseError, DecodeStage::ParseRequest)
+        })?;
+        ensure_cluster_access(&cluster_id.0)?;
+        let spore_id = decoder
+            .resolve_spore_by_serial(cluster_id.0, serial)
+            .await
+            .map_err(|error| {
+                DecodeFailure::new(error, DecodeStage::FetchSpore).with_cluster_id(cluster_id.0)
+            })?;
+        let options = DecodeOptions {
+            anchor_block_hash,
+            include_metadata: include_metadata.unwrap_or_default(),
+            traits,
+            ..Default::default()
+        };
+        let result = decode_dob(decoder, hex::encode(spore_id), &options).await?;
+        Ok(DecodeResponse::new(spore_id.into(), result))
+    }
+
+    async fn batch_decode(
+        &self,
+        spore_ids: Vec<SporeIdParam>,
+        anchor_block_hash: Option<String>,
+        include_metadata: Option<bool>,
+        traits: Option<Vec<String>>,
+        network: Option<String>,
+    ) -> Result<Vec<BatchDecodeItem>, ErrorObjectOwned> {
+        self.check_batch_size(spore_ids.len())?;
+        let decoder = self.network_decoder(network.as_deref())?;
+        let options = DecodeOptions {
+            anchor_block_hash,
+            include_metadata: include_metadata.unwrap_or_default(),
+            traits,
+            ..Default::default()
+        };
+        let results = batch_decode_dob(
+            decoder,
+            spore_ids.iter().map(SporeIdParam::to_hex).collect(),
+            &options,
+            self.batch_concurrency(),
+        )
+        .await;
+        let items = results
+            .into_iter()
+            .zip(spore_ids)
+            .map(|((hexed_spore_id, result), spore_id)| match result {
+                Ok(result) => BatchDecodeItem {
+                    spore_id,
+                    result: Some(DecodeResponse::new(
+                        normalize_spore_id(&hexed_spore_id),
+                        result,
+                    )),
+                    error: None,
+                },
+                Err(failure) => BatchDecodeItem {
+                    spore_id,
+                    result: None,
+                    error: Some(failure.into()),
+                },
+            })
+            .collect();
+        Ok(items)
+    }
+}
+
+// only called on spore ids which have been decoded successfully, so they must be valid
+fn normalize_spore_id(hexed_spore_id: &str) -> H256 {
+    let hexed_spore_id = hexed_spore_id.strip_prefix("0x").unwrap_or(hexed_spore_id);
+    H256::from_str(hexed_spore_id).expect("decoded spore id")
+}
@@ -0,0 +1,71 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::time::Duration;
+
+use ckb_types::H256;
+use jsonrpsee::tracing;
+
+// phases of one decode timed along the way, collected through a task local so that timings don't
+// have to be passed down through the whole pipeline
+#[derive(Debug, Default, Clone)]
+pub struct DecodeTrace {
+    pub phases: Vec<(&'static str, Duration)>,
+    pub cluster_id: Option<H256>,
+    pub decoder_hash: Option<H256>,
+}
+
+tokio::task_local! {
+    static DECODE_TRACE: RefCell<DecodeTrace>;
+}
+
+// run one decode while collecting its trace, decodes running concurrently in a batch are traced
+// separately
+pub async fn trace_decode<F: Future>(decode: F) -> (F::Output, DecodeTrace) {
+    DECODE_TRACE
+        .scope(RefCell::default(), async {
+            let output = decode.await;
+            (output, DECODE_TRACE.with(RefCell::take))
+        })
+        .await
+}
+
+// nothing is recorded outside of `trace_decode`, like decoding in blocking threads
+pub fn record_phase(phase: &'static str, latency: Duration) {
+    let _ = DECODE_TRACE.try_with(|trace| trace.borrow_mut().phases.push((phase, latency)));
+}
+
+// cluster is known even if the result comes from render cache
+pub fn record_cluster(cluster_id: H256) {
+    let _ = DECODE_TRACE.try_with(|trace| trace.borrow_mut().cluster_id = Some(cluster_id));
+}
+
+pub fn record_decoder(cluster_id: [u8; 32], decoder_hash: Option<H256>) {
+    let _ = DECODE_TRACE.try_with(|trace| {
+        let mut trace = trace.borrow_mut();
+        trace.cluster_id = Some(cluster_id.into());
+        trace.decoder_hash = decoder_hash;
+    });
+}
+
+// warn about decodes taking longer than `slow_decode_threshold_ms`, with timings of each phase
+// telling whether the indexer or the decoder is to blame
+pub fn log_slow_decode(spore_id: &[u8; 32], elapsed: Duration, trace: &DecodeTrace) {
+    let hexed = |hash: &Option<H256>| match hash {
+        Some(hash) => format!("0x{hash}"),
+        None => "unknown".to_string(),
+    };
+    let phases = trace
+        .phases
+        .iter()
+        .map(|(phase, latency)| format!("{phase}={}ms", latency.as_millis()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    tracing::warn!(
+        spore_id = %format!("0x{}", hex::encode(spore_id)),
+        cluster_id = %hexed(&trace.cluster_id),
+        decoder_hash = %hexed(&trace.decoder_hash),
+        elapsed_ms = elapsed.as_millis() as u64,
+        phases = %phases,
+        "slow decode"
+    );
+}
@@ -0,0 +1,139 @@
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ckb_types::H256;
+use lru::LruCache;
+use serde::Serialize;
+
+use crate::types::{DecodeFailure, DecodeStage};
+
+// max clusters tracked, the least recently requested ones are dropped beyond
+const CLUSTER_STATS_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Default)]
+struct ClusterCounters {
+    requests: u64,
+    cache_hits: u64,
+    errors: u64,
+    executions: u64,
+    execution_time: Duration,
+    last_error: Option<ClusterDecodeError>,
+}
+
+// the latest decoding failure of spores in the cluster
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClusterDecodeError {
+    pub code: i32,
+    pub message: String,
+    pub stage: DecodeStage,
+    // unix timestamp in seconds
+    pub occurred_at: u64,
+}
+
+// decoding statistics of a cluster since server started
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ClusterStatsReport {
+    pub requests: u64,
+    pub cache_hits: u64,
+    pub cache_hit_ratio: f64,
+    pub errors: u64,
+    pub executions: u64,
+    pub average_execution_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_decode_error: Option<ClusterDecodeError>,
+}
+
+// statistics of a cluster listed along with others
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ClusterStatsEntry {
+    pub cluster_id: H256,
+    #[serde(flatten)]
+    pub report: ClusterStatsReport,
+}
+
+impl ClusterCounters {
+    fn report(&self) -> ClusterStatsReport {
+        let ratio = |part: f64, whole: u64| if whole == 0 { 0.0 } else { part / whole as f64 };
+        ClusterStatsReport {
+            requests: self.requests,
+            cache_hits: self.cache_hits,
+            cache_hit_ratio: ratio(self.cache_hits as f64, self.requests),
+            errors: self.errors,
+            executions: self.executions,
+            average_execution_ms: ratio(
+                self.execution_time.as_secs_f64() * 1000.0,
+                self.executions,
+            ),
+            last_decode_error: self.last_error.clone(),
+        }
+    }
+}
+
+// per-cluster counters for collection teams monitoring health of their own collections
+pub struct ClusterStats(Mutex<LruCache<[u8; 32], ClusterCounters>>);
+
+impl Default for ClusterStats {
+    fn default() -> Self {
+        Self(Mutex::new(LruCache::new(
+            NonZeroUsize::new(CLUSTER_STATS_CAPACITY).unwrap(),
+        )))
+    }
+}
+
+impl ClusterStats {
+    pub fn record_request(&self, cluster_id: &[u8; 32], cache_hit: bool) {
+        let mut stats = self.0.lock().unwrap();
+        let counters = stats.get_or_insert_mut(*cluster_id, ClusterCounters::default);
+        counters.requests += 1;
+        if cache_hit {
+            counters.cache_hits += 1;
+        }
+    }
+
+    pub fn record_execution(&self, cluster_id: &[u8; 32], execution_time: Duration) {
+        let mut stats = self.0.lock().unwrap();
+        let counters = stats.get_or_insert_mut(*cluster_id, ClusterCounters::default);
+        counters.executions += 1;
+        counters.execution_time += execution_time;
+    }
+
+    // failed requests are counted as well, only failures after cluster is known are recorded
+    pub fn record_failure(&self, failure: &DecodeFailure) {
+        let Some(cluster_id) = &failure.cluster_id else {
+            return;
+        };
+        let mut stats = self.0.lock().unwrap();
+        let counters = stats.get_or_insert_mut(cluster_id.0, ClusterCounters::default);
+        counters.requests += 1;
+        counters.errors += 1;
+        counters.last_error = Some(ClusterDecodeError {
+            code: failure.error as i32,
+            message: failure.error.to_string(),
+            stage: failure.stage,
+            occurred_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        });
+    }
+
+    pub fn report(&self, cluster_id: &[u8; 32]) -> Option<ClusterStatsReport> {
+        let stats = self.0.lock().unwrap();
+        stats.peek(cluster_id).map(ClusterCounters::report)
+    }
+
+    // all tracked clusters, the busiest first, for telling which collections generate the load
+    pub fn reports(&self) -> Vec<ClusterStatsEntry> {
+        let stats = self.0.lock().unwrap();
+        let mut reports = stats
+            .iter()
+            .map(|(cluster_id, counters)| ClusterStatsEntry {
+                cluster_id: H256(*cluster_id),
+                report: counters.report(),
+            })
+            .collect::<Vec<_>>();
+        reports.sort_by(|a, b| b.report.requests.cmp(&a.report.requests));
+        reports
+    }
+}
@@ -0,0 +1,102 @@
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use jsonrpsee::tracing;
+
+use crate::types::{StatsdFlavor, StatsdSettings};
+
+// push metrics to a StatsD or DogStatsD agent over UDP, each one in its own datagram, losing a
+// few of them is acceptable by design of StatsD, so sending never blocks nor fails decoding
+pub struct StatsdClient {
+    socket: UdpSocket,
+    prefix: String,
+    flavor: StatsdFlavor,
+    tags: Vec<String>,
+}
+
+impl StatsdClient {
+    pub fn new(settings: &StatsdSettings) -> std::io::Result<Self> {
+        let address = settings
+            .address
+            .to_socket_addrs()?
+            .next()
+            .ok_or(std::io::ErrorKind::AddrNotAvailable)?;
+        let local_address: SocketAddr = match address {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+        let socket = UdpSocket::bind(local_address)?;
+        socket.connect(address)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            prefix: settings.prefix.clone(),
+            flavor: settings.flavor,
+            tags: settings.tags.clone(),
+        })
+    }
+
+    pub fn count(&self, name: &str, value: u64, tags: &[(&str, &str)]) {
+        self.send(name, &value.to_string(), "c", tags);
+    }
+
+    pub fn timing(&self, name: &str, latency: Duration, tags: &[(&str, &str)]) {
+        let millis = format!("{:.3}", latency.as_secs_f64() * 1000.0);
+        self.send(name, &millis, "ms", tags);
+    }
+
+    fn send(&self, name: &str, value: &str, kind: &str, tags: &[(&str, &str)]) {
+        let metric = format_metric(
+            &self.prefix,
+            self.flavor,
+            &self.tags,
+            name,
+            value,
+            kind,
+            tags,
+        );
+        if let Err(error) = self.socket.send(metric.as_bytes()) {
+            tracing::debug!("failed to send metric {metric}: {error}");
+        }
+    }
+}
+
+// DogStatsD carries tags after `|#`, while plain StatsD has no tags, so their values are
+// appended to the metric name instead, like `dob.render_cache.hit:1|c`
+pub fn format_metric(
+    prefix: &str,
+    flavor: StatsdFlavor,
+    constant_tags: &[String],
+    name: &str,
+    value: &str,
+    kind: &str,
+    tags: &[(&str, &str)],
+) -> String {
+    let mut metric_name = if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{prefix}.{name}")
+    };
+    match flavor {
+        StatsdFlavor::Statsd => {
+            tags.iter()
+                .for_each(|(_, tag_value)| metric_name.push_str(&format!(".{tag_value}")));
+            format!("{metric_name}:{value}|{kind}")
+        }
+        StatsdFlavor::Datadog => {
+            let tags = constant_tags
+                .iter()
+                .cloned()
+                .chain(
+                    tags.iter()
+                        .map(|(key, tag_value)| format!("{key}:{tag_value}")),
+                )
+                .collect::<Vec<_>>();
+            if tags.is_empty() {
+                format!("{metric_name}:{value}|{kind}")
+            } else {
+                format!("{metric_name}:{value}|{kind}|#{}", tags.join(","))
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::os::unix::net::UnixDatagram;
+use std::sync::Arc;
+use std::time::Duration;
+
+use jsonrpsee::tracing;
+
+use crate::decoder::DOBDecoder;
+use crate::health::check_ckb_rpc;
+use crate::vm;
+
+// interval of retrying CKB health check before notifying readiness
+const READY_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+// send state like `READY=1` to `NOTIFY_SOCKET`, nothing is sent if not started by systemd
+pub fn notify(state: &str) -> std::io::Result<()> {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(());
+    };
+    let socket = UnixDatagram::unbound()?;
+    match path.to_str().and_then(|path| path.strip_prefix('@')) {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &address)?;
+        }
+        _ => {
+            socket.send_to(state.as_bytes(), &path)?;
+        }
+    }
+    Ok(())
+}
+
+// pinging interval expected by `WatchdogSec=` of the unit, half of it as systemd suggests, none if
+// watchdog is not enabled for this process
+pub fn watchdog_interval() -> Option<Duration> {
+    parse_watchdog_interval(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+pub fn parse_watchdog_interval(
+    usec: Option<&str>,
+    pid: Option<&str>,
+    own_pid: u32,
+) -> Option<Duration> {
+    if pid.is_some_and(|pid| pid.parse::<u32>().ok() != Some(own_pid)) {
+        return None;
+    }
+    let usec: u64 = usec?.parse().ok().filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+// tell systemd the server is ready once CKB node answers, for units of `Type=notify`, and then ping
+// watchdog as long as VM is still able to run, so that a hung VM loop gets the process restarted
+pub async fn run(decoder: Arc<DOBDecoder>) {
+    while let Err(error) = check_ckb_rpc(&decoder).await {
+        tracing::warn!("waiting for CKB node before notifying systemd: {error}");
+        tokio::time::sleep(READY_RETRY_INTERVAL).await;
+    }
+    match notify("READY=1") {
+        Ok(()) => tracing::info!("notified systemd of readiness"),
+        Err(error) => tracing::error!("failed to notify systemd: {error}"),
+    }
+    let Some(watchdog_interval) = watchdog_interval() else {
+        return;
+    };
+    tracing::info!("pinging systemd watchdog every {watchdog_interval:?}");
+    let mut interval = tokio::time::interval(watchdog_interval);
+    loop {
+        interval.tick().await;
+        // VM runs on blocking threads, which are all taken if decodings are stuck
+        let smoke_test = tokio::time::timeout(
+            watchdog_interval,
+            tokio::task::spawn_blocking(vm::smoke_test),
+        )
+        .await;
+        match smoke_test {
+            Ok(Ok(Ok(()))) => {
+                if let Err(error) = notify("WATCHDOG=1") {
+                    tracing::error!("failed to ping systemd watchdog: {error}");
+                }
+            }
+            Ok(Ok(Err(error))) => tracing::error!("VM smoke test failed, skip watchdog: {error}"),
+            Ok(Err(error)) => tracing::error!("VM smoke test panicked, skip watchdog: {error}"),
+            Err(_) => tracing::error!("VM smoke test timed out, skip watchdog"),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use jsonrpsee::tracing::Subscriber;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::types::OpenTelemetrySettings;
+
+// spans of this crate only, so that those of dependencies don't flood the collector
+const EXPORTED_SPANS: &str = "dob_decoder_server=info";
+
+// export spans through OTLP over gRPC, batch exporter runs on the tokio runtime, which must have
+// been entered before
+pub fn opentelemetry_layer<S>(settings: &OpenTelemetrySettings) -> impl Layer<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&settings.endpoint),
+        )
+        .with_trace_config(
+            trace::config()
+                .with_sampler(trace::Sampler::TraceIdRatioBased(settings.sample_ratio))
+                .with_resource(Resource::new([KeyValue::new(
+                    "service.name",
+                    settings.service_name.clone(),
+                )])),
+        )
+        .install_batch(runtime::Tokio)
+        .expect("install opentelemetry pipeline");
+    tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(EnvFilter::new(EXPORTED_SPANS))
+}
+
+// flush spans not exported yet before exiting
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use serde_json::json;
+
+use crate::assets::{
+    collect_asset_uris, parse_inscriptions, rewrite_asset_uris, Asset, InscriptionId,
+};
+use crate::types::Error;
+
+#[test]
+fn test_parse_inscription_id() {
+    let txid = "b1ef66c2d1a047cbaa6260b74daac43813924378fe08ef8545da4cb79e8fcf00";
+    assert_eq!(
+        InscriptionId::parse(&format!("{txid}i0")),
+        Ok(InscriptionId {
+            txid: txid.to_string(),
+            index: 0,
+        })
+    );
+    assert_eq!(
+        InscriptionId::parse(&format!("{txid}i12")).map(|id| id.to_string()),
+        Ok(format!("{txid}i12"))
+    );
+    assert_eq!(InscriptionId::parse(txid), Err(Error::AssetUriInvalid));
+    assert_eq!(InscriptionId::parse("abcdi0"), Err(Error::AssetUriInvalid));
+}
+
+#[test]
+fn test_parse_inscriptions() {
+    // <pubkey> OP_CHECKSIG OP_FALSE OP_IF "ord" OP_1 "image/svg+xml" OP_0 "<svg>" "</svg>" OP_ENDIF
+    let mut script = vec![0x20];
+    script.extend_from_slice(&[0xab; 32]);
+    script.extend_from_slice(&[0xac, 0x00, 0x63, 0x03]);
+    script.extend_from_slice(b"ord");
+    script.extend_from_slice(&[0x51, 0x0d]);
+    script.extend_from_slice(b"image/svg+xml");
+    script.extend_from_slice(&[0x00, 0x05]);
+    script.extend_from_slice(b"<svg>");
+    script.extend_from_slice(&[0x4c, 0x06]);
+    script.extend_from_slice(b"</svg>");
+    script.push(0x68);
+    assert_eq!(
+        parse_inscriptions(&script),
+        vec![Asset {
+            content_type: "image/svg+xml".to_string(),
+            content: b"<svg></svg>".to_vec(),
+        }]
+    );
+    assert!(parse_inscriptions(&[0x00, 0x63, 0x68]).is_empty());
+}
+
+#[test]
+fn test_rewrite_asset_uris() {
+    let inscription = "btcfs://b1ef66c2d1a047cbaa6260b74daac43813924378fe08ef8545da4cb79e8fcf00i0";
+    let mut render_output = json!([
+        { "name": "prev.bg", "traits": [{ "String": inscription }] },
+        { "name": "Background", "traits": [{ "String": "ipfs://bafybeigdyrzt/bg.png" }] },
+        { "name": "Layer", "traits": [{ "String": inscription }] },
+        { "name": "Name", "traits": [{ "String": "see https://" }] },
+        { "name": "Level", "traits": [{ "Number": 3 }] },
+    ]);
+    assert_eq!(
+        collect_asset_uris(&render_output),
+        vec![inscription, "ipfs://bafybeigdyrzt/bg.png"]
+    );
+    let local_urls = HashMap::from([(inscription.to_string(), "/assets/content/ab".to_string())]);
+    rewrite_asset_uris(&mut render_output, &local_urls);
+    assert_eq!(
+        render_output[0]["traits"][0]["String"],
+        "/assets/content/ab"
+    );
+    assert_eq!(
+        render_output[1]["traits"][0]["String"],
+        "ipfs://bafybeigdyrzt/bg.png"
+    );
+    assert_eq!(
+        render_output[2]["traits"][0]["String"],
+        "/assets/content/ab"
+    );
+}
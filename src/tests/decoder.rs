@@ -1,11 +1,20 @@
 use ckb_types::{h256, H256};
 use serde_json::{json, Value};
 
-use crate::decoder::DOBDecoder;
-use crate::tests::prepare_settings;
+use crate::decoder::{
+    merge_render_outputs, parse_cluster_data, parse_decoder_file_name, parse_spore_data, DOBDecoder,
+};
+use crate::registry::{parse_decoder_registry, DecoderRegistry};
+use crate::tests::{
+    encode_molecule_table, mock_live_cell, prepare_directory, prepare_settings, serve_mock_ckb_rpc,
+};
 use crate::types::{
-    ClusterDescriptionField, DOBClusterFormat, DOBDecoderFormat, DecoderLocationType,
+    ClusterDescriptionField, DOBClusterFormat, DOBDecoderFormat, DecodePipeline,
+    DecoderChunkLocation, DecoderLocationType, Error, OnchainDecoderDeployment,
+    ProtocolVersionInfo, ProtocolVersionMatching, RawDecoderParam,
 };
+use crate::validation::{pattern_dna_bytes, validate_metadata, MetadataCheck};
+use crate::vm;
 
 const EXPECTED_UNICORN_RENDER_RESULT: &str = "[{\"name\":\"wuxing_yinyang\",\"traits\":[{\"String\":\"3<_>\"}]},{\"name\":\"prev.bgcolor\",\"traits\":[{\"String\":\"(%wuxing_yinyang):['#DBAB00', '#09D3FF', '#A028E9', '#FF3939', '#(135deg, #FE4F4F, #66C084, #00E2E2, #E180E2, #F4EC32)']\"}]},{\"name\":\"prev<%v>\",\"traits\":[{\"String\":\"(%wuxing_yinyang):['#000000', '#000000', '#000000', '#000000', '#000000', '#FFFFFF', '#FFFFFF', '#FFFFFF', '#FFFFFF', '#FFFFFF'])\"}]},{\"name\":\"Spirits\",\"traits\":[{\"String\":\"(%wuxing_yinyang):['Metal, Golden Body', 'Wood, Blue Body', 'Water, White Body', 'Fire, Red Body', 'Earth, Colorful Body']\"}]},{\"name\":\"Yin Yang\",\"traits\":[{\"String\":\"(%wuxing_yinyang):['Yin, Long hair', 'Yin, Long hair', 'Yin, Long hair', 'Yin, Long hair', 'Yin, Long hair', 'Yang, Short Hair', 'Yang, Short Hair', 'Yang, Short Hair', 'Yang, Short Hair', 'Yang, Short Hair']\"}]},{\"name\":\"Talents\",\"traits\":[{\"String\":\"(%wuxing_yinyang):['Guard<~>', 'Death<~>', 'Forget<~>', 'Curse<~>', 'Hermit<~>', 'Attack<~>', 'Revival<~>', 'Summon<~>', 'Prophet<~>', 'Crown<~>']\"}]},{\"name\":\"Horn\",\"traits\":[{\"String\":\"(%wuxing_yinyang):['Praetorian Horn', 'Hel Horn', 'Lethe Horn', 'Necromancer Horn', 'Lao Tsu Horn', 'Warrior Horn', 'Shaman Horn', 'Bard Horn', 'Sibyl Horn', 'Caesar Horn']\"}]},{\"name\":\"Wings\",\"traits\":[{\"String\":\"Sun Wings\"}]},{\"name\":\"Tail\",\"traits\":[{\"String\":\"Meteor Tail\"}]},{\"name\":\"Horseshoes\",\"traits\":[{\"String\":\"Silver Horseshoes\"}]},{\"name\":\"Destiny Number\",\"traits\":[{\"Number\":65321}]},{\"name\":\"Lucky Number\",\"traits\":[{\"Number\":35}]}]";
 const EXPECTED_EXAMPLE_RENDER_RESULT: &str = "[{\"name\":\"Name\",\"traits\":[{\"String\":\"Ethan\"}]},{\"name\":\"Age\",\"traits\":[{\"Number\":23}]},{\"name\":\"Score\",\"traits\":[{\"Number\":136}]},{\"name\":\"DNA\",\"traits\":[{\"String\":\"0xaabbcc\"}]},{\"name\":\"URL\",\"traits\":[{\"String\":\"http://127.0.0.1:8090\"}]},{\"name\":\"Value\",\"traits\":[{\"Number\":13417386}]}]";
@@ -38,6 +47,7 @@ fn generate_unicorn_dob_ingredients(onchain_decoder: bool) -> (Value, ClusterDes
                 ver: Some(0),
                 decoder,
                 pattern: serde_json::from_str("[[\"wuxing_yinyang\",\"string\",0,1,\"options\",[\"0<_>\",\"1<_>\",\"2<_>\",\"3<_>\",\"4<_>\",\"5<_>\",\"6<_>\",\"7<_>\",\"8<_>\",\"9<_>\"]],[\"prev.bgcolor\",\"string\",1,1,\"options\",[\"(%wuxing_yinyang):['#DBAB00', '#09D3FF', '#A028E9', '#FF3939', '#(135deg, #FE4F4F, #66C084, #00E2E2, #E180E2, #F4EC32)']\"]],[\"prev<%v>\",\"string\",2,1,\"options\",[\"(%wuxing_yinyang):['#000000', '#000000', '#000000', '#000000', '#000000', '#FFFFFF', '#FFFFFF', '#FFFFFF', '#FFFFFF', '#FFFFFF'])\"]],[\"Spirits\",\"string\",3,1,\"options\",[\"(%wuxing_yinyang):['Metal, Golden Body', 'Wood, Blue Body', 'Water, White Body', 'Fire, Red Body', 'Earth, Colorful Body']\"]],[\"Yin Yang\",\"string\",4,1,\"options\",[\"(%wuxing_yinyang):['Yin, Long hair', 'Yin, Long hair', 'Yin, Long hair', 'Yin, Long hair', 'Yin, Long hair', 'Yang, Short Hair', 'Yang, Short Hair', 'Yang, Short Hair', 'Yang, Short Hair', 'Yang, Short Hair']\"]],[\"Talents\",\"string\",5,1,\"options\",[\"(%wuxing_yinyang):['Guard<~>', 'Death<~>', 'Forget<~>', 'Curse<~>', 'Hermit<~>', 'Attack<~>', 'Revival<~>', 'Summon<~>', 'Prophet<~>', 'Crown<~>']\"]],[\"Horn\",\"string\",6,1,\"options\",[\"(%wuxing_yinyang):['Praetorian Horn', 'Hel Horn', 'Lethe Horn', 'Necromancer Horn', 'Lao Tsu Horn', 'Warrior Horn', 'Shaman Horn', 'Bard Horn', 'Sibyl Horn', 'Caesar Horn']\"]],[\"Wings\",\"string\",7,1,\"options\",[\"Wind Wings\",\"Night Shadow Wings\",\"Lightning Wings\",\"Sun Wings\",\"Golden Wings\",\"Cloud Wings\",\"Morning Glow Wings\",\"Star Wings\",\"Spring Wings\",\"Moon Wings\",\"Angel Wings\"]],[\"Tail\",\"string\",8,1,\"options\",[\"Meteor Tail\",\"Rainbow Tail\",\"Willow Tail\",\"Phoenix Tail\",\"Sunset Shadow Tail\",\"Socrates Tail\",\"Dumbledore Tail\",\"Venus Tail\",\"Gaia Tail\"]],[\"Horseshoes\",\"string\",9,1,\"options\",[\"Ice Horseshoes\",\"Crystal Horseshoes\",\"Maple Horseshoes\",\"Flame Horseshoes\",\"Thunder Horseshoes\",\"Lotus Horseshoes\",\"Silver Horseshoes\"]],[\"Destiny Number\",\"number\",10,4,\"range\",[50000,100000]],[\"Lucky Number\",\"number\",14,1,\"range\",[1,49]]]").unwrap(),
+                stages: Vec::new(),
             },
         };
     (unicorn_content, unicorn_metadata)
@@ -66,6 +76,7 @@ fn generate_example_dob_ingredients(onchain_decoder: bool) -> (Value, ClusterDes
                 ver: Some(0),
                 decoder,
                 pattern: serde_json::from_str("[[\"Name\",\"string\",0,1,\"options\",[\"Alice\",\"Bob\",\"Charlie\",\"David\",\"Ethan\",\"Florence\",\"Grace\",\"Helen\"]],[\"Age\",\"number\",1,1,\"range\",[0,100]],[\"Score\",\"number\",2,1,\"raw\"],[\"DNA\",\"string\",3,3,\"raw\"],[\"URL\",\"string\",6,21,\"utf8\"],[\"Value\",\"number\",3,3,\"raw\"]]").unwrap(),
+                stages: Vec::new(),
             },
         };
     (unicorn_content, unicorn_metadata)
@@ -118,6 +129,132 @@ async fn test_fetch_and_decode_example_dna() {
     assert_eq!(render_result, EXPECTED_EXAMPLE_RENDER_RESULT);
 }
 
+#[tokio::test]
+async fn test_decode_raw_dna() {
+    let decoder = DOBDecoder::new(prepare_settings("text/plain"));
+    let (content, metadata) = generate_unicorn_dob_ingredients(false);
+    let dna = content["dna"].as_str().unwrap();
+    let located = RawDecoderParam::Located(metadata.dob.decoder.clone());
+    let render_result = decoder
+        .decode_dna_raw(dna, &metadata.dob.pattern, &located)
+        .await
+        .expect("decode located");
+    assert_eq!(render_result, EXPECTED_UNICORN_RENDER_RESULT);
+
+    let binary = std::fs::read(
+        "cache/decoders/code_hash_32f29aba4b17f3d05bec8cec55d50ef86766fd0bf82fdedaa14269f344d3784a.bin",
+    )
+    .unwrap();
+    let inline = RawDecoderParam::Binary {
+        binary: hex::encode(binary),
+    };
+    let render_result = decoder
+        .decode_dna_raw(dna, &metadata.dob.pattern, &inline)
+        .await
+        .expect("decode inline");
+    assert_eq!(render_result, EXPECTED_UNICORN_RENDER_RESULT);
+
+    let malformed = RawDecoderParam::Binary {
+        binary: "0xzz".to_string(),
+    };
+    let failure = decoder
+        .decode_dna_raw(dna, &metadata.dob.pattern, &malformed)
+        .await
+        .unwrap_err();
+    assert_eq!(failure.error, Error::HexedDecoderBinaryParseError);
+
+    // inline binaries are capped whatever the settings say
+    let oversized = RawDecoderParam::Binary {
+        binary: "00".repeat(512 * 1024 + 1),
+    };
+    let failure = decoder
+        .decode_dna_raw(dna, &metadata.dob.pattern, &oversized)
+        .await
+        .unwrap_err();
+    assert_eq!(failure.error, Error::DecoderBinaryTooLarge);
+}
+
+#[test]
+fn test_decoder_status() {
+    let decoder = DOBDecoder::new(prepare_settings("text/plain"));
+    let (_, metadata) = generate_unicorn_dob_ingredients(false);
+    let status = decoder.decoder_status(&metadata.dob.decoder);
+    assert!(status.cached);
+    assert_eq!(
+        status.deployment.map(|deployment| deployment.tx_hash),
+        Some(h256!(
+            "0x987cf95d129a2dcc2cdf7bd387c1bd888fa407e3c5a3d511fd80c80dcf6c6b67"
+        ))
+    );
+
+    let status = decoder.decoder_status(&DOBDecoderFormat {
+        location: DecoderLocationType::TypeId,
+        hash: H256::default(),
+    });
+    assert!(!status.cached);
+    assert!(status.deployment.is_none());
+}
+
+#[test]
+fn test_cached_decoders_and_reload() {
+    let settings = prepare_settings("text/plain");
+    let decoder = DOBDecoder::new(settings.clone());
+    let (_, metadata) = generate_unicorn_dob_ingredients(false);
+    let cached_decoders = decoder.cached_decoders();
+    let unicorn_decoder = cached_decoders
+        .iter()
+        .find(|cached| cached.decoder == metadata.dob.decoder)
+        .expect("cached unicorn decoder");
+    assert!(unicorn_decoder.deployment.is_some());
+    assert!(unicorn_decoder.size.is_some_and(|size| size > 0));
+    assert!(parse_decoder_file_name("code_hash_00.bin").is_none());
+    assert!(parse_decoder_file_name("spore.dob").is_none());
+
+    let mut reloaded = settings;
+    reloaded.onchain_decoder_deployment.clear();
+    reloaded.decoder_max_cycles += 1;
+    reloaded.protocol_versions.push("dob/2".to_string());
+    reloaded.available_clusters.truncate(1);
+    assert_eq!(
+        decoder.reload_settings(reloaded),
+        vec!["decoder_max_cycles".to_string()]
+    );
+    assert!(decoder
+        .decoder_status(&metadata.dob.decoder)
+        .deployment
+        .is_none());
+    assert!(decoder.protocol_versions().contains(&"dob/2".to_string()));
+    assert_eq!(decoder.available_clusters().len(), 1);
+}
+
+#[tokio::test]
+async fn test_validate_metadata() {
+    let decoder = DOBDecoder::new(prepare_settings("text/plain"));
+    let (_, metadata) = generate_unicorn_dob_ingredients(false);
+    assert_eq!(pattern_dna_bytes(&metadata.dob.pattern), Some(15));
+    let description = serde_json::to_string(&metadata).unwrap();
+    let validation = validate_metadata(&decoder, &Value::String(description), None).await;
+    assert!(validation.valid, "{:?}", validation.issues);
+    assert_eq!(validation.sample_dna, Some("00".repeat(15)));
+    assert!(validation.render_output.is_some());
+
+    let mut malformed = serde_json::to_value(&metadata).unwrap();
+    malformed["dob"]["pattern"] = json!([["Horn", "string", 0, 1, "options"], 1]);
+    malformed["dob"]["decoder"]["hash"] = json!(format!("0x{}", "00".repeat(32)));
+    let validation = validate_metadata(&decoder, &malformed, None).await;
+    let checks = validation
+        .issues
+        .iter()
+        .map(|issue| issue.check)
+        .collect::<Vec<_>>();
+    assert_eq!(checks, vec![MetadataCheck::Pattern, MetadataCheck::Decoder]);
+    assert_eq!(validation.sample_dna, None);
+
+    let validation = validate_metadata(&decoder, &json!({"description": ""}), None).await;
+    assert!(!validation.valid);
+    assert_eq!(validation.issues[0].check, MetadataCheck::Parse);
+}
+
 #[test]
 fn test_example_json_serde() {
     let (content, metadata) = generate_example_dob_ingredients(false);
@@ -126,3 +263,348 @@ fn test_example_json_serde() {
     println!("[spore_content] = {json_content}");
     println!("[cluster_description] = {json_metadata}");
 }
+
+#[test]
+fn test_dob1_cluster_serde() {
+    let metadata: ClusterDescriptionField = serde_json::from_value(json!({
+        "description": "DOB/1 example.",
+        "dob": {
+            "ver": 1,
+            "decoders": [
+                {
+                    "decoder": {
+                        "type": "code_hash",
+                        "hash": "0x32f29aba4b17f3d05bec8cec55d50ef86766fd0bf82fdedaa14269f344d3784a",
+                    },
+                    "pattern": [["Name", "string", 0, 1, "options", ["Alice", "Bob"]]],
+                },
+                {
+                    "decoder": {
+                        "type": "type_id",
+                        "hash": "0x564870fab22ae50ac2bf1e986f21f34d5c9b50a30ec5c7bd5bf9f29aafb21a76",
+                    },
+                    "pattern": [["IMAGE.0", "attributes", "", "raw", "width='200' height='200'"]],
+                },
+            ],
+        },
+    }))
+    .unwrap();
+    assert_eq!(metadata.dob.ver, Some(1));
+    assert_eq!(
+        metadata.dob.decoder.hash,
+        h256!("0x32f29aba4b17f3d05bec8cec55d50ef86766fd0bf82fdedaa14269f344d3784a")
+    );
+    assert_eq!(metadata.dob.pattern[0][0], "Name");
+    assert_eq!(metadata.dob.stages.len(), 1);
+    assert!(matches!(
+        metadata.dob.stages[0].decoder.location,
+        DecoderLocationType::TypeId
+    ));
+    assert_eq!(metadata.dob.stages[0].pattern[0][0], "IMAGE.0");
+
+    let missing_decoder = serde_json::from_value::<ClusterDescriptionField>(json!({
+        "description": "",
+        "dob": { "ver": 1, "decoders": [] },
+    }));
+    assert!(missing_decoder.is_err());
+}
+
+#[test]
+fn test_dob1_decoder_chain_serde() {
+    let stage = |hash: &str, pattern: &str| {
+        json!({
+            "decoder": { "type": "code_hash", "hash": hash },
+            "pattern": pattern,
+        })
+    };
+    let metadata: ClusterDescriptionField = serde_json::from_value(json!({
+        "description": "DOB/1 chain example.",
+        "dob": {
+            "ver": 1,
+            "decoders": [
+                stage("0x32f29aba4b17f3d05bec8cec55d50ef86766fd0bf82fdedaa14269f344d3784a", "traits"),
+                stage("0x564870fab22ae50ac2bf1e986f21f34d5c9b50a30ec5c7bd5bf9f29aafb21a76", "layers"),
+                stage("0x0bbe768b519d8ea7b96d58f1182eb7e6ef96c541fbd9526975077ee09f049058", "images"),
+            ],
+        },
+    }))
+    .unwrap();
+    assert_eq!(metadata.dob.pattern, "traits");
+    let stage_patterns = metadata
+        .dob
+        .stages
+        .iter()
+        .map(|stage| stage.pattern.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(stage_patterns, vec!["layers", "images"]);
+    assert_eq!(
+        metadata.dob.stages[1].decoder.hash,
+        h256!("0x0bbe768b519d8ea7b96d58f1182eb7e6ef96c541fbd9526975077ee09f049058")
+    );
+}
+
+#[test]
+fn test_merge_render_outputs() {
+    let merged = merge_render_outputs(
+        r#"[{"name":"Name","traits":[{"String":"Alice"}]}]"#,
+        r#"[{"name":"IMAGE","traits":[{"SVG":"<svg></svg>"}]}]"#,
+    )
+    .unwrap();
+    assert_eq!(
+        merged,
+        r#"[{"name":"Name","traits":[{"String":"Alice"}]},{"name":"IMAGE","traits":[{"SVG":"<svg></svg>"}]}]"#
+    );
+    assert!(merge_render_outputs("[]", "<svg></svg>").is_err());
+}
+
+#[test]
+fn test_parse_multiple_schema_revisions() {
+    let cluster_id = [1u8; 32];
+    let spore_v1 = encode_molecule_table(&[Some(b"dob/0"), Some(b"\"aabbcc\""), Some(&cluster_id)]);
+    let spore_extended = encode_molecule_table(&[
+        Some(b"dob/0"),
+        Some(b"\"aabbcc\""),
+        Some(&cluster_id),
+        Some(b"extra"),
+    ]);
+    for spore_data in [spore_v1, spore_extended] {
+        let spore = parse_spore_data(&spore_data).expect("parse spore");
+        assert_eq!(spore.content_type, b"dob/0");
+        assert_eq!(spore.cluster_id, Some(cluster_id.to_vec()));
+    }
+    let spore_truncated = encode_molecule_table(&[Some(b"dob/0"), Some(b"\"aabbcc\"")]);
+    assert_eq!(
+        parse_spore_data(&spore_truncated).unwrap_err(),
+        Error::SporeDataUncompatible
+    );
+
+    let cluster_v1 = encode_molecule_table(&[Some(b"name"), Some(b"description")]);
+    let cluster = parse_cluster_data(&cluster_v1).expect("parse cluster v1");
+    assert_eq!(cluster.description, b"description");
+    assert_eq!(cluster.mutant_id, None);
+
+    let cluster_v2 =
+        encode_molecule_table(&[Some(b"name"), Some(b"description"), Some(&[2u8; 32])]);
+    let cluster = parse_cluster_data(&cluster_v2).expect("parse cluster v2");
+    assert_eq!(cluster.description, b"description");
+    assert_eq!(cluster.mutant_id, Some(vec![2u8; 32]));
+}
+
+#[test]
+fn test_check_spore_data() {
+    let decoder = DOBDecoder::new(prepare_settings("dob/0"));
+    let cluster_id = [1u8; 32];
+    let spore = encode_molecule_table(&[Some(b"dob/0"), Some(b"\"aabbcc\""), Some(&cluster_id)]);
+    let check = decoder.check_spore_data(&spore);
+    assert!(check.decodable);
+    assert_eq!(check.version, Some(0));
+    assert_eq!(check.cluster_id, Some(H256(cluster_id)));
+    assert_eq!(check.reason, None);
+
+    let unsupported =
+        encode_molecule_table(&[Some(b"dob/1"), Some(b"\"aabbcc\""), Some(&cluster_id)]);
+    let check = decoder.check_spore_data(&unsupported);
+    assert!(!check.decodable);
+    assert_eq!(check.version, Some(1));
+    assert_eq!(check.reason, Some(Error::DOBVersionUnexpected.to_string()));
+
+    let clusterless = encode_molecule_table(&[Some(b"dob/0"), Some(b"\"aabbcc\""), None]);
+    let check = decoder.check_spore_data(&clusterless);
+    assert_eq!(check.reason, Some(Error::ClusterIdNotSet.to_string()));
+
+    let empty = encode_molecule_table(&[Some(b"dob/0"), Some(b""), Some(&cluster_id)]);
+    let check = decoder.check_spore_data(&empty);
+    assert_eq!(check.reason, Some(Error::DOBContentUnexpected.to_string()));
+}
+
+#[test]
+fn test_protocol_version_matching() {
+    let decoder = DOBDecoder::new(prepare_settings("dob/0"));
+    assert!(decoder.check_protocol_version("dob/0").is_ok());
+    assert!(decoder
+        .check_protocol_version("dob/0;charset=utf-8")
+        .is_ok());
+    assert!(decoder.check_protocol_version("dob/0abc").is_ok());
+    assert!(decoder.check_protocol_version("dob/1").is_err());
+
+    let mut settings = prepare_settings("dob/0");
+    settings.protocol_version_matching = ProtocolVersionMatching::Strict;
+    let decoder = DOBDecoder::new(settings);
+    assert!(decoder.check_protocol_version("dob/0").is_ok());
+    assert!(decoder
+        .check_protocol_version("dob/0;charset=utf-8")
+        .is_ok());
+    assert!(decoder.check_protocol_version("dob/0abc").is_err());
+}
+
+#[test]
+fn test_protocol_pipelines() {
+    let mut settings = prepare_settings("dob/0");
+    settings.protocol_versions = vec![
+        "dob/0".to_string(),
+        "dob/1".to_string(),
+        "dob/2".to_string(),
+    ];
+    settings
+        .protocol_pipelines
+        .insert("dob/2".to_string(), DecodePipeline::Compose);
+    let decoder = DOBDecoder::new(settings);
+    assert_eq!(
+        decoder.check_protocol_version("dob/0"),
+        Ok(DecodePipeline::Traits)
+    );
+    assert_eq!(
+        decoder.check_protocol_version("dob/1;charset=utf-8"),
+        Ok(DecodePipeline::Compose)
+    );
+    assert_eq!(
+        decoder.check_protocol_version("dob/2"),
+        Ok(DecodePipeline::Compose)
+    );
+    let infos = decoder.protocol_version_infos();
+    assert_eq!(infos[2].version, Some(2));
+    assert!(infos[2].features.contains(&"image_composition"));
+}
+
+#[test]
+fn test_protocol_version_info() {
+    let info = ProtocolVersionInfo::new("dob/0");
+    assert_eq!(info.version, Some(0));
+    assert_eq!(info.features, vec!["raw_dna", "text_traits"]);
+
+    let info = ProtocolVersionInfo::new("dob/1");
+    assert_eq!(info.version, Some(1));
+    assert!(info.features.contains(&"image_composition"));
+
+    let info = ProtocolVersionInfo::new("custom");
+    assert_eq!(info.version, None);
+    assert_eq!(info.features, vec!["raw_dna"]);
+}
+
+#[test]
+fn test_parse_decoder_registry() {
+    let (privkey, pubkey) = ckb_crypto::secp::Generator::random_keypair();
+    let mut registry = DecoderRegistry {
+        deployments: vec![OnchainDecoderDeployment {
+            code_hash: h256!("0x01"),
+            tx_hash: h256!("0x02"),
+            ..Default::default()
+        }],
+        signature: None,
+    };
+    let signature = privkey
+        .sign_recoverable(&registry.signing_message())
+        .expect("sign registry");
+    let public_key = hex::encode(pubkey.serialize());
+
+    // unsigned registry is only accepted without public key, like those from on-chain cell
+    let document = serde_json::to_vec(&registry).unwrap();
+    assert_eq!(parse_decoder_registry(&document, None).unwrap().len(), 1);
+    assert!(parse_decoder_registry(&document, Some(&public_key)).is_err());
+
+    registry.signature = Some(hex::encode(signature.serialize()));
+    let document = serde_json::to_vec(&registry).unwrap();
+    let deployments = parse_decoder_registry(&document, Some(&public_key)).expect("verified");
+    assert_eq!(deployments[0].code_hash, h256!("0x01"));
+
+    registry.deployments[0].out_index = 1;
+    let document = serde_json::to_vec(&registry).unwrap();
+    assert!(parse_decoder_registry(&document, Some(&public_key)).is_err());
+}
+
+#[test]
+fn test_vm_smoke_test() {
+    vm::smoke_test().expect("vm smoke test");
+}
+
+#[tokio::test]
+async fn test_fetch_chunked_decoder_deployment() {
+    let chunks: [&[u8]; 3] = [b"first chunk,", b" second chunk,", b" last chunk"];
+    let tx_hashes = [h256!("0x01"), h256!("0x02"), h256!("0x03")];
+    let cells = tx_hashes
+        .iter()
+        .cloned()
+        .zip(chunks.iter().map(|chunk| chunk.to_vec()))
+        .collect::<std::collections::HashMap<_, _>>();
+    let ckb_rpc = serve_mock_ckb_rpc(move |method, params| {
+        assert_eq!(method, "get_live_cell");
+        let tx_hash: H256 = serde_json::from_value(params[0]["tx_hash"].clone()).unwrap();
+        mock_live_cell(&cells[&tx_hash])
+    });
+    let code_hash = H256(ckb_hash::blake2b_256(chunks.concat()));
+    let deployment = |order: [usize; 3]| OnchainDecoderDeployment {
+        code_hash: code_hash.clone(),
+        tx_hash: tx_hashes[order[0]].clone(),
+        out_index: 0,
+        chunks: order[1..]
+            .iter()
+            .map(|index| DecoderChunkLocation {
+                tx_hash: tx_hashes[*index].clone(),
+                out_index: 0,
+            })
+            .collect(),
+    };
+    let decoder_format = DOBDecoderFormat {
+        location: DecoderLocationType::CodeHash,
+        hash: code_hash.clone(),
+    };
+
+    let mut settings = prepare_settings("dob/0");
+    settings.ckb_rpc = ckb_rpc;
+    settings.decoders_cache_directory = prepare_directory("chunked-decoder");
+    settings.onchain_decoder_deployment = vec![deployment([0, 1, 2])];
+    let decoder = DOBDecoder::new(settings.clone());
+    let decoder_path = decoder
+        .resolve_decoder_path(&decoder_format)
+        .await
+        .expect("reassemble chunks");
+    assert_eq!(std::fs::read(decoder_path).unwrap(), chunks.concat());
+
+    // chunks out of order don't match `code_hash`, and nothing is cached
+    settings.decoders_cache_directory = prepare_directory("chunked-decoder-misordered");
+    settings.onchain_decoder_deployment = vec![deployment([0, 2, 1])];
+    let decoder = DOBDecoder::new(settings.clone());
+    let error = decoder
+        .resolve_decoder_path(&decoder_format)
+        .await
+        .expect_err("hash mismatch");
+    assert_eq!(error, Error::DecoderBinaryHashInvalid);
+    assert_eq!(
+        std::fs::read_dir(&settings.decoders_cache_directory)
+            .unwrap()
+            .count(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_serial_index_restricted_to_configured_clusters() {
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let ckb_rpc = serve_mock_ckb_rpc({
+        let calls = calls.clone();
+        move |method, _| {
+            assert_eq!(method, "get_cells");
+            calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            json!({ "objects": [], "last_cursor": "0x" })
+        }
+    });
+    let mut settings = prepare_settings("dob/0");
+    settings.ckb_rpc = ckb_rpc;
+    settings.serial_index_clusters = vec![H256::from([0x11; 32])];
+    let decoder = DOBDecoder::new(settings);
+
+    // clusters not configured are never scanned
+    let error = decoder
+        .resolve_spore_by_serial([0x22; 32], 1)
+        .await
+        .expect_err("cluster not indexed");
+    assert_eq!(error, Error::ClusterNotIndexed);
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+    let error = decoder
+        .resolve_spore_by_serial([0x11; 32], 1)
+        .await
+        .expect_err("no spore in cluster");
+    assert_eq!(error, Error::SporeSerialNotFound);
+    assert!(calls.load(std::sync::atomic::Ordering::SeqCst) > 0);
+}
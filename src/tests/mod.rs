@@ -1,9 +1,19 @@
-use ckb_types::h256;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use ckb_types::{h256, H256};
+use hyper::service::service_fn;
+use hyper::{Body, Request, Response};
+use serde_json::{json, Value};
 
 use crate::types::{HashType, OnchainDecoderDeployment, ScriptId, Settings};
 
+mod assets;
 mod decoder;
 mod legacy_decoder;
+mod server;
+mod settings;
 
 fn prepare_settings(version: &str) -> Settings {
     Settings {
@@ -49,6 +59,7 @@ fn prepare_settings(version: &str) -> Settings {
                     "0xb2497dc3e616055125ef8276be7ee21986d2cd4b2ce90992725386cabcb6ea7f"
                 ),
                 out_index: 0,
+                chunks: vec![],
             },
             OnchainDecoderDeployment {
                 code_hash: h256!(
@@ -58,8 +69,301 @@ fn prepare_settings(version: &str) -> Settings {
                     "0x987cf95d129a2dcc2cdf7bd387c1bd888fa407e3c5a3d511fd80c80dcf6c6b67"
                 ),
                 out_index: 0,
+                chunks: vec![],
             },
         ],
         ..Default::default()
     }
 }
+
+// CKB node on a local port answering each JSON-RPC call by `handler`, which takes method and params
+// and returns the result, for chain states that testnet can't reproduce on demand
+fn serve_mock_ckb_rpc<F>(handler: F) -> String
+where
+    F: Fn(&str, &Value) -> Value + Send + Sync + 'static,
+{
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind mock rpc");
+    listener.set_nonblocking(true).unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let handler = handler.clone();
+            let service = service_fn(move |request: Request<Body>| {
+                let handler = handler.clone();
+                async move {
+                    let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                    let request: Value = serde_json::from_slice(&body).unwrap();
+                    let respond = |request: &Value| {
+                        let method = request["method"].as_str().unwrap_or_default();
+                        json!({
+                            "jsonrpc": "2.0",
+                            "id": request["id"],
+                            "result": handler(method, &request["params"]),
+                        })
+                    };
+                    let response = match &request {
+                        Value::Array(requests) => requests.iter().map(respond).collect(),
+                        request => respond(request),
+                    };
+                    Ok::<_, Infallible>(Response::new(Body::from(response.to_string())))
+                }
+            });
+            tokio::spawn(hyper::server::conn::Http::new().serve_connection(stream, service));
+        }
+    });
+    url
+}
+
+// `get_live_cell` result of a live cell holding `data`
+fn mock_live_cell(data: &[u8]) -> Value {
+    json!({
+        "cell": {
+            "output": {
+                "capacity": "0x0",
+                "lock": {
+                    "code_hash": format!("0x{}", hex::encode([0u8; 32])),
+                    "hash_type": "data",
+                    "args": "0x",
+                },
+                "type": null,
+            },
+            "data": {
+                "content": format!("0x{}", hex::encode(data)),
+                "hash": format!("0x{}", hex::encode(ckb_hash::blake2b_256(data))),
+            },
+        },
+        "status": "live",
+    })
+}
+
+// empty directory only used by the named test
+fn prepare_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("dob-decoder-test-{name}"));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).expect("create test directory");
+    directory
+}
+
+// encode molecule table with all fields typed in `Bytes` or `BytesOpt`
+fn encode_molecule_table(fields: &[Option<&[u8]>]) -> Vec<u8> {
+    let fields = fields
+        .iter()
+        .map(|field| match field {
+            Some(bytes) => [(bytes.len() as u32).to_le_bytes().as_slice(), bytes].concat(),
+            None => vec![],
+        })
+        .collect::<Vec<_>>();
+    let header_size = 4 * (fields.len() + 1);
+    let total_size = header_size + fields.iter().map(Vec::len).sum::<usize>();
+    let mut table = (total_size as u32).to_le_bytes().to_vec();
+    let mut offset = header_size;
+    for field in &fields {
+        table.extend((offset as u32).to_le_bytes());
+        offset += field.len();
+    }
+    fields.into_iter().for_each(|field| table.extend(field));
+    table
+}
+
+// `dob/0` spore in a cluster decoded by the example decoder in local cache, whose cells and
+// transactions are served by mock node
+#[derive(Clone, Copy)]
+struct MockExampleSpore {
+    spore_id: [u8; 32],
+    cluster_id: [u8; 32],
+    // where the spore cell is created, while the cluster cell is at genesis
+    block_number: u64,
+    // where the spore cell is consumed, if it has been melted
+    melted_at: Option<u64>,
+}
+
+impl MockExampleSpore {
+    fn new(block_number: u64) -> Self {
+        Self {
+            spore_id: [0x11; 32],
+            cluster_id: [0x22; 32],
+            block_number,
+            melted_at: None,
+        }
+    }
+
+    fn is_spore(&self, args: &str) -> bool {
+        args == format!("0x{}", hex::encode(self.spore_id))
+    }
+
+    // data of the spore or its cluster typed by `args` in hex, along with hash and block number of
+    // the transaction creating it
+    fn cell(&self, args: &str) -> Option<(Vec<u8>, H256, u64)> {
+        if self.is_spore(args) {
+            let content = json!({ "dna": "df4ffcb5e7a283ea7e6f09a504d0e256" }).to_string();
+            let spore_data = encode_molecule_table(&[
+                Some(b"dob/0"),
+                Some(content.as_bytes()),
+                Some(&self.cluster_id),
+            ]);
+            Some((spore_data, mock_tx_hash(&self.spore_id), self.block_number))
+        } else if args == format!("0x{}", hex::encode(self.cluster_id)) {
+            let description = json!({
+                "description": "DOB/0 example.",
+                "dob": {
+                    "ver": 0,
+                    "decoder": {
+                        "type": "code_hash",
+                        "hash": "0x32f29aba4b17f3d05bec8cec55d50ef86766fd0bf82fdedaa14269f344d3784a",
+                    },
+                    "pattern": [["Name", "string", 0, 1, "options", ["Alice", "Bob"]]],
+                },
+            })
+            .to_string();
+            let cluster_data =
+                encode_molecule_table(&[Some(b"example"), Some(description.as_bytes())]);
+            Some((cluster_data, mock_tx_hash(&self.cluster_id), 0))
+        } else {
+            None
+        }
+    }
+
+    // answer calls about the spore and its cluster, and blocks on the canonical chain
+    fn handle(&self, method: &str, params: &Value) -> Value {
+        match method {
+            "get_cells" => self.get_cells(params),
+            "get_transactions" => self.get_transactions(params),
+            "get_transaction" => self.get_transaction(params),
+            "get_header" => {
+                let block_hash: H256 = serde_json::from_value(params[0].clone()).unwrap();
+                (0..1000)
+                    .find(|number| mock_block_hash(*number) == block_hash)
+                    .map(mock_header)
+                    .unwrap_or(Value::Null)
+            }
+            "get_block_hash" => json!(mock_block_hash(parse_mock_number(&params[0]))),
+            _ => Value::Null,
+        }
+    }
+
+    // `get_cells` result listing the live spore or its cluster by type args
+    fn get_cells(&self, params: &Value) -> Value {
+        let args = params[0]["script"]["args"].as_str().unwrap_or_default();
+        let cell = self
+            .cell(args)
+            .filter(|_| !self.is_spore(args) || self.melted_at.is_none())
+            .map(|(data, tx_hash, block_number)| {
+                json!({
+                    "output": mock_cell_output(args),
+                    "output_data": format!("0x{}", hex::encode(data)),
+                    "out_point": { "tx_hash": tx_hash, "index": "0x0" },
+                    "block_number": format!("{block_number:#x}"),
+                    "tx_index": "0x0",
+                })
+            });
+        json!({ "objects": cell.into_iter().collect::<Vec<_>>(), "last_cursor": "0x" })
+    }
+
+    // `get_transactions` result grouped by transaction, newest first, within block range of filter
+    fn get_transactions(&self, params: &Value) -> Value {
+        let args = params[0]["script"]["args"].as_str().unwrap_or_default();
+        let until = match &params[0]["filter"]["block_range"][1] {
+            Value::Null => u64::MAX,
+            until => parse_mock_number(until),
+        };
+        let mut transactions = Vec::new();
+        if let Some((_, tx_hash, block_number)) = self.cell(args) {
+            transactions.push((tx_hash, block_number, "output"));
+            if let Some(melted_at) = self.melted_at.filter(|_| self.is_spore(args)) {
+                transactions.push((mock_tx_hash(&[0xff; 32]), melted_at, "input"));
+            }
+        }
+        let transactions = transactions
+            .into_iter()
+            .rev()
+            .filter(|(_, block_number, _)| *block_number < until)
+            .map(|(tx_hash, block_number, cell_type)| {
+                json!({
+                    "tx_hash": tx_hash,
+                    "block_number": format!("{block_number:#x}"),
+                    "tx_index": "0x0",
+                    "cells": [[cell_type, "0x0"]],
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({ "objects": transactions, "last_cursor": "0x" })
+    }
+
+    // `get_transaction` result of the transaction creating the spore or its cluster
+    fn get_transaction(&self, params: &Value) -> Value {
+        let tx_hash: H256 = serde_json::from_value(params[0].clone()).unwrap();
+        let cell = [self.spore_id, self.cluster_id]
+            .into_iter()
+            .find_map(|args| {
+                let args = format!("0x{}", hex::encode(args));
+                self.cell(&args)
+                    .filter(|(_, cell_tx_hash, _)| *cell_tx_hash == tx_hash)
+                    .map(|(data, _, block_number)| (args, data, block_number))
+            });
+        let Some((args, data, block_number)) = cell else {
+            return Value::Null;
+        };
+        json!({
+            "transaction": {
+                "version": "0x0",
+                "cell_deps": [],
+                "header_deps": [],
+                "inputs": [],
+                "outputs": [mock_cell_output(&args)],
+                "outputs_data": [format!("0x{}", hex::encode(data))],
+                "witnesses": [],
+                "hash": tx_hash,
+            },
+            "cycles": null,
+            "tx_status": {
+                "status": "committed",
+                "block_number": format!("{block_number:#x}"),
+                "block_hash": mock_block_hash(block_number),
+                "reason": null,
+            },
+        })
+    }
+}
+
+fn parse_mock_number(number: &Value) -> u64 {
+    let number = number.as_str().expect("hex number");
+    u64::from_str_radix(number.trim_start_matches("0x"), 16).expect("hex number")
+}
+
+fn mock_tx_hash(args: &[u8; 32]) -> H256 {
+    H256(ckb_hash::blake2b_256(args))
+}
+
+fn mock_block_hash(number: u64) -> H256 {
+    H256(ckb_hash::blake2b_256(number.to_le_bytes()))
+}
+
+fn mock_header(number: u64) -> Value {
+    let zero_hash = H256::default();
+    json!({
+        "version": "0x0",
+        "compact_target": "0x0",
+        "timestamp": "0x0",
+        "number": format!("{number:#x}"),
+        "epoch": "0x0",
+        "parent_hash": zero_hash,
+        "transactions_root": zero_hash,
+        "proposals_hash": zero_hash,
+        "extra_hash": zero_hash,
+        "dao": zero_hash,
+        "nonce": "0x0",
+        "hash": mock_block_hash(number),
+    })
+}
+
+// output of a cell typed by a script with `args` in hex
+fn mock_cell_output(args: &str) -> Value {
+    let zero_hash = H256::default();
+    json!({
+        "capacity": "0x0",
+        "lock": { "code_hash": zero_hash, "hash_type": "data", "args": "0x" },
+        "type": { "code_hash": zero_hash, "hash_type": "data1", "args": args },
+    })
+}
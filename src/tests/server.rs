@@ -0,0 +1,1139 @@
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use ckb_types::prelude::{Builder, Entity, Pack};
+use ckb_types::{h256, H256};
+use hyper::{Body, Request, Response};
+use jsonrpsee::types::ErrorObjectOwned;
+use serde_json::{json, Value};
+use tower::{Layer, ServiceExt};
+
+use crate::access::{ApiKeyLayer, API_KEY_HEADER};
+use crate::address::{encode_address, parse_address};
+use crate::concurrency::AdaptiveConcurrency;
+use crate::crawler::{ClusterCrawler, CrawlState};
+use crate::decoder::DOBDecoder;
+use crate::forwarded::{client_ip, IpNet, PeerAddr};
+use crate::invalidation::{apply_invalidation, InvalidationEvent};
+use crate::middleware::ResponseEncoding;
+use crate::openrpc::openrpc_document;
+use crate::rarity::aggregate_rarity;
+use crate::rate_limit::{RateLimiter, TokenBucket};
+use crate::render::{compose_svg, RenderFormat, RenderTarget, THUMBNAIL_SIZES};
+use crate::request_id::{request_id, REQUEST_ID_HEADER};
+use crate::rest::{nft_metadata, parse_decode_options, parse_render_target};
+use crate::retention::{select_evictions, CacheEntry, ClusterActivity};
+use crate::rgbpp::{parse_btc_outpoint, rgbpp_lock_script};
+use crate::server::{
+    decode_dob, decode_dob_verbose, dob_cache_path, filter_traits, parse_dob_cache_content,
+    write_dob_to_cache, DOBCacheInfo, DecodeOptions, DecoderRpcServer, DecoderStandaloneServer,
+    OwnerParam, SporeIdParam,
+};
+use crate::server_v2::DecoderRpcV2Server;
+use crate::stats::ClusterStats;
+use crate::statsd::format_metric;
+use crate::tests::{
+    mock_block_hash, prepare_directory, prepare_settings, serve_mock_ckb_rpc, MockExampleSpore,
+};
+use crate::types::{
+    ApiKeySettings, BatchConcurrencySettings, DecodeFailure, DecodeStage, Error, Network,
+    OwnedSporesCursor, RateLimitSettings, RenderedTraits, StatsdFlavor, TraitValue,
+    UnconfirmedSporePolicy,
+};
+
+#[test]
+fn test_sharded_dob_cache_path() {
+    let spore_id = [0xabu8; 32];
+    let cache_path = dob_cache_path("cache/dobs".as_ref(), &spore_id);
+    let expected_path = format!("cache/dobs/ab/ab/{}.dob", hex::encode(spore_id));
+    assert_eq!(cache_path.to_string_lossy(), expected_path);
+}
+
+#[test]
+fn test_parse_dob_cache_content() {
+    let (render_output, dob_content, cache_info) =
+        parse_dob_cache_content("[]\n\"aabbcc\"").expect("legacy cache");
+    assert_eq!(render_output, "[]");
+    assert_eq!(dob_content, json!("aabbcc"));
+    assert_eq!(cache_info, DOBCacheInfo::default());
+
+    let cluster_id = h256!("0x0101010101010101010101010101010101010101010101010101010101010101");
+    let file_content = format!(
+        "[]\n\"aabbcc\"\n{}",
+        json!({ "cluster_id": cluster_id, "cluster_data_hash": null })
+    );
+    let (_, _, cache_info) = parse_dob_cache_content(&file_content).expect("cache with info");
+    assert_eq!(cache_info.cluster_id, Some(cluster_id));
+    assert_eq!(cache_info.cluster_data_hash, None);
+
+    assert!(parse_dob_cache_content("[]").is_err());
+}
+
+#[test]
+fn test_invalidate_cluster_cache() {
+    let mut settings = prepare_settings("text/plain");
+    settings.dobs_cache_directory = std::env::temp_dir().join("dob_decoder_test_invalidation");
+    let _ = std::fs::remove_dir_all(&settings.dobs_cache_directory);
+    let decoder = DOBDecoder::new(settings);
+    let cache_directory = &decoder.setting().dobs_cache_directory;
+    let cluster_id = H256::from([0xccu8; 32]);
+    for (spore_id, cache_cluster_id) in [
+        ([1u8; 32], Some(cluster_id.clone())),
+        ([2u8; 32], Some(cluster_id.clone())),
+        ([3u8; 32], Some(H256::from([0xddu8; 32]))),
+        ([4u8; 32], None),
+    ] {
+        let cache_info = DOBCacheInfo {
+            cluster_id: cache_cluster_id,
+            cluster_data_hash: None,
+        };
+        let cache_path = dob_cache_path(cache_directory, &spore_id);
+        write_dob_to_cache("[]", &json!("aabbcc"), &cache_info, cache_path).unwrap();
+    }
+
+    let event = InvalidationEvent::Cluster { cluster_id };
+    assert_eq!(apply_invalidation(&decoder, &event), 2);
+    assert!(!dob_cache_path(cache_directory, &[1u8; 32]).exists());
+    assert!(dob_cache_path(cache_directory, &[3u8; 32]).exists());
+    // entries recording no cluster are left behind
+    assert!(dob_cache_path(cache_directory, &[4u8; 32]).exists());
+    assert_eq!(apply_invalidation(&decoder, &event), 0);
+    assert_eq!(apply_invalidation(&decoder, &InvalidationEvent::Purge), 2);
+}
+
+#[test]
+fn test_decode_failure_error_object() {
+    let failure = DecodeFailure::new(Error::DecoderExecutionError, DecodeStage::Execute)
+        .with_spore_id([0x01; 32])
+        .with_decoder_hash(h256!("0x02"));
+    let error = ErrorObjectOwned::from(failure);
+    assert_eq!(error.code(), Error::DecoderExecutionError as i32);
+    let data: serde_json::Value = serde_json::from_str(error.data().unwrap().get()).unwrap();
+    assert_eq!(data["stage"], json!("execute"));
+    assert_eq!(data["spore_id"], json!(H256::from([0x01; 32])));
+    assert_eq!(data["decoder_hash"], json!(h256!("0x02")));
+    assert!(data.get("cluster_id").is_none());
+}
+
+#[test]
+fn test_spore_id_param_forms() {
+    let hexed: SporeIdParam = serde_json::from_value(json!("0x0101")).unwrap();
+    assert_eq!(hexed, SporeIdParam::Hex("0x0101".to_string()));
+    assert_eq!(hexed.to_hex(), "0x0101");
+
+    let bytes: SporeIdParam = serde_json::from_value(json!([1, 171])).unwrap();
+    assert_eq!(bytes, SporeIdParam::Bytes(vec![1, 171]));
+    assert_eq!(bytes.to_hex(), "01ab");
+    assert_eq!(serde_json::to_value(&bytes).unwrap(), json!([1, 171]));
+}
+
+#[test]
+fn test_filter_traits() {
+    let mut render_output = json!([
+        { "name": "Horn", "traits": [{ "String": "Hel Horn" }] },
+        { "name": "Wings", "traits": [{ "String": "Sun Wings" }] },
+        { "name": "Tail", "traits": [{ "String": "Meteor Tail" }] },
+    ]);
+    filter_traits(
+        &mut render_output,
+        &["Horn".to_string(), "Tail".to_string()],
+    );
+    assert_eq!(
+        render_output,
+        json!([
+            { "name": "Horn", "traits": [{ "String": "Hel Horn" }] },
+            { "name": "Tail", "traits": [{ "String": "Meteor Tail" }] },
+        ])
+    );
+}
+
+#[test]
+fn test_rendered_traits() {
+    let render_output = json!([
+        { "name": "Horn", "traits": [{ "String": "Hel Horn" }] },
+        { "name": "Lucky Number", "traits": [{ "Number": 35 }] },
+        { "name": "Weight", "traits": [{ "Float": 1.5 }] },
+        { "name": "IMAGE", "traits": [{ "SVG": "<svg></svg>" }] },
+    ]);
+    let rendered_traits = RenderedTraits::parse(&render_output).expect("parse traits");
+    assert_eq!(
+        rendered_traits.get("Horn").unwrap()[0].as_str(),
+        Some("Hel Horn")
+    );
+    assert_eq!(
+        rendered_traits.get("Lucky Number").unwrap()[0]
+            .as_number()
+            .and_then(|number| number.as_u64()),
+        Some(35)
+    );
+    assert_eq!(
+        rendered_traits.get("Weight").unwrap()[0],
+        TraitValue::Other(json!({ "Float": 1.5 }))
+    );
+    assert_eq!(rendered_traits.get("Tail"), None);
+    assert_eq!(
+        serde_json::to_value(&rendered_traits).unwrap(),
+        json!([
+            { "name": "Horn", "traits": [{ "type": "String", "value": "Hel Horn" }] },
+            { "name": "Lucky Number", "traits": [{ "type": "Number", "value": 35 }] },
+            { "name": "Weight", "traits": [{ "type": "Float", "value": 1.5 }] },
+            { "name": "IMAGE", "traits": [{ "type": "SVG", "value": "<svg></svg>" }] },
+        ])
+    );
+
+    let invalid_output = json!({ "name": "Horn" });
+    assert_eq!(
+        RenderedTraits::parse(&invalid_output),
+        Err(Error::DecoderOutputInvalid)
+    );
+}
+
+#[test]
+fn test_aggregate_rarity() {
+    let spore = |horn: &str, level: u64| {
+        RenderedTraits::parse(&json!([
+            { "name": "prev.bgcolor", "traits": [{ "String": "#ffffff" }] },
+            { "name": "Horn", "traits": [{ "String": horn }] },
+            { "name": "Level", "traits": [{ "Number": level }] },
+            { "name": "IMAGE", "traits": [{ "SVG": "<svg></svg>" }] },
+        ]))
+        .unwrap()
+    };
+    let spores = vec![
+        ([1u8; 32], spore("Hel Horn", 1)),
+        ([2u8; 32], spore("Hel Horn", 2)),
+        ([3u8; 32], spore("Lethe Horn", 1)),
+        ([4u8; 32], spore("Hel Horn", 1)),
+    ];
+    let report = aggregate_rarity(&spores, 1);
+    assert_eq!(report.spores, 4);
+    assert_eq!(report.failures, 1);
+    let names = report
+        .traits
+        .iter()
+        .map(|distribution| distribution.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Horn", "Level"]);
+    assert_eq!(report.traits[0].values[0].value.as_str(), Some("Hel Horn"));
+    assert_eq!(report.traits[0].values[0].count, 3);
+    assert_eq!(report.traits[0].values[0].frequency, 0.75);
+
+    // spores 2 and 3 each have a trait value owned by nobody else
+    let ranks = report
+        .rarity
+        .iter()
+        .map(|rarity| (rarity.spore_id.0[0], rarity.rank))
+        .collect::<Vec<_>>();
+    assert_eq!(ranks, vec![(2, 1), (3, 1), (1, 3), (4, 3)]);
+    assert_eq!(report.rarity[0].score, 4.0 / 3.0 + 4.0);
+}
+
+#[cfg(feature = "trait_index")]
+#[test]
+fn test_trait_index_search() {
+    use crate::trait_index::TraitIndex;
+    use crate::types::{TraitFilter, TraitIndexSettings};
+
+    let directory = std::env::temp_dir().join("dob_decoder_test_trait_index");
+    let _ = std::fs::remove_dir_all(&directory);
+    let trait_index = TraitIndex::open(&TraitIndexSettings { directory }).expect("open index");
+    let spore = |horn: &str, level: u64| {
+        RenderedTraits::parse(&json!([
+            { "name": "Horn", "traits": [{ "String": horn }] },
+            { "name": "Level", "traits": [{ "Number": level }] },
+        ]))
+        .unwrap()
+    };
+    let cluster_id = [0xccu8; 32];
+    for (spore_id, horn, level) in [
+        (1, "Caesar Horn", 1),
+        (2, "Caesar Horn", 2),
+        (3, "Hel Horn", 1),
+    ] {
+        trait_index
+            .insert(&[spore_id; 32], &cluster_id, &spore(horn, level))
+            .unwrap();
+    }
+    let filter = |name: &str, value| TraitFilter {
+        name: name.to_string(),
+        value,
+    };
+    let search = |filters: &[TraitFilter], after, limit| {
+        let page = trait_index
+            .search(&cluster_id, filters, after, limit)
+            .unwrap();
+        let spore_ids = page.spore_ids.iter().map(|id| id.0[0]).collect::<Vec<_>>();
+        (spore_ids, page.next_cursor.is_some())
+    };
+    let caesar_horn = [filter("Horn", json!("Caesar Horn"))];
+    assert_eq!(search(&caesar_horn, None, 10), (vec![1, 2], false));
+    assert_eq!(search(&caesar_horn, None, 1), (vec![1], true));
+    assert_eq!(search(&caesar_horn, Some([1u8; 32]), 1), (vec![2], false));
+    let caesar_horn_level_1 = [caesar_horn[0].clone(), filter("Level", json!(1))];
+    assert_eq!(search(&caesar_horn_level_1, None, 10), (vec![1], false));
+
+    // re-decoded spores replace their indexed traits
+    trait_index
+        .insert(&[1u8; 32], &cluster_id, &spore("Hel Horn", 1))
+        .unwrap();
+    assert_eq!(search(&caesar_horn, None, 10), (vec![2], false));
+    trait_index.clear(Some(&cluster_id)).unwrap();
+    assert_eq!(search(&caesar_horn, None, 10), (vec![], false));
+}
+
+#[test]
+fn test_cluster_crawler_start() {
+    let crawler = ClusterCrawler::new(&prepare_settings("dob/0"));
+    let cluster_id = [0xccu8; 32];
+    assert!(crawler.report(&cluster_id).is_none());
+    let started = crawler.start(cluster_id).expect("start");
+    // crawls of the same cluster never overlap, while other clusters are free to start
+    assert_eq!(crawler.start(cluster_id), Err(started));
+    assert!(crawler.start([0xddu8; 32]).is_ok());
+    let report = crawler.report(&cluster_id).expect("report");
+    assert_eq!(report.state, CrawlState::Running);
+    assert_eq!((report.spores, report.decoded, report.failures), (0, 0, 0));
+    assert!(report.finished_at.is_none());
+}
+
+#[test]
+fn test_owner_param_and_cursor() {
+    let owner: OwnerParam = serde_json::from_value(json!(
+        "ckb1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqdnnw7qkdnnclfkg59uzn8umtfd2kwxceqxwquc4"
+    ))
+    .expect("address");
+    let lock_script: ckb_jsonrpc_types::Script = owner.lock_script().expect("lock script").into();
+    assert_eq!(
+        lock_script.code_hash,
+        h256!("0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8")
+    );
+    assert_eq!(
+        lock_script.args.as_bytes(),
+        hex::decode("b39bbc0b3673c7d36450bc14cfcdad2d559c6c64").unwrap()
+    );
+
+    let owner: OwnerParam = serde_json::from_value(json!({
+        "code_hash": "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8",
+        "hash_type": "type",
+        "args": "0xb39bbc0b3673c7d36450bc14cfcdad2d559c6c64",
+    }))
+    .expect("lock script");
+    assert_eq!(
+        ckb_jsonrpc_types::Script::from(owner.lock_script().unwrap()),
+        lock_script
+    );
+
+    let invalid: OwnerParam = serde_json::from_value(json!("ckb1invalid")).unwrap();
+    assert_eq!(invalid.lock_script().err(), Some(Error::AddressParseError));
+
+    let cursor = OwnedSporesCursor {
+        script_index: 1,
+        indexer_cursor: Some(vec![0xab, 0xcd]),
+    };
+    assert_eq!(cursor.encode(), "1:0xabcd");
+    assert_eq!(OwnedSporesCursor::decode("1:0xabcd"), Ok(cursor));
+    assert_eq!(
+        OwnedSporesCursor::decode("x:0xabcd"),
+        Err(Error::OwnedSporesCursorParseError)
+    );
+}
+
+#[test]
+fn test_encode_owner_address() {
+    let address = "ckb1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqdnnw7qkdnnclfkg59uzn8umtfd2kwxceqxwquc4";
+    let lock_script = parse_address(address).unwrap();
+    assert_eq!(
+        encode_address(&lock_script, Network::Mainnet).as_deref(),
+        Some(address)
+    );
+    let testnet_address = encode_address(&lock_script, Network::Testnet).unwrap();
+    assert!(testnet_address.starts_with("ckt1"));
+    assert_eq!(
+        parse_address(&testnet_address).unwrap().as_slice(),
+        lock_script.as_slice()
+    );
+
+    // locks other than those of well-known wallets are not rendered
+    let unknown_lock = lock_script.as_builder().code_hash([0u8; 32].pack()).build();
+    assert_eq!(encode_address(&unknown_lock, Network::Mainnet), None);
+}
+
+#[test]
+fn test_rgbpp_lock_script() {
+    let txid = "0x0102030405060708091011121314151617181920212223242526272829303132";
+    let (txid, vout) = parse_btc_outpoint(&format!("{txid}:2")).unwrap();
+    assert_eq!(vout, 2);
+    let lock_script = rgbpp_lock_script(Network::Testnet, txid, vout);
+    let args = lock_script.args().raw_data();
+    assert_eq!(&args[..4], &[2, 0, 0, 0]);
+    assert_eq!(args[4], 0x32);
+    assert_eq!(args[35], 0x01);
+    assert_ne!(
+        rgbpp_lock_script(Network::Mainnet, txid, vout)
+            .code_hash()
+            .raw_data(),
+        lock_script.code_hash().raw_data()
+    );
+
+    for invalid in [
+        "0x0102:0",
+        "zz:0",
+        &format!("{}:x", hex::encode(txid)),
+        "no colon",
+    ] {
+        assert_eq!(
+            parse_btc_outpoint(invalid).err(),
+            Some(Error::BtcOutpointParseError)
+        );
+    }
+}
+
+#[test]
+fn test_response_encoding_negotiation() {
+    let request = |uri: &str, accept: Option<&str>| {
+        let mut builder = hyper::Request::builder().uri(uri);
+        if let Some(accept) = accept {
+            builder = builder.header(hyper::header::ACCEPT, accept);
+        }
+        builder.body(()).unwrap()
+    };
+    assert_eq!(ResponseEncoding::negotiate(&request("/", None)), None);
+    assert_eq!(
+        ResponseEncoding::negotiate(&request("/", Some("application/json"))),
+        None
+    );
+    assert_eq!(
+        ResponseEncoding::negotiate(&request("/", Some("application/json, application/cbor"))),
+        Some(ResponseEncoding::Cbor)
+    );
+    assert_eq!(
+        ResponseEncoding::negotiate(&request("/?encoding=msgpack", Some("application/cbor"))),
+        Some(ResponseEncoding::MessagePack)
+    );
+
+    let value = json!({"jsonrpc": "2.0", "id": 1, "result": {"dob_content": {"dna": "aabb"}}});
+    let encoded = ResponseEncoding::MessagePack.encode(&value).unwrap();
+    assert_eq!(
+        rmp_serde::from_slice::<serde_json::Value>(&encoded).unwrap(),
+        value
+    );
+    let encoded = ResponseEncoding::Cbor.encode(&value).unwrap();
+    assert_eq!(
+        ciborium::from_reader::<serde_json::Value, _>(encoded.as_slice()).unwrap(),
+        value
+    );
+}
+
+#[test]
+fn test_select_cache_evictions() {
+    let entry = |name: &str, cluster_id: Option<u8>, modified_secs: u64| CacheEntry {
+        path: name.into(),
+        size: 100,
+        modified: SystemTime::UNIX_EPOCH + Duration::from_secs(modified_secs),
+        cluster_id: cluster_id.map(|byte| [byte; 32]),
+    };
+    let entries = vec![
+        entry("pinned", Some(1), 0),
+        entry("active", Some(2), 0),
+        entry("dormant_new", Some(3), 20),
+        entry("dormant_old", Some(3), 10),
+        entry("legacy", None, 30),
+    ];
+    let score = |cluster_id: &[u8; 32]| match cluster_id[0] {
+        1 => 50.0,
+        2 => 5.0,
+        _ => 0.0,
+    };
+
+    assert!(select_evictions(entries.clone(), 500, score, 20.0).is_empty());
+    let evictions = select_evictions(entries.clone(), 250, score, 20.0)
+        .into_iter()
+        .map(|entry| entry.path.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(evictions, vec!["dormant_old", "dormant_new", "legacy"]);
+    // pinned entries are kept even if the cap is exceeded
+    assert_eq!(select_evictions(entries, 0, score, 20.0).len(), 4);
+}
+
+#[test]
+fn test_cluster_activity_score() {
+    let activity = ClusterActivity::new(3600);
+    activity.record(&[1u8; 32]);
+    activity.record(&[1u8; 32]);
+    activity.record(&[2u8; 32]);
+    assert!(activity.score(&[1u8; 32]) > activity.score(&[2u8; 32]));
+    assert!(activity.score(&[1u8; 32]) <= 2.0);
+    assert_eq!(activity.score(&[3u8; 32]), 0.0);
+}
+
+#[test]
+fn test_cluster_stats_report() {
+    let stats = ClusterStats::default();
+    let cluster_id = [1u8; 32];
+    assert_eq!(stats.report(&cluster_id), None);
+
+    stats.record_request(&cluster_id, true);
+    stats.record_request(&cluster_id, false);
+    stats.record_execution(&cluster_id, Duration::from_millis(10));
+    stats.record_failure(
+        &DecodeFailure::new(Error::DecoderExecutionError, DecodeStage::Execute)
+            .with_cluster_id(cluster_id),
+    );
+    // failures without cluster are not counted
+    stats.record_failure(&DecodeFailure::new(
+        Error::SporeIdNotFound,
+        DecodeStage::FetchSpore,
+    ));
+
+    let report = stats.report(&cluster_id).expect("cluster stats");
+    assert_eq!(report.requests, 3);
+    assert_eq!(report.cache_hits, 1);
+    assert_eq!(report.errors, 1);
+    assert_eq!(report.executions, 1);
+    assert!((report.average_execution_ms - 10.0).abs() < 1e-6);
+    let last_error = report.last_decode_error.expect("last error");
+    assert_eq!(last_error.code, Error::DecoderExecutionError as i32);
+    assert_eq!(last_error.stage, DecodeStage::Execute);
+
+    stats.record_request(&[2u8; 32], true);
+    let reports = stats.reports();
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[0].cluster_id.0, cluster_id);
+    assert_eq!(reports[1].report.requests, 1);
+}
+
+#[test]
+fn test_format_statsd_metric() {
+    let tags = [("result", "hit")];
+    assert_eq!(
+        format_metric(
+            "dob",
+            StatsdFlavor::Statsd,
+            &[],
+            "render_cache",
+            "1",
+            "c",
+            &tags
+        ),
+        "dob.render_cache.hit:1|c"
+    );
+    assert_eq!(
+        format_metric(
+            "dob",
+            StatsdFlavor::Datadog,
+            &["env:production".to_string()],
+            "render_cache",
+            "1",
+            "c",
+            &tags
+        ),
+        "dob.render_cache:1|c|#env:production,result:hit"
+    );
+    assert_eq!(
+        format_metric(
+            "",
+            StatsdFlavor::Datadog,
+            &[],
+            "decode.duration",
+            "1.500",
+            "ms",
+            &[]
+        ),
+        "decode.duration:1.500|ms"
+    );
+}
+
+#[test]
+fn test_parse_rest_decode_options() {
+    let options = parse_decode_options(None);
+    assert_eq!(options.anchor_block_hash, None);
+    assert!(!options.include_metadata);
+    assert!(!options.refresh);
+    assert_eq!(options.traits, None);
+
+    let options = parse_decode_options(Some(
+        "include_metadata=true&traits=Name,Level&anchor_block_hash=0xab&encoding=cbor&include_traits=true&include_owner=true&refresh=true",
+    ));
+    assert_eq!(options.anchor_block_hash.as_deref(), Some("0xab"));
+    assert!(options.include_metadata);
+    assert!(options.include_traits);
+    assert!(options.include_owner);
+    assert!(options.refresh);
+    assert_eq!(
+        options.traits,
+        Some(vec!["Name".to_string(), "Level".to_string()])
+    );
+}
+
+#[test]
+fn test_openrpc_document() {
+    // error codes are contiguous, so that `Error::ALL` catches up with new variants
+    for (index, error) in Error::ALL.iter().enumerate() {
+        assert_eq!(*error as i32, 1001 + index as i32);
+    }
+
+    let document = openrpc_document();
+    assert_eq!(document["info"]["version"], env!("CARGO_PKG_VERSION"));
+    let errors = document["components"]["errors"].as_object().unwrap();
+    assert_eq!(errors.len(), Error::ALL.len());
+    assert_eq!(errors["SporeIdNotFound"]["code"], 1004);
+
+    let methods = document["methods"].as_array().unwrap();
+    let dob_decode = methods
+        .iter()
+        .find(|method| method["name"] == "dob_decode")
+        .expect("dob_decode");
+    assert_eq!(
+        dob_decode["errors"].as_array().unwrap().len(),
+        Error::ALL.len()
+    );
+    assert!(methods
+        .iter()
+        .any(|method| method["name"] == "rpc.discover"));
+}
+
+#[test]
+fn test_nft_metadata() {
+    let render_output = json!([
+        { "name": "prev.type", "traits": [{ "String": "text" }] },
+        { "name": "prev.bg", "traits": [{ "String": "ipfs://bafy" }] },
+        { "name": "Wings", "traits": [{ "String": "Sun Wings" }] },
+        { "name": "Lucky Number", "traits": [{ "Number": 35 }] },
+    ]);
+    let metadata = nft_metadata("0xabcdef0123456789", &render_output, Some("Unicorns"));
+    assert_eq!(
+        metadata,
+        json!({
+            "name": "DOB #abcdef01",
+            "description": "Unicorns",
+            "image": "ipfs://bafy",
+            "attributes": [
+                { "trait_type": "Wings", "value": "Sun Wings" },
+                { "trait_type": "Lucky Number", "display_type": "number", "value": 35 },
+            ],
+        })
+    );
+
+    let render_output = json!([
+        { "name": "Name", "traits": [{ "String": "Spirit" }] },
+        { "name": "IMAGE", "traits": [{ "String": "<svg/>" }] },
+    ]);
+    let metadata = nft_metadata("abcdef", &render_output, None);
+    assert_eq!(metadata["name"], "Spirit");
+    assert_eq!(
+        metadata["image"],
+        "data:image/svg+xml;charset=utf-8,%3Csvg%2F%3E"
+    );
+    assert_eq!(metadata["attributes"], json!([]));
+}
+
+#[test]
+fn test_compose_svg() {
+    let render_output = json!([
+        { "name": "prev.type", "traits": [{ "String": "text" }] },
+        { "name": "prev.bgcolor", "traits": [{ "String": "#ff0000" }] },
+        { "name": "prev.bg", "traits": [{ "String": "btcfs://0123i0" }] },
+        { "name": "Wings", "traits": [{ "String": "<Sun> & Moon" }] },
+        { "name": "Lucky Number", "traits": [{ "Number": 35 }] },
+    ]);
+    let svg = compose_svg(&render_output);
+    assert!(svg.starts_with("<svg "));
+    assert!(svg.contains(r##"<rect width="100%" height="100%" fill="#ff0000"/>"##));
+    assert!(!svg.contains("<image"));
+    assert!(svg.contains(">Wings: &lt;Sun&gt; &amp; Moon</text>"));
+    assert!(svg.contains(">Lucky Number: 35</text>"));
+    assert!(!svg.contains("prev"));
+
+    let render_output = json!([
+        { "name": "prev.bg", "traits": [{ "String": "https://example.com/bg.png" }] },
+    ]);
+    assert!(compose_svg(&render_output).contains(r#"<image href="https://example.com/bg.png""#));
+
+    let render_output = json!([
+        { "name": "Name", "traits": [{ "String": "Spirit" }] },
+        { "name": "IMAGE", "traits": [{ "SVG": "<svg><circle r=\"1\"/></svg>" }] },
+    ]);
+    assert_eq!(compose_svg(&render_output), r#"<svg><circle r="1"/></svg>"#);
+}
+
+#[test]
+fn test_parse_render_target() {
+    let svg = |size| {
+        Ok((
+            "0xabcd".to_string(),
+            RenderTarget::new(RenderFormat::Svg, size),
+        ))
+    };
+    assert_eq!(parse_render_target("0xabcd.svg", None), svg(None));
+    assert_eq!(parse_render_target("0xabcd", None), svg(None));
+    assert_eq!(
+        parse_render_target("0xabcd.png", Some("format=svg")),
+        svg(None)
+    );
+    // vectors are never scaled
+    assert_eq!(
+        parse_render_target("0xabcd.svg", Some("size=256")),
+        svg(None)
+    );
+    assert_eq!(
+        parse_render_target("0xabcd.gif", None),
+        Err(Error::RenderFormatUnsupported)
+    );
+    assert_eq!(
+        parse_render_target("0xabcd.svg", Some("size=300")),
+        Err(Error::RenderSizeUnsupported)
+    );
+    let rasterized = parse_render_target("0xabcd.svg", Some("format=webp&size=256"));
+    if cfg!(feature = "raster") {
+        assert_eq!(
+            rasterized,
+            Ok((
+                "0xabcd".to_string(),
+                RenderTarget::new(RenderFormat::Webp, Some(256))
+            ))
+        );
+        assert_eq!(rasterized.unwrap().1.cache_extension(), "256.webp");
+    } else {
+        assert_eq!(rasterized, Err(Error::RenderFormatUnsupported));
+    }
+    assert_eq!(
+        RenderTarget::all().count(),
+        1 + 2 * (1 + THUMBNAIL_SIZES.len())
+    );
+}
+
+#[test]
+fn test_request_id() {
+    let request = |id: Option<&str>| {
+        let mut request = hyper::Request::builder();
+        if let Some(id) = id {
+            request = request.header(REQUEST_ID_HEADER, id);
+        }
+        request.body(()).unwrap()
+    };
+    assert_eq!(request_id(&request(Some("edge-7f3a"))), "edge-7f3a");
+    let generated = request_id(&request(None));
+    assert_ne!(generated, request_id(&request(None)));
+    assert_ne!(request_id(&request(Some("with space"))), "with space");
+    let flooding = "a".repeat(256);
+    assert_ne!(request_id(&request(Some(&flooding))), flooding);
+}
+
+#[test]
+fn test_rate_limit_token_bucket() {
+    let settings = RateLimitSettings {
+        requests_per_second: 2.0,
+        burst: 3,
+    };
+    let now = Instant::now();
+    let mut bucket = TokenBucket::new(settings.burst, now);
+    for _ in 0..3 {
+        assert_eq!(bucket.acquire(&settings, now), Ok(()));
+    }
+    assert_eq!(
+        bucket.acquire(&settings, now),
+        Err(Duration::from_millis(500))
+    );
+    assert_eq!(
+        bucket.acquire(&settings, now + Duration::from_millis(500)),
+        Ok(())
+    );
+    // refilled up to burst only
+    let later = now + Duration::from_secs(60);
+    for _ in 0..3 {
+        assert_eq!(bucket.acquire(&settings, later), Ok(()));
+    }
+    assert!(bucket.acquire(&settings, later).is_err());
+}
+
+#[test]
+fn test_rate_limiter_buckets() {
+    let settings = RateLimitSettings {
+        requests_per_second: 1.0,
+        burst: 1,
+    };
+    let limiter = RateLimiter::new(settings);
+    let now = Instant::now();
+    let client: IpAddr = "10.0.0.1".parse().unwrap();
+    assert!(limiter.acquire_at(Some(client), now).is_ok());
+    assert!(limiter.acquire_at(Some(client), now).is_err());
+    // unknown clients share one bucket instead of bypassing the limit
+    assert!(limiter.acquire_at(None, now).is_ok());
+    assert!(limiter.acquire_at(None, now).is_err());
+    assert_eq!(limiter.tracked_clients(), 2);
+    // IPv6 clients are keyed by /64, IPv4-mapped ones by their IPv4 address
+    let ipv6_client: IpAddr = "2001:db8:0:1::1".parse().unwrap();
+    let ipv6_neighbour: IpAddr = "2001:db8:0:1:ffff::2".parse().unwrap();
+    assert!(limiter.acquire_at(Some(ipv6_client), now).is_ok());
+    assert!(limiter.acquire_at(Some(ipv6_neighbour), now).is_err());
+    let mapped_client: IpAddr = "::ffff:10.0.0.1".parse().unwrap();
+    assert!(limiter.acquire_at(Some(mapped_client), now).is_err());
+    assert_eq!(limiter.tracked_clients(), 3);
+    // idle buckets refilled to full are evicted once a sweep is due
+    let later = now + Duration::from_secs(120);
+    let another_client: IpAddr = "10.0.0.2".parse().unwrap();
+    assert!(limiter.acquire_at(Some(another_client), later).is_ok());
+    assert_eq!(limiter.tracked_clients(), 1);
+}
+
+#[test]
+fn test_forwarded_client_ip() {
+    let trusted_proxies = ["10.0.0.0/8", "2400:cb00::/32"]
+        .map(|net| net.parse::<IpNet>().unwrap())
+        .to_vec();
+    let request = |peer_addr: Option<&str>, forwarded_for: &str| {
+        let mut request = hyper::Request::builder()
+            .header("x-forwarded-for", forwarded_for)
+            .body(())
+            .unwrap();
+        if let Some(peer_addr) = peer_addr {
+            request
+                .extensions_mut()
+                .insert(PeerAddr(peer_addr.parse().unwrap()));
+        }
+        request
+    };
+    let ip = |ip: &str| Some(ip.parse::<std::net::IpAddr>().unwrap());
+
+    // spoofed address in front is skipped
+    let forwarded = request(Some("10.0.0.1:51234"), "1.1.1.1, 203.0.113.7, 10.0.0.2");
+    assert_eq!(client_ip(&forwarded, &trusted_proxies), ip("203.0.113.7"));
+    let untrusted = request(Some("198.51.100.1:51234"), "203.0.113.7");
+    assert_eq!(client_ip(&untrusted, &trusted_proxies), ip("198.51.100.1"));
+    let ipv6 = request(Some("[2400:cb00::1]:443"), "203.0.113.7");
+    assert_eq!(client_ip(&ipv6, &trusted_proxies), ip("203.0.113.7"));
+    let unix_socket = request(None, "203.0.113.7");
+    assert_eq!(client_ip(&unix_socket, &[]), ip("203.0.113.7"));
+
+    assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+    assert!("10.0.0.1"
+        .parse::<IpNet>()
+        .unwrap()
+        .contains(&"10.0.0.1".parse().unwrap()));
+    assert!(!"10.0.0.1"
+        .parse::<IpNet>()
+        .unwrap()
+        .contains(&"10.0.0.2".parse().unwrap()));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_parse_watchdog_interval() {
+    use crate::systemd::parse_watchdog_interval;
+
+    assert_eq!(
+        parse_watchdog_interval(Some("30000000"), None, 42),
+        Some(Duration::from_secs(15))
+    );
+    assert_eq!(
+        parse_watchdog_interval(Some("30000000"), Some("42"), 42),
+        Some(Duration::from_secs(15))
+    );
+    // watchdog of another process
+    assert_eq!(
+        parse_watchdog_interval(Some("30000000"), Some("7"), 42),
+        None
+    );
+    assert_eq!(parse_watchdog_interval(Some("0"), None, 42), None);
+    assert_eq!(parse_watchdog_interval(None, None, 42), None);
+}
+
+#[tokio::test]
+async fn test_unconfirmed_spore_policies() {
+    // spore created at block 95 under tip 100, which has 5 confirmations
+    let spore = MockExampleSpore::new(95);
+    let ckb_rpc = serve_mock_ckb_rpc(move |method, params| match method {
+        "get_tip_block_number" => json!("0x64"),
+        _ => spore.handle(method, params),
+    });
+    let mut settings = prepare_settings("dob/0");
+    settings.ckb_rpc = ckb_rpc;
+    settings.dobs_cache_directory = prepare_directory("unconfirmed-spore");
+    settings.spore_confirmations = 10;
+    let hexed_spore_id = hex::encode(spore.spore_id);
+    let cache_path = dob_cache_path(&settings.dobs_cache_directory, &spore.spore_id);
+    let options = DecodeOptions::default();
+
+    settings.unconfirmed_spore_policy = UnconfirmedSporePolicy::Refuse;
+    let decoder = DOBDecoder::new(settings.clone());
+    let failure = decode_dob(&decoder, hexed_spore_id.clone(), &options)
+        .await
+        .expect_err("refuse unconfirmed spore");
+    assert_eq!(failure.error, Error::SporeUnconfirmed);
+    assert_eq!(failure.stage, DecodeStage::FetchSpore);
+
+    settings.unconfirmed_spore_policy = UnconfirmedSporePolicy::Provisional;
+    let decoder = DOBDecoder::new(settings.clone());
+    let result = decode_dob(&decoder, hexed_spore_id.clone(), &options)
+        .await
+        .expect("decode provisionally");
+    assert!(result.provisional);
+    assert_eq!(serde_json::to_value(&result).unwrap()["provisional"], true);
+    // provisional results may be invalidated by reorg, so they are never cached
+    assert!(!cache_path.exists());
+
+    settings.spore_confirmations = 5;
+    let decoder = DOBDecoder::new(settings);
+    let result = decode_dob(&decoder, hexed_spore_id, &options)
+        .await
+        .expect("decode confirmed spore");
+    assert!(!result.provisional);
+    assert!(serde_json::to_value(&result)
+        .unwrap()
+        .get("provisional")
+        .is_none());
+    assert!(cache_path.exists());
+}
+
+#[tokio::test]
+async fn test_anchored_decoding_of_melted_spore() {
+    // spore minted at block 10 and melted at block 50
+    let spore = MockExampleSpore {
+        melted_at: Some(50),
+        ..MockExampleSpore::new(10)
+    };
+    let ckb_rpc = serve_mock_ckb_rpc(move |method, params| spore.handle(method, params));
+    let mut settings = prepare_settings("dob/0");
+    settings.ckb_rpc = ckb_rpc;
+    settings.dobs_cache_directory = prepare_directory("anchored-spore");
+    let decoder = DOBDecoder::new(settings);
+    let hexed_spore_id = hex::encode(spore.spore_id);
+    let anchored_at = |block_number| DecodeOptions {
+        anchor_block_hash: Some(hex::encode(mock_block_hash(block_number).as_bytes())),
+        ..Default::default()
+    };
+
+    let result = decode_dob(&decoder, hexed_spore_id.clone(), &anchored_at(20))
+        .await
+        .expect("decode spore alive at anchor");
+    assert_eq!(
+        result.dob_content,
+        json!({ "dna": "df4ffcb5e7a283ea7e6f09a504d0e256" })
+    );
+    assert!(!dob_cache_path(&decoder.setting().dobs_cache_directory, &spore.spore_id).exists());
+
+    for options in [anchored_at(5), anchored_at(60), DecodeOptions::default()] {
+        let failure = decode_dob(&decoder, hexed_spore_id.clone(), &options)
+            .await
+            .expect_err("spore not alive");
+        assert_eq!(failure.error, Error::SporeIdNotFound);
+        assert_eq!(failure.stage, DecodeStage::FetchSpore);
+    }
+
+    let failure = decode_dob(&decoder, hexed_spore_id, &anchored_at(1000))
+        .await
+        .expect_err("anchor block not on chain");
+    assert_eq!(failure.error, Error::AnchorBlockNotFound);
+}
+
+#[test]
+fn test_adaptive_concurrency() {
+    let concurrency = AdaptiveConcurrency::new(&BatchConcurrencySettings {
+        min_parallelism: 2,
+        max_parallelism: 16,
+        target_latency_ms: 1000,
+    });
+    assert_eq!(concurrency.limit(), 8);
+    let (fast, slow) = (Duration::from_millis(100), Duration::from_millis(1500));
+    let start = Instant::now();
+
+    // increased by one on each healthy decoding, up to the max
+    for _ in 0..4 {
+        concurrency.record_at(fast, None, start);
+    }
+    assert_eq!(concurrency.limit(), 12);
+    for _ in 0..10 {
+        concurrency.record_at(fast, None, start);
+    }
+    assert_eq!(concurrency.limit(), 16);
+
+    // a batch of slow decodings halves the limit only once in a window
+    for _ in 0..8 {
+        concurrency.record_at(slow, None, start);
+    }
+    assert_eq!(concurrency.limit(), 8);
+    concurrency.record_at(fast, Some(Error::FetchLiveCellsError), start);
+    assert_eq!(concurrency.limit(), 8);
+    // errors of the spore itself are not pressure
+    concurrency.record_at(fast, Some(Error::SporeIdNotFound), start);
+    assert_eq!(concurrency.limit(), 9);
+
+    // and again in the next window, down to the min
+    concurrency.record_at(slow, None, start + Duration::from_millis(1000));
+    assert_eq!(concurrency.limit(), 4);
+    for window in 2..5 {
+        let now = start + Duration::from_millis(1000 * window);
+        concurrency.record_at(fast, Some(Error::FetchTransactionError), now);
+    }
+    assert_eq!(concurrency.limit(), 2);
+
+    let concurrency = AdaptiveConcurrency::new(&BatchConcurrencySettings {
+        min_parallelism: 0,
+        max_parallelism: 1,
+        target_latency_ms: 1000,
+    });
+    assert_eq!(concurrency.limit(), 1);
+    concurrency.record_at(fast, None, start);
+    assert_eq!(concurrency.limit(), 1);
+}
+
+// run the call as if it were requested with an api key restricted to the clusters
+async fn call_with_api_key<T: Send + 'static>(
+    allowed_clusters: Vec<H256>,
+    call: impl Future<Output = T> + Send + 'static,
+) -> T {
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    let call = Mutex::new(Some(async move {
+        let _ = sender.send(call.await);
+    }));
+    let api_keys = [ApiKeySettings {
+        key: "restricted".to_string(),
+        allowed_clusters,
+    }];
+    let service = ApiKeyLayer::new(&api_keys).layer(tower::service_fn(move |_: Request<Body>| {
+        let call = call.lock().unwrap().take().expect("called once");
+        async move {
+            call.await;
+            Ok::<_, Infallible>(Response::new(Body::empty()))
+        }
+    }));
+    let request = Request::builder()
+        .header(API_KEY_HEADER, "restricted")
+        .body(Body::empty())
+        .unwrap();
+    service.oneshot(request).await.unwrap();
+    receiver.await.expect("call finished")
+}
+
+#[tokio::test]
+async fn test_cluster_access_checked_before_decoding() {
+    let spore = MockExampleSpore::new(10);
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let ckb_rpc = serve_mock_ckb_rpc({
+        let calls = calls.clone();
+        move |method, params| {
+            calls.lock().unwrap().push(format!("{method} {params}"));
+            match method {
+                "get_tip_block_number" => json!("0x64"),
+                _ => spore.handle(method, params),
+            }
+        }
+    });
+    let mut settings = prepare_settings("dob/0");
+    settings.ckb_rpc = ckb_rpc;
+    settings.dobs_cache_directory = prepare_directory("cluster-access");
+    let decoder = Arc::new(DOBDecoder::new(settings));
+    let server = DecoderStandaloneServer::new(decoder.clone());
+    let cluster_id = format!("0x{}", hex::encode(spore.cluster_id));
+    let other_cluster = H256::from([0x33; 32]);
+
+    // methods taking the cluster are denied before anything is fetched from node
+    macro_rules! assert_denied {
+        ($method:path $(, $arg:expr)*) => {{
+            let (server, cluster_id) = (server.clone(), cluster_id.clone());
+            let code = call_with_api_key(vec![other_cluster.clone()], async move {
+                $method(&server, cluster_id $(, $arg)*)
+                    .await
+                    .err()
+                    .map(|error| error.code())
+            })
+            .await;
+            assert_eq!(
+                code,
+                Some(Error::ClusterAccessDenied as i32),
+                "{}",
+                stringify!($method)
+            );
+        }};
+    }
+    assert_denied!(DecoderRpcServer::decode_by_serial, 1, None, None, None);
+    assert_denied!(DecoderRpcServer::decode_cluster, None, None, None);
+    assert_denied!(DecoderRpcServer::decode_dry_run, "0x00".to_string(), None);
+    assert_denied!(
+        DecoderRpcServer::batch_decode_dna,
+        vec!["0x00".to_string()],
+        None
+    );
+    assert_denied!(DecoderRpcServer::cluster_info, None);
+    assert_denied!(DecoderRpcServer::cluster_stats, None);
+    assert_denied!(DecoderRpcServer::cluster_rarity, None);
+    assert_denied!(DecoderRpcServer::crawl_status);
+    assert_denied!(DecoderRpcServer::search_traits, Vec::new(), None, None);
+    assert_denied!(
+        DecoderRpcV2Server::decode_by_serial,
+        1,
+        None,
+        None,
+        None,
+        None
+    );
+    assert!(calls.lock().unwrap().is_empty());
+
+    // spores are denied once their cluster is known, before the cluster is fetched
+    let hexed_spore_id = hex::encode(spore.spore_id);
+    let failure = call_with_api_key(vec![other_cluster.clone()], {
+        let (decoder, hexed_spore_id) = (decoder.clone(), hexed_spore_id.clone());
+        async move { decode_dob(&decoder, hexed_spore_id, &DecodeOptions::default()).await }
+    })
+    .await
+    .expect_err("deny spore of another cluster");
+    assert_eq!(failure.error, Error::ClusterAccessDenied);
+    assert_eq!(failure.stage, DecodeStage::FetchCluster);
+    let failure = call_with_api_key(vec![other_cluster], {
+        let decoder = decoder.clone();
+        async move { decode_dob_verbose(&decoder, spore.spore_id).await }
+    })
+    .await
+    .err()
+    .expect("deny spore of another cluster verbosely");
+    assert_eq!(failure.error, Error::ClusterAccessDenied);
+    let hexed_cluster_id = hex::encode(spore.cluster_id);
+    assert!(!calls.lock().unwrap().is_empty());
+    assert!(calls
+        .lock()
+        .unwrap()
+        .iter()
+        .all(|call| !call.contains(&hexed_cluster_id)));
+    assert!(!dob_cache_path(&decoder.setting().dobs_cache_directory, &spore.spore_id).exists());
+
+    // while keys allowed to the cluster decode as usual
+    let result = call_with_api_key(vec![spore.cluster_id.into()], {
+        let decoder = decoder.clone();
+        async move { decode_dob(&decoder, hexed_spore_id, &DecodeOptions::default()).await }
+    })
+    .await
+    .expect("decode spore of allowed cluster");
+    assert_eq!(result.render_output[0]["name"], "Name");
+}
+
+#[tokio::test]
+async fn test_decode_by_serial_of_cluster_not_indexed() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let ckb_rpc = serve_mock_ckb_rpc({
+        let calls = calls.clone();
+        move |method, _| {
+            calls.lock().unwrap().push(method.to_string());
+            json!({ "objects": [], "last_cursor": "0x" })
+        }
+    });
+    let mut settings = prepare_settings("dob/0");
+    settings.ckb_rpc = ckb_rpc;
+    let server = DecoderStandaloneServer::new(Arc::new(DOBDecoder::new(settings)));
+    let cluster_id = format!("0x{}", hex::encode([0x22u8; 32]));
+
+    // serial lookups of arbitrary clusters never scan spore cells on node
+    let error =
+        DecoderRpcServer::decode_by_serial(&server, cluster_id.clone(), 1, None, None, None)
+            .await
+            .expect_err("cluster not indexed");
+    assert_eq!(error.code(), Error::ClusterNotIndexed as i32);
+    let error =
+        DecoderRpcV2Server::decode_by_serial(&server, cluster_id, 1, None, None, None, None)
+            .await
+            .expect_err("cluster not indexed");
+    assert_eq!(error.code(), Error::ClusterNotIndexed as i32);
+    assert!(calls.lock().unwrap().is_empty());
+}
@@ -0,0 +1,297 @@
+use ckb_types::{h256, H256};
+
+use crate::config::{merge_table, parse_settings, ConfigFormat, SettingsSource};
+use crate::env_overrides::apply_env_overrides;
+use crate::tests::prepare_settings;
+use crate::types::{
+    ApiKeySettings, ClusterDecoderOverride, CorsSettings, DecodePipeline, LoggingSettings, Network,
+    NetworkSettings, OnchainDecoderDeployment,
+};
+
+#[test]
+fn test_validate_settings() {
+    let mut settings = prepare_settings("dob/0");
+    settings.rpc_server_address = "127.0.0.1:8090".to_string();
+    assert!(settings.validate().is_ok());
+
+    settings.protocol_versions = vec![];
+    settings.available_spores = vec![];
+    settings.dobs_cache_directory = settings.decoders_cache_directory.clone();
+    settings
+        .onchain_decoder_deployment
+        .push(OnchainDecoderDeployment {
+            code_hash: H256::default(),
+            tx_hash: h256!("0x01"),
+            ..Default::default()
+        });
+    let errors = settings.validate().expect_err("invalid settings");
+    assert_eq!(errors.len(), 4);
+}
+
+#[test]
+fn test_validate_cluster_decoder_overrides() {
+    let mut settings = prepare_settings("dob/0");
+    settings.rpc_server_address = "127.0.0.1:8090".to_string();
+    settings.cluster_decoder_overrides = vec![
+        ClusterDecoderOverride {
+            cluster_id: h256!("0x01"),
+            code_hash: Some(h256!("0x02")),
+            ..Default::default()
+        },
+        ClusterDecoderOverride {
+            cluster_id: h256!("0x03"),
+            type_id: Some(h256!("0x04")),
+            ..Default::default()
+        },
+    ];
+    assert!(settings.validate().is_ok());
+
+    // duplicated cluster, and no locator for it
+    settings
+        .cluster_decoder_overrides
+        .push(ClusterDecoderOverride {
+            cluster_id: h256!("0x01"),
+            ..Default::default()
+        });
+    let errors = settings.validate().expect_err("invalid overrides");
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn test_logging_settings() {
+    let mut settings = prepare_settings("dob/0");
+    settings.rpc_server_address = "127.0.0.1:8090".to_string();
+    settings.logging = LoggingSettings {
+        level: "warn".to_string(),
+        modules: [(
+            "dob_decoder_server::decoder".to_string(),
+            "DEBUG".to_string(),
+        )]
+        .into(),
+    };
+    assert!(settings.validate().is_ok());
+    assert_eq!(
+        settings.logging.directives(),
+        "warn,render_debug=info,dob_decoder_server::decoder=DEBUG"
+    );
+
+    settings.logging.level = "verbose".to_string();
+    let errors = settings.validate().expect_err("invalid log level");
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn test_validate_api_keys() {
+    let mut settings = prepare_settings("dob/0");
+    settings.rpc_server_address = "127.0.0.1:8090".to_string();
+    settings.api_keys = vec![
+        ApiKeySettings {
+            key: "internal".to_string(),
+            allowed_clusters: Vec::new(),
+        },
+        ApiKeySettings {
+            key: "white-label".to_string(),
+            allowed_clusters: vec![h256!("0x01")],
+        },
+    ];
+    assert!(settings.validate().is_ok());
+
+    settings.api_keys[1].key = "internal".to_string();
+    let errors = settings.validate().expect_err("duplicate api keys");
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn test_validate_cors_settings() {
+    let mut settings = prepare_settings("dob/0");
+    settings.rpc_server_address = "127.0.0.1:8090".to_string();
+    settings.cors = Some(CorsSettings {
+        allowed_origins: vec!["*".to_string()],
+        allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+        allowed_headers: vec!["content-type".to_string(), "x-api-key".to_string()],
+        max_age_secs: Some(600),
+    });
+    assert!(settings.validate().is_ok());
+
+    settings.cors = Some(CorsSettings {
+        allowed_origins: vec!["https://app.example.com\n".to_string()],
+        allowed_methods: vec!["GET POST".to_string()],
+        allowed_headers: vec!["content type".to_string()],
+        max_age_secs: None,
+    });
+    let errors = settings.validate().expect_err("invalid cors");
+    assert_eq!(errors.len(), 3);
+}
+
+#[test]
+fn test_apply_env_overrides() {
+    let mut table = toml::from_str::<serde_json::Map<String, serde_json::Value>>(
+        r#"
+        ckb_rpc = "https://testnet.ckb.dev"
+        rpc_server_address = "0.0.0.0:8090"
+        [batch_concurrency]
+        min_parallelism = 1
+        "#,
+    )
+    .unwrap();
+    let vars = [
+        ("DOB_CKB_RPC", "https://mainnet.ckb.dev"),
+        ("DOB_BATCH_CONCURRENCY__MAX_PARALLELISM", "16"),
+        ("DOB_PROTOCOL_VERSIONS", r#"["dob/0", "dob/1"]"#),
+        ("DOB_API_KEY", r#""123""#),
+        ("DOB_", "ignored"),
+        ("PATH", "/usr/bin"),
+    ]
+    .map(|(name, value)| (name.to_string(), value.to_string()));
+    apply_env_overrides(&mut table, vars);
+
+    let expected = toml::from_str::<serde_json::Map<String, serde_json::Value>>(
+        r#"
+        ckb_rpc = "https://mainnet.ckb.dev"
+        rpc_server_address = "0.0.0.0:8090"
+        protocol_versions = ["dob/0", "dob/1"]
+        api_key = "123"
+        [batch_concurrency]
+        min_parallelism = 1
+        max_parallelism = 16
+        "#,
+    )
+    .unwrap();
+    assert_eq!(table, expected);
+}
+
+#[test]
+fn test_validate_protocol_pipelines() {
+    let mut settings = prepare_settings("dob/0");
+    settings.rpc_server_address = "127.0.0.1:8090".to_string();
+    settings.protocol_versions.push("dob/2".to_string());
+    let errors = settings.validate().expect_err("dob/2 without pipeline");
+    assert_eq!(errors.len(), 1);
+
+    settings
+        .protocol_pipelines
+        .insert("dob/2".to_string(), DecodePipeline::Compose);
+    assert!(settings.validate().is_ok());
+}
+
+#[test]
+fn test_parse_settings_formats() {
+    assert_eq!(
+        ConfigFormat::from_path("settings.toml".as_ref()),
+        ConfigFormat::Toml
+    );
+    assert_eq!(
+        ConfigFormat::from_path("config/settings.YML".as_ref()),
+        ConfigFormat::Yaml
+    );
+    assert_eq!(
+        ConfigFormat::from_path("settings.json".as_ref()),
+        ConfigFormat::Json
+    );
+    assert_eq!(
+        ConfigFormat::from_path("settings".as_ref()),
+        ConfigFormat::Toml
+    );
+
+    let settings = prepare_settings("dob/0");
+    let expected = serde_json::to_value(&settings).unwrap();
+    let json = serde_json::to_string(&settings).unwrap();
+    let parsed = parse_settings(&json, ConfigFormat::Json).expect("parse json");
+    assert_eq!(serde_json::to_value(&parsed).unwrap(), expected);
+    let yaml = serde_yaml::to_string(&settings).unwrap();
+    let parsed = parse_settings(&yaml, ConfigFormat::Yaml).expect("parse yaml");
+    assert_eq!(serde_json::to_value(&parsed).unwrap(), expected);
+    assert!(parse_settings("- dob/0", ConfigFormat::Yaml).is_err());
+}
+
+#[test]
+fn test_network_profiles() {
+    for network in [Network::Mainnet, Network::Testnet] {
+        let source = SettingsSource {
+            network: Some(network),
+            file: None,
+        };
+        let settings = source.read().expect("parse profile");
+        assert_eq!(settings.network, network);
+        assert!(!settings.available_spores.is_empty());
+        assert!(!settings.available_clusters.is_empty());
+        assert!(!settings.onchain_decoder_deployment.is_empty());
+    }
+
+    let mut base = serde_json::json!({
+        "ckb_rpc": "https://mainnet.ckb.dev/",
+        "available_spores": [{ "code_hash": "0x01" }],
+        "batch_concurrency": { "min_parallelism": 1, "max_parallelism": 32 },
+    });
+    let overlay = serde_json::json!({
+        "available_spores": [{ "code_hash": "0x02" }],
+        "batch_concurrency": { "max_parallelism": 8 },
+    });
+    merge_table(
+        base.as_object_mut().unwrap(),
+        overlay.as_object().unwrap().clone(),
+    );
+    assert_eq!(
+        base,
+        serde_json::json!({
+            "ckb_rpc": "https://mainnet.ckb.dev/",
+            "available_spores": [{ "code_hash": "0x02" }],
+            "batch_concurrency": { "min_parallelism": 1, "max_parallelism": 8 },
+        })
+    );
+}
+
+#[test]
+fn test_validate_networks() {
+    let mut settings = prepare_settings("dob/0");
+    settings.rpc_server_address = "127.0.0.1:8090".to_string();
+    let testnet = NetworkSettings {
+        name: "testnet".to_string(),
+        ckb_rpc: "https://testnet.ckbapp.dev/".to_string(),
+        network: Network::Testnet,
+        decoders_cache_directory: "cache/decoders-testnet".into(),
+        dobs_cache_directory: "cache/dobs-testnet".into(),
+        onchain_decoder_deployment: vec![],
+        available_spores: settings.available_spores.clone(),
+        available_clusters: settings.available_clusters.clone(),
+    };
+    settings.networks = vec![testnet.clone()];
+    settings.cluster_decoder_overrides = vec![ClusterDecoderOverride {
+        cluster_id: h256!("0x01"),
+        code_hash: Some(h256!("0x02")),
+        ..Default::default()
+    }];
+    settings.api_keys = vec![ApiKeySettings {
+        key: "key".to_string(),
+        allowed_clusters: vec![h256!("0x01")],
+    }];
+    assert!(settings.validate().is_ok());
+
+    let testnet_settings = settings.for_network(&testnet);
+    assert_eq!(testnet_settings.ckb_rpc, testnet.ckb_rpc);
+    assert_eq!(testnet_settings.network, Network::Testnet);
+    assert_eq!(
+        testnet_settings.dobs_cache_directory,
+        testnet.dobs_cache_directory
+    );
+    assert_eq!(
+        testnet_settings.protocol_versions,
+        settings.protocol_versions
+    );
+    assert!(testnet_settings.networks.is_empty());
+    // cluster ids of this network mean nothing on the other one
+    assert!(testnet_settings.cluster_decoder_overrides.is_empty());
+    assert!(testnet_settings.api_keys.is_empty());
+
+    // decoders deployed by type id differ among networks
+    settings.networks[0].decoders_cache_directory = settings.decoders_cache_directory.clone();
+    let errors = settings.validate().expect_err("shared decoders cache");
+    assert_eq!(errors.len(), 1);
+    settings.networks[0].decoders_cache_directory = testnet.decoders_cache_directory.clone();
+
+    let mut duplicated = testnet.clone();
+    duplicated.decoders_cache_directory = "cache/decoders-testnet2".into();
+    settings.networks.push(duplicated);
+    let errors = settings.validate().expect_err("invalid networks");
+    assert_eq!(errors.len(), 3);
+}
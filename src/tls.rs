@@ -0,0 +1,56 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+use crate::types::TlsSettings;
+
+// handshakes bounded in time and number, so that clients stalling in the middle of handshakes
+// can't exhaust sockets or memory of the server
+#[derive(Clone)]
+pub struct TlsHandshaker {
+    acceptor: TlsAcceptor,
+    timeout: Duration,
+    permits: Arc<Semaphore>,
+}
+
+impl TlsHandshaker {
+    pub fn new(settings: &TlsSettings, alpn_protocol: &[u8]) -> io::Result<Self> {
+        Ok(Self {
+            acceptor: tls_acceptor(settings, alpn_protocol)?,
+            timeout: Duration::from_secs(settings.handshake_timeout_secs),
+            permits: Arc::new(Semaphore::new(settings.max_concurrent_handshakes)),
+        })
+    }
+
+    // connections beyond `max_concurrent_handshakes` are dropped without waiting
+    pub async fn accept(&self, stream: TcpStream) -> io::Result<TlsStream<TcpStream>> {
+        let _permit = self.permits.try_acquire().map_err(|_| {
+            io::Error::new(io::ErrorKind::Other, "too many TLS handshakes in progress")
+        })?;
+        tokio::time::timeout(self.timeout, self.acceptor.accept(stream))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "TLS handshake timed out"))?
+    }
+}
+
+// load certificate chain and private key in PEM, certificates renewed by an ACME client like
+// certbot take effect after restarting, `alpn_protocol` is `http/1.1` for json-rpc and `h2` for gRPC
+fn tls_acceptor(settings: &TlsSettings, alpn_protocol: &[u8]) -> io::Result<TlsAcceptor> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(&settings.cert_path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(&settings.key_path)?))?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no private key found"))?;
+    let mut config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+    config.alpn_protocols = vec![alpn_protocol.to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
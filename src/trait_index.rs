@@ -0,0 +1,174 @@
+use std::collections::BTreeSet;
+
+use ckb_types::H256;
+use jsonrpsee::tracing;
+use serde_json::Value;
+
+use crate::types::{
+    Error, RenderedTraits, TraitFilter, TraitIndexSettings, TraitSearchPage, TraitValue,
+};
+
+// embedded index of trait values of decoded spores, built up incrementally along with render
+// cache, so that spores matching trait values are found without decoding the whole cluster
+//
+// tree `traits` is keyed by `cluster_id | name | value | spore_id`, where name and value are
+// length-prefixed, and tree `spores` keeps keys of each spore for replacing them on re-decoding
+pub struct TraitIndex {
+    traits: sled::Tree,
+    spores: sled::Tree,
+}
+
+impl TraitIndex {
+    pub fn open(settings: &TraitIndexSettings) -> Result<Self, sled::Error> {
+        let db = sled::open(&settings.directory)?;
+        Ok(Self {
+            traits: db.open_tree("traits")?,
+            spores: db.open_tree("spores")?,
+        })
+    }
+
+    pub fn contains(&self, spore_id: &[u8; 32]) -> bool {
+        self.spores.contains_key(spore_id).unwrap_or_default()
+    }
+
+    // replace indexed traits of the spore, render hints of `prev.*` and SVG images are left out
+    // since they don't make up traits, same as rarity statistics
+    pub fn insert(
+        &self,
+        spore_id: &[u8; 32],
+        cluster_id: &[u8; 32],
+        rendered_traits: &RenderedTraits,
+    ) -> Result<(), Error> {
+        let mut keys = Vec::new();
+        for item in &rendered_traits.0 {
+            if item.name.starts_with("prev") {
+                continue;
+            }
+            for value in &item.traits {
+                if matches!(value, TraitValue::Svg(_)) {
+                    continue;
+                }
+                let mut key = trait_prefix(cluster_id, &item.name, &indexed_value(value));
+                key.extend_from_slice(spore_id);
+                keys.push(key);
+            }
+        }
+        let mut batch = sled::Batch::default();
+        for key in self.indexed_keys(spore_id)? {
+            batch.remove(key);
+        }
+        for key in &keys {
+            batch.insert(key.as_slice(), Vec::new());
+        }
+        self.traits.apply_batch(batch).map_err(index_error)?;
+        let keys = keys.iter().map(hex::encode).collect::<Vec<_>>();
+        self.spores
+            .insert(spore_id, serde_json::to_vec(&keys).unwrap())
+            .map_err(index_error)?;
+        Ok(())
+    }
+
+    pub fn remove(&self, spore_id: &[u8; 32]) -> Result<(), Error> {
+        let mut batch = sled::Batch::default();
+        for key in self.indexed_keys(spore_id)? {
+            batch.remove(key);
+        }
+        self.traits.apply_batch(batch).map_err(index_error)?;
+        self.spores.remove(spore_id).map_err(index_error)?;
+        Ok(())
+    }
+
+    // drop all indexed spores, or those of a cluster
+    pub fn clear(&self, cluster_id: Option<&[u8; 32]>) -> Result<(), Error> {
+        let Some(cluster_id) = cluster_id else {
+            self.traits.clear().map_err(index_error)?;
+            return self.spores.clear().map_err(index_error);
+        };
+        let spore_ids = self
+            .traits
+            .scan_prefix(cluster_id)
+            .keys()
+            .filter_map(|key| key.ok())
+            .filter_map(|key| key[key.len() - 32..].try_into().ok())
+            .collect::<BTreeSet<[u8; 32]>>();
+        for spore_id in spore_ids {
+            self.remove(&spore_id)?;
+        }
+        Ok(())
+    }
+
+    // spores of the cluster matching all filters, in ascending order of spore id, starting after
+    // the one in cursor
+    pub fn search(
+        &self,
+        cluster_id: &[u8; 32],
+        filters: &[TraitFilter],
+        after: Option<[u8; 32]>,
+        limit: usize,
+    ) -> Result<TraitSearchPage, Error> {
+        let mut matched: Option<BTreeSet<[u8; 32]>> = None;
+        for filter in filters {
+            let prefix = trait_prefix(cluster_id, &filter.name, &filter.value.to_string());
+            let mut spore_ids = BTreeSet::new();
+            for key in self.traits.scan_prefix(&prefix).keys() {
+                let key = key.map_err(index_error)?;
+                if let Ok(spore_id) = key[prefix.len()..].try_into() {
+                    spore_ids.insert(spore_id);
+                }
+            }
+            matched = Some(match matched {
+                Some(matched) => matched.intersection(&spore_ids).copied().collect(),
+                None => spore_ids,
+            });
+        }
+        let mut spore_ids = matched
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|spore_id| after.map_or(true, |after| *spore_id > after))
+            .take(limit + 1)
+            .map(H256)
+            .collect::<Vec<_>>();
+        let next_cursor = if spore_ids.len() > limit {
+            spore_ids.truncate(limit);
+            spore_ids.last().map(|spore_id| format!("0x{spore_id}"))
+        } else {
+            None
+        };
+        Ok(TraitSearchPage {
+            spore_ids,
+            next_cursor,
+        })
+    }
+
+    fn indexed_keys(&self, spore_id: &[u8; 32]) -> Result<Vec<Vec<u8>>, Error> {
+        let Some(keys) = self.spores.get(spore_id).map_err(index_error)? else {
+            return Ok(Vec::new());
+        };
+        let keys: Vec<String> = serde_json::from_slice(&keys).unwrap_or_default();
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| hex::decode(key).ok())
+            .collect())
+    }
+}
+
+// values are indexed in JSON regardless of their types, so that `"Caesar Horn"` or `3` in filter
+// matches the trait value as it is
+fn indexed_value(value: &TraitValue) -> String {
+    let typed_value = serde_json::to_value(value).unwrap();
+    typed_value.get("value").unwrap_or(&Value::Null).to_string()
+}
+
+fn trait_prefix(cluster_id: &[u8; 32], name: &str, value: &str) -> Vec<u8> {
+    let mut prefix = cluster_id.to_vec();
+    for part in [name, value] {
+        prefix.extend_from_slice(&(part.len() as u32).to_be_bytes());
+        prefix.extend_from_slice(part.as_bytes());
+    }
+    prefix
+}
+
+fn index_error(error: sled::Error) -> Error {
+    tracing::error!("trait index error: {error}");
+    Error::TraitIndexError
+}
@@ -1,16 +1,21 @@
-use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    net::ToSocketAddrs,
+    path::PathBuf,
+    sync::Arc,
+};
 
 use ckb_types::{core::ScriptHashType, H256};
 use serde::Deserialize;
 use serde_json::Value;
 
 #[cfg(feature = "standalone_server")]
-use jsonrpsee::types::ErrorCode;
+use jsonrpsee::types::{ErrorCode, ErrorObject, ErrorObjectOwned};
 #[cfg(feature = "standalone_server")]
 use serde::Serialize;
 
 #[allow(clippy::enum_variant_names)]
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(i32)]
 pub enum Error {
     #[error("DNA bytes length not match the requirement in Cluster")]
@@ -67,6 +72,159 @@ pub enum Error {
     DecoderBinaryNotFoundInCell,
     #[error("error ocurred while requesing json-rpc")]
     JsonRpcRequestError,
+    #[error("fetched cluster cell doesn't match the requested cluster id")]
+    ClusterCellMismatch,
+    #[error("encounter error while fetching tip block number")]
+    FetchTipBlockNumberError,
+    #[error("spore cell hasn't reached the required confirmations")]
+    SporeUnconfirmed,
+    #[error("block hash string is not in hex format")]
+    HexedBlockHashParseError,
+    #[error("anchor block not found on the canonical chain")]
+    AnchorBlockNotFound,
+    #[error("failed to broadcast cache invalidation to other replicas")]
+    InvalidationPublishError,
+    #[error("cluster id string is not in hex format")]
+    HexedClusterIdParseError,
+    #[error("no spore found with the serial in cluster")]
+    SporeSerialNotFound,
+    #[error("address is neither a valid CKB address nor a lock script")]
+    AddressParseError,
+    #[error("cursor of owned spores is malformed")]
+    OwnedSporesCursorParseError,
+    #[error("failed to fetch decoder registry from its source")]
+    DecoderRegistryFetchError,
+    #[error("decoder registry is malformed or its signature is invalid")]
+    DecoderRegistryInvalid,
+    #[error("failed to decode through upstream server")]
+    UpstreamDecodeError,
+    #[error("render format is unknown or not enabled in this build")]
+    RenderFormatUnsupported,
+    #[error("failed to rasterize composed SVG")]
+    RasterizeError,
+    #[error("thumbnail size is not one of supported sizes")]
+    RenderSizeUnsupported,
+    #[error("asset uri is malformed or its scheme is not configured")]
+    AssetUriInvalid,
+    #[error("failed to fetch asset from its source")]
+    AssetFetchError,
+    #[error("no asset found at the uri")]
+    AssetNotFound,
+    #[error("trait index is not configured or not enabled in this build")]
+    TraitIndexDisabled,
+    #[error("failed to read or write trait index")]
+    TraitIndexError,
+    #[error("cursor of trait search is malformed")]
+    TraitSearchCursorParseError,
+    #[error("bitcoin outpoint should be in the form of `txid:vout`")]
+    BtcOutpointParseError,
+    #[error("transaction hash string is not in hex format")]
+    HexedTxHashParseError,
+    #[error("cell at the outpoint is not a spore")]
+    CellNotSpore,
+    #[error("decoder binary string is not in hex format")]
+    HexedDecoderBinaryParseError,
+    #[error("failed to reload settings file")]
+    SettingsReloadError,
+    #[error("invalid log filter directives")]
+    LogFilterInvalid,
+    #[error("too many requests, retry later")]
+    RateLimitExceeded,
+    #[error("missing or unknown api key")]
+    ApiKeyInvalid,
+    #[error("cluster of the spore is not allowed to the api key")]
+    ClusterAccessDenied,
+    #[error("too many spore ids or DNAs in one batch")]
+    BatchSizeExceeded,
+    #[error("network is not served")]
+    NetworkNotFound,
+    #[error("inline decoder binary exceeds the size limit")]
+    DecoderBinaryTooLarge,
+    #[error("spore serials are not indexed for the cluster")]
+    ClusterNotIndexed,
+}
+
+impl Error {
+    // errors telling something wrong with decoders or render cache of this server, rather than
+    // with requests or chain data, which operators should learn about before users do
+    pub fn is_severe(&self) -> bool {
+        matches!(
+            self,
+            Error::DecoderOutputInvalid
+                | Error::DecoderExecutionError
+                | Error::DecoderExecutionInternalError
+                | Error::DOBRenderCacheNotFound
+                | Error::DOBRenderCacheModified
+                | Error::DecoderBinaryHashInvalid
+                | Error::DecoderBinaryNotFoundInCell
+        )
+    }
+
+    // all errors in order of their codes, new variants should be appended here as well
+    pub const ALL: [Error; 62] = [
+        Error::DnaLengthNotMatch,
+        Error::SporeIdLengthInvalid,
+        Error::NativeDecoderNotFound,
+        Error::SporeIdNotFound,
+        Error::SporeDataUncompatible,
+        Error::SporeDataContentTypeUncompatible,
+        Error::DOBVersionUnexpected,
+        Error::ClusterIdNotSet,
+        Error::ClusterIdNotFound,
+        Error::ClusterDataUncompatible,
+        Error::DecoderIdNotFound,
+        Error::DecoderOutputInvalid,
+        Error::HexedDNAParseError,
+        Error::HexedSporeIdParseError,
+        Error::DecoderBinaryPathInvalid,
+        Error::DecoderExecutionError,
+        Error::DecoderExecutionInternalError,
+        Error::FetchLiveCellsError,
+        Error::FetchTransactionError,
+        Error::NoOutputCellInTransaction,
+        Error::DOBContentUnexpected,
+        Error::DOBMetadataUnexpected,
+        Error::DOBRenderCacheNotFound,
+        Error::DOBRenderCacheModified,
+        Error::DecoderBinaryHashInvalid,
+        Error::DecoderBinaryNotFoundInCell,
+        Error::JsonRpcRequestError,
+        Error::ClusterCellMismatch,
+        Error::FetchTipBlockNumberError,
+        Error::SporeUnconfirmed,
+        Error::HexedBlockHashParseError,
+        Error::AnchorBlockNotFound,
+        Error::InvalidationPublishError,
+        Error::HexedClusterIdParseError,
+        Error::SporeSerialNotFound,
+        Error::AddressParseError,
+        Error::OwnedSporesCursorParseError,
+        Error::DecoderRegistryFetchError,
+        Error::DecoderRegistryInvalid,
+        Error::UpstreamDecodeError,
+        Error::RenderFormatUnsupported,
+        Error::RasterizeError,
+        Error::RenderSizeUnsupported,
+        Error::AssetUriInvalid,
+        Error::AssetFetchError,
+        Error::AssetNotFound,
+        Error::TraitIndexDisabled,
+        Error::TraitIndexError,
+        Error::TraitSearchCursorParseError,
+        Error::BtcOutpointParseError,
+        Error::HexedTxHashParseError,
+        Error::CellNotSpore,
+        Error::HexedDecoderBinaryParseError,
+        Error::SettingsReloadError,
+        Error::LogFilterInvalid,
+        Error::RateLimitExceeded,
+        Error::ApiKeyInvalid,
+        Error::ClusterAccessDenied,
+        Error::BatchSizeExceeded,
+        Error::NetworkNotFound,
+        Error::DecoderBinaryTooLarge,
+        Error::ClusterNotIndexed,
+    ];
 }
 
 #[cfg(feature = "standalone_server")]
@@ -76,28 +234,262 @@ impl From<Error> for ErrorCode {
     }
 }
 
+#[cfg(feature = "standalone_server")]
+impl From<Error> for ErrorObjectOwned {
+    fn from(value: Error) -> Self {
+        let data = crate::request_id::current()
+            .map(|request_id| serde_json::json!({ "request_id": request_id }));
+        ErrorObject::owned(value as i32, value.to_string(), data)
+    }
+}
+
+// stage of the decoding pipeline where a failure happened
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DecodeStage {
+    ParseRequest,
+    ReadCache,
+    FetchSpore,
+    FetchCluster,
+    DownloadDecoder,
+    Execute,
+    WriteCache,
+    Upstream,
+    Render,
+    FetchAsset,
+}
+
+// decoding error along with where it happened, carried in `data` of JSON-RPC error so that
+// failures can be diagnosed without access to server logs
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DecodeFailure {
+    #[serde(skip)]
+    pub error: Error,
+    pub stage: DecodeStage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spore_id: Option<H256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster_id: Option<H256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoder_hash: Option<H256>,
+    // filled in once the failure is returned to client
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl DecodeFailure {
+    pub fn new(error: Error, stage: DecodeStage) -> Self {
+        Self {
+            error,
+            stage,
+            spore_id: None,
+            cluster_id: None,
+            decoder_hash: None,
+            request_id: None,
+        }
+    }
+
+    pub fn with_spore_id(mut self, spore_id: [u8; 32]) -> Self {
+        self.spore_id = Some(spore_id.into());
+        self
+    }
+
+    pub fn with_cluster_id(mut self, cluster_id: [u8; 32]) -> Self {
+        self.cluster_id = Some(cluster_id.into());
+        self
+    }
+
+    pub fn with_decoder_hash(mut self, decoder_hash: H256) -> Self {
+        self.decoder_hash = Some(decoder_hash);
+        self
+    }
+}
+
+#[cfg(feature = "standalone_server")]
+impl From<DecodeFailure> for ErrorObjectOwned {
+    fn from(mut value: DecodeFailure) -> Self {
+        value.request_id = crate::request_id::current();
+        ErrorObject::owned(value.error as i32, value.error.to_string(), Some(value))
+    }
+}
+
+// spore data normalized from any supported molecule schema revision
+pub struct NormalizedSporeData {
+    pub content_type: Vec<u8>,
+    pub content: Vec<u8>,
+    pub cluster_id: Option<Vec<u8>>,
+}
+
+// cluster data normalized from any supported molecule schema revision
+#[allow(dead_code)]
+pub struct NormalizedClusterData {
+    pub name: Vec<u8>,
+    pub description: Vec<u8>,
+    pub mutant_id: Option<Vec<u8>>,
+}
+
+// DOB content and its relevant information extracted from spore cell
+pub struct SporeCellContent {
+    pub dob_content: Value,
+    pub dna: String,
+    pub cluster_id: [u8; 32],
+    // spore cell is not buried under enough blocks yet, so result shouldn't be cached
+    pub provisional: bool,
+    // pipeline of the protocol version matched by content type of the spore
+    pub pipeline: DecodePipeline,
+    pub lock_script: ckb_jsonrpc_types::Script,
+    pub out_point: ckb_jsonrpc_types::OutPoint,
+}
+
+// decoder output in its conventional structure, like `[{"name": "Horn", "traits": [{"String":
+// "Hel Horn"}]}]`, parsed once so that consumers don't walk through raw JSON themselves
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct RenderedTraits(pub Vec<RenderedTrait>);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RenderedTrait {
+    pub name: String,
+    pub traits: Vec<TraitValue>,
+}
+
+// value of a trait tagged by its type in decoder output, while serialized in responses like
+// `{"type": "String", "value": "Hel Horn"}` which is easier to be typed by clients
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(into = "TypedTraitValue")]
+pub enum TraitValue {
+    String(String),
+    Number(serde_json::Number),
+    #[serde(rename = "SVG")]
+    Svg(String),
+    // values in types unknown to the server, kept as they are like `{"Float": 1.5}`
+    #[serde(untagged)]
+    Other(Value),
+}
+
+#[derive(Serialize)]
+struct TypedTraitValue {
+    #[serde(rename = "type")]
+    value_type: String,
+    value: Value,
+}
+
+impl From<TraitValue> for TypedTraitValue {
+    fn from(value: TraitValue) -> Self {
+        let (value_type, value) = match value {
+            TraitValue::String(text) => ("String".to_string(), Value::String(text)),
+            TraitValue::Number(number) => ("Number".to_string(), Value::Number(number)),
+            TraitValue::Svg(svg) => ("SVG".to_string(), Value::String(svg)),
+            TraitValue::Other(Value::Object(fields)) if fields.len() == 1 => {
+                fields.into_iter().next().unwrap()
+            }
+            TraitValue::Other(value) => ("Unknown".to_string(), value),
+        };
+        Self { value_type, value }
+    }
+}
+
+impl RenderedTraits {
+    pub fn parse(render_output: &Value) -> Result<Self, Error> {
+        Self::deserialize(render_output).map_err(|_| Error::DecoderOutputInvalid)
+    }
+
+    // values of the first trait with the name
+    pub fn get(&self, name: &str) -> Option<&[TraitValue]> {
+        self.0
+            .iter()
+            .find(|item| item.name == name)
+            .map(|item| item.traits.as_slice())
+    }
+}
+
+impl TraitValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TraitValue::String(text) | TraitValue::Svg(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<&serde_json::Number> {
+        match self {
+            TraitValue::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+}
+
 // value on `description` field in Cluster data, adapting for DOB protocol in JSON format
-#[derive(Deserialize)]
-#[cfg_attr(test, derive(serde::Serialize, PartialEq, Debug))]
+#[derive(Deserialize, Clone)]
+#[cfg_attr(any(test, feature = "standalone_server"), derive(serde::Serialize))]
+#[cfg_attr(test, derive(PartialEq, Debug))]
 pub struct ClusterDescriptionField {
     pub description: String,
     pub dob: DOBClusterFormat,
 }
 
-// contains `decoder` and `pattern` identifiers
-#[derive(Deserialize)]
-#[cfg_attr(test, derive(serde::Serialize, PartialEq, Debug))]
+// contains `decoder` and `pattern` identifiers, DOB/1 clusters declare them in `decoders` instead,
+// where the first one decodes traits and each of following `stages` consumes output of the previous
+// one, like composing images from traits
+#[derive(Deserialize, Clone)]
+#[serde(try_from = "DOBClusterFormatRepr")]
+#[cfg_attr(any(test, feature = "standalone_server"), derive(serde::Serialize))]
+#[cfg_attr(test, derive(PartialEq, Debug))]
 pub struct DOBClusterFormat {
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ver: Option<u8>,
     pub decoder: DOBDecoderFormat,
     pub pattern: Value,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<DOBDecoderStage>,
+}
+
+// decoder along with its own pattern, as entries of `decoders` in DOB/1 clusters
+#[derive(Deserialize, Clone)]
+#[cfg_attr(any(test, feature = "standalone_server"), derive(serde::Serialize))]
+#[cfg_attr(test, derive(PartialEq, Debug))]
+pub struct DOBDecoderStage {
+    pub decoder: DOBDecoderFormat,
+    pub pattern: Value,
 }
 
-// restricted decoder locator type
 #[derive(Deserialize)]
-#[cfg_attr(test, derive(serde::Serialize, PartialEq, Debug))]
+struct DOBClusterFormatRepr {
+    #[serde(default)]
+    ver: Option<u8>,
+    #[serde(default)]
+    decoder: Option<DOBDecoderFormat>,
+    #[serde(default)]
+    pattern: Option<Value>,
+    #[serde(default)]
+    decoders: Vec<DOBDecoderStage>,
+}
+
+impl TryFrom<DOBClusterFormatRepr> for DOBClusterFormat {
+    type Error = String;
+
+    fn try_from(repr: DOBClusterFormatRepr) -> Result<Self, Self::Error> {
+        let mut decoders = repr.decoders.into_iter();
+        let (decoder, pattern, stages) = match (repr.decoder, repr.pattern, decoders.next()) {
+            (Some(decoder), Some(pattern), _) => (decoder, pattern, Vec::new()),
+            (_, _, Some(traits)) => (traits.decoder, traits.pattern, decoders.collect()),
+            _ => return Err("missing `decoder` and `pattern`, or `decoders`".to_string()),
+        };
+        Ok(Self {
+            ver: repr.ver,
+            decoder,
+            pattern,
+            stages,
+        })
+    }
+}
+
+// restricted decoder locator type
+#[derive(Deserialize, Clone)]
+#[cfg_attr(any(test, feature = "standalone_server"), derive(serde::Serialize))]
+#[cfg_attr(test, derive(PartialEq, Debug))]
 pub enum DecoderLocationType {
     #[serde(rename(serialize = "type_id", deserialize = "type_id"))]
     TypeId,
@@ -106,14 +498,91 @@ pub enum DecoderLocationType {
 }
 
 // decoder location information
-#[derive(Deserialize)]
-#[cfg_attr(test, derive(serde::Serialize, PartialEq, Debug))]
+#[derive(Deserialize, Clone)]
+#[cfg_attr(any(test, feature = "standalone_server"), derive(serde::Serialize))]
+#[cfg_attr(test, derive(PartialEq, Debug))]
 pub struct DOBDecoderFormat {
     #[serde(rename(serialize = "type", deserialize = "type"))]
     pub location: DecoderLocationType,
     pub hash: H256,
 }
 
+// decoder run by raw decoding, either located like that in cluster metadata, or given inline as
+// hex string of its binary
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum RawDecoderParam {
+    Located(DOBDecoderFormat),
+    Binary { binary: String },
+}
+
+// decoder declared by a cluster, along with its deployment known to server and whether its binary
+// is cached locally
+#[cfg_attr(feature = "standalone_server", derive(Serialize))]
+pub struct DecoderStatus {
+    #[serde(flatten)]
+    pub decoder: DOBDecoderFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deployment: Option<OnchainDecoderDeployment>,
+    pub cached: bool,
+}
+
+// decoder binary found in decoders cache, along with its deployment known to server, size is
+// unknown under shuttle
+#[cfg_attr(feature = "standalone_server", derive(Serialize))]
+pub struct CachedDecoder {
+    #[serde(flatten)]
+    pub decoder: DOBDecoderFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deployment: Option<OnchainDecoderDeployment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+// classification of a spore without decoding it, along with the reason if it's not decodable
+#[cfg_attr(feature = "standalone_server", derive(Serialize))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SporeCheck {
+    pub decodable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    // numeric DOB protocol version detected from content type
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster_id: Option<H256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+// memory usage of the server process, for correlating OOM events with decoding workload
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct MemoryUsage {
+    // resident set size, only available on Linux
+    pub process_rss_bytes: Option<u64>,
+    pub cluster_cache_entries: usize,
+    pub cluster_cache_bytes: usize,
+    // allocator statistics, only available when jemalloc feature enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jemalloc: Option<JemallocStats>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct JemallocStats {
+    pub allocated_bytes: usize,
+    pub active_bytes: usize,
+    pub resident_bytes: usize,
+}
+
+// cluster metadata parsed from chain, along with its pattern stringified for decoder arguments
+pub struct ParsedClusterMetadata {
+    pub metadata: ClusterDescriptionField,
+    pub pattern: Arc<str>,
+    // patterns of following DOB/1 stages, in order
+    pub stage_patterns: Vec<Arc<str>>,
+    pub cluster_data_hash: [u8; 32],
+}
+
 // asscoiate `code_hash` of decoder binary with its onchain deployment information
 #[cfg_attr(
     feature = "standalone_server",
@@ -124,6 +593,20 @@ pub struct OnchainDecoderDeployment {
     pub code_hash: H256,
     pub tx_hash: H256,
     pub out_index: u32,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<DecoderChunkLocation>,
+}
+
+// location of the subsequent chunk cell if a large decoder binary is split across cells
+#[cfg_attr(
+    feature = "standalone_server",
+    derive(Serialize, Deserialize, Debug, Clone)
+)]
+#[cfg_attr(test, derive(Default))]
+pub struct DecoderChunkLocation {
+    pub tx_hash: H256,
+    pub out_index: u32,
 }
 
 #[cfg_attr(
@@ -164,11 +647,147 @@ pub struct ScriptId {
     pub hash_type: HashType,
 }
 
+// how to serve spores that haven't reached the required confirmations
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnconfirmedSporePolicy {
+    #[serde(rename(serialize = "refuse", deserialize = "refuse"))]
+    #[default]
+    Refuse,
+    #[serde(rename(serialize = "provisional", deserialize = "provisional"))]
+    Provisional,
+}
+
+// network of the backing CKB node, which decides prefix of addresses rendered in results
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    #[serde(rename(serialize = "mainnet", deserialize = "mainnet"))]
+    #[default]
+    Mainnet,
+    #[serde(rename(serialize = "testnet", deserialize = "testnet"))]
+    Testnet,
+}
+
+impl Network {
+    pub fn address_prefix(self) -> &'static str {
+        match self {
+            Network::Mainnet => "ckb",
+            Network::Testnet => "ckt",
+        }
+    }
+}
+
+// structured description of a served DOB protocol version
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolVersionInfo {
+    // content type prefix of spores in this version, like `dob/0`
+    pub content_type: String,
+    // numeric part of the content type, missing if not in `dob/<number>` form
+    pub version: Option<u32>,
+    pub features: Vec<&'static str>,
+}
+
+impl ProtocolVersionInfo {
+    pub fn new(content_type: &str) -> Self {
+        Self::with_pipeline(content_type, DecodePipeline::builtin(content_type))
+    }
+
+    // raw DNA is served by all versions, while features beyond come from the pipeline if known
+    pub fn with_pipeline(content_type: &str, pipeline: Option<DecodePipeline>) -> Self {
+        let version = content_type
+            .strip_prefix("dob/")
+            .and_then(|version| version.parse::<u32>().ok());
+        let features = match pipeline {
+            Some(DecodePipeline::Traits) => vec!["raw_dna", "text_traits"],
+            Some(DecodePipeline::Compose) => vec!["raw_dna", "text_traits", "image_composition"],
+            None => vec!["raw_dna"],
+        };
+        Self {
+            content_type: content_type.to_string(),
+            version,
+            features,
+        }
+    }
+}
+
+// how render output is produced for spores of a protocol version
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodePipeline {
+    // only the first decoder of the cluster runs, turning DNA into traits, like `dob/0`
+    #[serde(rename(serialize = "traits", deserialize = "traits"))]
+    Traits,
+    // following stages of the cluster run on decoded traits, like composing images in `dob/1`
+    #[serde(rename(serialize = "compose", deserialize = "compose"))]
+    Compose,
+}
+
+impl DecodePipeline {
+    // pipelines of versions known by the server, others have to be configured
+    pub fn builtin(content_type: &str) -> Option<Self> {
+        match content_type {
+            "dob/0" => Some(DecodePipeline::Traits),
+            "dob/1" => Some(DecodePipeline::Compose),
+            _ => None,
+        }
+    }
+}
+
+// how content type of spore is matched against configured protocol versions
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolVersionMatching {
+    // content type must start with a configured version, which also accepts like `dob/0abc`
+    #[serde(rename(serialize = "lenient", deserialize = "lenient"))]
+    #[default]
+    Lenient,
+    // content type must equal to a configured version, parameters after `;` are ignored
+    #[serde(rename(serialize = "strict", deserialize = "strict"))]
+    Strict,
+}
+
+// position in paginated search of owned spores or spores in a cluster, which walks available
+// spore scripts in order,
+// encoded like `1:0x...` to be passed back by clients as an opaque string
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OwnedSporesCursor {
+    pub script_index: usize,
+    pub indexer_cursor: Option<Vec<u8>>,
+}
+
+impl OwnedSporesCursor {
+    pub fn encode(&self) -> String {
+        match &self.indexer_cursor {
+            Some(indexer_cursor) => {
+                format!("{}:0x{}", self.script_index, hex::encode(indexer_cursor))
+            }
+            None => self.script_index.to_string(),
+        }
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, Error> {
+        let (script_index, indexer_cursor) = match cursor.split_once(':') {
+            Some((script_index, indexer_cursor)) => {
+                let indexer_cursor = indexer_cursor.strip_prefix("0x").unwrap_or(indexer_cursor);
+                let indexer_cursor =
+                    hex::decode(indexer_cursor).map_err(|_| Error::OwnedSporesCursorParseError)?;
+                (script_index, Some(indexer_cursor))
+            }
+            None => (cursor, None),
+        };
+        Ok(Self {
+            script_index: script_index
+                .parse()
+                .map_err(|_| Error::OwnedSporesCursorParseError)?,
+            indexer_cursor,
+        })
+    }
+}
+
 // standalone server settings in TOML format
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
 pub struct Settings {
     pub protocol_versions: Vec<String>,
     pub ckb_rpc: String,
+    #[serde(default)]
+    pub network: Network,
     pub rpc_server_address: String,
     pub ckb_vm_runner: String,
     pub decoders_cache_directory: PathBuf,
@@ -176,4 +795,1014 @@ pub struct Settings {
     pub onchain_decoder_deployment: Vec<OnchainDecoderDeployment>,
     pub available_spores: Vec<ScriptId>,
     pub available_clusters: Vec<ScriptId>,
+    #[serde(default)]
+    pub verify_cluster_cell: bool,
+    #[serde(default)]
+    pub spore_confirmations: u64,
+    #[serde(default)]
+    pub unconfirmed_spore_policy: UnconfirmedSporePolicy,
+    #[serde(default)]
+    pub runtime_worker_threads: Option<usize>,
+    #[serde(default)]
+    pub runtime_max_blocking_threads: Option<usize>,
+    #[serde(default)]
+    pub decoders_warm_up_parallelism: usize,
+    #[serde(default)]
+    pub batch_concurrency: BatchConcurrencySettings,
+    #[serde(default)]
+    pub cluster_cache_capacity: usize,
+    #[serde(default)]
+    pub stage_cache_capacity: usize,
+    #[serde(default)]
+    pub decoder_max_cycles: u64,
+    #[serde(default)]
+    pub response_compression: ResponseCompressionSettings,
+    #[serde(default)]
+    pub listen_reuse_port: bool,
+    #[serde(default)]
+    pub cache_invalidation: Option<CacheInvalidationSettings>,
+    #[serde(default)]
+    pub protocol_version_matching: ProtocolVersionMatching,
+    #[serde(default)]
+    pub protocol_pipelines: HashMap<String, DecodePipeline>,
+    #[serde(default)]
+    pub render_debug: RenderDebugSettings,
+    #[serde(default = "default_cluster_index_ttl_secs")]
+    pub cluster_index_ttl_secs: u64,
+    #[serde(default)]
+    pub serial_index_clusters: Vec<H256>,
+    #[serde(default)]
+    pub decoder_registry: Option<DecoderRegistrySettings>,
+    #[serde(default)]
+    pub dobs_cache_retention: DobsCacheRetentionSettings,
+    #[serde(default)]
+    pub cluster_decoder_overrides: Vec<ClusterDecoderOverride>,
+    #[serde(default)]
+    pub upstream: Option<UpstreamSettings>,
+    #[serde(default)]
+    pub canary: Option<CanarySettings>,
+    #[serde(default)]
+    pub rest_api: bool,
+    #[serde(default = "default_decode_cache_max_age_secs")]
+    pub decode_cache_max_age_secs: u64,
+    #[serde(default)]
+    pub websocket: Option<WebSocketSettings>,
+    #[serde(default)]
+    pub grpc_server_address: Option<String>,
+    #[serde(default)]
+    pub admin_rpc_server_address: Option<String>,
+    #[serde(default)]
+    pub unix_socket: Option<UnixSocketSettings>,
+    #[serde(default)]
+    pub assets: Option<AssetsSettings>,
+    #[serde(default)]
+    pub trait_index: Option<TraitIndexSettings>,
+    #[serde(default)]
+    pub cluster_crawler: ClusterCrawlerSettings,
+    #[serde(default)]
+    pub statsd: Option<StatsdSettings>,
+    #[serde(default)]
+    pub opentelemetry: Option<OpenTelemetrySettings>,
+    #[serde(default)]
+    pub logging: LoggingSettings,
+    #[serde(default)]
+    pub sentry: Option<SentrySettings>,
+    #[serde(default)]
+    pub slow_decode_threshold_ms: Option<u64>,
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitSettings>,
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeySettings>,
+    #[serde(default)]
+    pub cors: Option<CorsSettings>,
+    #[serde(default)]
+    pub tls: Option<TlsSettings>,
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+    #[serde(default)]
+    pub request_limits: RequestLimitsSettings,
+    #[serde(default)]
+    pub networks: Vec<NetworkSettings>,
+}
+
+fn default_cluster_index_ttl_secs() -> u64 {
+    600
+}
+
+fn default_decode_cache_max_age_secs() -> u64 {
+    86400
+}
+
+impl Settings {
+    // settings applied by reloading without restarting
+    pub const RELOADABLE: [&'static str; 9] = [
+        "render_debug",
+        "protocol_versions",
+        "protocol_pipelines",
+        "onchain_decoder_deployment",
+        "cluster_decoder_overrides",
+        "available_spores",
+        "available_clusters",
+        "logging",
+        "rate_limit",
+    ];
+
+    // settings of another network served along with this one, sharing everything else but
+    // networks, which are not nested, and settings keyed by cluster ids or bound to nodes of
+    // this network, which mean nothing on the other one
+    pub fn for_network(&self, network: &NetworkSettings) -> Settings {
+        Settings {
+            ckb_rpc: network.ckb_rpc.clone(),
+            network: network.network,
+            decoders_cache_directory: network.decoders_cache_directory.clone(),
+            dobs_cache_directory: network.dobs_cache_directory.clone(),
+            onchain_decoder_deployment: network.onchain_decoder_deployment.clone(),
+            available_spores: network.available_spores.clone(),
+            available_clusters: network.available_clusters.clone(),
+            cluster_decoder_overrides: Vec::new(),
+            api_keys: Vec::new(),
+            upstream: None,
+            canary: None,
+            trait_index: None,
+            networks: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    // check settings as a whole on load, collecting all problems found into one report instead
+    // of failing on the first one, or worse, in the middle of serving requests
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.protocol_versions.is_empty() {
+            errors.push("`protocol_versions` should not be empty".to_string());
+        }
+        if self
+            .protocol_versions
+            .iter()
+            .any(|version| version.trim().is_empty())
+        {
+            errors.push("`protocol_versions` contains empty version".to_string());
+        }
+        for version in self.protocol_pipelines.keys() {
+            if !self.protocol_versions.contains(version) {
+                errors.push(format!(
+                    "`protocol_pipelines` contains {version} which is not in `protocol_versions`"
+                ));
+            }
+        }
+        for version in &self.protocol_versions {
+            if DecodePipeline::builtin(version).is_none()
+                && !self.protocol_pipelines.contains_key(version)
+            {
+                errors.push(format!(
+                    "`protocol_versions` contains {version} without known pipeline, map it to \
+                     \"traits\" or \"compose\" in `protocol_pipelines`"
+                ));
+            }
+        }
+        if !self.ckb_rpc.starts_with("http://") && !self.ckb_rpc.starts_with("https://") {
+            errors.push(format!("`ckb_rpc` {} is not a HTTP url", self.ckb_rpc));
+        }
+        if let Some(unix_socket) = &self.unix_socket {
+            if !cfg!(unix) {
+                errors.push("`unix_socket` is only supported on unix platforms".to_string());
+            }
+            if unix_socket.mode > 0o777 {
+                errors.push(format!(
+                    "`unix_socket.mode` {:o} is not a valid permission mode",
+                    unix_socket.mode
+                ));
+            }
+        } else if self.rpc_server_address.to_socket_addrs().is_err() {
+            errors.push(format!(
+                "`rpc_server_address` {} is not a valid socket address",
+                self.rpc_server_address
+            ));
+        }
+        if self.available_spores.is_empty() {
+            errors.push("`available_spores` should contain at least one script".to_string());
+        }
+        if self.available_clusters.is_empty() {
+            errors.push("`available_clusters` should contain at least one script".to_string());
+        }
+        let mut code_hashes = HashSet::new();
+        for deployment in &self.onchain_decoder_deployment {
+            if deployment.code_hash == H256::default() {
+                errors.push("decoder deployment with zero `code_hash`".to_string());
+            } else if !code_hashes.insert(&deployment.code_hash) {
+                errors.push(format!(
+                    "decoder deployment {:#x} is duplicated",
+                    deployment.code_hash
+                ));
+            }
+            let mut tx_hashes = std::iter::once(&deployment.tx_hash)
+                .chain(deployment.chunks.iter().map(|chunk| &chunk.tx_hash));
+            if tx_hashes.any(|tx_hash| tx_hash == &H256::default()) {
+                errors.push(format!(
+                    "decoder deployment {:#x} with zero `tx_hash`",
+                    deployment.code_hash
+                ));
+            }
+        }
+        if let Some(registry) = &self.decoder_registry {
+            match (&registry.url, &registry.type_id) {
+                (Some(_), Some(_)) | (None, None) => errors.push(
+                    "`decoder_registry` should have exactly one of `url` and `type_id`".to_string(),
+                ),
+                (Some(_), None) if registry.public_key.is_none() => {
+                    errors.push("`decoder_registry.url` requires `public_key`".to_string())
+                }
+                _ => {}
+            }
+            if registry.sync_interval_secs == 0 {
+                errors.push("`decoder_registry.sync_interval_secs` should be positive".to_string());
+            }
+        }
+        let mut overridden_clusters = HashSet::new();
+        for decoder_override in &self.cluster_decoder_overrides {
+            let cluster_id = &decoder_override.cluster_id;
+            if !overridden_clusters.insert(cluster_id) {
+                errors.push(format!(
+                    "decoder override of cluster {cluster_id:#x} is duplicated"
+                ));
+            }
+            let locators = [
+                decoder_override.code_hash.is_some(),
+                decoder_override.type_id.is_some(),
+                decoder_override.binary_path.is_some(),
+            ];
+            if locators.into_iter().filter(|located| *located).count() != 1 {
+                errors.push(format!(
+                    "decoder override of cluster {cluster_id:#x} should have exactly one of \
+                     `code_hash`, `type_id` and `binary_path`"
+                ));
+            }
+            if let Some(binary_path) = &decoder_override.binary_path {
+                #[cfg(feature = "shuttle")]
+                errors.push(format!(
+                    "decoder override of cluster {cluster_id:#x} with `binary_path` {binary_path:?} \
+                     is not supported under shuttle"
+                ));
+                #[cfg(not(feature = "shuttle"))]
+                if !binary_path.is_file() {
+                    errors.push(format!(
+                        "decoder override of cluster {cluster_id:#x} with missing `binary_path` \
+                         {binary_path:?}"
+                    ));
+                }
+            }
+        }
+        if let Some(assets) = &self.assets {
+            if let Some(btcfs) = &assets.btcfs {
+                if !btcfs.url.starts_with("http://") && !btcfs.url.starts_with("https://") {
+                    errors.push(format!(
+                        "`assets.btcfs.url` {} is not a HTTP url",
+                        btcfs.url
+                    ));
+                }
+            }
+            let public_url = &assets.public_url;
+            if !public_url.is_empty()
+                && !public_url.starts_with("http://")
+                && !public_url.starts_with("https://")
+            {
+                errors.push(format!(
+                    "`assets.public_url` {public_url} is not a HTTP url"
+                ));
+            }
+            for gateway in &assets.ipfs_gateways {
+                if !gateway.starts_with("http://") && !gateway.starts_with("https://") {
+                    errors.push(format!(
+                        "`assets.ipfs_gateways` contains {gateway} which is not a HTTP url"
+                    ));
+                }
+            }
+        }
+        if let Some(upstream) = &self.upstream {
+            if !upstream.url.starts_with("http://") && !upstream.url.starts_with("https://") {
+                errors.push(format!("`upstream.url` {} is not a HTTP url", upstream.url));
+            }
+        }
+        let cluster_crawler = &self.cluster_crawler;
+        if cluster_crawler.interval_secs == 0 || cluster_crawler.max_parallelism == 0 {
+            errors.push(
+                "`cluster_crawler.interval_secs` and `cluster_crawler.max_parallelism` should be positive"
+                    .to_string(),
+            );
+        }
+        if let Some(canary) = &self.canary {
+            if canary.interval_secs == 0 || canary.failure_threshold == 0 {
+                errors.push(
+                    "`canary.interval_secs` and `canary.failure_threshold` should be positive"
+                        .to_string(),
+                );
+            }
+            let expected_render_output = canary.expected_render_output.as_deref();
+            if expected_render_output
+                .is_some_and(|expected| serde_json::from_str::<Value>(expected).is_err())
+            {
+                errors.push("`canary.expected_render_output` is not in JSON".to_string());
+            }
+        }
+        if let Some(grpc_server_address) = &self.grpc_server_address {
+            if grpc_server_address.to_socket_addrs().is_err() {
+                errors.push(format!(
+                    "`grpc_server_address` {grpc_server_address} is not a valid socket address"
+                ));
+            }
+        }
+        if let Some(admin_rpc_server_address) = &self.admin_rpc_server_address {
+            if admin_rpc_server_address.to_socket_addrs().is_err() {
+                errors.push(format!(
+                    "`admin_rpc_server_address` {admin_rpc_server_address} is not a valid socket address"
+                ));
+            }
+        }
+        let is_level =
+            |level: &str| LoggingSettings::LEVELS.contains(&level.to_lowercase().as_str());
+        if !is_level(&self.logging.level) {
+            errors.push(format!(
+                "`logging.level` {} is not a log level",
+                self.logging.level
+            ));
+        }
+        for (module, level) in &self.logging.modules {
+            if !is_level(level) {
+                errors.push(format!(
+                    "`logging.modules.{module}` {level} is not a log level"
+                ));
+            }
+        }
+        #[cfg(feature = "standalone_server")]
+        if let Some(cors) = &self.cors {
+            use hyper::header::{HeaderName, HeaderValue};
+            use hyper::Method;
+            cors.allowed_origins
+                .iter()
+                .filter(|origin| *origin != "*" && HeaderValue::from_str(origin).is_err())
+                .for_each(|origin| errors.push(format!("invalid origin {origin} in `cors`")));
+            cors.allowed_methods
+                .iter()
+                .filter(|method| Method::from_bytes(method.as_bytes()).is_err())
+                .for_each(|method| errors.push(format!("invalid method {method} in `cors`")));
+            cors.allowed_headers
+                .iter()
+                .filter(|header| HeaderName::from_bytes(header.as_bytes()).is_err())
+                .for_each(|header| errors.push(format!("invalid header {header} in `cors`")));
+        }
+        #[cfg(feature = "standalone_server")]
+        self.trusted_proxies
+            .iter()
+            .filter_map(|net| net.parse::<crate::forwarded::IpNet>().err())
+            .for_each(|error| errors.push(format!("{error} in `trusted_proxies`")));
+        if let Some(tls) = &self.tls {
+            [("cert_path", &tls.cert_path), ("key_path", &tls.key_path)]
+                .into_iter()
+                .filter(|(_, path)| !path.is_file())
+                .for_each(|(name, path)| errors.push(format!("`tls.{name}` {path:?} not found")));
+            if self.unix_socket.is_some() {
+                errors.push("`tls` doesn't apply to `unix_socket`".to_string());
+            }
+            if tls.handshake_timeout_secs == 0 || tls.max_concurrent_handshakes == 0 {
+                errors.push(
+                    "`tls.handshake_timeout_secs` and `tls.max_concurrent_handshakes` should be positive"
+                        .to_string(),
+                );
+            }
+        }
+        let mut api_keys = HashSet::new();
+        for api_key in &self.api_keys {
+            if api_key.key.is_empty() {
+                errors.push("empty key in `api_keys`".to_string());
+            } else if !api_keys.insert(&api_key.key) {
+                errors.push("duplicate key in `api_keys`".to_string());
+            }
+        }
+        if let Some(rate_limit) = &self.rate_limit {
+            if rate_limit.requests_per_second.is_nan() || rate_limit.requests_per_second <= 0.0 {
+                errors.push("`rate_limit.requests_per_second` should be positive".to_string());
+            }
+            if rate_limit.burst == 0 {
+                errors.push("`rate_limit.burst` should be at least 1".to_string());
+            }
+        }
+        if let Some(sentry) = &self.sentry {
+            if !(0.0..=1.0).contains(&sentry.sample_rate) {
+                errors.push("`sentry.sample_rate` should be between 0 and 1".to_string());
+            }
+        }
+        if let Some(opentelemetry) = &self.opentelemetry {
+            if !(0.0..=1.0).contains(&opentelemetry.sample_ratio) {
+                errors.push("`opentelemetry.sample_ratio` should be between 0 and 1".to_string());
+            }
+        }
+        if let Some(statsd) = &self.statsd {
+            if statsd.address.to_socket_addrs().is_err() {
+                errors.push(format!(
+                    "`statsd.address` {} is not a valid socket address",
+                    statsd.address
+                ));
+            }
+        }
+        if let Some(websocket) = &self.websocket {
+            if websocket.ping_interval_secs == 0 {
+                errors.push("`websocket.ping_interval_secs` should be positive".to_string());
+            }
+            if websocket.inactive_limit_secs < websocket.ping_interval_secs {
+                errors.push(
+                    "`websocket.inactive_limit_secs` is shorter than `ping_interval_secs`"
+                        .to_string(),
+                );
+            }
+        }
+        let retention = &self.dobs_cache_retention;
+        if retention.max_bytes > 0 && retention.sweep_interval_secs == 0 {
+            errors
+                .push("`dobs_cache_retention.sweep_interval_secs` should be positive".to_string());
+        }
+        let mut network_names = HashSet::new();
+        for network in &self.networks {
+            let name = &network.name;
+            if name.is_empty() {
+                errors.push("empty name in `networks`".to_string());
+            } else if !network_names.insert(name) {
+                errors.push(format!("network {name} is duplicated in `networks`"));
+            }
+            if !network.ckb_rpc.starts_with("http://") && !network.ckb_rpc.starts_with("https://") {
+                errors.push(format!(
+                    "`ckb_rpc` {} of network {name} is not a HTTP url",
+                    network.ckb_rpc
+                ));
+            }
+            if network.available_spores.is_empty() || network.available_clusters.is_empty() {
+                errors.push(format!(
+                    "`available_spores` and `available_clusters` of network {name} should \
+                     contain at least one script"
+                ));
+            }
+            // spore ids of different networks can't share render cache
+            let dobs_cache_directories = self
+                .networks
+                .iter()
+                .map(|network| &network.dobs_cache_directory)
+                .chain([&self.dobs_cache_directory]);
+            if dobs_cache_directories
+                .filter(|directory| **directory == network.dobs_cache_directory)
+                .count()
+                > 1
+            {
+                errors.push(format!(
+                    "`dobs_cache_directory` of network {name} is shared with another network"
+                ));
+            }
+            // nor can decoders deployed by the same type id on different networks
+            let decoders_cache_directories = self
+                .networks
+                .iter()
+                .map(|network| &network.decoders_cache_directory)
+                .chain([&self.decoders_cache_directory]);
+            if decoders_cache_directories
+                .filter(|directory| **directory == network.decoders_cache_directory)
+                .count()
+                > 1
+            {
+                errors.push(format!(
+                    "`decoders_cache_directory` of network {name} is shared with another network"
+                ));
+            }
+        }
+        if self.request_limits.max_batch_size == 0 {
+            errors.push("`request_limits.max_batch_size` should be at least 1".to_string());
+        }
+        let batch_concurrency = &self.batch_concurrency;
+        if batch_concurrency.min_parallelism > batch_concurrency.max_parallelism {
+            errors
+                .push("`batch_concurrency.min_parallelism` exceeds `max_parallelism`".to_string());
+        }
+        #[cfg(not(feature = "shuttle"))]
+        self.validate_cache_directories(&mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    #[cfg(not(feature = "shuttle"))]
+    fn validate_cache_directories(&self, errors: &mut Vec<String>) {
+        if self.decoders_cache_directory == self.dobs_cache_directory {
+            errors.push(
+                "`decoders_cache_directory` and `dobs_cache_directory` should be distinct"
+                    .to_string(),
+            );
+        }
+        let assets_cache_directory = self.assets.as_ref().map(|assets| &assets.cache_directory);
+        let trait_index_directory = self.trait_index.as_ref().map(|index| &index.directory);
+        for directory in [&self.decoders_cache_directory, &self.dobs_cache_directory]
+            .into_iter()
+            .chain(assets_cache_directory)
+            .chain(trait_index_directory)
+            .chain(self.networks.iter().flat_map(|network| {
+                [
+                    &network.decoders_cache_directory,
+                    &network.dobs_cache_directory,
+                ]
+            }))
+        {
+            let mut probe_path = directory.clone();
+            probe_path.push(".write_probe");
+            let writable = std::fs::create_dir_all(directory)
+                .and_then(|_| std::fs::write(&probe_path, []))
+                .and_then(|_| std::fs::remove_file(&probe_path));
+            if let Err(error) = writable {
+                errors.push(format!(
+                    "cache directory {directory:?} is not writable: {error}"
+                ));
+            }
+        }
+    }
+}
+
+// targets whose full decoder outputs are logged for debugging
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct RenderDebugSettings {
+    // log outputs of all decodings
+    pub all: bool,
+    pub spore_ids: Vec<H256>,
+    pub cluster_ids: Vec<H256>,
+}
+
+impl RenderDebugSettings {
+    pub fn matches(&self, spore_id: &[u8; 32], cluster_id: &[u8; 32]) -> bool {
+        self.all
+            || self.spore_ids.iter().any(|id| id.as_bytes() == spore_id)
+            || self
+                .cluster_ids
+                .iter()
+                .any(|id| id.as_bytes() == cluster_id)
+    }
+}
+
+// Redis pub/sub channel for broadcasting cache invalidation events between replicas
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheInvalidationSettings {
+    pub redis_url: String,
+    #[serde(default = "default_invalidation_channel")]
+    pub channel: String,
+}
+
+fn default_invalidation_channel() -> String {
+    "dob_decoder:cache_invalidation".to_string()
+}
+
+// decoder pinned by operator for a cluster, overriding the one declared in its cluster cell,
+// located by exactly one of `code_hash`, `type_id` or a local `binary_path`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ClusterDecoderOverride {
+    pub cluster_id: H256,
+    #[serde(default)]
+    pub code_hash: Option<H256>,
+    #[serde(default)]
+    pub type_id: Option<H256>,
+    #[serde(default)]
+    pub binary_path: Option<PathBuf>,
+}
+
+impl ClusterDecoderOverride {
+    // pinned decoder located on-chain, none if it's a local binary
+    pub fn decoder(&self) -> Option<DOBDecoderFormat> {
+        if let Some(code_hash) = &self.code_hash {
+            return Some(DOBDecoderFormat {
+                location: DecoderLocationType::CodeHash,
+                hash: code_hash.clone(),
+            });
+        }
+        self.type_id.clone().map(|type_id| DOBDecoderFormat {
+            location: DecoderLocationType::TypeId,
+            hash: type_id,
+        })
+    }
+}
+
+// metrics pushed over UDP for those not scraping prometheus, tags are only sent to DogStatsD
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StatsdSettings {
+    pub address: String,
+    #[serde(default = "default_statsd_prefix")]
+    pub prefix: String,
+    #[serde(default)]
+    pub flavor: StatsdFlavor,
+    // constant tags attached to every metric, like `env:production`
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_statsd_prefix() -> String {
+    "dob".to_string()
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsdFlavor {
+    #[serde(rename(serialize = "statsd", deserialize = "statsd"))]
+    #[default]
+    Statsd,
+    #[serde(rename(serialize = "datadog", deserialize = "datadog"))]
+    Datadog,
+}
+
+// severe decoding failures and panics reported to Sentry, only a `sample_rate` of them are sent
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SentrySettings {
+    pub dsn: String,
+    #[serde(default)]
+    pub environment: Option<String>,
+    #[serde(default = "default_sentry_sample_rate")]
+    pub sample_rate: f32,
+}
+
+fn default_sentry_sample_rate() -> f32 {
+    1.0
+}
+
+// log level of all modules, which can be set per module by its path as well, like
+// `"dob_decoder_server::decoder" = "debug"`, both are overridden by `RUST_LOG` on start
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LoggingSettings {
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    #[serde(default)]
+    pub modules: BTreeMap<String, String>,
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_log_level() -> String {
+    "error".to_string()
+}
+
+impl LoggingSettings {
+    pub const LEVELS: [&'static str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+
+    // filter directives in the form of `RUST_LOG`, render debug output has its own switch in
+    // `render_debug`, so it's logged unless turned off explicitly
+    pub fn directives(&self) -> String {
+        let mut directives = vec![self.level.clone()];
+        if !self.modules.contains_key("render_debug") {
+            directives.push("render_debug=info".to_string());
+        }
+        directives.extend(
+            self.modules
+                .iter()
+                .map(|(module, level)| format!("{module}={level}")),
+        );
+        directives.join(",")
+    }
+}
+
+// spans of decoding exported to an OTLP collector over gRPC, only a `sample_ratio` of traces are
+// kept, which is up to 1
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OpenTelemetrySettings {
+    #[serde(default = "default_opentelemetry_endpoint")]
+    pub endpoint: String,
+    #[serde(default = "default_opentelemetry_service_name")]
+    pub service_name: String,
+    #[serde(default = "default_opentelemetry_sample_ratio")]
+    pub sample_ratio: f64,
+}
+
+fn default_opentelemetry_endpoint() -> String {
+    "http://localhost:4317".to_string()
+}
+
+fn default_opentelemetry_service_name() -> String {
+    "dob-decoder-server".to_string()
+}
+
+fn default_opentelemetry_sample_ratio() -> f64 {
+    1.0
+}
+
+// spore decoded periodically bypassing cache, whose failures flip readiness of server
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CanarySettings {
+    pub spore_id: H256,
+    #[serde(default = "default_canary_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_canary_failure_threshold")]
+    pub failure_threshold: u32,
+    // render output in JSON which the canary must match, any output is accepted if not set
+    #[serde(default)]
+    pub expected_render_output: Option<String>,
+}
+
+fn default_canary_interval_secs() -> u64 {
+    60
+}
+
+fn default_canary_failure_threshold() -> u32 {
+    3
+}
+
+// token bucket of each client IP, refilled at `requests_per_second` up to `burst`, client IP is
+// taken from `X-Forwarded-For` only if the peer is one of `trusted_proxies`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RateLimitSettings {
+    pub requests_per_second: f64,
+    pub burst: u32,
+}
+
+// key given in `X-Api-Key` header, which decodes only spores of `allowed_clusters` if any, for
+// white-label deployments serving a single collection
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiKeySettings {
+    pub key: String,
+    #[serde(default)]
+    pub allowed_clusters: Vec<H256>,
+}
+
+// serve HTTPS on `rpc_server_address` with certificate chain and private key in PEM
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TlsSettings {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    // clients not finishing handshake in time are dropped, so that stalled ones don't pile up
+    #[serde(default = "default_tls_handshake_timeout_secs")]
+    pub handshake_timeout_secs: u64,
+    // handshakes in progress at most, connections beyond are dropped right away
+    #[serde(default = "default_tls_max_concurrent_handshakes")]
+    pub max_concurrent_handshakes: usize,
+}
+
+fn default_tls_handshake_timeout_secs() -> u64 {
+    10
+}
+
+fn default_tls_max_concurrent_handshakes() -> usize {
+    1024
+}
+
+// listen on a Unix domain socket instead of `rpc_server_address`, for co-located deployments
+// like a reverse proxy sidecar, the socket file is created in `mode` permissions
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnixSocketSettings {
+    pub path: PathBuf,
+    #[serde(default = "default_unix_socket_mode")]
+    pub mode: u32,
+}
+
+fn default_unix_socket_mode() -> u32 {
+    0o660
+}
+
+// serve json-rpc over WebSocket on the same address along with HTTP, idle connections are
+// pinged every `ping_interval_secs` and closed after `inactive_limit_secs` without a pong
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebSocketSettings {
+    #[serde(default = "default_websocket_ping_interval_secs")]
+    pub ping_interval_secs: u64,
+    #[serde(default = "default_websocket_inactive_limit_secs")]
+    pub inactive_limit_secs: u64,
+}
+
+fn default_websocket_ping_interval_secs() -> u64 {
+    30
+}
+
+fn default_websocket_inactive_limit_secs() -> u64 {
+    120
+}
+
+// resolve assets referenced in render output, like inscriptions of `btcfs://` or contents of
+// `ipfs://`, into local cache
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AssetsSettings {
+    pub cache_directory: PathBuf,
+    #[serde(default = "default_assets_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default)]
+    pub btcfs: Option<BtcfsSettings>,
+    // gateways tried in order, like `https://ipfs.io/ipfs`, `ipfs://` is unresolvable if empty
+    #[serde(default)]
+    pub ipfs_gateways: Vec<String>,
+    // fetch assets referenced by fresh decode results and rewrite them into local urls
+    #[serde(default)]
+    pub prefetch: bool,
+    // prefix of rewritten urls, like `https://dob.example.com`, urls are relative if empty
+    #[serde(default)]
+    pub public_url: String,
+}
+
+fn default_assets_timeout_ms() -> u64 {
+    10000
+}
+
+// embedded database indexing trait values of decoded spores, requires feature `trait_index`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TraitIndexSettings {
+    pub directory: PathBuf,
+}
+
+// spores whose trait `name` has `value`, like `{"name": "Horn", "value": "Caesar Horn"}`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TraitFilter {
+    pub name: String,
+    pub value: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TraitSearchPage {
+    pub spore_ids: Vec<H256>,
+    // pass to next request for the following page, absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+// bitcoin transactions revealing inscriptions are fetched from either an esplora api, like
+// `https://mempool.space/api`, or json-rpc of a bitcoin node with `txindex` enabled
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BtcfsSettings {
+    pub url: String,
+    #[serde(default)]
+    pub source: BtcfsSource,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BtcfsSource {
+    #[serde(rename(serialize = "esplora", deserialize = "esplora"))]
+    #[default]
+    Esplora,
+    #[serde(rename(serialize = "bitcoin_rpc", deserialize = "bitcoin_rpc"))]
+    BitcoinRpc,
+}
+
+// another decoder server which serves requests failed locally, for layered deployments
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UpstreamSettings {
+    pub url: String,
+    #[serde(default = "default_upstream_timeout_ms")]
+    pub timeout_ms: u64,
+    // cache results from upstream like local ones
+    #[serde(default)]
+    pub cache_results: bool,
+    // never decode locally, cache misses are all served by upstream
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+fn default_upstream_timeout_ms() -> u64 {
+    10000
+}
+
+// remote source of decoder deployments, either a url serving signed registry document, or an
+// on-chain registry cell identified by its type_id
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DecoderRegistrySettings {
+    #[serde(default)]
+    pub url: Option<String>,
+    // compressed secp256k1 public key in hex, which signs the registry served by url
+    #[serde(default)]
+    pub public_key: Option<String>,
+    #[serde(default)]
+    pub type_id: Option<H256>,
+    #[serde(default = "default_registry_sync_interval_secs")]
+    pub sync_interval_secs: u64,
+}
+
+fn default_registry_sync_interval_secs() -> u64 {
+    3600
+}
+
+// disk cap of render cache, evicting entries of dormant clusters first while pinning those of
+// clusters actively requested, where activity is request count decayed by half every half-life
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DobsCacheRetentionSettings {
+    // 0 means unlimited
+    pub max_bytes: u64,
+    pub sweep_interval_secs: u64,
+    pub activity_half_life_secs: u64,
+    // clusters with activity at or above this are never evicted
+    pub pinned_score: f64,
+}
+
+impl Default for DobsCacheRetentionSettings {
+    fn default() -> Self {
+        Self {
+            max_bytes: 0,
+            sweep_interval_secs: 300,
+            activity_half_life_secs: 3600,
+            pinned_score: 20.0,
+        }
+    }
+}
+
+// CORS policy for browser dApps calling the server directly, `*` in `allowed_origins` allows any
+// origin, and preflight results are cached by browsers for `max_age_secs` if set
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CorsSettings {
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_cors_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+    #[serde(default = "default_cors_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    #[serde(default)]
+    pub max_age_secs: Option<u64>,
+}
+
+fn default_cors_allowed_methods() -> Vec<String> {
+    vec!["GET".to_string(), "POST".to_string()]
+}
+
+fn default_cors_allowed_headers() -> Vec<String> {
+    vec!["content-type".to_string()]
+}
+
+// encodings enabled for compressing HTTP responses, negotiated with `Accept-Encoding` header
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct ResponseCompressionSettings {
+    pub gzip: bool,
+    pub br: bool,
+}
+
+// another network served from the same process, selected by `network` parameter of decode methods,
+// with its own CKB node, scripts, decoder deployments and cache directories
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NetworkSettings {
+    pub name: String,
+    pub ckb_rpc: String,
+    #[serde(default)]
+    pub network: Network,
+    pub decoders_cache_directory: PathBuf,
+    pub dobs_cache_directory: PathBuf,
+    #[serde(default)]
+    pub onchain_decoder_deployment: Vec<OnchainDecoderDeployment>,
+    pub available_spores: Vec<ScriptId>,
+    pub available_clusters: Vec<ScriptId>,
+}
+
+// caps on what a single request can ask for, so that one request can't tie up the server
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RequestLimitsSettings {
+    pub max_request_body_bytes: u32,
+    // spore ids or DNAs in one batch
+    pub max_batch_size: usize,
+}
+
+impl Default for RequestLimitsSettings {
+    fn default() -> Self {
+        Self {
+            max_request_body_bytes: 10 * 1024 * 1024,
+            max_batch_size: 1000,
+        }
+    }
+}
+
+// bounds of adaptive parallelism in batch decoding
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BatchConcurrencySettings {
+    pub min_parallelism: usize,
+    pub max_parallelism: usize,
+    // single decoding slower than this is considered as overloaded
+    pub target_latency_ms: u64,
+}
+
+// clusters whose spores are all decoded ahead into render cache, on start and periodically
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ClusterCrawlerSettings {
+    pub cluster_ids: Vec<H256>,
+    pub interval_secs: u64,
+    pub max_parallelism: usize,
+}
+
+impl Default for ClusterCrawlerSettings {
+    fn default() -> Self {
+        Self {
+            cluster_ids: Vec::new(),
+            interval_secs: 3600,
+            max_parallelism: 4,
+        }
+    }
+}
+
+impl Default for BatchConcurrencySettings {
+    fn default() -> Self {
+        Self {
+            min_parallelism: 1,
+            max_parallelism: 32,
+            target_latency_ms: 2000,
+        }
+    }
 }
@@ -0,0 +1,160 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::decoder::DOBDecoder;
+use crate::types::ClusterDescriptionField;
+
+// bytes of sample DNA if none is given and the pattern doesn't tell its length
+const DEFAULT_SAMPLE_DNA_BYTES: usize = 32;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataCheck {
+    Parse,
+    Pattern,
+    Decoder,
+    TrialRun,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct MetadataIssue {
+    pub check: MetadataCheck,
+    pub message: String,
+}
+
+// outcome of linting a candidate cluster description, trial run only happens once the other
+// checks pass, along with the sample DNA it took
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct MetadataValidation {
+    pub valid: bool,
+    pub issues: Vec<MetadataIssue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_dna: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub render_output: Option<Value>,
+}
+
+// lint cluster description before deploying it, since cluster is immutable on-chain, description
+// is either the JSON text to be put into cluster data or its parsed object
+pub async fn validate_metadata(
+    decoder: &DOBDecoder,
+    description: &Value,
+    sample_dna: Option<String>,
+) -> MetadataValidation {
+    let mut issues = Vec::new();
+    let metadata = match description {
+        Value::String(text) => serde_json::from_str::<ClusterDescriptionField>(text),
+        description => serde_json::from_value(description.clone()),
+    };
+    let metadata = match metadata {
+        Ok(metadata) => metadata,
+        Err(error) => {
+            issues.push(MetadataIssue {
+                check: MetadataCheck::Parse,
+                message: error.to_string(),
+            });
+            return MetadataValidation {
+                valid: false,
+                issues,
+                sample_dna: None,
+                render_output: None,
+            };
+        }
+    };
+    let decoders = std::iter::once((&metadata.dob.decoder, &metadata.dob.pattern)).chain(
+        metadata
+            .dob
+            .stages
+            .iter()
+            .map(|stage| (&stage.decoder, &stage.pattern)),
+    );
+    for (index, (decoder_format, pattern)) in decoders.enumerate() {
+        if let Err(message) = check_pattern(pattern) {
+            issues.push(MetadataIssue {
+                check: MetadataCheck::Pattern,
+                message: format!("pattern of decoder #{index}: {message}"),
+            });
+        }
+        if let Err(error) = decoder.resolve_decoder_path(decoder_format).await {
+            issues.push(MetadataIssue {
+                check: MetadataCheck::Decoder,
+                message: format!(
+                    "decoder #{index} (0x{}) is unreachable: {error}",
+                    decoder_format.hash
+                ),
+            });
+        }
+    }
+    let mut render_output = None;
+    let sample_dna = issues.is_empty().then(|| {
+        sample_dna.unwrap_or_else(|| {
+            "00".repeat(
+                pattern_dna_bytes(&metadata.dob.pattern).unwrap_or(DEFAULT_SAMPLE_DNA_BYTES),
+            )
+        })
+    });
+    if let Some(sample_dna) = &sample_dna {
+        match decoder.decode_dna(sample_dna, metadata).await {
+            Ok(output) => {
+                render_output =
+                    Some(serde_json::from_str(&output).unwrap_or(Value::String(output)));
+            }
+            Err(error) => issues.push(MetadataIssue {
+                check: MetadataCheck::TrialRun,
+                message: error.to_string(),
+            }),
+        }
+    }
+    MetadataValidation {
+        valid: issues.is_empty(),
+        issues,
+        sample_dna,
+        render_output,
+    }
+}
+
+// patterns are arrays of entries each starting with its name, given as either JSON or its text
+pub fn check_pattern(pattern: &Value) -> Result<(), String> {
+    let entries = pattern_entries(pattern)?;
+    if entries.is_empty() {
+        return Err("no entry in pattern".to_string());
+    }
+    for (index, entry) in entries.iter().enumerate() {
+        let named = entry
+            .as_array()
+            .and_then(|entry| entry.first())
+            .is_some_and(Value::is_string);
+        if !named {
+            return Err(format!(
+                "entry #{index} should be an array starting with its name"
+            ));
+        }
+    }
+    Ok(())
+}
+
+// bytes of DNA read by DOB/0 pattern, whose entries are like `[name, type, offset, length, ...]`
+pub fn pattern_dna_bytes(pattern: &Value) -> Option<usize> {
+    pattern_entries(pattern)
+        .ok()?
+        .iter()
+        .filter_map(|entry| {
+            let offset = entry.get(2)?.as_u64()?;
+            let length = entry.get(3)?.as_u64()?;
+            Some((offset + length) as usize)
+        })
+        .max()
+}
+
+fn pattern_entries(pattern: &Value) -> Result<Vec<Value>, String> {
+    let pattern = match pattern {
+        Value::String(text) => {
+            serde_json::from_str(text).map_err(|_| "pattern text is not JSON".to_string())?
+        }
+        pattern => pattern.clone(),
+    };
+    match pattern {
+        Value::Array(entries) => Ok(entries),
+        _ => Err("pattern should be an array".to_string()),
+    }
+}
@@ -51,7 +51,8 @@ impl<Mac: SupportMachine> Syscalls<Mac> for DebugSyscall {
 fn main_asm(
     code: Bytes,
     args: Vec<Bytes>,
-) -> Result<(i8, Vec<String>), Box<dyn std::error::Error>> {
+    max_cycles: u64,
+) -> Result<(i8, Vec<String>, u64), Box<dyn std::error::Error>> {
     let debug_result = Arc::new(Mutex::new(Vec::new()));
     let debug = Box::new(DebugSyscall {
         output: debug_result.clone(),
@@ -60,7 +61,7 @@ fn main_asm(
     let asm_core = ckb_vm::machine::asm::AsmCoreMachine::new(
         ckb_vm::ISA_IMC | ckb_vm::ISA_B | ckb_vm::ISA_MOP | ckb_vm::ISA_A,
         ckb_vm::machine::VERSION2,
-        u64::MAX,
+        max_cycles,
     );
     let core = ckb_vm::DefaultMachineBuilder::new(asm_core)
         .instruction_cycle_func(Box::new(estimate_cycles))
@@ -71,14 +72,15 @@ fn main_asm(
 
     let error_code = machine.run()?;
     let result = debug_result.lock().unwrap().clone();
-    Ok((error_code, result))
+    Ok((error_code, result, machine.machine.cycles()))
 }
 
 pub fn execute_riscv_binary(
     binary_path: &str,
     args: Vec<Bytes>,
+    max_cycles: u64,
     #[cfg(feature = "shuttle")] persist: &PersistInstance,
-) -> Result<(i8, Vec<String>), Box<dyn std::error::Error>> {
+) -> Result<(i8, Vec<String>, u64), Box<dyn std::error::Error>> {
     // if not shuttle
     #[cfg(not(feature = "shuttle"))]
     let code = std::fs::read(binary_path)?.into();
@@ -86,5 +88,58 @@ pub fn execute_riscv_binary(
     #[cfg(feature = "shuttle")]
     let code = persist.load::<Vec<u8>>(binary_path)?.into();
 
-    Ok(main_asm(code, args)?)
+    Ok(main_asm(code, args, max_cycles)?)
+}
+
+// execute binary held in memory, like decoders supplied inline by callers
+pub fn execute_riscv_code(
+    code: Bytes,
+    args: Vec<Bytes>,
+    max_cycles: u64,
+) -> Result<(i8, Vec<String>, u64), Box<dyn std::error::Error>> {
+    main_asm(code, args, max_cycles)
+}
+
+// base address of the smoke test program, which is loaded as a whole including its headers
+const SMOKE_TEST_VADDR: u64 = 0x10000;
+const SMOKE_TEST_MAX_CYCLES: u64 = 1_000_000;
+
+// smallest ELF that exits with 0 right away, made of an ELF header, a loadable segment covering
+// the whole file and 3 instructions following them, which are `li a0, 0`, `li a7, 93` and `ecall`
+fn smoke_test_program() -> Bytes {
+    const HEADERS_SIZE: u64 = 64 + 56;
+    let code = [0x00000513u32, 0x05d00893, 0x00000073];
+    let size = HEADERS_SIZE + code.len() as u64 * 4;
+    let mut program = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0];
+    program.extend_from_slice(&[0; 8]);
+    // executable for RISC-V, whose entry is the first instruction
+    program.extend_from_slice(&2u16.to_le_bytes());
+    program.extend_from_slice(&243u16.to_le_bytes());
+    program.extend_from_slice(&1u32.to_le_bytes());
+    program.extend_from_slice(&(SMOKE_TEST_VADDR + HEADERS_SIZE).to_le_bytes());
+    program.extend_from_slice(&64u64.to_le_bytes());
+    program.extend_from_slice(&0u64.to_le_bytes());
+    program.extend_from_slice(&0u32.to_le_bytes());
+    for half in [64u16, 56, 1, 64, 0, 0] {
+        program.extend_from_slice(&half.to_le_bytes());
+    }
+    // readable and executable segment
+    program.extend_from_slice(&1u32.to_le_bytes());
+    program.extend_from_slice(&5u32.to_le_bytes());
+    for word in [0, SMOKE_TEST_VADDR, SMOKE_TEST_VADDR, size, size, 0x1000] {
+        program.extend_from_slice(&word.to_le_bytes());
+    }
+    for instruction in code {
+        program.extend_from_slice(&instruction.to_le_bytes());
+    }
+    program.into()
+}
+
+// run a trivial program to tell whether the VM works on this host, apart from any decoder
+pub fn smoke_test() -> Result<(), String> {
+    match main_asm(smoke_test_program(), Vec::new(), SMOKE_TEST_MAX_CYCLES) {
+        Ok((0, _, _)) => Ok(()),
+        Ok((exit_code, _, _)) => Err(format!("smoke test exited with {exit_code}")),
+        Err(error) => Err(error.to_string()),
+    }
 }